@@ -621,6 +621,7 @@ impl BlockDeviceHandle for SpdkBlockDeviceHandle {
         cb: IoCompletionCallback,
         cb_arg: IoCompletionCallbackArg,
     ) -> Result<(), CoreError> {
+        let num_blocks = self.get_device().num_blocks();
         let ctx = alloc_bdev_io_ctx(
             IoType::Flush,
             IoCtx {
@@ -628,12 +629,21 @@ impl BlockDeviceHandle for SpdkBlockDeviceHandle {
                 cb,
                 cb_arg,
                 #[cfg(feature = "fault-injection")]
-                inj_op: InjectIoCtx::new(FaultDomain::BlockDevice),
+                inj_op: InjectIoCtx::without_iovs(
+                    FaultDomain::BlockDevice,
+                    self.get_device(),
+                    IoType::Flush,
+                    0,
+                    num_blocks,
+                ),
             },
             0,
-            0,
+            num_blocks,
         )?;
 
+        #[cfg(feature = "fault-injection")]
+        inject_submission_error(unsafe { &(*ctx).inj_op })?;
+
         let (desc, chan) = self.handle.io_tuple();
         let bdev_size = self.device.size_in_bytes();
         let rc = unsafe {
@@ -698,6 +708,9 @@ pub fn io_type_to_err(
         IoType::Reset => CoreError::ResetDispatch {
             source,
         },
+        IoType::Flush => CoreError::FlushDispatch {
+            source,
+        },
         _ => {
             warn!("Unsupported I/O operation: {:?}", op);
             CoreError::NotSupported {
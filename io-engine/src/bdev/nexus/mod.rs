@@ -13,10 +13,13 @@ mod nexus_bdev_rebuild;
 mod nexus_bdev_snapshot;
 mod nexus_channel;
 mod nexus_child;
+mod nexus_child_error_store;
+mod nexus_child_throttle;
 mod nexus_io;
 mod nexus_io_log;
 mod nexus_io_subsystem;
 mod nexus_iter;
+mod nexus_label;
 mod nexus_module;
 mod nexus_nbd;
 mod nexus_persistence;
@@ -42,15 +45,27 @@ pub use nexus_bdev::{
 };
 pub(crate) use nexus_bdev_error::nexus_err;
 pub use nexus_bdev_error::Error;
+pub use nexus_bdev_rebuild::{active_rebuild_count, queued_rebuild_count};
 pub(crate) use nexus_channel::{DrEvent, IoMode, NexusChannel};
 pub use nexus_child::{
     ChildError,
+    ChildIoError,
+    ChildLocality,
+    ChildRebuildUpdate,
     ChildState,
     ChildStateClient,
     ChildSyncState,
     FaultReason,
+    InaccessibleReason,
     NexusChild,
 };
+pub use nexus_child_error_store::{
+    ErrorCounts,
+    ErrorStats,
+    NexusErrRecord,
+    NexusErrStore,
+};
+pub use nexus_child_throttle::ChildThrottleLimits;
 use nexus_io::{NexusBio, NioCtx};
 use nexus_io_log::{IOLog, IOLogChannel};
 use nexus_io_subsystem::NexusIoSubsystem;
@@ -65,6 +80,7 @@ pub use nexus_iter::{
     nexus_lookup_nqn_mut,
     nexus_lookup_uuid_mut,
 };
+pub use nexus_label::{NexusLabel, LABEL_OFFSET, LABEL_SIZE};
 pub(crate) use nexus_module::{NexusModule, NEXUS_MODULE_NAME};
 pub(crate) use nexus_nbd::{NbdDisk, NbdError};
 pub(crate) use nexus_persistence::PersistOp;
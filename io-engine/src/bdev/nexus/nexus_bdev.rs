@@ -32,6 +32,7 @@ use super::{
     NexusBio,
     NexusChannel,
     NexusChild,
+    NexusLabel,
     NexusModule,
     PersistOp,
 };
@@ -64,7 +65,7 @@ use crate::{
         EventWithMeta,
     },
     rebuild::HistoryRecord,
-    subsys::NvmfSubsystem,
+    subsys::{Config, NvmfSubsystem},
 };
 
 use crate::core::{BlockDeviceIoStats, CoreError, IoCompletionStatus};
@@ -266,6 +267,8 @@ pub struct Nexus<'n> {
     pub(crate) state: parking_lot::Mutex<NexusState>,
     /// The offset in blocks where the data partition starts.
     pub(crate) data_ent_offset: u64,
+    /// The first block of the padded tail, fixed when the nexus is sized.
+    padded_tail_start: u64,
     /// enum containing the protocol-specific target used to publish the nexus
     pub(super) nexus_target: Option<NexusTarget>,
     /// Indicates if the Nexus has an I/O device.
@@ -282,8 +285,13 @@ pub struct Nexus<'n> {
     pub(super) rebuild_history: parking_lot::Mutex<Vec<HistoryRecord>>,
     /// Flag to control shutdown from I/O path.
     pub(crate) shutdown_requested: AtomicCell<bool>,
+    /// Number of children whose I/O is paused. The front-end I/Os are held
+    /// back while the I/O of any child is paused.
+    pub(super) paused_io_children: AtomicCell<usize>,
     /// Last child I/O error.
     pub(super) last_error: IoCompletionStatus,
+    /// Generation of the nexus written into the labels of its children.
+    pub(super) label_generation: u64,
     /// Prevent auto-Unpin.
     _pin: PhantomPinned,
 }
@@ -377,6 +385,7 @@ impl<'n> Nexus<'n> {
             state: parking_lot::Mutex::new(NexusState::Init),
             bdev: None,
             data_ent_offset: 0,
+            padded_tail_start: 0,
             req_size: size,
             nexus_target: None,
             nvme_params,
@@ -390,7 +399,9 @@ impl<'n> Nexus<'n> {
             event_sink: None,
             rebuild_history: parking_lot::Mutex::new(Vec::new()),
             shutdown_requested: AtomicCell::new(false),
+            paused_io_children: AtomicCell::new(0),
             last_error: IoCompletionStatus::Success,
+            label_generation: 0,
             _pin: Default::default(),
         };
 
@@ -473,6 +484,11 @@ impl<'n> Nexus<'n> {
         self.nexus_uuid
     }
 
+    /// Returns the label the nexus writes into its children.
+    pub fn label(&self) -> NexusLabel {
+        NexusLabel::new(self.nexus_uuid, self.label_generation)
+    }
+
     /// Add new initiator to the Nexus
     #[allow(dead_code)]
     pub(crate) fn add_initiator(&self, initiator: &str) {
@@ -563,6 +579,33 @@ impl<'n> Nexus<'n> {
         unsafe { self.bdev().num_blocks() }
     }
 
+    /// Returns the number of the first block of the padded tail of the nexus,
+    /// i.e. the blocks past the data partition of its smallest child, which
+    /// read as zeroes and reject writes. Children smaller than the nexus are
+    /// only tolerated as configured by `NexusOpts::child_size_tolerance`.
+    /// The tail is fixed when the nexus is sized, so that it never moves
+    /// over blocks already exposed as data.
+    pub fn padded_tail_start(&self) -> u64 {
+        self.padded_tail_start
+    }
+
+    /// Calculates the padded tail of the nexus from its current children.
+    /// Returns the size of the nexus in blocks when there is no padded tail.
+    fn calc_padded_tail_start(&self) -> u64 {
+        let num_blocks = self.num_blocks();
+        if Config::get().nexus_opts.child_size_tolerance == 0 {
+            return num_blocks;
+        }
+
+        self.children_iter()
+            .filter_map(|c| c.get_device().ok())
+            .map(|dev| {
+                partition::calc_data_end(dev.num_blocks(), dev.block_len())
+                    .saturating_sub(self.data_ent_offset)
+            })
+            .fold(num_blocks, min)
+    }
+
     /// Returns the alignment of the Nexus.
     pub fn alignment(&self) -> u64 {
         unsafe { self.bdev().alignment() }
@@ -659,6 +702,9 @@ impl<'n> Nexus<'n> {
             reconfiguring I/O channels...",
         );
 
+        // Do not reconnect the channels to children whose devices are gone.
+        self.validate_child_descriptors().await;
+
         let (sender, recv) = oneshot::channel::<ChannelTraverseStatus>();
 
         self.traverse_io_channels(
@@ -795,6 +841,8 @@ impl<'n> Nexus<'n> {
                     });
                 }
             }
+            let tail_start = self.calc_padded_tail_start();
+            self.as_mut().get_unchecked_mut().padded_tail_start = tail_start;
         }
 
         info!(
@@ -889,8 +937,10 @@ impl<'n> Nexus<'n> {
 
         self.as_mut().unshare_nexus().await?;
 
-        // wait for all rebuild jobs to be cancelled before proceeding with the
-        // destruction of the nexus
+        // drop the rebuilds still waiting for admission, and wait for all
+        // rebuild jobs to be cancelled before proceeding with the destruction
+        // of the nexus
+        self.dequeue_rebuilds(None);
         let child_uris = self.child_uris();
         for child in child_uris {
             self.as_mut().cancel_rebuild_jobs(&child).await;
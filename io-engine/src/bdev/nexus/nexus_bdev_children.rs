@@ -23,7 +23,10 @@
 //! When reconfiguring the nexus, we traverse all our children, create new IO
 //! channels for all children that are in the open state.
 
-use std::{cmp::min, pin::Pin};
+use std::{
+    cmp::{max, min},
+    pin::Pin,
+};
 
 use futures::channel::oneshot;
 use snafu::ResultExt;
@@ -32,6 +35,7 @@ use super::{
     nexus_err,
     nexus_lookup,
     nexus_lookup_mut,
+    ChildError,
     ChildState,
     ChildSyncState,
     Error,
@@ -55,9 +59,12 @@ use crate::{
         DeviceEventListener,
         DeviceEventType,
         Reactors,
+        Share,
+        UntypedBdev,
         VerboseError,
     },
     eventing::{EventMetaGen, EventWithMeta},
+    sleep::mayastor_sleep,
     subsys::NvmfSubsystem,
 };
 
@@ -148,6 +155,75 @@ impl<'n> Nexus<'n> {
         Ok(status)
     }
 
+    /// Replaces the block device of a faulted child with `new_bdev`. The
+    /// child is renamed after the URI of the new device, which takes the
+    /// place of the previous one in the persisted nexus info. As for an added
+    /// child, the new device is opened out-of-sync, and is rebuilt unless
+    /// `norebuild` is set.
+    pub async fn replace_child(
+        mut self: Pin<&mut Self>,
+        child_uri: &str,
+        new_bdev: UntypedBdev,
+        norebuild: bool,
+    ) -> Result<NexusStatus, Error> {
+        self.check_nexus_operation(NexusOperation::ReplicaAdd)?;
+
+        let Some(new_uri) = new_bdev.bdev_uri().map(|u| u.to_string()) else {
+            return Err(Error::ChildMissing {
+                child: new_bdev.name().to_owned(),
+                name: self.name.clone(),
+            });
+        };
+        if self.contains_child_uri(&new_uri) {
+            return Err(Error::ChildAlreadyExists {
+                child: new_uri,
+                name: self.name.clone(),
+            });
+        }
+
+        let nexus_name = self.name.clone();
+        let size = self.req_size();
+        self.as_mut()
+            .child_mut(child_uri)?
+            .replace_with(new_bdev, size)
+            .await
+            .context(nexus_err::OpenChild {
+                child: child_uri.to_owned(),
+                name: nexus_name,
+            })?;
+
+        if let Ok(child) = self.child(&new_uri) {
+            child.set_event_listener(self.get_event_sink());
+        }
+
+        self.persist(PersistOp::RemoveChild {
+            child_uri: child_uri.to_owned(),
+        })
+        .await?;
+        self.persist(PersistOp::AddChild {
+            child_uri: new_uri.clone(),
+            healthy: false,
+        })
+        .await?;
+
+        if !norebuild {
+            if let Err(e) = self.start_rebuild(&new_uri).await {
+                error!(
+                    "Child replaced but rebuild failed to start: {}",
+                    e.verbose()
+                );
+                if let Ok(child) = self.child(&new_uri) {
+                    child.close_faulted(FaultReason::RebuildFailed).await;
+                }
+            } else if let Ok(child) = self.child(&new_uri) {
+                self.event(EventAction::OnlineChild, child.meta())
+                    .generate();
+            }
+        }
+
+        Ok(self.status())
+    }
+
     /// The child may require a rebuild first, so the nexus will
     /// transition to degraded mode when the addition has been successful.
     async fn add_child_only(
@@ -166,11 +242,11 @@ impl<'n> Nexus<'n> {
 
         let child_bdev = match device_lookup(&name) {
             Some(child) => {
-                if child.block_len() != self.block_len()
-                    || self
-                        .min_num_blocks()
-                        .map_or(true, |n| n > child.num_blocks())
-                {
+                // Block size compatibility is checked when the child is
+                // opened, so compare the sizes in bytes here.
+                if self.min_num_blocks().map_or(true, |n| {
+                    n * self.block_len() > child.size_in_bytes()
+                }) {
                     if let Err(err) = device_destroy(uri).await {
                         error!(
                             "Failed to destroy child bdev with wrong geometry: {}",
@@ -209,7 +285,9 @@ impl<'n> Nexus<'n> {
 
         // it can never take part in the IO path
         // of the nexus until it's rebuilt from a healthy child.
-        let mut res = child.open(self.req_size(), ChildSyncState::OutOfSync);
+        let mut res = child
+            .open_with_retry(self.req_size(), ChildSyncState::OutOfSync)
+            .await;
 
         if res.is_ok() {
             // we have created the bdev, and created a nexusChild struct. To
@@ -217,8 +295,11 @@ impl<'n> Nexus<'n> {
             // data and metadata must be validated. The child
             // will be added and marked as faulted, once the rebuild has
             // completed the device can transition to online
+            let label = self.label();
             if let Err(e) = child.reservation_acquire(&self.nvme_params).await {
                 res = Err(e);
+            } else if let Err(e) = child.verify_label(&label).await {
+                res = Err(e);
             }
         }
 
@@ -346,6 +427,7 @@ impl<'n> Nexus<'n> {
                         .children
                         .retain(|c| c.uri() != uri);
                 }
+                self.dequeue_rebuilds(Some(uri));
 
                 res
             }
@@ -400,8 +482,12 @@ impl<'n> Nexus<'n> {
         res
     }
 
-    /// Checks that the given child can be removed or offlined.
-    fn check_child_remove_operation(
+    /// Checks that the given child can be removed or offlined without
+    /// taking the nexus down: it must not be the last child, nor the last
+    /// healthy one. Only the children which are open and synced hold a copy
+    /// of the data: faulted, out-of-sync or rebuilding children do not
+    /// count.
+    pub fn check_child_remove_operation(
         &self,
         child_uri: &str,
     ) -> Result<(), Error> {
@@ -430,14 +516,43 @@ impl<'n> Nexus<'n> {
         Ok(())
     }
 
+    /// Returns the children of the nexus sorted worst-first by their
+    /// `health_sort_key`. Children with the same key are ordered by their
+    /// rebuild progress, least advanced first, and then by uri, so that the
+    /// order is deterministic.
+    pub async fn children_by_health(&self) -> Vec<&NexusChild<'n>> {
+        let mut children = Vec::with_capacity(self.child_count());
+        for child in self.children_iter() {
+            let progress = child.get_rebuild_progress().await;
+            children.push((child.health_sort_key(), progress, child));
+        }
+        children
+            .sort_by(|a, b| (a.0, a.1, a.2.uri()).cmp(&(b.0, b.1, b.2.uri())));
+        children.into_iter().map(|(_, _, child)| child).collect()
+    }
+
     /// Onlines a child by re-opening its underlying block device and rebuilding
     /// the data from an existing child.
     pub async fn online_child(
+        self: Pin<&mut Self>,
+        child_uri: &str,
+    ) -> Result<NexusStatus, Error> {
+        self.online_child_ext(child_uri, false).await
+    }
+
+    /// Onlines a child as `online_child` does. With `skip_rebuild`, a child
+    /// which is proven to be in sync is brought back without being rebuilt:
+    /// it must carry the current label of the nexus, and the nexus must have
+    /// logged no write to it while it was offline. Otherwise, the child is
+    /// rebuilt.
+    pub async fn online_child_ext(
         mut self: Pin<&mut Self>,
         child_uri: &str,
+        skip_rebuild: bool,
     ) -> Result<NexusStatus, Error> {
         let nexus_name = self.name.clone();
         let nexus_size = self.req_size();
+        let label = self.label();
 
         self.check_nexus_operation(NexusOperation::ReplicaOnline)?;
 
@@ -468,9 +583,43 @@ impl<'n> Nexus<'n> {
             });
         }
 
+        // A child labelled by an older generation of the nexus has missed
+        // writes.
+        let same_label = skip_rebuild
+            && matches!(child.read_label().await, Ok(Some(l)) if l == label);
+
+        // Make sure the child belongs to this nexus before rebuilding it. The
+        // child is only labelled with the current generation of the nexus once
+        // rebuilt.
+        if let Err(e) = child.verify_label(&label).await {
+            if matches!(e, ChildError::LabelMismatch { .. }) {
+                child.close_faulted(FaultReason::LabelMismatch).await;
+            } else {
+                child.close().await.ok();
+            }
+
+            return Err(e).context(nexus_err::OnlineChild {
+                child: child_uri.to_owned(),
+                name: nexus_name.clone(),
+            });
+        }
+
         // Register event listener for onlined child.
         child.set_event_listener(self.get_event_sink());
 
+        if same_label && self.as_mut().online_child_in_sync(child_uri).await {
+            let child = self.child(child_uri)?;
+            info!("{child:?}: child is in sync, no rebuild needed");
+            self.persist(PersistOp::Update {
+                child_uri: child_uri.to_owned(),
+                healthy: true,
+            })
+            .await?;
+            self.event(EventAction::OnlineChild, child.meta())
+                .generate();
+            return Ok(self.status());
+        }
+
         // Start rebuild.
         if let Err(e) = self.start_rebuild(child_uri).await {
             child.close().await.ok();
@@ -483,6 +632,44 @@ impl<'n> Nexus<'n> {
         Ok(self.status())
     }
 
+    /// Marks an onlined child in sync, provided that the nexus has logged no
+    /// write to it while it was offline. The I/O of the nexus is paused while
+    /// checking, so that no write slips in before the child is connected to
+    /// the I/O channels again. Returns false if no I/O log proves the child
+    /// to be in sync.
+    async fn online_child_in_sync(
+        mut self: Pin<&mut Self>,
+        child_uri: &str,
+    ) -> bool {
+        if let Err(e) = self.as_mut().pause().await {
+            warn!("{self:?}: cannot pause to online '{child_uri}': {e}");
+            return false;
+        }
+
+        let in_sync = match self.lookup_child(child_uri) {
+            Some(c) if c.is_io_log_clean() => {
+                c.stop_io_log();
+                c.set_sync_state(ChildSyncState::Synced);
+                match c.refresh_channel().await {
+                    Ok(_) => true,
+                    Err(e) => {
+                        warn!("{c:?}: failed to refresh I/O channels: {e}");
+                        c.set_sync_state(ChildSyncState::OutOfSync);
+                        false
+                    }
+                }
+            }
+            _ => false,
+        };
+
+        if let Err(e) = self.as_mut().resume().await {
+            error!(
+                "{self:?}: failed to resume after onlining '{child_uri}': {e}"
+            );
+        }
+        in_sync
+    }
+
     /// Unconditionally closes all children of this nexus.
     pub(crate) async fn close_children(&self) {
         info!("{self:?}: closing {n} children...", n = self.children.len());
@@ -501,6 +688,24 @@ impl<'n> Nexus<'n> {
         }
     }
 
+    /// Validates the device descriptors of all opened children, faulting the
+    /// children whose descriptors are no longer valid. It is called on every
+    /// reconfiguration, to catch dead children which have not produced an I/O
+    /// error yet.
+    pub async fn validate_child_descriptors(&self) {
+        for child in self.children_iter() {
+            if !child.is_opened() {
+                continue;
+            }
+            if let Err(e) = child.validate_descriptor().await {
+                error!(
+                    "{child:?}: descriptor validation failed: {e}",
+                    e = e.verbose()
+                );
+            }
+        }
+    }
+
     /// Tries to open all the child devices.
     /// Opens children, determines and validates block size and block count
     /// of underlying devices.
@@ -519,16 +724,28 @@ impl<'n> Nexus<'n> {
         let mut error = None;
         let evt_listener = self.as_mut().get_event_sink();
 
-        unsafe {
-            for child in self.as_mut().children_iter_mut() {
-                match child.open(size, ChildSyncState::Synced) {
-                    Ok(_) => {
-                        child.set_event_listener(evt_listener.clone());
-                    }
-                    Err(err) => {
-                        error = Some(err);
-                    }
+        // Opening a child may sleep between retries, while the list of the
+        // children can change: look each child up again after every await.
+        let uris = self
+            .children_iter()
+            .map(|c| c.uri().to_owned())
+            .collect::<Vec<_>>();
+        for uri in uris {
+            match self
+                .as_mut()
+                .open_child_with_retry(&uri, size, ChildSyncState::Synced)
+                .await
+            {
+                None => continue,
+                Some(Err(err)) => {
+                    error = Some(err);
+                    continue;
                 }
+                Some(Ok(_)) => {}
+            }
+            match self.lookup_child(&uri) {
+                Some(child) => child.set_event_listener(evt_listener.clone()),
+                None => warn!("{self:?}: child '{uri}' removed while opening"),
             }
         }
 
@@ -591,6 +808,11 @@ impl<'n> Nexus<'n> {
             }
         }
 
+        if let Err(e) = self.as_mut().label_children().await {
+            self.close_children().await;
+            return Err(e);
+        }
+
         info!(
             "{:?}: all nexus children successfully opened: required_alignment={}",
             self, new_alignment,
@@ -598,6 +820,68 @@ impl<'n> Nexus<'n> {
         Ok(())
     }
 
+    /// Labels all children with a new generation of this nexus, following the
+    /// newest generation found in the labels the nexus has previously written
+    /// into its children. As when a child is added or onlined, a child
+    /// labelled by another nexus is faulted rather than relabelled, and the
+    /// nexus cannot be opened.
+    async fn label_children(mut self: Pin<&mut Self>) -> Result<(), Error> {
+        let uuid = self.uuid();
+        let mut generation = 0;
+
+        for child in self.children_iter() {
+            match child.read_label().await {
+                Ok(Some(label)) if label.nexus_uuid == uuid => {
+                    generation = max(generation, label.generation);
+                }
+                Ok(Some(label)) => {
+                    error!(
+                        "{child:?}: child is labelled by nexus {label}, \
+                        expected nexus {uuid}"
+                    );
+                    child.set_faulted_state(FaultReason::LabelMismatch);
+                    return Err(Error::NexusIncomplete {
+                        name: self.name.clone(),
+                        reason: format!(
+                            "Child {} is labelled by nexus {label}",
+                            child.uri()
+                        ),
+                    });
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    return Err(Error::NexusIncomplete {
+                        name: self.name.clone(),
+                        reason: format!(
+                            "Failed to read label of child {}: {e}",
+                            child.uri()
+                        ),
+                    })
+                }
+            }
+        }
+
+        unsafe {
+            self.as_mut().unpin_mut().label_generation = generation + 1;
+        }
+
+        let label = self.label();
+        for child in self.children_iter() {
+            if let Err(e) = child.write_label(&label).await {
+                return Err(Error::NexusIncomplete {
+                    name: self.name.clone(),
+                    reason: format!(
+                        "Failed to write label of child {}: {e}",
+                        child.uri()
+                    ),
+                });
+            }
+        }
+
+        info!("{self:?}: children labelled as {label}");
+        Ok(())
+    }
+
     /// Closes a child by its device name.
     pub async fn close_child(&self, device_name: &str) -> Result<(), Error> {
         info!("{self:?}: destroying child device: '{device_name}'");
@@ -702,6 +986,38 @@ impl<'n> Nexus<'n> {
             })
     }
 
+    /// Determines if the nexus is in active/passive mode, i.e. if one of its
+    /// children has been designated as the active I/O path.
+    pub fn is_active_passive(&self) -> bool {
+        self.children_iter().any(|c| c.is_active_path())
+    }
+
+    /// Returns the child designated as the active I/O path, if the nexus is
+    /// in active/passive mode.
+    pub fn active_path_child(&self) -> Option<&NexusChild<'n>> {
+        self.children_iter().find(|c| c.is_active_path())
+    }
+
+    /// Designates the given child as the only active I/O path of the nexus,
+    /// switching it to active/passive mode. With no child given, all the
+    /// children are active again.
+    pub fn set_active_path(
+        self: Pin<&mut Self>,
+        child_uri: Option<&str>,
+    ) -> Result<(), Error> {
+        if let Some(uri) = child_uri {
+            self.child(uri)?;
+        }
+
+        info!("{self:?}: setting active I/O path to {child_uri:?}");
+
+        unsafe {
+            self.children_iter_mut()
+                .for_each(|c| c.set_active_path(Some(c.uri()) == child_uri));
+        }
+        Ok(())
+    }
+
     /// Looks up a child by its URI and returns a mutable reference.
     pub fn lookup_child_mut(
         self: Pin<&mut Self>,
@@ -710,6 +1026,29 @@ impl<'n> Nexus<'n> {
         unsafe { self.children_iter_mut().find(|c| c.uri() == child_uri) }
     }
 
+    /// Opens the given child of this nexus like `NexusChild::open_with_retry`
+    /// does. As the children of the nexus may change while sleeping between
+    /// the retries, the child is looked up again before every attempt.
+    /// Returns `None` if the child is no longer found.
+    async fn open_child_with_retry(
+        mut self: Pin<&mut Self>,
+        child_uri: &str,
+        parent_size: u64,
+        sync_state: ChildSyncState,
+    ) -> Option<Result<String, ChildError>> {
+        for delay in NexusChild::open_retry_delays() {
+            let child = self.as_mut().child_mut(child_uri).ok()?;
+            if let Some(res) = child.try_open(parent_size, sync_state) {
+                return Some(res);
+            }
+            warn!("{child:?}: retrying to open in {delay:?}");
+            mayastor_sleep(delay).await.ok();
+        }
+
+        let child = self.as_mut().child_mut(child_uri).ok()?;
+        Some(child.open(parent_size, sync_state))
+    }
+
     /// Looks up a child by its URI and returns a mutable reference.
     /// Returns an error if child is not found.
     pub fn child_mut(
@@ -1152,6 +1491,26 @@ impl<'n> Nexus<'n> {
         debug!("{self:?}: set I/O mode to {mode:?}: done");
     }
 
+    /// Determines if the I/O of any child is paused, in which case the
+    /// front-end I/Os are held back on their channels.
+    #[inline(always)]
+    pub(super) fn is_child_io_paused(&self) -> bool {
+        self.paused_io_children.load() > 0
+    }
+
+    /// Resubmits the front-end I/Os held back while the I/O of a child was
+    /// paused, on all the channels whose I/O submissions are not frozen.
+    pub(super) async fn resubmit_paused_ios(&self) {
+        if !self.has_io_device {
+            return;
+        }
+
+        self.traverse_io_channels_async((), |channel, _| {
+            channel.resubmit_paused();
+        })
+        .await;
+    }
+
     /// TODO
     pub(super) fn try_self_shutdown(&self) {
         let nexus_name = self.nexus_name().to_owned();
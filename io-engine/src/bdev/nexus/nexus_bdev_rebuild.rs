@@ -1,12 +1,20 @@
-use futures::channel::oneshot::Receiver;
+use futures::channel::oneshot::{self, Receiver, Sender};
+use once_cell::sync::Lazy;
 use snafu::ResultExt;
-use std::{marker::PhantomData, sync::Arc};
+use spdk_rs::{libspdk::SPDK_NVME_SC_COMPARE_FAILURE, LbaRange, NvmeStatus};
+use std::{
+    cmp::{max, min},
+    collections::VecDeque,
+    marker::PhantomData,
+    sync::Arc,
+};
 
 use super::{
     nexus_err,
     nexus_lookup_mut,
     nexus_persistence::PersistOp,
     ChildSyncState,
+    ChildThrottleLimits,
     DrEvent,
     Error,
     FaultReason,
@@ -14,21 +22,69 @@ use super::{
 };
 
 use crate::{
-    core::{Reactors, VerboseError},
+    core::{
+        ClusterRange,
+        CoreError,
+        IoCompletionStatus,
+        Reactors,
+        ReadOptions,
+        SegmentMap,
+        UntypedBdev,
+        VerboseError,
+    },
     eventing::{EventMetaGen, EventWithMeta},
     rebuild::{
         HistoryRecord,
         NexusRebuildJob,
         NexusRebuildJobStarter,
-        RebuildError,
         RebuildJobOptions,
+        RebuildMap,
         RebuildState,
         RebuildStats,
         RebuildVerifyMode,
+        RebuildVerifyOutcome,
+        SEGMENT_SIZE,
     },
+    subsys::{Config, PostRebuildVerify},
 };
 use events_api::event::EventAction;
 
+/// A nexus rebuild waiting for admission.
+struct QueuedRebuild {
+    /// Name of the nexus.
+    nexus_name: String,
+    /// URI of the child to be rebuilt.
+    child_uri: String,
+    /// Ranges of the nexus to rebuild, or `None` to rebuild the whole child.
+    ranges: Option<Vec<ClusterRange>>,
+    /// Sender of the final rebuild state, once the rebuild is admitted.
+    sender: Sender<RebuildState>,
+}
+
+/// Nexus rebuilds waiting for admission, in the order they were requested.
+static REBUILD_QUEUE: Lazy<parking_lot::Mutex<VecDeque<QueuedRebuild>>> =
+    Lazy::new(Default::default);
+
+/// Returns the number of active nexus rebuilds on this node.
+pub fn active_rebuild_count() -> usize {
+    NexusRebuildJob::count()
+}
+
+/// Returns the number of nexus rebuilds on this node waiting for admission.
+pub fn queued_rebuild_count() -> usize {
+    REBUILD_QUEUE.lock().len()
+}
+
+/// Checks if a new rebuild can be started without exceeding the configured
+/// limit of concurrently active rebuilds.
+fn can_admit_rebuild() -> bool {
+    let limit = Config::get().nexus_opts.max_concurrent_rebuilds;
+    limit == 0 || active_rebuild_count() < limit
+}
+
+/// Number of segments compared by a sampled post-rebuild verification.
+const POST_REBUILD_VERIFY_SAMPLES: u64 = 64;
+
 /// Rebuild pause guard ensures rebuild jobs are resumed before it is dropped.
 pub(crate) struct RebuildPauseGuard<'a> {
     /// Nexus name.
@@ -76,20 +132,94 @@ impl<'a> RebuildPauseGuard<'a> {
     }
 }
 
+/// Lifts the rebuild throttle of the children of a finished rebuild once it
+/// is dropped, whichever way the nexus ends handling the rebuild.
+struct RebuildThrottleGuard<'a, 'n> {
+    /// Nexus of the rebuild.
+    nexus: &'a Nexus<'n>,
+    /// URI of the rebuilt child.
+    dst_uri: &'a str,
+    /// URI of the rebuild source.
+    src_uri: String,
+}
+
+impl<'a, 'n> Drop for RebuildThrottleGuard<'a, 'n> {
+    fn drop(&mut self) {
+        if let Some(dst) = self.nexus.lookup_child(self.dst_uri) {
+            dst.set_throttled(false);
+        }
+        // The source stays throttled while another child is rebuilt from it.
+        if NexusRebuildJob::lookup_src(&self.src_uri)
+            .iter()
+            .all(|j| j.dst_uri() == self.dst_uri)
+        {
+            if let Some(src) = self.nexus.lookup_child(&self.src_uri) {
+                src.set_throttled(false);
+            }
+        }
+    }
+}
+
 impl<'n> Nexus<'n> {
     /// Starts a rebuild job and returns a receiver channel
     /// which can be used to await the rebuild completion
     pub async fn start_rebuild(
         &self,
         child_uri: &str,
+    ) -> Result<Receiver<RebuildState>, Error> {
+        self.start_rebuild_ext(child_uri, None).await
+    }
+
+    /// Starts a rebuild job restricted to the given ranges of the nexus, in
+    /// bytes, and returns a receiver channel which can be used to await the
+    /// rebuild completion. The rebuild progress is relative to the ranges.
+    ///
+    /// The ranges are merged with the writes the nexus has logged since the
+    /// child has been faulted. This log is persisted along with the nexus
+    /// info when the nexus is shut down cleanly, so that it survives the
+    /// nexus being recreated. Without such a log, e.g. after a crash, the
+    /// ranges cannot be trusted to cover all the writes the child has
+    /// missed, and the whole child is rebuilt instead.
+    pub async fn rebuild_ranges(
+        &self,
+        child_uri: &str,
+        ranges: Vec<ClusterRange>,
+    ) -> Result<Receiver<RebuildState>, Error> {
+        let size = self.req_size();
+        if let Some(r) = ranges
+            .iter()
+            .find(|r| r.length == 0 || r.offset + r.length > size)
+        {
+            return Err(Error::InvalidArguments {
+                name: self.name.clone(),
+                args: format!(
+                    "rebuild range {}+{} is empty or exceeds the nexus size \
+                    of {size} bytes",
+                    r.offset, r.length
+                ),
+            });
+        }
+
+        self.start_rebuild_ext(child_uri, Some(ranges)).await
+    }
+
+    /// Starts a rebuild job, of the whole child or of the given ranges.
+    async fn start_rebuild_ext(
+        &self,
+        child_uri: &str,
+        ranges: Option<Vec<ClusterRange>>,
     ) -> Result<Receiver<RebuildState>, Error> {
         let name = self.name.clone();
         info!("{self:?}: start rebuild request for {child_uri}");
 
-        // Find a healthy child to rebuild from.
+        // Find the best healthy child to rebuild from.
         let src_child_uri = match self
             .children_iter()
-            .find(|c| c.is_healthy() && c.uri() != child_uri)
+            .filter(|c| c.uri() != child_uri)
+            .map(|c| (c.rebuild_source_score(), c))
+            .filter(|(score, _)| *score > 0)
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, c)| c)
         {
             Some(child) => Ok(child.uri().to_owned()),
             None => Err(Error::NoRebuildSource {
@@ -119,11 +249,26 @@ impl<'n> Nexus<'n> {
             }),
         }?;
 
+        // Queue the rebuild if too many rebuilds are already active, to avoid
+        // saturating the storage and network after a mass failure.
+        if !can_admit_rebuild() {
+            return self.queue_rebuild(&dst_child_uri, ranges);
+        }
+
         // Create a rebuild job for the child.
         let starter = self
             .create_rebuild_job(&src_child_uri, &dst_child_uri)
             .await?;
 
+        // Cap the rebuild traffic of the source and destination children, if
+        // configured so, until the rebuild ends.
+        if ChildThrottleLimits::from_config().is_limited() {
+            [&src_child_uri, &dst_child_uri]
+                .into_iter()
+                .filter_map(|uri| self.lookup_child(uri))
+                .for_each(|c| c.set_throttled(true));
+        }
+
         self.event(
             EventAction::RebuildBegin,
             self.rebuild_job(&dst_child_uri)?.meta(),
@@ -144,9 +289,55 @@ impl<'n> Nexus<'n> {
         // As this is done after the reconfiguration, any new write I/Os will
         // now reach the destination child, and no rebuild will be required
         // for them.
-        let map = self
-            .lookup_child(&dst_child_uri)
-            .and_then(|c| c.stop_io_log());
+        let dst = self.lookup_child(&dst_child_uri);
+        let mut map = dst.and_then(|c| c.stop_io_log());
+
+        // Restrict the rebuild to the given ranges, on top of the logged
+        // writes, either by this nexus or by its previous incarnation.
+        if let (Some(ranges), Some(dev)) =
+            (ranges, dst.and_then(|c| c.get_device().ok()))
+        {
+            let block_len = dev.block_len();
+            if map.is_none() {
+                if let Some(persisted) =
+                    self.persisted_dirty_ranges(&dst_child_uri).await
+                {
+                    let mut m = RebuildMap::new(
+                        &dev.device_name(),
+                        SegmentMap::new(
+                            dev.num_blocks(),
+                            block_len,
+                            SEGMENT_SIZE,
+                        ),
+                    );
+                    for r in persisted {
+                        m.blks_dirty(
+                            r.offset / block_len,
+                            r.length / block_len,
+                        );
+                    }
+                    map = Some(m);
+                }
+            }
+
+            match map.as_mut() {
+                Some(map) => {
+                    for r in ranges {
+                        let first = r.offset / block_len;
+                        let last = (r.offset + r.length - 1) / block_len;
+                        map.blks_dirty(
+                            self.data_ent_offset + first,
+                            last - first + 1,
+                        );
+                    }
+                }
+                None => warn!(
+                    "{self:?}: no log of the writes missed by \
+                    '{dst_child_uri}', rebuilding the whole child in place \
+                    of the given ranges"
+                ),
+            }
+        }
 
         starter
             .start(self.rebuild_job_mut(&dst_child_uri)?, map)
@@ -157,6 +348,102 @@ impl<'n> Nexus<'n> {
             })
     }
 
+    /// Queues a rebuild of the given child until it can be admitted.
+    /// The returned receiver gets the final state of the rebuild once it has
+    /// been admitted and completed.
+    fn queue_rebuild(
+        &self,
+        dst_child_uri: &str,
+        ranges: Option<Vec<ClusterRange>>,
+    ) -> Result<Receiver<RebuildState>, Error> {
+        let mut queue = REBUILD_QUEUE.lock();
+
+        if queue
+            .iter()
+            .any(|r| r.nexus_name == self.name && r.child_uri == dst_child_uri)
+        {
+            return Err(Error::RebuildJobAlreadyExists {
+                child: dst_child_uri.to_owned(),
+                name: self.name.clone(),
+            });
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        queue.push_back(QueuedRebuild {
+            nexus_name: self.name.clone(),
+            child_uri: dst_child_uri.to_owned(),
+            ranges,
+            sender,
+        });
+
+        info!(
+            "{self:?}: rebuild of '{dst_child_uri}' queued: {active} rebuilds \
+            active, {queued} queued",
+            active = active_rebuild_count(),
+            queued = queue.len(),
+        );
+
+        Ok(receiver)
+    }
+
+    /// Removes the queued rebuilds of the given child, or of all the children
+    /// of the nexus, so that they don't wait for admission once the child or
+    /// the nexus is gone.
+    pub(crate) fn dequeue_rebuilds(&self, child_uri: Option<&str>) {
+        let mut queue = REBUILD_QUEUE.lock();
+        let len = queue.len();
+        queue.retain(|r| {
+            r.nexus_name != self.name
+                || child_uri.map_or(false, |uri| r.child_uri != uri)
+        });
+
+        if queue.len() != len {
+            info!(
+                "{self:?}: removed {n} queued rebuild(s)",
+                n = len - queue.len()
+            );
+        }
+    }
+
+    /// Starts the queued rebuilds, as long as they can be admitted.
+    async fn admit_queued_rebuilds() {
+        while can_admit_rebuild() {
+            let Some(queued) = REBUILD_QUEUE.lock().pop_front() else {
+                return;
+            };
+
+            let Some(nexus) = nexus_lookup_mut(&queued.nexus_name) else {
+                warn!(
+                    "Nexus '{}': not found on starting queued rebuild of '{}'",
+                    queued.nexus_name, queued.child_uri
+                );
+                continue;
+            };
+
+            match nexus
+                .start_rebuild_ext(&queued.child_uri, queued.ranges)
+                .await
+            {
+                Ok(receiver) => {
+                    let sender = queued.sender;
+                    Reactors::current().send_future(async move {
+                        if let Ok(state) = receiver.await {
+                            sender.send(state).ok();
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!(
+                        "{nexus:?}: failed to start queued rebuild of \
+                        '{uri}': {e}",
+                        uri = queued.child_uri,
+                        e = e.verbose()
+                    );
+                }
+            }
+        }
+    }
+
     /// TODO
     async fn create_rebuild_job(
         &self,
@@ -195,7 +482,7 @@ impl<'n> Nexus<'n> {
             dst_child_uri,
             std::ops::Range::<u64> {
                 start: self.data_ent_offset,
-                end: self.num_blocks() + self.data_ent_offset,
+                end: self.padded_tail_start() + self.data_ent_offset,
             },
             opts,
             |nexus, job| {
@@ -214,11 +501,16 @@ impl<'n> Nexus<'n> {
 
     /// Translates the job into a new history record and pushes into
     /// the history.
-    fn create_history_record(&self, job: Arc<NexusRebuildJob>) {
-        let Some(rec) = job.history_record() else {
+    fn create_history_record(
+        &self,
+        job: Arc<NexusRebuildJob>,
+        verify_outcome: Option<RebuildVerifyOutcome>,
+    ) {
+        let Some(mut rec) = job.history_record() else {
             error!("{self:?}: try to get history record on unfinished job");
             return;
         };
+        rec.verify_outcome = verify_outcome;
 
         self.rebuild_history.lock().push(rec);
 
@@ -426,35 +718,109 @@ impl<'n> Nexus<'n> {
             return Ok(());
         }
 
+        let throttle = RebuildThrottleGuard {
+            nexus: self,
+            dst_uri: child_uri,
+            src_uri: job.src_uri().to_owned(),
+        };
+
+        let mut verify_outcome = None;
+
         match job_state {
             RebuildState::Completed => {
                 self.event(EventAction::RebuildEnd, job.meta()).generate();
-                c.set_sync_state(ChildSyncState::Synced);
-
-                if c.is_healthy() {
-                    match self
-                        .persist(PersistOp::Update {
-                            child_uri: child_uri.to_owned(),
-                            healthy: true,
-                        })
-                        .await
-                    {
-                        Ok(_) => {
-                            info!("{c:?}: rebuild is successfull");
+
+                let mode = Config::get().nexus_opts.rebuild_post_verify;
+                if mode != PostRebuildVerify::None {
+                    let outcome = self
+                        .verify_rebuilt_child(job.src_uri(), child_uri, mode)
+                        .await;
+                    match &outcome {
+                        RebuildVerifyOutcome::Passed {
+                            blocks_verified,
+                        } => {
+                            info!(
+                                "{c:?}: rebuild verified, \
+                                {blocks_verified} blocks match the source"
+                            );
                         }
-                        Err(e) => {
+                        RebuildVerifyOutcome::Mismatch {
+                            offset_blk,
+                        } => {
                             error!(
-                                "{self:?}: failed to update persistent store \
-                                after rebuilding child '{c:?}': {e}"
+                                "{c:?}: rebuild verification failed: data \
+                                differs from the source at block {offset_blk}"
+                            );
+                            c.close_faulted(FaultReason::DataCorruption).await;
+                        }
+                        RebuildVerifyOutcome::Failed {
+                            reason,
+                        } => {
+                            error!(
+                                "{c:?}: rebuild verification could not \
+                                complete: {reason}"
+                            );
+                            c.close_faulted(FaultReason::RebuildFailed).await;
+                        }
+                    }
+                    verify_outcome = Some(outcome);
+                }
+
+                // A child faulted by the verification must not be marked as
+                // synced.
+                if matches!(
+                    verify_outcome,
+                    None | Some(RebuildVerifyOutcome::Passed { .. })
+                ) {
+                    // The rebuilt data must be durable before the child serves
+                    // reads, and before it is labelled with the current
+                    // generation of the nexus, which tells it in sync.
+                    let synced = match c.flush().await {
+                        Ok(_) => c
+                            .write_label(&self.label())
+                            .await
+                            .map_err(|e| e.to_string()),
+                        Err(e) => Err(e.to_string()),
+                    };
+                    if let Err(error) = synced {
+                        error!(
+                            "{c:?}: failed to make the rebuilt data durable: \
+                            {error}"
+                        );
+                        c.close_faulted(FaultReason::RebuildFailed).await;
+                    } else {
+                        c.set_sync_state(ChildSyncState::Synced);
+
+                        if c.is_healthy() {
+                            match self
+                                .persist(PersistOp::Update {
+                                    child_uri: child_uri.to_owned(),
+                                    healthy: true,
+                                })
+                                .await
+                            {
+                                Ok(_) => {
+                                    info!("{c:?}: rebuild is successfull");
+                                }
+                                Err(e) => {
+                                    error!(
+                                        "{self:?}: failed to update \
+                                        persistent store after rebuilding \
+                                        child '{c:?}': {e}"
+                                    );
+                                    // The subscribers are told of the end of
+                                    // the rebuild all the same.
+                                    c.notify_rebuild_finished(job_state);
+                                    return Err(e);
+                                }
+                            }
+                        } else {
+                            warn!(
+                                "{c:?}: rebuild is successfull, but the \
+                                child is not healthy"
                             );
-                            return Err(e);
                         }
                     }
-                } else {
-                    warn!(
-                        "{c:?}: rebuild is successfull, but the child \
-                        is not healthy"
-                    );
                 }
             }
             RebuildState::Stopped => {
@@ -464,14 +830,6 @@ impl<'n> Nexus<'n> {
             RebuildState::Failed => {
                 // rebuild has failed so we need to set the child as faulted
                 // allowing the control plane to replace it with another
-
-                if let Some(RebuildError::ReadIoFailed {
-                    ..
-                }) = job.error()
-                {
-                    // todo: retry rebuild using another child as source?
-                }
-
                 error!(
                     "{c:?}: rebuild job failed with error: {e}",
                     e = job.error_desc()
@@ -489,6 +847,8 @@ impl<'n> Nexus<'n> {
             }
         }
 
+        c.notify_rebuild_finished(job_state);
+
         // TODO: Should this be done only after reconfigure?
         // Reason being if we remove the rebuild job then another rebuild could
         // potentially be triggered even though we haven't reconfigured
@@ -501,15 +861,126 @@ impl<'n> Nexus<'n> {
                 return Ok(());
             }
             Some(job) => {
-                self.create_history_record(job);
+                self.create_history_record(job, verify_outcome);
             }
         }
 
+        // Lift the rebuild throttle before the nexus is reconfigured.
+        drop(throttle);
+
         self.reconfigure(DrEvent::ChildRebuild).await;
 
         Ok(())
     }
 
+    /// Verifies a rebuilt child by comparing its data with the rebuild
+    /// source, either over the whole rebuilt range or over evenly spaced
+    /// segments of it. Each segment is compared with its range locked on the
+    /// nexus, as the rebuild copies it, so that no front end write to it can
+    /// race with the comparison and be reported as a mismatch.
+    async fn verify_rebuilt_child(
+        &self,
+        src_uri: &str,
+        dst_uri: &str,
+        mode: PostRebuildVerify,
+    ) -> RebuildVerifyOutcome {
+        let failed = |reason: String| RebuildVerifyOutcome::Failed {
+            reason,
+        };
+
+        let (Ok(src), Ok(dst)) = (self.child(src_uri), self.child(dst_uri))
+        else {
+            return failed("rebuild source or target not found".to_string());
+        };
+        let (src_hdl, dst_hdl) = match (
+            src.get_io_handle_nonblock().await,
+            dst.get_io_handle_nonblock().await,
+        ) {
+            (Ok(s), Ok(d)) => (s, d),
+            (Err(e), _) | (_, Err(e)) => return failed(e.to_string()),
+        };
+
+        let nexus_desc = match UntypedBdev::open_by_name(&self.name, false) {
+            Ok(desc) => desc,
+            Err(e) => return failed(e.to_string()),
+        };
+
+        let block_len = self.block_len();
+        let seg_blks = SEGMENT_SIZE / block_len;
+        let start = self.data_ent_offset;
+        let end = start + self.padded_tail_start();
+        let num_segs = (end - start + seg_blks - 1) / seg_blks;
+        let step = match mode {
+            PostRebuildVerify::Sampled => {
+                max(1, num_segs / POST_REBUILD_VERIFY_SAMPLES)
+            }
+            _ => 1,
+        };
+
+        let mut blocks_verified = 0;
+        for seg in (0 .. num_segs).step_by(step as usize) {
+            let offset_blk = start + seg * seg_blks;
+            let num_blks = min(seg_blks, end - offset_blk);
+            let mut buf = match src_hdl.dma_malloc(num_blks * block_len) {
+                Ok(buf) => buf,
+                Err(e) => return failed(e.to_string()),
+            };
+
+            // The range is relative to the data partition of the nexus.
+            let lock = match nexus_desc
+                .lock_lba_range(LbaRange::new(offset_blk - start, num_blks))
+                .await
+            {
+                Ok(lock) => lock,
+                Err(e) => return failed(e.to_string()),
+            };
+            let compared = async {
+                src_hdl
+                    .read_buf_blocks_async(
+                        &mut buf,
+                        offset_blk,
+                        num_blks,
+                        ReadOptions::None,
+                    )
+                    .await?;
+                dst_hdl
+                    .comparev_blocks_async(
+                        &[buf.to_io_vec()],
+                        offset_blk,
+                        num_blks,
+                    )
+                    .await
+            }
+            .await;
+            if let Err(e) = nexus_desc.unlock_lba_range(lock).await {
+                return failed(e.to_string());
+            }
+
+            match compared {
+                Ok(_) => {}
+                Err(CoreError::CompareFailed {
+                    status, ..
+                }) if matches!(
+                    status,
+                    IoCompletionStatus::NvmeError(NvmeStatus::Media(
+                        SPDK_NVME_SC_COMPARE_FAILURE
+                    ))
+                ) =>
+                {
+                    return RebuildVerifyOutcome::Mismatch {
+                        offset_blk,
+                    };
+                }
+                Err(e) => return failed(e.to_string()),
+            }
+            blocks_verified += num_blks;
+        }
+
+        RebuildVerifyOutcome::Passed {
+            blocks_verified,
+        }
+    }
+
     /// Rebuild updated callback when a rebuild job state updates
     async fn notify_rebuild(nexus: String, dst_uri: String) {
         if let Some(nexus) = nexus_lookup_mut(&nexus) {
@@ -527,5 +998,8 @@ impl<'n> Nexus<'n> {
                 nexus {nexus} cannot be found"
             );
         }
+
+        // A rebuild slot may have been freed, however the update went.
+        Self::admit_queued_rebuilds().await;
     }
 }
@@ -178,7 +178,7 @@ impl ReplicaSnapshotExecutor {
                     snapshot.txn_id(),
                     snapshot.name(),
                     Some(ctx.snapshot_uuid.clone()),
-                    snapshot.create_time(),
+                    snapshot.create_time_str(),
                     false,
                 );
 
@@ -300,9 +300,7 @@ impl<'n> Nexus<'n> {
         Ok(NexusSnapshotStatus {
             replicas_done,
             replicas_skipped,
-            snapshot_timestamp: snapshot
-                .create_time()
-                .map(|t| t.parse::<DateTime<Utc>>().unwrap_or_default()),
+            snapshot_timestamp: snapshot.create_time().ok().flatten(),
         })
     }
 
@@ -318,6 +316,12 @@ impl<'n> Nexus<'n> {
                 reason: "Snapshot name must be provided".to_string(),
             });
         }
+        if let Err(error) = snapshot.create_time() {
+            return Err(Error::FailedCreateSnapshot {
+                name: self.bdev_name(),
+                reason: format!("Invalid snapshot create time: {error}"),
+            });
+        }
 
         self.check_nexus_state()?;
 
@@ -20,6 +20,7 @@ pub struct NexusChannel<'n> {
     detached: Vec<Box<dyn BlockDeviceHandle>>,
     io_logs: Vec<IOLogChannel>,
     previous_reader: UnsafeCell<usize>,
+    padded_tail_start: u64,
     fail_fast: u32,
     io_mode: IoMode,
     frozen_ios: Vec<NexusBio<'n>>,
@@ -62,6 +63,8 @@ pub enum DrEvent {
     ChildUnplug,
     /// Child rebuild event.
     ChildRebuild,
+    /// Child I/O handles refresh event.
+    ChildRefresh,
 }
 
 impl Display for DrEvent {
@@ -72,6 +75,7 @@ impl Display for DrEvent {
             match self {
                 Self::ChildUnplug => "unplug",
                 Self::ChildRebuild => "rebuild",
+                Self::ChildRefresh => "refresh",
             }
         )
     }
@@ -118,6 +122,7 @@ impl<'n> NexusChannel<'n> {
             detached: Vec::new(),
             io_logs: nexus.io_log_channels(),
             previous_reader: UnsafeCell::new(0),
+            padded_tail_start: nexus.padded_tail_start(),
             nexus: unsafe { nexus.pinned_mut() },
             fail_fast: 0,
             io_mode: IoMode::Normal,
@@ -190,6 +195,13 @@ impl<'n> NexusChannel<'n> {
         self.io_logs.iter().for_each(f)
     }
 
+    /// Returns the number of the first block of the padded tail of the nexus,
+    /// as of the last (re)connection of the children.
+    #[inline(always)]
+    pub(super) fn padded_tail_start(&self) -> u64 {
+        self.padded_tail_start
+    }
+
     /// Very simplistic routine to rotate between children for read operations
     /// note that the channels can be None during a reconfigure; this is usually
     /// not the case but a side effect of using the async. As we poll
@@ -359,6 +371,7 @@ impl<'n> NexusChannel<'n> {
 
         self.writers = writers;
         self.readers = readers;
+        self.padded_tail_start = self.nexus().padded_tail_start();
     }
 
     /// Reconnects all active I/O logs.
@@ -409,10 +422,21 @@ impl<'n> NexusChannel<'n> {
             n = self.frozen_ios.len()
         );
 
-        self.frozen_ios.drain(..).for_each(|io| {
-            trace!("{io:?}: resubmitting a frozen I/O");
-            io.submit_request();
-        });
+        // The I/Os may be frozen again as they are resubmitted.
+        std::mem::take(&mut self.frozen_ios)
+            .into_iter()
+            .for_each(|io| {
+                trace!("{io:?}: resubmitting a frozen I/O");
+                io.submit_request();
+            });
+    }
+
+    /// Resubmits the I/Os held back while the I/O of a child was paused,
+    /// unless I/O submissions are frozen.
+    pub(super) fn resubmit_paused(&mut self) {
+        if !self.is_frozen() {
+            self.resubmit_frozen();
+        }
     }
 
     /// Aborts all frozen I/Os.
@@ -1,36 +1,75 @@
 use std::{
+    cmp::{max, min},
+    collections::VecDeque,
     convert::TryFrom,
     fmt::{Debug, Display, Formatter},
     marker::PhantomData,
-    sync::atomic::Ordering,
+    net::IpAddr,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Utc};
 use crossbeam::atomic::AtomicCell;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{select, Either},
+    Future,
+};
 use nix::errno::Errno;
 use parking_lot::Mutex;
 use serde::Serialize;
 use snafu::{ResultExt, Snafu};
+use tokio::sync::Notify;
 use url::Url;
 
-use super::{nexus_lookup_mut, DrEvent, IOLog, IOLogChannel};
+use super::{
+    nexus_child_error_store::{ErrorStats, NexusErrStore},
+    nexus_child_throttle::{ChildThrottle, ChildThrottleLimits},
+    nexus_label::{NexusLabel, LABEL_OFFSET, LABEL_SIZE},
+    nexus_lookup,
+    nexus_lookup_mut,
+    DrEvent,
+    Error,
+    IOLog,
+    IOLogChannel,
+};
 
 use crate::{
     bdev::{device_create, device_destroy, device_lookup},
     bdev_api::BdevError,
     core::{
+        partition,
+        runtime,
         BlockDevice,
         BlockDeviceDescriptor,
         BlockDeviceHandle,
         CoreError,
+        Cores,
         DeviceEventSink,
+        IoCompletionCallbackArg,
+        IoCompletionStatus,
+        IoType,
+        LvolFailure,
+        NvmeStatus,
         Reactor,
         Reactors,
+        ReadOptions,
+        Share,
+        UntypedBdev,
         VerboseError,
     },
     eventing::replica_events::state_change_event_meta,
     persistent_store::PersistentStore,
-    rebuild::{NexusRebuildJob, RebuildMap},
+    rebuild::{
+        NexusRebuildJob,
+        RebuildMap,
+        RebuildProgress,
+        RebuildProgressListener,
+        RebuildState,
+    },
+    sleep::mayastor_sleep,
+    subsys::Config,
 };
 
 use crate::{
@@ -49,10 +88,13 @@ use spdk_rs::{
     libspdk::{
         spdk_nvme_registered_ctrlr_extended_data,
         spdk_nvme_reservation_status_extended_data,
+        SPDK_NVME_SC_ABORTED_SQ_DELETION,
+        SPDK_NVME_SC_COMMAND_TRANSIENT_TRANSPORT_ERROR,
     },
     nvme_reservation_acquire_action,
     nvme_reservation_register_action,
     nvme_reservation_register_cptpl,
+    DmaBuf,
     DmaError,
 };
 
@@ -71,14 +113,52 @@ pub enum ChildError {
         parent_size
     ))]
     ChildTooSmall { child_size: u64, parent_size: u64 },
+    #[snafu(display(
+        "Child block size {} does not match parent block size {}",
+        child,
+        parent
+    ))]
+    BlockSizeMismatch { child: u64, parent: u64 },
     #[snafu(display("Open child"))]
     OpenChild { source: CoreError },
     #[snafu(display("Claim child"))]
     ClaimChild { source: Errno },
-    #[snafu(display("Child is inaccessible"))]
-    ChildInaccessible {},
+    #[snafu(display("Child is inaccessible: {}", reason))]
+    ChildInaccessible { reason: InaccessibleReason },
     #[snafu(display("Cannot online child in its current state"))]
     CannotOnlineChild {},
+    #[snafu(display("Child is opened read-only"))]
+    ChildReadOnly {},
+    #[snafu(display("Cannot open child read-only in state {}", state))]
+    CannotOpenReadOnly { state: ChildState },
+    #[snafu(display(
+        "Child reopen is backing off, next attempt allowed in {:?}",
+        retry_in
+    ))]
+    ReopenBackoff { retry_in: Duration },
+    #[snafu(display("Child has no device descriptor"))]
+    NoDescriptor {},
+    #[snafu(display("Failed to read the label of child: {}", source))]
+    LabelRead { source: CoreError },
+    #[snafu(display(
+        "Short read of the label of child: {} of {} bytes",
+        read,
+        len
+    ))]
+    LabelShortRead { read: u64, len: u64 },
+    #[snafu(display("Failed to write the label of child: {}", source))]
+    LabelWrite { source: CoreError },
+    #[snafu(display(
+        "Child is labelled by nexus {}, expected nexus {}",
+        found,
+        expected
+    ))]
+    LabelMismatch {
+        found: NexusLabel,
+        expected: NexusLabel,
+    },
+    #[snafu(display("Child device descriptor is no longer valid"))]
+    DeadDescriptor {},
     #[snafu(display("Failed to create a BlockDeviceHandle for child"))]
     HandleCreate { source: CoreError },
     #[snafu(display("Failed to open a BlockDeviceHandle for child"))]
@@ -115,6 +195,65 @@ pub enum ChildError {
     NvmeHostId { source: CoreError },
     #[snafu(display("Failed to create a BlockDevice for child {}", child))]
     ChildBdevCreate { child: String, source: BdevError },
+    #[snafu(display("Failed to destroy block device of child {}", child))]
+    ChildBdevDestroy { child: String, source: BdevError },
+    #[snafu(display("Only a faulted child can be replaced"))]
+    ReplaceNotFaulted {},
+    #[snafu(display("Child I/O failed: {}", source))]
+    ChildIo { source: ChildIoError },
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(suffix(false)))]
+pub enum ChildIoError {
+    #[snafu(display("Error writing to {}: {}", name, source))]
+    WriteError { source: CoreError, name: String },
+    #[snafu(display("Error reading from {}: {}", name, source))]
+    ReadError { source: CoreError, name: String },
+    #[snafu(display("Error flushing {}: {}", name, source))]
+    FlushError { source: CoreError, name: String },
+    #[snafu(display("Error unmapping {}: {}", name, source))]
+    UnmapError { source: CoreError, name: String },
+    #[snafu(display("{} is not supported by child {}", op, name))]
+    Unsupported { name: String, op: &'static str },
+    #[snafu(display("Reservation error on {}: {}", name, source))]
+    ReservationError { source: CoreError, name: String },
+    #[snafu(display("Child {} is inaccessible: {}", name, reason))]
+    Inaccessible {
+        name: String,
+        reason: InaccessibleReason,
+    },
+    #[snafu(display("Invalid descriptor for child bdev {}", name))]
+    InvalidDescriptor { name: String },
+    #[snafu(display("I/O to paused child {} has been aborted", name))]
+    Paused { name: String },
+    #[snafu(display("Child {} is not the active I/O path", name))]
+    NotActivePath { name: String },
+    #[snafu(display("No reactor on core {} to submit I/O to {}", core, name))]
+    InvalidCore { name: String, core: u32 },
+    #[snafu(display(
+        "I/O to child {} on core {} has been cancelled",
+        name,
+        core
+    ))]
+    Cancelled { name: String, core: u32 },
+    #[snafu(display("Child {} is read-only", name))]
+    ReadOnly { name: String },
+    #[snafu(display("I/O to child {} timed out", name))]
+    Timeout { name: String },
+    #[snafu(display(
+        "I/O of {} bytes to child {} exceeds the maximum of {} bytes",
+        requested,
+        name,
+        max
+    ))]
+    TransferTooLarge {
+        name: String,
+        requested: u64,
+        max: u64,
+    },
+    #[snafu(display("No data read from child {} at offset {}", name, offset))]
+    NoDataRead { name: String, offset: u64 },
 }
 
 /// Fault reason.
@@ -136,6 +275,13 @@ pub enum FaultReason {
     TimedOut,
     /// The child has been faulted due to I/O error(s).
     IoError,
+    /// The child has been faulted as its media failed an I/O, e.g. on a bad
+    /// sector. Media errors are specific to the child and are not expected
+    /// to go away.
+    MediaError,
+    /// The child has been faulted as an I/O failed to be transported to it,
+    /// e.g. on a network failure. Transport errors may be transient.
+    TransportError,
     /// The child failed to rebuild successfully.
     RebuildFailed,
     /// Admin command failure.
@@ -144,6 +290,12 @@ pub enum FaultReason {
     Offline,
     /// The child has been permanently offlined by a client API call.
     OfflinePermanent,
+    /// The data of the child was found to differ from its rebuild source
+    /// after it was rebuilt.
+    DataCorruption,
+    /// The child is labelled by another nexus, or by a newer generation of
+    /// its parent.
+    LabelMismatch,
 }
 
 impl Display for FaultReason {
@@ -154,15 +306,47 @@ impl Display for FaultReason {
             Self::NoSpace => write!(f, "no space"),
             Self::TimedOut => write!(f, "timed out"),
             Self::IoError => write!(f, "I/O error"),
+            Self::MediaError => write!(f, "media error"),
+            Self::TransportError => write!(f, "transport error"),
             Self::RebuildFailed => write!(f, "rebuild failed"),
             Self::AdminCommandFailed => write!(f, "admin command failed"),
             Self::Offline => write!(f, "offline"),
             Self::OfflinePermanent => write!(f, "offline permanent"),
+            Self::DataCorruption => write!(f, "data corruption"),
+            Self::LabelMismatch => write!(f, "label mismatch"),
         }
     }
 }
 
 impl FaultReason {
+    /// Classifies the status of a failed child I/O into a fault reason.
+    pub fn from_io_status(status: IoCompletionStatus) -> Self {
+        match status {
+            IoCompletionStatus::LvolError(LvolFailure::NoSpace) => {
+                Self::NoSpace
+            }
+            IoCompletionStatus::NvmeError(NvmeStatus::Media(_)) => {
+                Self::MediaError
+            }
+            IoCompletionStatus::NvmeError(
+                NvmeStatus::DATA_TRANSFER_ERROR
+                | NvmeStatus::Generic(
+                    SPDK_NVME_SC_ABORTED_SQ_DELETION
+                    | SPDK_NVME_SC_COMMAND_TRANSIENT_TRANSPORT_ERROR,
+                ),
+            ) => Self::TransportError,
+            _ => Self::IoError,
+        }
+    }
+
+    /// Determines if the child has been faulted due to a failed I/O.
+    pub fn is_io_failure(&self) -> bool {
+        matches!(
+            self,
+            Self::IoError | Self::MediaError | Self::TransportError
+        )
+    }
+
     /// Determines if the fault reason is recoverable.
     pub fn is_recoverable(&self) -> bool {
         matches!(
@@ -170,6 +354,7 @@ impl FaultReason {
             Self::NoSpace
                 | Self::TimedOut
                 | Self::IoError
+                | Self::TransportError
                 | Self::Offline
                 | Self::AdminCommandFailed
                 | Self::RebuildFailed
@@ -177,6 +362,126 @@ impl FaultReason {
     }
 }
 
+/// Locality of a nexus child, i.e. how the nexus reaches the block device of
+/// the child.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChildLocality {
+    /// The device is on the same node as the nexus: a local bdev, a
+    /// locally-attached PCIe NVMe device, or a namespace of this node which
+    /// has been exported over NVMe-oF and imported back.
+    Local,
+    /// The device is on another node, reached over NVMe-oF/TCP.
+    RemoteTcp,
+    /// The device is on another node, reached over NVMe-oF/RDMA.
+    RemoteRdma,
+    /// The device is on another node, reached over iSCSI.
+    RemoteIscsi,
+    /// The URI of the device cannot be parsed, or its scheme is unknown.
+    Unknown,
+}
+
+impl Display for ChildLocality {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "local"),
+            Self::RemoteTcp => write!(f, "remote (nvmf-tcp)"),
+            Self::RemoteRdma => write!(f, "remote (nvmf-rdma)"),
+            Self::RemoteIscsi => write!(f, "remote (iscsi)"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+impl ChildLocality {
+    /// Determines the locality of a block device from its URI. An NVMe-oF
+    /// device exported by this node is local, as its I/Os do not leave the
+    /// node. A URI which cannot be parsed, or whose scheme is not one of a
+    /// block device, has an unknown locality.
+    pub fn from_uri(uri: &str) -> Self {
+        let Ok(url) = Url::parse(uri) else {
+            return Self::Unknown;
+        };
+
+        let remote = match url.scheme() {
+            "nvmf" | "nvmf+tcp" => Self::RemoteTcp,
+            "nvmf+rdma" => Self::RemoteRdma,
+            "iscsi" => Self::RemoteIscsi,
+            "aio" | "bdev" | "loopback" | "lvol" | "malloc" | "nexus"
+            | "null" | "pcie" | "uring" => return Self::Local,
+            _ => return Self::Unknown,
+        };
+
+        match url.host_str() {
+            Some(host) if Self::is_local_host(host) => Self::Local,
+            _ => remote,
+        }
+    }
+
+    /// Determines if the given host is this node.
+    fn is_local_host(host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host == "localhost" {
+            return true;
+        }
+        if host.parse::<IpAddr>().map_or(false, |ip| ip.is_loopback()) {
+            return true;
+        }
+        MayastorEnvironment::get_nvmf_tgt_ip().map_or(false, |ip| ip == host)
+    }
+
+    /// Determines if the locality is local.
+    pub fn is_local(&self) -> bool {
+        matches!(self, Self::Local)
+    }
+}
+
+/// Update on the rebuild of a child, see `NexusChild::subscribe_rebuild`.
+#[derive(Debug, Clone)]
+pub enum ChildRebuildUpdate {
+    /// The rebuild has progressed, or its state has changed.
+    Progress(RebuildProgress),
+    /// The rebuild has ended and the nexus has updated the child accordingly:
+    /// a successfully rebuilt child is `Open`, while a child which failed to
+    /// rebuild is faulted with `FaultReason::RebuildFailed`. This is the last
+    /// update of the rebuild.
+    Finished {
+        /// Final state of the rebuild job.
+        state: RebuildState,
+        /// State of the child after the rebuild.
+        child_state: ChildState,
+    },
+}
+
+/// Reason why a nexus child cannot serve I/O.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InaccessibleReason {
+    /// The child is faulted.
+    Faulted(FaultReason),
+    /// The child has been closed.
+    Closed,
+    /// The child has not been opened yet.
+    Init,
+    /// The child is incompatible with its parent.
+    ConfigInvalid,
+    /// The child has no block device.
+    NoDevice,
+    /// The block device of the child has not been opened.
+    NoDescriptor,
+}
+
+impl Display for InaccessibleReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Faulted(r) => write!(f, "faulted ({r})"),
+            Self::Closed => write!(f, "closed"),
+            Self::Init => write!(f, "not opened yet"),
+            Self::ConfigInvalid => write!(f, "config invalid"),
+            Self::NoDevice => write!(f, "no block device"),
+            Self::NoDescriptor => write!(f, "block device not opened"),
+        }
+    }
+}
+
 /// State of a nexus child.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
 pub enum ChildState {
@@ -187,6 +492,8 @@ pub enum ChildState {
     ConfigInvalid,
     /// The child is open for I/O.
     Open,
+    /// The child is open for reads only, outside of the nexus I/O path.
+    OpenReadOnly,
     /// The child has been closed by the nexus.
     Closed,
     /// The child is faulted.
@@ -199,6 +506,7 @@ pub enum ChildStateClient {
     Init,
     ConfigInvalid,
     Open,
+    OpenReadOnly,
     Closed,
     Faulted(FaultReason),
     Faulting(FaultReason),
@@ -211,6 +519,7 @@ impl Display for ChildState {
             Self::Init => write!(f, "init"),
             Self::ConfigInvalid => write!(f, "config invalid"),
             Self::Open => write!(f, "open"),
+            Self::OpenReadOnly => write!(f, "open read-only"),
             Self::Closed => write!(f, "closed"),
             Self::Faulted(r) => write!(f, "faulted ({r})"),
         }
@@ -223,7 +532,10 @@ impl ChildState {
     pub fn is_open_or_init(&self) -> bool {
         matches!(
             self,
-            ChildState::Open | ChildState::Init | ChildState::ConfigInvalid
+            ChildState::Open
+                | ChildState::OpenReadOnly
+                | ChildState::Init
+                | ChildState::ConfigInvalid
         )
     }
 
@@ -261,6 +573,68 @@ impl Display for ChildSyncState {
     }
 }
 
+/// Initial delay between two consecutive reopen attempts of a child.
+const REOPEN_BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+/// Maximum delay between two consecutive reopen attempts of a child.
+const REOPEN_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Base score of a child eligible to be a rebuild source.
+const REBUILD_SOURCE_BASE_SCORE: u32 = 1000;
+
+/// Rebuild source score bonus of a child local to the nexus.
+const REBUILD_SOURCE_LOCAL_BONUS: u32 = 1000;
+
+/// Rebuild source score penalty per rebuild job already reading from a child.
+const REBUILD_SOURCE_BUSY_PENALTY: u32 = 150;
+
+/// Rebuild source score penalty of a child which faulted recently.
+const REBUILD_SOURCE_FAULT_PENALTY: u32 = 300;
+
+/// Period in minutes during which a past fault of a child lowers its rebuild
+/// source score.
+const REBUILD_SOURCE_FAULT_WINDOW_MINS: i64 = 30;
+
+/// Size of the reads issued when scrubbing a child.
+const SCRUB_SEGMENT_SIZE: u64 = 1024 * 1024;
+
+/// Size of the reads issued when comparing two children.
+const COMPARE_SEGMENT_SIZE: u64 = 128 * 1024;
+
+/// Amount of data read by each pass of a scheduled scrub.
+const SCRUB_PASS_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Number of failed reads in a pass of a scheduled scrub at which the child
+/// is faulted.
+const SCRUB_FAULT_THRESHOLD: u64 = 1;
+
+/// Exponential backoff state of child reopen attempts.
+/// It prevents a storm of reopen attempts when many remote children fault
+/// at the same time, e.g. due to a node or network blip.
+#[derive(Debug, Default)]
+struct ReopenBackoff {
+    /// Number of consecutive failed reopen attempts.
+    failures: u32,
+    /// Earliest time the next reopen attempt is allowed at.
+    next_allowed: Option<Instant>,
+}
+
+impl ReopenBackoff {
+    /// Records a failed reopen attempt and schedules the next allowed one.
+    fn fail(&mut self) {
+        let delay = REOPEN_BACKOFF_BASE
+            .checked_mul(1 << self.failures.min(16))
+            .map_or(REOPEN_BACKOFF_MAX, |d| d.min(REOPEN_BACKOFF_MAX));
+        self.failures = self.failures.saturating_add(1);
+        self.next_allowed = Some(Instant::now() + delay);
+    }
+
+    /// Resets the backoff state after a successful open.
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 /// State of a child device destroy process.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
 pub(crate) enum ChildDestroyState {
@@ -279,6 +653,17 @@ impl Display for ChildDestroyState {
     }
 }
 
+/// I/O pause state of a child.
+/// While paused, the I/Os of the child wait in the order they were submitted
+/// until the child is resumed.
+#[derive(Debug, Default)]
+struct IoPause {
+    /// Indicates that the child I/O is paused.
+    paused: bool,
+    /// I/Os waiting for the child to be resumed.
+    waiters: VecDeque<oneshot::Sender<()>>,
+}
+
 #[derive(Serialize)]
 pub struct NexusChild<'c> {
     /// name of the parent this child belongs too
@@ -295,6 +680,9 @@ pub struct NexusChild<'c> {
     /// last fault timestamp if this child went faulted
     #[serde(skip_serializing)]
     faulted_at: parking_lot::Mutex<Option<DateTime<Utc>>>,
+    /// backoff state of the reopen attempts
+    #[serde(skip_serializing)]
+    reopen_backoff: parking_lot::Mutex<ReopenBackoff>,
     /// TODO
     #[serde(skip_serializing)]
     remove_channel: (async_channel::Sender<()>, async_channel::Receiver<()>),
@@ -304,6 +692,9 @@ pub struct NexusChild<'c> {
     /// TODO: we don't rename this field due to possible issues with
     /// TODO: child serialized state.
     name: String,
+    /// Indicates that the child has been designated as the active I/O path
+    /// of its nexus, which is then in active/passive mode.
+    is_active_path: bool,
     /// Underlying block device.
     #[serde(skip_serializing)]
     device: Option<Box<dyn BlockDevice>>,
@@ -313,6 +704,39 @@ pub struct NexusChild<'c> {
     /// I/O log.
     #[serde(skip_serializing)]
     io_log: Mutex<Option<IOLog>>,
+    /// I/O pause state.
+    #[serde(skip_serializing)]
+    io_pause: Mutex<IoPause>,
+    /// Orders the writes submitted via `write_at` with respect to write
+    /// barriers: writes share the lock, barriers hold it exclusively.
+    #[serde(skip_serializing)]
+    write_order: tokio::sync::RwLock<()>,
+    /// Error history of the child.
+    #[serde(skip_serializing)]
+    err_store: Mutex<Option<NexusErrStore>>,
+    /// Cancels the scheduled scrubbing of the child, if any.
+    #[serde(skip_serializing)]
+    scrub_schedule: Mutex<Option<Arc<Notify>>>,
+    /// Set while the child is being faulted for exceeding the error
+    /// threshold, so that further failed I/Os do not fault it again.
+    #[serde(skip_serializing)]
+    error_fault_pending: AtomicCell<bool>,
+    /// Set once the child is found over the error threshold while being the
+    /// last healthy child of its nexus, so that it is only warned about once,
+    /// until its errors fall below the threshold.
+    #[serde(skip_serializing)]
+    last_healthy_warned: AtomicCell<bool>,
+    /// Subscribers to the updates of the rebuild of the child.
+    #[serde(skip_serializing)]
+    rebuild_subscribers: Mutex<Vec<mpsc::UnboundedSender<ChildRebuildUpdate>>>,
+    /// Key and type of the reservation taken on the child via `reserve()`,
+    /// re-asserted whenever the child is reopened.
+    #[serde(skip_serializing)]
+    reservation: Mutex<Option<(u64, NvmeReservation)>>,
+    /// Throttle of the I/Os submitted via `read_at` and `write_at`, if the
+    /// child is throttled.
+    #[serde(skip_serializing)]
+    throttle: Mutex<Option<ChildThrottle>>,
     /// TODO
     #[serde(skip_serializing)]
     _c: PhantomData<&'c ()>,
@@ -347,12 +771,14 @@ impl Debug for NexusChild<'_> {
 impl<'c> NexusChild<'c> {
     /// TODO
     fn set_state(&self, state: ChildState) {
-        let previous = self.state();
-        debug!("{self:?}: changing state to '{state}'");
-        self.state.store(state);
+        let previous = self.state.swap(state);
+        if previous == state {
+            return;
+        }
+        debug!("{self:?}: changed state from '{previous}' to '{state}'");
         self.event(
             EventAction::StateChange,
-            state_change_event_meta(previous, state),
+            state_change_event_meta(self, previous, state),
         )
         .generate();
     }
@@ -377,6 +803,97 @@ impl<'c> NexusChild<'c> {
         &mut self,
         parent_size: u64,
         sync_state: ChildSyncState,
+    ) -> Result<String, ChildError> {
+        self.open_ext(parent_size, sync_state, false, true)
+    }
+
+    /// Opens the child like `open()` does, but retries with an exponential
+    /// backoff when the device cannot be opened, as configured in the nexus
+    /// options, e.g. while a remote target is reconnecting. The child is only
+    /// faulted once all the retries have failed.
+    /// The child is held across the sleeps between the retries, and thus must
+    /// not belong to a nexus yet: the children of a nexus are opened with
+    /// `Nexus::open_child_with_retry`, which looks them up after every sleep.
+    pub(crate) async fn open_with_retry(
+        &mut self,
+        parent_size: u64,
+        sync_state: ChildSyncState,
+    ) -> Result<String, ChildError> {
+        for delay in Self::open_retry_delays() {
+            match self.try_open(parent_size, sync_state) {
+                Some(res) => return res,
+                None => {
+                    warn!("{self:?}: retrying to open in {delay:?}");
+                    mayastor_sleep(delay).await.ok();
+                }
+            }
+        }
+
+        self.open(parent_size, sync_state)
+    }
+
+    /// Returns the delays between the retries to open a child, as configured
+    /// in the nexus options.
+    pub(super) fn open_retry_delays() -> impl Iterator<Item = Duration> {
+        let opts = &Config::get().nexus_opts;
+        let first = Duration::from_millis(opts.child_open_retry_delay_ms);
+        (0 .. opts.child_open_retries).map(move |i| {
+            first.saturating_mul(1u32.checked_shl(i).unwrap_or(u32::MAX))
+        })
+    }
+
+    /// Makes an attempt to open the child like `open()` does, which returns
+    /// `None` without faulting the child if its device cannot be opened, for
+    /// the attempt to be retried.
+    pub(super) fn try_open(
+        &mut self,
+        parent_size: u64,
+        sync_state: ChildSyncState,
+    ) -> Option<Result<String, ChildError>> {
+        match self.open_ext(parent_size, sync_state, false, false) {
+            Err(ChildError::OpenChild {
+                source,
+            }) => {
+                warn!("{self:?}: failed to open: {source}");
+                None
+            }
+            res => Some(res),
+        }
+    }
+
+    /// Determines if a child of the given size is too small for a parent of
+    /// the given size. A child smaller than its parent by no more than the
+    /// configured size tolerance is accepted, the parent presenting the
+    /// missing tail as padding.
+    fn is_too_small(parent_size: u64, child_size: u64) -> bool {
+        parent_size.saturating_sub(child_size)
+            > Config::get().nexus_opts.child_size_tolerance
+    }
+
+    /// Determines if the data partition of the given device ends before the
+    /// padded tail of an already sized parent. Such a child is rejected even
+    /// within the size tolerance, as the tail would have to move over blocks
+    /// the parent already exposes as data.
+    fn shrinks_padded_tail(&self, dev: &dyn BlockDevice) -> bool {
+        nexus_lookup(&self.parent).map_or(false, |n| {
+            n.num_blocks() > 0
+                && partition::calc_data_end(dev.num_blocks(), dev.block_len())
+                    .saturating_sub(n.data_ent_offset)
+                    < n.padded_tail_start()
+        })
+    }
+
+    /// Opens the child like `open()` does. If `preserve_err_store` is set,
+    /// the error history of the child is kept instead of being reset, so
+    /// that a child reopened after a transient fault does not start over
+    /// with a clean record. If `fault` is not set, the child is not faulted
+    /// when its device cannot be opened.
+    fn open_ext(
+        &mut self,
+        parent_size: u64,
+        sync_state: ChildSyncState,
+        preserve_err_store: bool,
+        fault: bool,
     ) -> Result<String, ChildError> {
         info!("{self:?}: opening child device...");
 
@@ -398,13 +915,19 @@ impl<'c> NexusChild<'c> {
                 warn!("{:?}: already opened", self);
                 return Ok(self.name.clone());
             }
+            ChildState::OpenReadOnly => {
+                error!("{self:?}: cannot open: already opened read-only");
+                return Err(ChildError::ChildReadOnly {});
+            }
             _ => {}
         }
 
         let dev = self.device.as_ref().unwrap();
 
         let child_size = dev.size_in_bytes();
-        if parent_size > child_size {
+        if Self::is_too_small(parent_size, child_size)
+            || self.shrinks_padded_tail(dev.as_ref())
+        {
             error!(
                 "{:?}: child is too small, parent size: {} child size: {}",
                 self, parent_size, child_size
@@ -415,23 +938,112 @@ impl<'c> NexusChild<'c> {
                 parent_size,
                 child_size,
             });
+        } else if parent_size > child_size {
+            warn!(
+                "{:?}: child is smaller than parent, parent size: {} \
+                child size: {}: the parent tail will be padded",
+                self, parent_size, child_size
+            );
+        }
+
+        // A parent which has not determined its block size yet (i.e. which is
+        // still being created) accepts any block size.
+        let child_block_len = dev.block_len();
+        let parent_block_len =
+            nexus_lookup(&self.parent).map_or(0, |n| n.block_len());
+        if parent_block_len != 0 && child_block_len != parent_block_len {
+            error!(
+                "{:?}: block size mismatch, parent block size: {} \
+                child block size: {}",
+                self, parent_block_len, child_block_len
+            );
+
+            self.set_state(ChildState::ConfigInvalid);
+            return Err(ChildError::BlockSizeMismatch {
+                child: child_block_len,
+                parent: parent_block_len,
+            });
         }
 
         let desc = dev.open(true).map_err(|source| {
-            self.set_faulted_state(FaultReason::CantOpen);
+            if let Some(owner) = self.claim_owner() {
+                error!(
+                    "{self:?}: failed to open: device is claimed by '{owner}'"
+                );
+            }
+            if fault {
+                self.set_faulted_state(FaultReason::CantOpen);
+            }
             ChildError::OpenChild {
                 source,
             }
         })?;
         self.device_descriptor = Some(desc);
 
+        {
+            let mut err_store = self.err_store.lock();
+            if !Config::get().err_store_opts.enabled {
+                *err_store = None;
+            } else if !preserve_err_store || err_store.is_none() {
+                *err_store = Some(NexusErrStore::default());
+            }
+        }
+
         self.set_state(ChildState::Open);
         self.set_sync_state(sync_state);
+        self.reopen_backoff.lock().reset();
 
         info!("{:?}: opened successfully", self);
         Ok(self.name.clone())
     }
 
+    /// Opens the child read-only, without claiming its device, e.g. to
+    /// inspect a suspected corrupt replica without risking any write to it.
+    /// A read-only child accepts reads via `read_at` but rejects writes, and
+    /// never takes part in the nexus I/O path nor in rebuilds.
+    ///
+    /// Only children in the closed or Init state can be opened read-only.
+    pub fn open_readonly(&mut self) -> Result<String, ChildError> {
+        info!("{self:?}: opening child device read-only...");
+
+        if self.is_destroying() {
+            error!("{self:?}: cannot open: block device is being destroyed");
+            return Err(ChildError::ChildBeingDestroyed {});
+        }
+
+        match self.state() {
+            ChildState::Init | ChildState::Closed => {}
+            ChildState::OpenReadOnly => {
+                warn!("{self:?}: already opened read-only");
+                return Ok(self.name.clone());
+            }
+            state => {
+                error!("{self:?}: cannot open read-only: state is {state}");
+                return Err(ChildError::CannotOpenReadOnly {
+                    state,
+                });
+            }
+        }
+
+        let Some(dev) = self.device.as_ref() else {
+            return Err(ChildError::ChildInaccessible {
+                reason: InaccessibleReason::NoDevice,
+            });
+        };
+        let desc = dev.open(false).context(OpenChild {})?;
+        self.device_descriptor = Some(desc);
+        self.set_state(ChildState::OpenReadOnly);
+
+        info!("{self:?}: opened read-only successfully");
+        Ok(self.name.clone())
+    }
+
+    /// Determines if the child is opened read-only.
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.state() == ChildState::OpenReadOnly
+    }
+
     /// Returns the state of the child.
     pub fn state(&self) -> ChildState {
         self.state.load()
@@ -447,6 +1059,7 @@ impl<'c> NexusChild<'c> {
             ChildState::Init => ChildStateClient::Init,
             ChildState::ConfigInvalid => ChildStateClient::ConfigInvalid,
             ChildState::Open => ChildStateClient::Open,
+            ChildState::OpenReadOnly => ChildStateClient::OpenReadOnly,
             ChildState::Closed => ChildStateClient::Closed,
             ChildState::Faulted(r) => {
                 // If the child is in the `Faulted` state but the device is
@@ -501,6 +1114,15 @@ impl<'c> NexusChild<'c> {
         *self.faulted_at.lock() = Some(Utc::now());
     }
 
+    /// Returns the earliest time the next reopen attempt is allowed at, if the
+    /// child is currently backing off after failed reopen attempts.
+    pub fn next_reopen_allowed(&self) -> Option<Instant> {
+        self.reopen_backoff
+            .lock()
+            .next_allowed
+            .filter(|t| *t > Instant::now())
+    }
+
     /// Determines if the child is opened.
     #[inline]
     pub fn is_opened(&self) -> bool {
@@ -522,12 +1144,53 @@ impl<'c> NexusChild<'c> {
             && self.sync_state() == ChildSyncState::Synced
     }
 
+    /// Returns the reason why the child cannot serve I/O, or `None` if the
+    /// child is accessible.
+    pub fn inaccessible_reason(&self) -> Option<InaccessibleReason> {
+        match self.state() {
+            ChildState::Faulted(r) => Some(InaccessibleReason::Faulted(r)),
+            ChildState::Closed => Some(InaccessibleReason::Closed),
+            ChildState::Init => Some(InaccessibleReason::Init),
+            ChildState::ConfigInvalid => {
+                Some(InaccessibleReason::ConfigInvalid)
+            }
+            ChildState::Open | ChildState::OpenReadOnly
+                if self.device.is_none() =>
+            {
+                Some(InaccessibleReason::NoDevice)
+            }
+            ChildState::Open | ChildState::OpenReadOnly
+                if self.device_descriptor.is_none() =>
+            {
+                Some(InaccessibleReason::NoDescriptor)
+            }
+            ChildState::Open | ChildState::OpenReadOnly => None,
+        }
+    }
+
     /// Determines if the child is being rebuilt.
     #[inline]
     pub(crate) fn is_rebuilding(&self) -> bool {
         self.rebuild_job().is_some() && self.is_opened_unsync()
     }
 
+    /// Returns an ordinal of the health of the child, suitable to sort
+    /// children worst-first: faulted or invalid children come first,
+    /// followed by closed, initializing, rebuilding and out-of-sync children,
+    /// healthy children sort last.
+    /// See `Nexus::children_by_health` to sort the children of a nexus,
+    /// with the ties broken.
+    pub fn health_sort_key(&self) -> u8 {
+        match self.state() {
+            ChildState::Faulted(_) | ChildState::ConfigInvalid => 0,
+            ChildState::Closed | ChildState::OpenReadOnly => 1,
+            ChildState::Init => 2,
+            ChildState::Open if self.is_rebuilding() => 3,
+            ChildState::Open if self.is_opened_unsync() => 4,
+            ChildState::Open => 5,
+        }
+    }
+
     /// Register an NVMe reservation, specifying a new key
     async fn resv_register(
         &self,
@@ -971,48 +1634,279 @@ impl<'c> NexusChild<'c> {
         Ok(())
     }
 
-    /// Closes the child and forces a faulted state.
-    pub(crate) async fn close_faulted(&self, reason: FaultReason) {
-        self.close().await.ok();
-        self.set_faulted_state(reason);
+    /// Registers the given key on the child and acquires a reservation of
+    /// the given type with it, e.g. to fence the child off other hosts. The
+    /// reservation is re-asserted whenever the child is reopened, until it is
+    /// released.
+    pub async fn reserve(
+        &self,
+        key: u64,
+        rtype: NvmeReservation,
+    ) -> Result<(), ChildIoError> {
+        self.resv_assert(key, rtype)
+            .await
+            .map_err(|source| self.resv_io_error(source))?;
+        *self.reservation.lock() = Some((key, rtype));
+        Ok(())
     }
 
-    /// Get URI of this Nexus child.
-    pub fn uri(&self) -> &str {
-        &self.name
-    }
+    /// Releases the reservation held on the child with the given key. The
+    /// type of the reservation is the one taken by `reserve()`, or the one of
+    /// the nexus otherwise.
+    pub async fn release(&self, key: u64) -> Result<(), ChildIoError> {
+        let hdl = self.get_io_handle_nonblock().await.map_err(|_| {
+            ChildIoError::InvalidDescriptor {
+                name: self.name.clone(),
+            }
+        })?;
 
-    /// Get UUID of this nexus child, if available.
-    pub fn get_uuid(&self) -> Option<String> {
-        Self::uuid(self.uri())
-    }
+        let rtype = match *self.reservation.lock() {
+            Some((k, t)) if k == key => t,
+            _ => nexus_lookup(&self.parent)
+                .map_or(NvmeReservation::WriteExclusiveAllRegs, |n| {
+                    n.nvme_params.resv_type
+                }),
+        };
 
-    /// Get name of the nexus this child belongs to.
-    pub fn nexus_name(&self) -> &str {
-        &self.parent
+        self.resv_release(&*hdl, key, rtype, 0)
+            .await
+            .map_err(|source| self.resv_io_error(source))?;
+
+        let mut reservation = self.reservation.lock();
+        if matches!(*reservation, Some((k, _)) if k == key) {
+            *reservation = None;
+        }
+        Ok(())
     }
 
-    /// Onlines a previously offlined child.
-    /// The child is set out-of-sync so that it will be rebuilt.
-    /// TODO: channels need to be updated when block devices are opened.
-    pub(crate) async fn online(
-        &mut self,
-        parent_size: u64,
-    ) -> Result<String, ChildError> {
-        info!("{:?}: bringing child online", self);
+    /// Registers the given key on the child and acquires a reservation of
+    /// the given type with it.
+    async fn resv_assert(
+        &self,
+        key: u64,
+        rtype: NvmeReservation,
+    ) -> Result<(), CoreError> {
+        let hdl = self.get_io_handle_nonblock().await?;
+        self.resv_register(&*hdl, key).await?;
+        hdl.nvme_resv_acquire(
+            key,
+            0,
+            nvme_reservation_acquire_action::ACQUIRE,
+            rtype as u8,
+        )
+        .await?;
+        info!("{self:?}: reserved with key {key:0x}h, type {rtype:?}");
+        Ok(())
+    }
 
-        let state = self.state.load();
+    /// Re-asserts the reservation taken on the child via `reserve()`, if any.
+    /// A device without reservation support is left unreserved, but any
+    /// other failure faults the child, which is no longer fenced off.
+    async fn resv_reassert(&self) -> Result<(), ChildError> {
+        let Some((key, rtype)) = *self.reservation.lock() else {
+            return Ok(());
+        };
 
-        if state.is_open_or_init() {
-            warn!(
-                "{:?}: child is in {} state and cannot be onlined",
-                self, state
-            );
-            return Err(ChildError::CannotOnlineChild {});
+        match self.resv_assert(key, rtype).await {
+            Ok(()) => Ok(()),
+            Err(CoreError::NotSupported {
+                ..
+            }) => {
+                warn!(
+                    "{self:?}: cannot re-assert reservation with key \
+                    {key:0x}h: device has no reservation support"
+                );
+                Ok(())
+            }
+            Err(source) => {
+                error!(
+                    "{self:?}: failed to re-assert reservation with key \
+                    {key:0x}h, type {rtype:?}: {}",
+                    source.verbose()
+                );
+                self.close_faulted(FaultReason::AdminCommandFailed).await;
+                Err(ChildError::ResvAcquire {
+                    source,
+                })
+            }
         }
+    }
 
-        if self.is_destroying() {
-            error!("{self:?}: child is being destroyed and cannot be onlined");
+    /// Converts an error of a reservation command into a child I/O error.
+    fn resv_io_error(&self, source: CoreError) -> ChildIoError {
+        match source {
+            CoreError::NotSupported {
+                ..
+            } => ChildIoError::Unsupported {
+                name: self.name.clone(),
+                op: "Reservation",
+            },
+            source => ChildIoError::ReservationError {
+                source,
+                name: self.name.clone(),
+            },
+        }
+    }
+
+    /// Reads the nexus label of this child. Returns `None` if the child
+    /// carries no label, e.g. when it is blank.
+    pub async fn read_label(&self) -> Result<Option<NexusLabel>, ChildError> {
+        let hdl = self.get_io_handle_nonblock().await.context(LabelRead {})?;
+        let dev = hdl.get_device();
+        let mut buf = DmaBuf::new(dev.block_len(), dev.alignment())
+            .context(HandleDmaMalloc {})?;
+
+        let n = hdl
+            .read_at(LABEL_OFFSET, &mut buf)
+            .await
+            .context(LabelRead {})?;
+        // Do not decode a label out of the stale part of a short read.
+        if n < LABEL_SIZE as u64 {
+            return Err(ChildError::LabelShortRead {
+                read: n,
+                len: LABEL_SIZE as u64,
+            });
+        }
+
+        Ok(NexusLabel::decode(&buf.as_slice()[.. LABEL_SIZE]))
+    }
+
+    /// Writes the given nexus label into this child, flushing it so that the
+    /// label is durable before the child is relied upon as in sync.
+    pub(crate) async fn write_label(
+        &self,
+        label: &NexusLabel,
+    ) -> Result<(), ChildError> {
+        let hdl = self.get_io_handle_nonblock().await.context(LabelWrite {})?;
+        let dev = hdl.get_device();
+        let mut buf = DmaBuf::new(dev.block_len(), dev.alignment())
+            .context(HandleDmaMalloc {})?;
+        buf.as_mut_slice().fill(0);
+        label.encode(buf.as_mut_slice());
+
+        hdl.write_at(LABEL_OFFSET, &buf)
+            .await
+            .context(LabelWrite {})?;
+        hdl.flush_io_async().await.context(LabelWrite {})?;
+
+        Ok(())
+    }
+
+    /// Verifies the nexus label of this child against the label of its
+    /// parent, before the child is accepted into the nexus. A child without
+    /// a label is left out-of-sync, to be rebuilt. A child labelled by
+    /// another nexus, or by a newer generation of its parent, is faulted
+    /// rather than serving stale data.
+    pub(crate) async fn verify_label(
+        &self,
+        parent: &NexusLabel,
+    ) -> Result<(), ChildError> {
+        match self.read_label().await? {
+            None => {
+                info!("{self:?}: child has no label, it must be rebuilt");
+                Ok(())
+            }
+            Some(label) if label.is_compatible(parent) => Ok(()),
+            Some(label) => {
+                error!(
+                    "{self:?}: child is labelled by nexus {label}, \
+                    expected nexus {parent}"
+                );
+                self.set_faulted_state(FaultReason::LabelMismatch);
+                Err(ChildError::LabelMismatch {
+                    found: label,
+                    expected: *parent,
+                })
+            }
+        }
+    }
+
+    /// Closes the child and forces a faulted state.
+    pub(crate) async fn close_faulted(&self, reason: FaultReason) {
+        self.close().await.ok();
+        self.set_faulted_state(reason);
+    }
+
+    /// Verifies that the device descriptor of this child is still valid,
+    /// i.e. that the underlying block device has not been removed without
+    /// the child noticing. A child with a dead descriptor is faulted.
+    pub async fn validate_descriptor(&self) -> Result<(), ChildError> {
+        let Some(desc) = self.device_descriptor.as_ref() else {
+            return Err(ChildError::NoDescriptor {});
+        };
+
+        let alive = device_lookup(&desc.device_name()).is_some()
+            && self.get_io_handle_nonblock().await.is_ok();
+
+        if !alive {
+            warn!("{self:?}: device descriptor is no longer valid");
+            self.close_faulted(FaultReason::CantOpen).await;
+            return Err(ChildError::DeadDescriptor {});
+        }
+
+        Ok(())
+    }
+
+    /// Drops the I/O handles the nexus channels hold for its children, and
+    /// acquires fresh ones from their current descriptors. To be called once
+    /// this child has been reopened, so that no channel keeps using a handle
+    /// of its previous descriptor.
+    pub async fn refresh_channel(&self) -> Result<(), ChildError> {
+        if self.device_descriptor.is_none() {
+            return Err(ChildError::NoDescriptor {});
+        }
+        if self.get_io_handle_nonblock().await.is_err() {
+            return Err(ChildError::DeadDescriptor {});
+        }
+
+        match nexus_lookup(&self.parent) {
+            Some(nexus) => {
+                nexus.reconfigure(DrEvent::ChildRefresh).await;
+                Ok(())
+            }
+            None => Err(ChildError::ChildInaccessible {
+                reason: InaccessibleReason::NoDevice,
+            }),
+        }
+    }
+
+    /// Get URI of this Nexus child.
+    pub fn uri(&self) -> &str {
+        &self.name
+    }
+
+    /// Get UUID of this nexus child, if available.
+    pub fn get_uuid(&self) -> Option<String> {
+        Self::uuid(self.uri())
+    }
+
+    /// Get name of the nexus this child belongs to.
+    pub fn nexus_name(&self) -> &str {
+        &self.parent
+    }
+
+    /// Onlines a previously offlined child.
+    /// The child is set out-of-sync so that it will be rebuilt; the nexus
+    /// channels are reconnected to it when its rebuild starts, or by
+    /// `refresh_channel()` if the nexus finds it already in sync.
+    pub(crate) async fn online(
+        &mut self,
+        parent_size: u64,
+    ) -> Result<String, ChildError> {
+        info!("{:?}: bringing child online", self);
+
+        let state = self.state.load();
+
+        if state.is_open_or_init() {
+            warn!(
+                "{:?}: child is in {} state and cannot be onlined",
+                self, state
+            );
+            return Err(ChildError::CannotOnlineChild {});
+        }
+
+        if self.is_destroying() {
+            error!("{self:?}: child is being destroyed and cannot be onlined");
             return Err(ChildError::ChildBeingDestroyed {});
         }
 
@@ -1024,6 +1918,31 @@ impl<'c> NexusChild<'c> {
             return Err(ChildError::PermanentlyFaulted {});
         }
 
+        if let Some(next) = self.next_reopen_allowed() {
+            let retry_in = next.saturating_duration_since(Instant::now());
+            warn!(
+                "{self:?}: child reopen is backing off, \
+                next attempt allowed in {retry_in:?}"
+            );
+            return Err(ChildError::ReopenBackoff {
+                retry_in,
+            });
+        }
+
+        match self.reopen(parent_size).await {
+            Ok(name) => {
+                self.resv_reassert().await?;
+                Ok(name)
+            }
+            Err(error) => {
+                self.reopen_backoff.lock().fail();
+                Err(error)
+            }
+        }
+    }
+
+    /// Re-creates the block device of the child and opens it out-of-sync.
+    async fn reopen(&mut self, parent_size: u64) -> Result<String, ChildError> {
         // Re-create the block device as it will have been previously
         // destroyed.
         let name =
@@ -1037,10 +1956,300 @@ impl<'c> NexusChild<'c> {
                 "{:?}: failed to find device after successful creation",
                 self,
             );
-            return Err(ChildError::ChildInaccessible {});
+            return Err(ChildError::ChildInaccessible {
+                reason: InaccessibleReason::NoDevice,
+            });
         }
 
-        self.open(parent_size, ChildSyncState::OutOfSync)
+        self.open_ext(parent_size, ChildSyncState::OutOfSync, true, true)
+    }
+
+    /// Replaces the block device of a faulted child with `new_bdev`, see
+    /// `Nexus::replace_child`. The child is renamed after the URI of the new
+    /// device, its previous block device is destroyed and the new one is
+    /// opened out-of-sync, to be rebuilt.
+    /// The size and block size of the replacement are validated before the
+    /// previous block device is closed. The reservation taken on the child
+    /// via `reserve()`, if any, is asserted on the new device.
+    pub(super) async fn replace_with(
+        &mut self,
+        new_bdev: UntypedBdev,
+        parent_size: u64,
+    ) -> Result<(), ChildError> {
+        if self.is_destroying() {
+            error!("{self:?}: cannot replace: child is being destroyed");
+            return Err(ChildError::ChildBeingDestroyed {});
+        }
+        if !matches!(self.state(), ChildState::Faulted(_)) {
+            error!("{self:?}: cannot replace: child is not faulted");
+            return Err(ChildError::ReplaceNotFaulted {});
+        }
+
+        let (Some(uri), Some(device)) = (
+            new_bdev.bdev_uri().map(|u| u.to_string()),
+            device_lookup(new_bdev.name()),
+        ) else {
+            error!(
+                "{self:?}: cannot replace: device '{}' not found",
+                new_bdev.name()
+            );
+            return Err(ChildError::ChildInaccessible {
+                reason: InaccessibleReason::NoDevice,
+            });
+        };
+
+        let child_size = device.size_in_bytes();
+        if Self::is_too_small(parent_size, child_size)
+            || self.shrinks_padded_tail(device.as_ref())
+        {
+            return Err(ChildError::ChildTooSmall {
+                parent_size,
+                child_size,
+            });
+        }
+        let child_block_len = device.block_len();
+        let parent_block_len =
+            nexus_lookup(&self.parent).map_or(0, |n| n.block_len());
+        if parent_block_len != 0 && child_block_len != parent_block_len {
+            return Err(ChildError::BlockSizeMismatch {
+                child: child_block_len,
+                parent: parent_block_len,
+            });
+        }
+
+        info!("{self:?}: replacing block device with '{uri}'");
+
+        self.close().await.context(ChildBdevDestroy {
+            child: self.name.clone(),
+        })?;
+
+        self.device = Some(device);
+        self.name = uri;
+        self.device_descriptor = None;
+
+        // The previous fault does not apply to the new device.
+        self.set_state(ChildState::Closed);
+        self.reopen_backoff.lock().reset();
+
+        self.open(parent_size, ChildSyncState::OutOfSync)?;
+        self.resv_reassert().await
+    }
+
+    /// Records a failed I/O in the error history of the child.
+    pub fn record_io_error(
+        &self,
+        io_op: IoType,
+        io_offset: u64,
+        io_num_blocks: u64,
+    ) {
+        if let Some(store) = self.err_store.lock().as_mut() {
+            store.add_record(io_op, io_offset, io_num_blocks, Instant::now());
+        } else {
+            return;
+        }
+
+        self.check_error_threshold();
+    }
+
+    /// Returns the rate of failed I/Os of the child, in errors per second,
+    /// over the retention window of the error store options. The child is
+    /// faulted once the number of errors within the window reaches
+    /// `ErrStoreOpts::max_errors`.
+    pub fn io_error_rate(&self) -> f64 {
+        let window = Self::error_window();
+        let errors = self.io_error_count_within(window);
+        errors as f64 / window.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Returns the window over which the failed I/Os are counted.
+    fn error_window() -> Duration {
+        Duration::from_millis(Config::get().err_store_opts.retention_ms)
+    }
+
+    /// Returns the number of failed I/Os recorded for the child within the
+    /// given window, ending now. A window longer than the uptime of the
+    /// system starts no later than the process, and thus covers all the
+    /// failed I/Os recorded.
+    fn io_error_count_within(&self, window: Duration) -> usize {
+        match Instant::now().checked_sub(window) {
+            Some(since) => self.io_error_count_since(since),
+            None => self.io_error_count(),
+        }
+    }
+
+    /// Faults the child with `FaultReason::IoError` if its number of failed
+    /// I/Os within the retention window has reached the configured
+    /// threshold. The nexus refuses to fault its last healthy child, which
+    /// is then kept.
+    fn check_error_threshold(&self) {
+        let max_errors = Config::get().err_store_opts.max_errors;
+        if max_errors == 0 || !self.is_healthy() {
+            return;
+        }
+
+        let errors = self.io_error_count_within(Self::error_window());
+        if errors < max_errors {
+            self.last_healthy_warned.store(false);
+            return;
+        }
+        if self.error_fault_pending.swap(true) {
+            return;
+        }
+
+        warn!(
+            "{self:?}: {errors} I/O errors within {window:?}, \
+            faulting child",
+            window = Self::error_window()
+        );
+
+        let parent = self.parent.clone();
+        let name = self.name.clone();
+        Reactors::master().send_future(async move {
+            let Some(nexus) = nexus_lookup_mut(&parent) else {
+                return;
+            };
+            let res = nexus.fault_child(&name, FaultReason::IoError).await;
+            let Some(child) =
+                nexus_lookup(&parent).and_then(|n| n.child(&name).ok())
+            else {
+                return;
+            };
+            match res {
+                Ok(_) => {}
+                Err(Error::RemoveLastHealthyChild {
+                    ..
+                }) => {
+                    if !child.last_healthy_warned.swap(true) {
+                        warn!(
+                            "{child:?}: over its I/O error threshold, but \
+                            kept as the last healthy child of its nexus"
+                        );
+                    }
+                }
+                Err(error) => {
+                    warn!(
+                        "Failed to fault child '{name}' over its I/O error \
+                        threshold: {error}"
+                    );
+                }
+            }
+            child.error_fault_pending.store(false);
+        });
+    }
+
+    /// Returns the number of failed I/Os recorded for the child.
+    pub fn io_error_count(&self) -> usize {
+        self.err_store.lock().as_ref().map_or(0, |s| s.len())
+    }
+
+    /// Returns the number of failed I/Os recorded for the child at or after
+    /// `since`.
+    pub fn io_error_count_since(&self, since: Instant) -> usize {
+        self.err_store
+            .lock()
+            .as_ref()
+            .map_or(0, |s| s.count_since(since))
+    }
+
+    /// Returns the statistics of the failed I/Os recorded for the child, the
+    /// recent ones being those recorded within the last `since`, e.g. to
+    /// decide whether the child is to be faulted due to I/O errors.
+    /// Returns `None` if the error store is disabled.
+    pub fn io_error_stats(&self, since: Duration) -> Option<ErrorStats> {
+        let since = Instant::now().checked_sub(since);
+        self.err_store.lock().as_ref().map(|s| s.stats(since))
+    }
+
+    /// Reads back `num_blocks` blocks of the child starting at `offset_blk`,
+    /// to detect latent media errors on data which front-end I/O does not
+    /// touch. Failed reads are recorded in the error history of the child.
+    /// Returns the number of failed reads.
+    pub async fn scrub(
+        &self,
+        offset_blk: u64,
+        num_blocks: u64,
+    ) -> Result<u64, ChildError> {
+        self.wait_io_resumed().await.context(ChildIo {})?;
+
+        let hdl = self.get_io_handle_nonblock().await.context(HandleOpen {})?;
+        let block_len = hdl.get_device().block_len();
+        let end = min(offset_blk + num_blocks, hdl.get_device().num_blocks());
+        let seg_blks = max(1, SCRUB_SEGMENT_SIZE / block_len);
+        let mut buf = hdl
+            .dma_malloc(seg_blks * block_len)
+            .context(HandleDmaMalloc {})?;
+
+        let mut failed = 0;
+        let mut offset = offset_blk;
+        while offset < end {
+            let blks = min(seg_blks, end - offset);
+            if blks != seg_blks {
+                buf = hdl
+                    .dma_malloc(blks * block_len)
+                    .context(HandleDmaMalloc {})?;
+            }
+            if let Err(e) = hdl
+                .read_buf_blocks_async(
+                    &mut buf,
+                    offset,
+                    blks,
+                    ReadOptions::None,
+                )
+                .await
+            {
+                warn!("{self:?}: scrub read failed at block {offset}: {e}");
+                self.record_io_error(IoType::Read, offset, blks);
+                failed += 1;
+            }
+            offset += blks;
+        }
+        Ok(failed)
+    }
+
+    /// Scrubs the child on a rolling basis in the background: every
+    /// `interval`, the next part of the child is scrubbed, wrapping around
+    /// at its end. The child is faulted if a pass fails too many reads.
+    /// Replaces any previous schedule of the child.
+    pub fn schedule_scrub(&self, interval: Duration) {
+        let cancel = Arc::new(Notify::new());
+        if let Some(prev) = self.scrub_schedule.lock().replace(cancel.clone()) {
+            prev.notify_one();
+        }
+
+        info!("{self:?}: scheduling scrub every {interval:?}");
+
+        let nexus_name = self.parent.clone();
+        let child_name = self.name.clone();
+        runtime::spawn_cooperative(move |mut token| async move {
+            let mut offset_blk = 0;
+            loop {
+                tokio::select! {
+                    _ = token.shutdown() => break,
+                    _ = cancel.notified() => break,
+                    _ = tokio::time::sleep(interval) => {}
+                }
+
+                let nexus_name = nexus_name.clone();
+                let child_name = child_name.clone();
+                let Ok(r) = Reactor::spawn_at_primary(async move {
+                    scrub_pass(&nexus_name, &child_name, offset_blk).await
+                }) else {
+                    break;
+                };
+                match r.await {
+                    Ok(Some(next)) => offset_blk = next,
+                    _ => break,
+                }
+            }
+        });
+    }
+
+    /// Cancels the scheduled scrubbing of the child, if any.
+    pub fn cancel_scrub_schedule(&self) {
+        if let Some(cancel) = self.scrub_schedule.lock().take() {
+            info!("{self:?}: cancelling scheduled scrub");
+            cancel.notify_one();
+        }
     }
 
     /// Extract a UUID from a URI.
@@ -1067,6 +2276,10 @@ impl<'c> NexusChild<'c> {
             return Ok(());
         }
 
+        // Abort the I/Os waiting for the paused child to be resumed, and
+        // unpause it, so that it does not come back paused once reopened.
+        drop(self.unpause_io());
+
         if self.device.is_none() {
             self.set_destroy_state(ChildDestroyState::None);
             warn!("{self:?}: no block device: appears to be already closed");
@@ -1074,7 +2287,8 @@ impl<'c> NexusChild<'c> {
         }
 
         // TODO: Check device claiming scheme.
-        if self.device_descriptor.is_some() {
+        // A read-only child has not claimed its device.
+        if self.device_descriptor.is_some() && !self.is_read_only() {
             self.device_descriptor.as_ref().unwrap().unclaim();
         }
 
@@ -1137,7 +2351,7 @@ impl<'c> NexusChild<'c> {
         // TODO: Revisit nexus reconfiguration once Nexus has switched to
         // BlockDevice-based children and is able to listen to
         // device-related events directly.
-        if state != ChildState::Faulted(FaultReason::IoError) {
+        if !matches!(state, ChildState::Faulted(r) if r.is_io_failure()) {
             let nexus_name = self.parent.clone();
             Reactor::block_on(async move {
                 match nexus_lookup_mut(&nexus_name) {
@@ -1191,8 +2405,19 @@ impl<'c> NexusChild<'c> {
             sync_state: AtomicCell::new(ChildSyncState::Synced),
             destroy_state: AtomicCell::new(ChildDestroyState::None),
             faulted_at: parking_lot::Mutex::new(None),
+            reopen_backoff: parking_lot::Mutex::new(ReopenBackoff::default()),
             remove_channel: async_channel::bounded(1),
             io_log: Mutex::new(None),
+            is_active_path: false,
+            io_pause: Mutex::new(IoPause::default()),
+            write_order: tokio::sync::RwLock::new(()),
+            err_store: Mutex::new(None),
+            scrub_schedule: Mutex::new(None),
+            error_fault_pending: AtomicCell::new(false),
+            last_healthy_warned: AtomicCell::new(false),
+            rebuild_subscribers: Mutex::new(Vec::new()),
+            reservation: Mutex::new(None),
+            throttle: Mutex::new(None),
             _c: Default::default(),
         }
     }
@@ -1202,7 +2427,11 @@ impl<'c> NexusChild<'c> {
         if let Some(ref device) = self.device {
             Ok(&**device)
         } else {
-            Err(ChildError::ChildInaccessible {})
+            Err(ChildError::ChildInaccessible {
+                reason: self
+                    .inaccessible_reason()
+                    .unwrap_or(InaccessibleReason::NoDevice),
+            })
         }
     }
 
@@ -1228,16 +2457,88 @@ impl<'c> NexusChild<'c> {
         }
     }
 
+    /// Subscribes to the updates of the rebuild of this child, if
+    /// rebuilding. Progress updates are pushed as the rebuild advances, and
+    /// the stream ends with a `ChildRebuildUpdate::Finished` update once the
+    /// nexus has processed the end of the rebuild.
+    pub fn subscribe_rebuild(
+        &self,
+    ) -> Option<mpsc::UnboundedReceiver<ChildRebuildUpdate>> {
+        let job = self.rebuild_job()?;
+        let (s, r) = mpsc::unbounded();
+
+        let progress = s.clone();
+        job.add_progress_listener(RebuildProgressListener::new(move |p| {
+            progress
+                .unbounded_send(ChildRebuildUpdate::Progress(p.clone()))
+                .is_ok()
+        }));
+        self.rebuild_subscribers.lock().push(s);
+
+        Some(r)
+    }
+
+    /// Sends the final update of the rebuild of this child to its
+    /// subscribers.
+    pub(crate) fn notify_rebuild_finished(&self, state: RebuildState) {
+        let update = ChildRebuildUpdate::Finished {
+            state,
+            child_state: self.state(),
+        };
+        for s in self.rebuild_subscribers.lock().drain(..) {
+            s.unbounded_send(update.clone()).ok();
+        }
+    }
+
+    /// Returns the locality of the child, if it has a block device.
+    pub fn locality(&self) -> Option<ChildLocality> {
+        self.device
+            .as_ref()
+            .map(|_| ChildLocality::from_uri(&self.name))
+    }
+
     /// Determine if a child is local to the nexus (i.e. on the same node).
     pub fn is_local(&self) -> Option<bool> {
-        match &self.device {
-            Some(dev) => {
-                // A local child is not exported over nvme.
-                let local = dev.driver_name() != "nvme";
-                Some(local)
+        self.locality().map(|l| l.is_local())
+    }
+
+    /// Returns how suitable this child is as a rebuild source, the higher the
+    /// better. A child which is not healthy (e.g. faulted or out-of-sync)
+    /// scores zero, which makes it ineligible.
+    ///
+    /// An eligible child starts with a base score and then:
+    /// - gains a bonus if it is local to the nexus, as reading from it does not
+    ///   consume network bandwidth;
+    /// - loses a penalty for every rebuild job already reading from it, as such
+    ///   a child is busy serving rebuild I/O on top of front-end I/O;
+    /// - loses a penalty if it faulted recently, as it is more likely to fail
+    ///   again and abort the rebuild.
+    ///
+    /// An eligible child never scores below 1.
+    pub fn rebuild_source_score(&self) -> u32 {
+        if !self.is_healthy() {
+            return 0;
+        }
+
+        let mut score = REBUILD_SOURCE_BASE_SCORE;
+
+        if self.is_local() == Some(true) {
+            score += REBUILD_SOURCE_LOCAL_BONUS;
+        }
+
+        let busy = NexusRebuildJob::lookup_src(&self.name).len() as u32;
+        score = score
+            .saturating_sub(busy.saturating_mul(REBUILD_SOURCE_BUSY_PENALTY));
+
+        if let Some(t) = self.fault_timestamp() {
+            let window =
+                chrono::Duration::minutes(REBUILD_SOURCE_FAULT_WINDOW_MINS);
+            if Utc::now() - t < window {
+                score = score.saturating_sub(REBUILD_SOURCE_FAULT_PENALTY);
             }
-            None => None,
         }
+
+        score.max(1)
     }
 
     /// Get I/O handle for the block device associated with this Nexus child.
@@ -1247,7 +2548,12 @@ impl<'c> NexusChild<'c> {
         if let Some(desc) = self.device_descriptor.as_ref() {
             desc.get_io_handle()
         } else {
-            error!("{:?}: child does not have valid descriptor", self);
+            error!(
+                "{self:?}: child does not have valid descriptor: {r}",
+                r = self
+                    .inaccessible_reason()
+                    .unwrap_or(InaccessibleReason::NoDescriptor)
+            );
             Err(CoreError::InvalidDescriptor {
                 name: self.name.clone(),
             })
@@ -1260,18 +2566,603 @@ impl<'c> NexusChild<'c> {
         if let Some(desc) = self.device_descriptor.as_ref() {
             desc.get_io_handle_nonblock().await
         } else {
-            error!("{:?}: child does not have valid descriptor", self);
+            error!(
+                "{self:?}: child does not have valid descriptor: {r}",
+                r = self
+                    .inaccessible_reason()
+                    .unwrap_or(InaccessibleReason::NoDescriptor)
+            );
             Err(CoreError::InvalidDescriptor {
                 name: self.name.clone(),
             })
         }
     }
 
+    /// Pauses the I/Os of the child, e.g. to update the child metadata
+    /// without racing other I/Os, apart from the label I/Os. The I/Os
+    /// submitted to the child itself wait until the child is resumed, or
+    /// fail if the child is closed meanwhile. The front-end I/Os of the
+    /// nexus, of all types, are held back on the nexus channels until then.
+    pub fn pause_io(&mut self) {
+        debug!("{self:?}: pausing I/O");
+        let was_paused =
+            std::mem::replace(&mut self.io_pause.lock().paused, true);
+        if !was_paused {
+            if let Some(nexus) = nexus_lookup(&self.parent) {
+                nexus.paused_io_children.fetch_add(1);
+            }
+        }
+    }
+
+    /// Resumes the I/Os of the child, in the order they were paused in.
+    pub fn resume_io(&mut self) {
+        let waiters = self.unpause_io();
+        debug!("{self:?}: resuming I/O, {} I/O(s) paused", waiters.len());
+        waiters.into_iter().for_each(|w| {
+            w.send(()).ok();
+        });
+    }
+
+    /// Unpauses the I/Os of the child, returning the I/Os waiting for it.
+    /// The front-end I/Os held back by the pause are resubmitted.
+    fn unpause_io(&self) -> VecDeque<oneshot::Sender<()>> {
+        let (was_paused, waiters) = {
+            let mut p = self.io_pause.lock();
+            let was_paused = std::mem::replace(&mut p.paused, false);
+            (was_paused, std::mem::take(&mut p.waiters))
+        };
+        if was_paused {
+            let parent = self.parent.clone();
+            if let Some(nexus) = nexus_lookup(&parent) {
+                nexus.paused_io_children.fetch_sub(1);
+            }
+            Reactors::current().send_future(async move {
+                if let Some(nexus) = nexus_lookup(&parent) {
+                    nexus.resubmit_paused_ios().await;
+                }
+            });
+        }
+        waiters
+    }
+
+    /// Waits until the child I/O is not paused.
+    async fn wait_io_resumed(&self) -> Result<(), ChildIoError> {
+        let r = {
+            let mut p = self.io_pause.lock();
+            if !p.paused {
+                return Ok(());
+            }
+            let (s, r) = oneshot::channel();
+            p.waiters.push_back(s);
+            r
+        };
+
+        r.await.map_err(|_| ChildIoError::Paused {
+            name: self.name.clone(),
+        })
+    }
+
+    /// Returns the configured timeout of the I/Os submitted to this child,
+    /// if any.
+    fn io_timeout(&self) -> Option<Duration> {
+        match Config::get().nexus_opts.child_io_timeout_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Awaits an I/O submitted to this child through the given handle, for at
+    /// most the configured I/O timeout. On timeout, the I/O is aborted by
+    /// resetting the device of the child, and still waited for: it borrows
+    /// the buffer of its caller, and must not complete once the caller gave
+    /// up on it, e.g. for a timed out write to land after its retry. The child
+    /// is faulted, if configured so.
+    async fn with_io_timeout<T, F>(
+        &self,
+        hdl: &dyn BlockDeviceHandle,
+        io: F,
+    ) -> Result<T, ChildIoError>
+    where
+        F: Future<Output = T>,
+    {
+        let Some(timeout) = self.io_timeout() else {
+            return Ok(io.await);
+        };
+
+        futures::pin_mut!(io);
+        if let Either::Left((res, _)) =
+            select(io.as_mut(), runtime::timer(timeout)).await
+        {
+            return Ok(res);
+        }
+
+        error!("{self:?}: I/O timed out after {timeout:?}, aborting it");
+
+        if Config::get().nexus_opts.child_io_timeout_fault {
+            let parent = self.parent.clone();
+            let name = self.name.clone();
+            Reactors::master().send_future(async move {
+                if let Some(nexus) = nexus_lookup_mut(&parent) {
+                    if let Err(error) =
+                        nexus.fault_child(&name, FaultReason::IoError).await
+                    {
+                        error!(
+                            "Failed to fault child '{name}' after I/O \
+                            timeout: {error}"
+                        );
+                    }
+                }
+            });
+        }
+
+        if let Err(error) =
+            hdl.reset(Self::io_timeout_reset_done, std::ptr::null_mut())
+        {
+            error!("{self:?}: failed to reset after I/O timeout: {error}");
+        }
+        io.await;
+
+        Err(ChildIoError::Timeout {
+            name: self.name.clone(),
+        })
+    }
+
+    /// Completion callback of the reset aborting a timed out I/O.
+    fn io_timeout_reset_done(
+        dev: &dyn BlockDevice,
+        status: IoCompletionStatus,
+        _ctx: IoCompletionCallbackArg,
+    ) {
+        if status != IoCompletionStatus::Success {
+            error!(
+                "{}: reset after I/O timeout failed: {status:?}",
+                dev.device_name()
+            );
+        }
+    }
+
+    /// Enables or disables the throttling of the I/Os submitted to this child
+    /// via `read_at` and `write_at`, e.g. to cap the rebuild traffic of the
+    /// child without affecting the other children. The limits are the ones
+    /// configured in the nexus options when the throttle is enabled.
+    pub fn set_throttled(&self, throttled: bool) {
+        let mut throttle = self.throttle.lock();
+        if throttled {
+            let limits = ChildThrottleLimits::from_config();
+            info!("{self:?}: throttling I/Os: {limits:?}");
+            *throttle = Some(ChildThrottle::new(limits));
+        } else if throttle.take().is_some() {
+            info!("{self:?}: no longer throttling I/Os");
+        }
+    }
+
+    /// Returns the limits of the throttle of this child, if it is throttled.
+    pub fn throttle(&self) -> Option<ChildThrottleLimits> {
+        self.throttle.lock().as_ref().map(|t| t.limits())
+    }
+
+    /// Takes an I/O of the given size in bytes from the throttle of this
+    /// child, if any, and returns how long the I/O has to wait before being
+    /// submitted.
+    pub(crate) fn throttle_delay(&self, len: u64) -> Duration {
+        self.throttle
+            .lock()
+            .as_mut()
+            .map_or(Duration::ZERO, |throttle| throttle.admit(len))
+    }
+
+    /// Waits for an I/O of the given size in bytes to be admitted by the
+    /// throttle of this child, if any. The wait is scheduled on the tokio
+    /// runtime so that the reactor is not held.
+    async fn throttle_io(&self, len: u64) {
+        let delay = self.throttle_delay(len);
+        if !delay.is_zero() {
+            mayastor_sleep(delay).await.ok();
+        }
+    }
+
+    /// Checks that a single I/O of the given size in bytes can be submitted
+    /// to this child, both with respect to the configured cap and to the
+    /// limit of the device.
+    fn check_transfer_size(&self, requested: u64) -> Result<(), ChildIoError> {
+        let cap = match Config::get().nexus_opts.max_child_io_size {
+            0 => None,
+            cap => Some(cap),
+        };
+        let dev_max = self
+            .device
+            .as_ref()
+            .and_then(|d| d.max_transfer_size())
+            .filter(|max| *max > 0);
+
+        match cap.into_iter().chain(dev_max).min() {
+            Some(max) if requested > max => {
+                Err(ChildIoError::TransferTooLarge {
+                    name: self.name.clone(),
+                    requested,
+                    max,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the contents of the buffer to this child.
+    pub async fn write_at(
+        &self,
+        offset: u64,
+        buf: &DmaBuf,
+    ) -> Result<u64, ChildIoError> {
+        self.wait_io_resumed().await?;
+
+        if self.is_read_only() {
+            return Err(ChildIoError::ReadOnly {
+                name: self.name.clone(),
+            });
+        }
+
+        // In active/passive mode, only the active child accepts writes.
+        if !self.is_active_path
+            && nexus_lookup(&self.parent)
+                .map_or(false, |n| n.is_active_passive())
+        {
+            return Err(ChildIoError::NotActivePath {
+                name: self.name.clone(),
+            });
+        }
+
+        self.check_transfer_size(buf.len())?;
+        self.throttle_io(buf.len()).await;
+
+        let _order = self.write_order.read().await;
+
+        let hdl = self.get_io_handle_nonblock().await.map_err(|_| {
+            ChildIoError::InvalidDescriptor {
+                name: self.name.clone(),
+            }
+        })?;
+
+        self.with_io_timeout(&*hdl, hdl.write_at(offset, buf))
+            .await?
+            .context(WriteError {
+                name: self.name.clone(),
+            })
+    }
+
+    /// Flushes this child.
+    pub async fn flush(&self) -> Result<(), ChildIoError> {
+        self.wait_io_resumed().await?;
+
+        let hdl = self.get_io_handle_nonblock().await.map_err(|_| {
+            ChildIoError::InvalidDescriptor {
+                name: self.name.clone(),
+            }
+        })?;
+
+        hdl.flush_io_async().await.context(FlushError {
+            name: self.name.clone(),
+        })
+    }
+
+    /// Unmaps the given range of this child, in bytes, for the device to
+    /// reclaim its space. The range must be aligned on the block size of the
+    /// device.
+    pub async fn unmap(
+        &self,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), ChildIoError> {
+        self.wait_io_resumed().await?;
+
+        if self.is_read_only() {
+            return Err(ChildIoError::ReadOnly {
+                name: self.name.clone(),
+            });
+        }
+
+        // In active/passive mode, only the active child accepts writes.
+        if !self.is_active_path
+            && nexus_lookup(&self.parent)
+                .map_or(false, |n| n.is_active_passive())
+        {
+            return Err(ChildIoError::NotActivePath {
+                name: self.name.clone(),
+            });
+        }
+
+        let _order = self.write_order.read().await;
+
+        let hdl = self.get_io_handle_nonblock().await.map_err(|_| {
+            ChildIoError::InvalidDescriptor {
+                name: self.name.clone(),
+            }
+        })?;
+
+        let dev = hdl.get_device();
+        if !dev.io_type_supported(IoType::Unmap) {
+            return Err(ChildIoError::Unsupported {
+                name: self.name.clone(),
+                op: "Unmap",
+            });
+        }
+
+        let block_len = dev.block_len();
+        if offset % block_len != 0 || len % block_len != 0 {
+            return Err(ChildIoError::UnmapError {
+                source: CoreError::UnmapDispatch {
+                    source: Errno::EINVAL,
+                    offset,
+                    len,
+                },
+                name: self.name.clone(),
+            });
+        }
+
+        self.with_io_timeout(
+            &*hdl,
+            hdl.unmap_blocks_async(offset / block_len, len / block_len),
+        )
+        .await?
+        .context(UnmapError {
+            name: self.name.clone(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Makes all the writes previously submitted to this child via
+    /// `write_at` durable. Unlike a plain flush, the barrier first waits for
+    /// the in-flight writes to complete, and the writes submitted after it
+    /// wait for the barrier to complete before being issued.
+    pub async fn write_barrier(&self) -> Result<(), ChildIoError> {
+        let _order = self.write_order.write().await;
+        self.flush().await
+    }
+
+    /// Writes the contents of the buffer to this child, submitting the write
+    /// from the reactor of the given core.
+    /// I/O channels are per core, so pinning the I/O of a child to a single
+    /// core keeps its channel hot in that core's cache, and avoids
+    /// contention between cores on the same channel.
+    /// The buffer is moved to that core for the duration of the write, and is
+    /// handed back along with the number of bytes written.
+    pub async fn write_at_on(
+        &self,
+        core: u32,
+        offset: u64,
+        buf: DmaBuf,
+    ) -> Result<(u64, DmaBuf), ChildIoError> {
+        let (parent, name) = (self.parent.clone(), self.name.clone());
+        self.submit_on(core, async move {
+            let child = Self::lookup_on_core(&parent, &name)?;
+            let n = child.write_at(offset, &buf).await?;
+            Ok((n, buf))
+        })
+        .await
+    }
+
+    /// Reads from this child into the buffer, submitting the read from the
+    /// reactor of the given core. See `write_at_on`.
+    /// As with `read_at`, the number of bytes handed back may be short of the
+    /// length of the buffer.
+    pub async fn read_at_on(
+        &self,
+        core: u32,
+        offset: u64,
+        mut buf: DmaBuf,
+    ) -> Result<(u64, DmaBuf), ChildIoError> {
+        let (parent, name) = (self.parent.clone(), self.name.clone());
+        self.submit_on(core, async move {
+            let child = Self::lookup_on_core(&parent, &name)?;
+            let n = child.read_at(offset, &mut buf).await?;
+            Ok((n, buf))
+        })
+        .await
+    }
+
+    /// Looks up a child by the names of its nexus and of itself, from the
+    /// core the I/O is submitted from, so that no reference to the child
+    /// crosses cores.
+    fn lookup_on_core<'a>(
+        parent: &str,
+        name: &str,
+    ) -> Result<&'a NexusChild<'a>, ChildIoError> {
+        nexus_lookup(parent)
+            .and_then(|n| n.child(name).ok())
+            .ok_or_else(|| ChildIoError::InvalidDescriptor {
+                name: name.to_string(),
+            })
+    }
+
+    /// Runs an I/O on this child from the reactor of the given core. The I/O
+    /// owns everything it needs, down to the names it looks the child up by,
+    /// so that it can safely outlive the caller.
+    async fn submit_on<Fut>(
+        &self,
+        core: u32,
+        io: Fut,
+    ) -> Result<(u64, DmaBuf), ChildIoError>
+    where
+        Fut: Future<Output = Result<(u64, DmaBuf), ChildIoError>> + 'static,
+    {
+        if Reactors::get_by_core(core).is_none() {
+            return Err(ChildIoError::InvalidCore {
+                name: self.name.clone(),
+                core,
+            });
+        }
+
+        if core == Cores::current() {
+            return io.await;
+        }
+
+        let r = Reactor::spawn_at_core(core, io).map_err(|_| {
+            ChildIoError::InvalidCore {
+                name: self.name.clone(),
+                core,
+            }
+        })?;
+
+        r.await.map_err(|_| ChildIoError::Cancelled {
+            name: self.name.clone(),
+            core,
+        })?
+    }
+
+    /// Reads from this child into the buffer.
+    ///
+    /// Returns the number of bytes actually read. A device may complete a
+    /// read with fewer bytes than the length of the buffer, in which case the
+    /// short count is returned as is and the remainder of the buffer is
+    /// zeroed: it is up to the caller to retry reading the remainder.
+    pub async fn read_at(
+        &self,
+        offset: u64,
+        buf: &mut DmaBuf,
+    ) -> Result<u64, ChildIoError> {
+        self.wait_io_resumed().await?;
+        self.check_transfer_size(buf.len())?;
+        self.throttle_io(buf.len()).await;
+
+        let hdl = self.get_io_handle_nonblock().await.map_err(|_| {
+            ChildIoError::InvalidDescriptor {
+                name: self.name.clone(),
+            }
+        })?;
+
+        self.read_with_handle(hdl, offset, buf).await
+    }
+
+    /// Reads from this child into the buffer through the given I/O handle of
+    /// its device, as `read_at` does. Only built for the tests, to read
+    /// through a device handle completing short reads.
+    #[cfg(feature = "io-engine-testing")]
+    pub async fn read_at_with(
+        &self,
+        hdl: Box<dyn BlockDeviceHandle>,
+        offset: u64,
+        buf: &mut DmaBuf,
+    ) -> Result<u64, ChildIoError> {
+        self.read_with_handle(hdl, offset, buf).await
+    }
+
+    /// Reads from this child into the buffer through the given I/O handle of
+    /// its device, accounting for short reads.
+    async fn read_with_handle(
+        &self,
+        hdl: Box<dyn BlockDeviceHandle>,
+        offset: u64,
+        buf: &mut DmaBuf,
+    ) -> Result<u64, ChildIoError> {
+        let len = buf.len();
+        let bytes_read = self
+            .with_io_timeout(&*hdl, hdl.read_at(offset, buf))
+            .await?
+            .context(ReadError {
+                name: self.name.clone(),
+            })?;
+
+        if bytes_read < len {
+            debug!(
+                "{self:?}: short read at offset {offset}: \
+                {bytes_read} of {len} bytes"
+            );
+            buf.as_mut_slice()[bytes_read as usize ..].fill(0);
+        }
+
+        Ok(min(bytes_read, len))
+    }
+
+    /// Compares the given range of this child, in bytes, with the same range
+    /// of another child. Returns the offset of the first byte which differs,
+    /// or `None` if the data of both children match. Only the bytes read from
+    /// both children are compared, the rest of a short read is read again.
+    /// Both children must be accessible. The range is read without pausing
+    /// the I/O of the nexus, so a write racing with the comparison can be
+    /// reported as a mismatch.
+    pub async fn compare_at(
+        &self,
+        other: &Self,
+        offset: u64,
+        len: u64,
+    ) -> Result<Option<u64>, ChildIoError> {
+        for child in [self, other] {
+            if let Some(reason) = child.inaccessible_reason() {
+                return Err(ChildIoError::Inaccessible {
+                    name: child.name.clone(),
+                    reason,
+                });
+            }
+        }
+
+        let alignment = [self, other]
+            .iter()
+            .filter_map(|c| c.get_device().ok())
+            .map(|d| d.alignment())
+            .max()
+            .unwrap_or_default();
+        let new_buf = |size: u64| {
+            DmaBuf::new(size, alignment).map_err(|_| ChildIoError::ReadError {
+                source: CoreError::DmaAllocationFailed {
+                    size,
+                },
+                name: self.name.clone(),
+            })
+        };
+
+        let end = offset + len;
+        let mut pos = offset;
+        while pos < end {
+            let size = min(COMPARE_SEGMENT_SIZE, end - pos);
+            let mut ours = new_buf(size)?;
+            let mut theirs = new_buf(size)?;
+            let ours_read = self.read_at(pos, &mut ours).await?;
+            let theirs_read = other.read_at(pos, &mut theirs).await?;
+            if ours_read == 0 || theirs_read == 0 {
+                let child = if ours_read == 0 { self } else { other };
+                return Err(ChildIoError::NoDataRead {
+                    name: child.name.clone(),
+                    offset: pos,
+                });
+            }
+
+            let read = min(ours_read, theirs_read) as usize;
+            if let Some(i) = ours.as_slice()[.. read]
+                .iter()
+                .zip(&theirs.as_slice()[.. read])
+                .position(|(a, b)| a != b)
+            {
+                return Ok(Some(pos + i as u64));
+            }
+            pos += read as u64;
+        }
+        Ok(None)
+    }
+
     /// TODO
     pub fn get_device_name(&self) -> Option<String> {
         self.device.as_ref().map(|d| d.device_name())
     }
 
+    /// Determines if the child is the active I/O path of its nexus.
+    #[inline]
+    pub fn is_active_path(&self) -> bool {
+        self.is_active_path
+    }
+
+    /// Designates the child as the active I/O path of its nexus, or not.
+    #[inline]
+    pub(crate) fn set_active_path(&mut self, active: bool) {
+        self.is_active_path = active;
+    }
+
+    /// Returns the name of the module claiming the bdev of this child, if
+    /// the child has a bdev and it is claimed.
+    pub fn claim_owner(&self) -> Option<String> {
+        let name = self.get_device_name()?;
+        UntypedBdev::lookup_by_name(&name)?.first_claim_module_name()
+    }
+
     /// TODO
     pub fn match_device_name(&self, bdev_name: &str) -> bool {
         match self.get_device_name() {
@@ -1348,8 +3239,58 @@ impl<'c> NexusChild<'c> {
         self.io_log.lock().as_ref().map(|log| log.current_channel())
     }
 
+    /// Determines if the child has an active I/O log, which has logged no
+    /// write so far.
+    pub(super) fn is_io_log_clean(&self) -> bool {
+        self.io_log
+            .lock()
+            .as_ref()
+            .map_or(false, |log| log.is_clean())
+    }
+
     /// Determines if the child has an active I/O log.
     pub(crate) fn has_io_log(&self) -> bool {
         self.io_log.lock().is_some()
     }
 }
+
+/// Runs a pass of the scheduled scrub of a child, starting at the given
+/// block. Returns the block the next pass starts at, or `None` if the
+/// schedule must stop as the child no longer exists.
+async fn scrub_pass(
+    nexus_name: &str,
+    child_name: &str,
+    offset_blk: u64,
+) -> Option<u64> {
+    let nexus = nexus_lookup(nexus_name)?;
+    let child = nexus.child(child_name).ok()?;
+    if !child.is_opened() {
+        return Some(offset_blk);
+    }
+    let device = child.get_device().ok()?;
+    let num_blocks = device.num_blocks();
+    let offset_blk = if offset_blk >= num_blocks {
+        0
+    } else {
+        offset_blk
+    };
+    let pass_blks = max(1, SCRUB_PASS_SIZE / device.block_len());
+
+    match child.scrub(offset_blk, pass_blks).await {
+        Ok(failed) if failed >= SCRUB_FAULT_THRESHOLD => {
+            error!(
+                "{child:?}: scrub failed {failed} read(s) from block \
+                {offset_blk}, faulting child"
+            );
+            nexus.retire_child_device(
+                &device.device_name(),
+                FaultReason::IoError,
+                false,
+            );
+        }
+        Ok(_) => {}
+        Err(e) => warn!("{child:?}: failed to scrub: {e}"),
+    }
+
+    Some(offset_blk + pass_blks)
+}
@@ -0,0 +1,136 @@
+use std::{collections::VecDeque, time::Instant};
+
+use crate::core::IoType;
+
+/// Default number of records kept by the error store of a child.
+pub(crate) const NEXUS_ERR_STORE_SIZE: usize = 256;
+
+/// A single failed I/O of a nexus child.
+#[derive(Debug, Clone, Copy)]
+pub struct NexusErrRecord {
+    /// Type of the failed I/O.
+    pub io_op: IoType,
+    /// Offset of the failed I/O in blocks.
+    pub io_offset: u64,
+    /// Number of blocks of the failed I/O.
+    pub io_num_blocks: u64,
+    /// Time at which the failure was recorded.
+    pub timestamp: Instant,
+}
+
+/// Number of failed I/Os of a nexus child, per type of I/O.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    /// Number of failed reads.
+    pub read: usize,
+    /// Number of failed writes.
+    pub write: usize,
+    /// Number of failed I/Os of any type.
+    pub total: usize,
+}
+
+impl ErrorCounts {
+    fn add(&mut self, io_op: IoType) {
+        match io_op {
+            IoType::Read => self.read += 1,
+            IoType::Write => self.write += 1,
+            _ => {}
+        }
+        self.total += 1;
+    }
+}
+
+/// Failed I/Os of a nexus child, both overall and within a recent time
+/// window, to detect bursts of errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorStats {
+    /// Failures since the store was created, including those whose records
+    /// have been overwritten since.
+    pub total: ErrorCounts,
+    /// Failures within the time window, out of the records kept.
+    pub recent: ErrorCounts,
+}
+
+/// Error history of a nexus child. The store is a fixed size ring buffer: once
+/// it is full, the oldest records are overwritten by new ones.
+#[derive(Debug)]
+pub struct NexusErrStore {
+    records: VecDeque<NexusErrRecord>,
+    capacity: usize,
+    /// Failures recorded since the store was created, which keep counting
+    /// once the ring is full.
+    total: ErrorCounts,
+}
+
+impl Default for NexusErrStore {
+    fn default() -> Self {
+        Self::new(NEXUS_ERR_STORE_SIZE)
+    }
+}
+
+impl NexusErrStore {
+    /// Creates a new error store holding at most `capacity` records.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+            total: ErrorCounts::default(),
+        }
+    }
+
+    /// Records a failed I/O.
+    pub fn add_record(
+        &mut self,
+        io_op: IoType,
+        io_offset: u64,
+        io_num_blocks: u64,
+        timestamp: Instant,
+    ) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(NexusErrRecord {
+            io_op,
+            io_offset,
+            io_num_blocks,
+            timestamp,
+        });
+        self.total.add(io_op);
+    }
+
+    /// Returns the recorded failures, oldest first.
+    pub fn records(&self) -> impl Iterator<Item = &NexusErrRecord> {
+        self.records.iter()
+    }
+
+    /// Returns the number of failures recorded at or after `since`.
+    pub fn count_since(&self, since: Instant) -> usize {
+        self.records.iter().filter(|r| r.timestamp >= since).count()
+    }
+
+    /// Returns the statistics of the recorded failures, the recent ones being
+    /// those recorded at or after `since`, or all of them if not given.
+    pub fn stats(&self, since: Option<Instant>) -> ErrorStats {
+        let mut stats = ErrorStats {
+            total: self.total,
+            ..Default::default()
+        };
+        for r in &self.records {
+            if since.map_or(true, |since| r.timestamp >= since) {
+                stats.recent.add(r.io_op);
+            }
+        }
+        stats
+    }
+
+    /// Returns the number of recorded failures.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Determines if no failures have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
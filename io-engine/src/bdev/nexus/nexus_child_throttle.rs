@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use crate::subsys::Config;
+
+/// Limits of the throttle of a nexus child. A limit of 0 means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChildThrottleLimits {
+    /// Maximum number of bytes transferred per second.
+    pub bytes_per_sec: u64,
+    /// Maximum number of I/Os per second.
+    pub iops: u64,
+}
+
+impl ChildThrottleLimits {
+    /// Returns the limits configured in the nexus options.
+    pub(crate) fn from_config() -> Self {
+        let opts = &Config::get().nexus_opts;
+        Self {
+            bytes_per_sec: opts.child_throttle_bytes_per_sec,
+            iops: opts.child_throttle_iops,
+        }
+    }
+
+    /// Checks if any of the limits is set.
+    pub(crate) fn is_limited(&self) -> bool {
+        self.bytes_per_sec > 0 || self.iops > 0
+    }
+}
+
+/// Token bucket refilled at a given rate per second, holding up to one
+/// second worth of tokens.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: u64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            rate,
+            tokens: rate as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes the given number of tokens and returns how long the caller has
+    /// to wait for them to become available. The bucket may go into debt,
+    /// so that a request larger than the bucket is not held forever.
+    fn take(&mut self, count: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.refilled_at = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate as f64)
+            .min(self.rate as f64);
+        self.tokens -= count as f64;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate as f64)
+        }
+    }
+}
+
+/// Throttle of the I/Os submitted to a nexus child by the nexus itself.
+#[derive(Debug)]
+pub(crate) struct ChildThrottle {
+    limits: ChildThrottleLimits,
+    bytes: Option<TokenBucket>,
+    ios: Option<TokenBucket>,
+}
+
+impl ChildThrottle {
+    /// Creates a new throttle with the given limits.
+    pub(crate) fn new(limits: ChildThrottleLimits) -> Self {
+        let bucket = |rate| (rate > 0).then(|| TokenBucket::new(rate));
+        Self {
+            limits,
+            bytes: bucket(limits.bytes_per_sec),
+            ios: bucket(limits.iops),
+        }
+    }
+
+    /// Returns the limits of the throttle.
+    pub(crate) fn limits(&self) -> ChildThrottleLimits {
+        self.limits
+    }
+
+    /// Accounts for an I/O of the given size in bytes and returns how long
+    /// it must be delayed to stay within the limits.
+    pub(crate) fn admit(&mut self, len: u64) -> Duration {
+        let now = Instant::now();
+        let bytes = self.bytes.as_mut().map(|b| b.take(len, now));
+        let ios = self.ios.as_mut().map(|b| b.take(1, now));
+        bytes.into_iter().chain(ios).max().unwrap_or_default()
+    }
+}
@@ -15,6 +15,7 @@ use spdk_rs::{
         SPDK_NVME_SC_ABORTED_SQ_DELETION,
         SPDK_NVME_SC_CAPACITY_EXCEEDED,
         SPDK_NVME_SC_INVALID_OPCODE,
+        SPDK_NVME_SC_LBA_OUT_OF_RANGE,
         SPDK_NVME_SC_RESERVATION_CONFLICT,
     },
     BdevIo,
@@ -178,7 +179,9 @@ impl<'n> NexusBio<'n> {
 
     /// TODO
     pub(super) fn submit_request(mut self) {
-        if self.channel().is_frozen() {
+        // I/Os of all types are held back while the I/O of a child is
+        // paused, and resubmitted once it is resumed.
+        if self.channel().is_frozen() || self.nexus().is_child_io_paused() {
             let s = self.clone();
             self.channel_mut().freeze_io_submission(s);
             return;
@@ -186,6 +189,19 @@ impl<'n> NexusBio<'n> {
 
         if let Err(_e) = match self.io_type() {
             IoType::Read => self.readv(),
+            // the padded tail of the nexus has no backing blocks on its
+            // smallest child, so nothing can be written there
+            IoType::Write | IoType::WriteZeros | IoType::Unmap
+                if self.overlaps_padded_tail() =>
+            {
+                warn!("{self:?}: rejecting write to the padded tail");
+                self.fail_nvme_status(NvmeStatus::Generic(
+                    SPDK_NVME_SC_LBA_OUT_OF_RANGE,
+                ));
+                Err(CoreError::NotSupported {
+                    source: Errno::ERANGE,
+                })
+            }
             // these IOs are submitted to all the underlying children
             IoType::Write
             | IoType::WriteZeros
@@ -359,6 +375,31 @@ impl<'n> NexusBio<'n> {
         self.offset() + self.data_ent_offset()
     }
 
+    /// Determines if the I/O overlaps the padded tail of the nexus.
+    #[inline]
+    fn overlaps_padded_tail(&self) -> bool {
+        self.offset() + self.num_blocks() > self.channel().padded_tail_start()
+    }
+
+    /// Returns the number of blocks of the I/O ahead of the padded tail of
+    /// the nexus, i.e. which are backed by the children.
+    #[inline]
+    fn num_blocks_ahead_of_padded_tail(&self) -> u64 {
+        self.channel()
+            .padded_tail_start()
+            .saturating_sub(self.offset())
+            .min(self.num_blocks())
+    }
+
+    /// Zeroes the data buffers of the I/O from the given byte offset on.
+    fn zero_fill_from(&self, mut skip: u64) {
+        for iov in self.iovs_mut() {
+            let len = iov.len();
+            iov[skip.min(len) ..].fill(0);
+            skip = skip.saturating_sub(len);
+        }
+    }
+
     /// submit a read operation to one of the children of this nexus
     #[inline]
     fn submit_read(
@@ -368,10 +409,19 @@ impl<'n> NexusBio<'n> {
         #[cfg(feature = "fault-injection")]
         self.inject_submission_error(hdl)?;
 
+        // only the blocks ahead of the padded tail are read from the child,
+        // the rest of the buffers reads as zeroes; reads entirely within the
+        // tail never get here (see `do_readv`)
+        let num_blocks = self.num_blocks_ahead_of_padded_tail();
+        debug_assert!(num_blocks > 0);
+        if num_blocks < self.num_blocks() {
+            self.zero_fill_from(num_blocks * self.nexus().block_len());
+        }
+
         hdl.readv_blocks(
             self.iovs_mut(),
             self.effective_offset(),
-            self.num_blocks(),
+            num_blocks,
             ReadOptions::None,
             Self::child_completion,
             self.as_ptr().cast(),
@@ -422,6 +472,13 @@ impl<'n> NexusBio<'n> {
     /// In case of submission error the requiest is transparently resubmitted
     /// to the next available replica.
     fn do_readv(&mut self) -> Result<(), CoreError> {
+        // reads entirely within the padded tail need no child
+        if self.num_blocks_ahead_of_padded_tail() == 0 {
+            self.zero_fill_from(0);
+            self.ok();
+            return Ok(());
+        }
+
         match self.__do_readv_one() {
             Err(e) => {
                 match e {
@@ -701,12 +758,7 @@ impl<'n> NexusBio<'n> {
         child_device: &str,
         io_status: IoCompletionStatus,
     ) -> Option<IOLogChannel> {
-        let reason = match io_status {
-            IoCompletionStatus::LvolError(LvolFailure::NoSpace) => {
-                FaultReason::NoSpace
-            }
-            _ => FaultReason::IoError,
-        };
+        let reason = FaultReason::from_io_status(io_status);
 
         self.channel_mut().fault_device(child_device, reason)
     }
@@ -776,6 +828,12 @@ impl<'n> NexusBio<'n> {
             );
         }
 
+        if let Some(c) =
+            self.nexus().lookup_child_by_device(&child.device_name())
+        {
+            c.record_io_error(self.io_type(), self.offset(), self.num_blocks());
+        }
+
         if let Some(log) = self.fault_device(&child.device_name(), status) {
             self.log_io(&log);
         }
@@ -186,6 +186,15 @@ impl IOLog {
             .clone()
     }
 
+    /// Determines if no write has been logged so far. The I/O of the nexus
+    /// must be paused for the answer to hold.
+    pub(crate) fn is_clean(&self) -> bool {
+        self.channels
+            .lock()
+            .values()
+            .all(|x| x.segments().count_dirty_blks() == 0)
+    }
+
     /// Consumes an I/O log instance and returns the corresponding rebuild map.
     pub(crate) fn finalize(self) -> RebuildMap {
         let segments = self
@@ -0,0 +1,78 @@
+//! Nexus label of a child: identifies the nexus the data of the child belongs
+//! to. The label is stored at the start of the metadata reservation of the
+//! child device (see `core::partition`), outside of the data partition.
+
+use std::fmt::{Display, Formatter};
+
+use uuid::Uuid;
+
+use crate::core::partition::METADATA_RESERVATION_OFFSET;
+
+/// Magic bytes identifying a nexus label.
+const LABEL_MAGIC: &[u8; 8] = b"MAYANXLB";
+
+/// Size of an encoded label in bytes: magic, nexus uuid and generation.
+pub const LABEL_SIZE: usize = 8 + 16 + 8;
+
+/// Offset of the label on the child device, in bytes.
+pub const LABEL_OFFSET: u64 = METADATA_RESERVATION_OFFSET;
+
+/// Nexus label of a child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NexusLabel {
+    /// Uuid of the nexus which labelled the child.
+    pub nexus_uuid: Uuid,
+    /// Generation of the nexus which labelled the child. A nexus bumps its
+    /// generation every time it is created.
+    pub generation: u64,
+}
+
+impl Display for NexusLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.nexus_uuid, self.generation)
+    }
+}
+
+impl NexusLabel {
+    /// Creates a new label for the given nexus generation.
+    pub fn new(nexus_uuid: Uuid, generation: u64) -> Self {
+        Self {
+            nexus_uuid,
+            generation,
+        }
+    }
+
+    /// Encodes the label at the start of the given buffer, which must be at
+    /// least `LABEL_SIZE` bytes long.
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[.. 8].copy_from_slice(LABEL_MAGIC);
+        buf[8 .. 24].copy_from_slice(self.nexus_uuid.as_bytes());
+        buf[24 .. LABEL_SIZE].copy_from_slice(&self.generation.to_le_bytes());
+    }
+
+    /// Decodes a label from the start of the given buffer. Returns `None` if
+    /// the buffer does not hold a label, e.g. when the device is blank.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < LABEL_SIZE || &buf[.. 8] != LABEL_MAGIC {
+            return None;
+        }
+
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&buf[8 .. 24]);
+        let mut generation = [0u8; 8];
+        generation.copy_from_slice(&buf[24 .. LABEL_SIZE]);
+
+        Some(Self::new(
+            Uuid::from_bytes(uuid),
+            u64::from_le_bytes(generation),
+        ))
+    }
+
+    /// Determines if a child carrying this label can join the nexus labelled
+    /// with `current`: the child must have been labelled by the same nexus,
+    /// and not by a newer generation of it.
+    pub fn is_compatible(&self, current: &NexusLabel) -> bool {
+        self.nexus_uuid == current.nexus_uuid
+            && self.generation <= current.generation
+    }
+}
@@ -1,5 +1,9 @@
 use super::{IoMode, Nexus, NexusChild};
-use crate::{persistent_store::PersistentStore, sleep::mayastor_sleep};
+use crate::{
+    core::ClusterRange,
+    persistent_store::PersistentStore,
+    sleep::mayastor_sleep,
+};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
@@ -47,6 +51,12 @@ pub struct ChildInfo {
     pub uuid: String,
     /// Child's state of health.
     pub healthy: bool,
+    /// Ranges of the child device, in bytes, written by the nexus while the
+    /// child was out of sync, as logged until the nexus was shut down.
+    /// Only recorded on a clean shutdown, for a later ranged rebuild of the
+    /// child to cover them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dirty_ranges: Option<Vec<ClusterRange>>,
 }
 
 /// Defines the type of persist operations.
@@ -101,9 +111,32 @@ impl<'n> Nexus<'n> {
                         uuid: NexusChild::uuid(c.uri())
                             .expect("Failed to get child UUID."),
                         healthy: c.is_healthy(),
+                        dirty_ranges: None,
                     };
                     nexus_info.children.push(child_info);
                 });
+
+                // Keep the writes logged for the children still out of sync
+                // by the previous incarnation of the nexus, if it recorded
+                // them all, i.e. was shut down cleanly.
+                let key = self.store_key(&persistent_nexus_info);
+                let previous = PersistentStore::get(&key)
+                    .await
+                    .ok()
+                    .and_then(|v| serde_json::from_value::<NexusInfo>(v).ok())
+                    .filter(|info| info.clean_shutdown);
+                let nexus_info = persistent_nexus_info.inner_mut();
+                if let Some(previous) = previous {
+                    for c in
+                        nexus_info.children.iter_mut().filter(|c| !c.healthy)
+                    {
+                        if let Some(p) =
+                            previous.children.iter().find(|p| p.uuid == c.uuid)
+                        {
+                            c.dirty_ranges = p.dirty_ranges.clone();
+                        }
+                    }
+                }
             }
             PersistOp::AddChild {
                 child_uri,
@@ -116,6 +149,7 @@ impl<'n> Nexus<'n> {
                     uuid: NexusChild::uuid(child_uri)
                         .expect("Failed to get child UUID."),
                     healthy: *healthy,
+                    dirty_ranges: None,
                 };
 
                 // Check if there is a child with the same UUID already
@@ -149,6 +183,9 @@ impl<'n> Nexus<'n> {
                 nexus_info.children.iter_mut().for_each(|c| {
                     if c.uuid == uuid {
                         c.healthy = *healthy;
+                        if c.healthy {
+                            c.dirty_ranges = None;
+                        }
                     }
                 });
             }
@@ -170,6 +207,9 @@ impl<'n> Nexus<'n> {
                 nexus_info.children.iter_mut().for_each(|c| {
                     if c.uuid == uuid {
                         c.healthy = *healthy;
+                        if c.healthy {
+                            c.dirty_ranges = None;
+                        }
                     }
                 });
             }
@@ -178,6 +218,23 @@ impl<'n> Nexus<'n> {
                 // child state information.
                 // This should only be called when destroying a nexus.
                 nexus_info.clean_shutdown = true;
+
+                // Record the writes logged for the children out of sync, for
+                // a ranged rebuild of the nexus, once recreated, to cover
+                // them.
+                self.children_iter().for_each(|c| {
+                    let Some(map) = c.stop_io_log() else {
+                        return;
+                    };
+                    let Some(uuid) = NexusChild::uuid(c.uri()) else {
+                        return;
+                    };
+                    if let Some(info) =
+                        nexus_info.children.iter_mut().find(|i| i.uuid == uuid)
+                    {
+                        info.dirty_ranges = Some(map.dirty_ranges());
+                    }
+                });
             }
         }
 
@@ -203,16 +260,38 @@ impl<'n> Nexus<'n> {
         }
     }
 
+    /// Returns the ranges of the given child, in bytes of its device, which
+    /// were logged as written by the previous incarnation of the nexus, if
+    /// it logged them.
+    pub(crate) async fn persisted_dirty_ranges(
+        &self,
+        child_uri: &str,
+    ) -> Option<Vec<ClusterRange>> {
+        let uuid = NexusChild::uuid(child_uri)?;
+        self.nexus_info
+            .lock()
+            .await
+            .inner
+            .children
+            .iter()
+            .find(|c| c.uuid == uuid)
+            .and_then(|c| c.dirty_ranges.clone())
+    }
+
+    // Returns the key the nexus info is stored with: the key provided, if
+    // any, or the nexus uuid otherwise.
+    fn store_key(&self, info: &PersistentNexusInfo) -> String {
+        match &info.key {
+            Some(k) => k.clone(),
+            None => self.uuid().to_string(),
+        }
+    }
+
     // Saves the nexus info to the store. This is integral to ensuring data
     // consistency across restarts of Mayastor. Therefore, keep retrying
     // until successful.
     async fn save(&self, info: &PersistentNexusInfo) -> Result<(), Error> {
-        // If a key has been provided, use it to store the NexusInfo; use the
-        // nexus uuid as the key otherwise.
-        let key = match &info.key {
-            Some(k) => k.clone(),
-            None => self.uuid().to_string(),
-        };
+        let key = self.store_key(info);
 
         let mut retry = PersistentStore::retries();
         loop {
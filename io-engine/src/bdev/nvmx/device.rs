@@ -189,6 +189,10 @@ impl BlockDevice for NvmeBlockDevice {
         self.ns.alignment()
     }
 
+    fn max_transfer_size(&self) -> Option<u64> {
+        Some(self.ns.max_io_xfer_size())
+    }
+
     fn io_type_supported(&self, io_type: IoType) -> bool {
         // bdev_nvme_io_type_supported
         match io_type {
@@ -926,6 +926,8 @@ fn child_reason_to_str_v1(r: v1::nexus::ChildStateReason) -> &'static str {
         v1::nexus::ChildStateReason::ConfigInvalid => "config invalid",
         v1::nexus::ChildStateReason::RebuildFailed => "rebuild failed",
         v1::nexus::ChildStateReason::IoFailure => "I/O failure",
+        v1::nexus::ChildStateReason::MediaFailure => "media failure",
+        v1::nexus::ChildStateReason::TransportFailure => "transport failure",
         v1::nexus::ChildStateReason::ByClient => "by client",
         v1::nexus::ChildStateReason::OutOfSync => "out of sync",
         v1::nexus::ChildStateReason::NoSpace => "no space",
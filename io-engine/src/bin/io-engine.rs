@@ -248,6 +248,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     info!("{}", fmt_package_info!());
 
+    // The runtime options must be set before anything builds the runtime.
+    let runtime_opts = runtime::RuntimeOpts::default();
+    info!(?runtime_opts, "runtime options");
+    runtime::init(runtime_opts)?;
+
     // Handle diagnostics-related commands before initializing the agent.
     // Once diagnostics command is executed (regardless of status), exit the
     // agent.
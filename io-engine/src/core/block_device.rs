@@ -81,6 +81,12 @@ pub trait BlockDevice {
     /// Returns aligment of the device.
     fn alignment(&self) -> u64;
 
+    /// Returns the maximum size in bytes of a single I/O to the device, if
+    /// the device limits it.
+    fn max_transfer_size(&self) -> Option<u64> {
+        None
+    }
+
     /// Checks whether target I/O type is supported by the device.
     fn io_type_supported(&self, io_type: IoType) -> bool;
 
@@ -165,7 +171,9 @@ pub trait BlockDeviceHandle {
     /// TODO
     fn dma_malloc(&self, size: u64) -> Result<DmaBuf, DmaError>;
 
-    /// TODO
+    /// Reads into the buffer from the device, starting at the given offset in
+    /// bytes. Returns the number of bytes read, which may be less than the
+    /// length of the buffer if the device completes a short read.
     #[deprecated(note = "use read_buf_blocks_async()")]
     async fn read_at(
         &self,
@@ -501,6 +509,50 @@ pub trait BlockDeviceHandle {
         cb_arg: IoCompletionCallbackArg,
     ) -> Result<(), CoreError>;
 
+    /// Flushes the device.
+    ///
+    /// Operation is performed asynchronously; I/O completion status is wrapped
+    /// into `CoreError::FlushFailed` in the case of failure.
+    async fn flush_io_async(&self) -> Result<(), CoreError> {
+        let (s, r) = oneshot::channel::<IoCompletionStatus>();
+
+        self.flush_io(block_device_io_completion, cb_arg(s))?;
+
+        match r.await.expect("Failed awaiting at flush_io()") {
+            IoCompletionStatus::Success => Ok(()),
+            status => Err(CoreError::FlushFailed {
+                status,
+            }),
+        }
+    }
+
+    /// Unmaps the given range of blocks of the device.
+    ///
+    /// Operation is performed asynchronously; a failed unmap is reported as
+    /// `CoreError::UnmapFailed`.
+    async fn unmap_blocks_async(
+        &self,
+        offset_blocks: u64,
+        num_blocks: u64,
+    ) -> Result<(), CoreError> {
+        let (s, r) = oneshot::channel::<IoCompletionStatus>();
+
+        self.unmap_blocks(
+            offset_blocks,
+            num_blocks,
+            block_device_io_completion,
+            cb_arg(s),
+        )?;
+
+        match r.await.expect("Failed awaiting at unmap_blocks()") {
+            IoCompletionStatus::Success => Ok(()),
+            _ => Err(CoreError::UnmapFailed {
+                offset: offset_blocks,
+                len: num_blocks,
+            }),
+        }
+    }
+
     /// Determines if the underlying controller is failed.
     fn is_ctrlr_failed(&self) -> bool {
         false
@@ -46,6 +46,7 @@ use crate::{
     core::{
         nic,
         reactor::{Reactor, ReactorState, Reactors},
+        runtime,
         Cores,
         MayastorFeatures,
         Mthread,
@@ -455,6 +456,9 @@ impl Default for MayastorEnvironment {
     }
 }
 
+/// Maximum time to wait for the background tasks to stop during shutdown.
+const SHUTDOWN_TASKS_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// The actual routine which does the mayastor shutdown.
 /// Must be called on the same thread which did the init.
 async fn do_shutdown(arg: *mut c_void) {
@@ -488,12 +492,36 @@ async fn do_shutdown(arg: *mut c_void) {
     if let Some(reg) = Registration::get() {
         reg.fini();
     }
+    // Stop the cooperative background tasks before tearing down anything
+    // they may be using.
+    if !runtime::await_tasks_drained(SHUTDOWN_TASKS_DRAIN_TIMEOUT).await {
+        warn!(
+            "Background tasks did not stop within {:?}",
+            SHUTDOWN_TASKS_DRAIN_TIMEOUT
+        );
+    }
     nexus::shutdown_nexuses().await;
     crate::rebuild::shutdown_snapshot_rebuilds().await;
+    // Let the tasks in flight on the runtime, e.g. the snapshot and rebuild
+    // ones, complete before the pools and the subsystems go away.
+    if !runtime::drain_await(SHUTDOWN_TASKS_DRAIN_TIMEOUT).await {
+        warn!(
+            "Runtime tasks did not complete within {:?}",
+            SHUTDOWN_TASKS_DRAIN_TIMEOUT
+        );
+    }
+    // The pools are exported while the runtime still accepts tasks, as the
+    // LVM commands run on it.
     crate::lvs::Lvs::export_all().await;
     if MayastorFeatures::get_features().lvm() {
         crate::lvm::VolumeGroup::export_all().await;
     }
+    if !runtime::shutdown_await(SHUTDOWN_TASKS_DRAIN_TIMEOUT).await {
+        warn!(
+            "Runtime tasks did not complete within {:?}",
+            SHUTDOWN_TASKS_DRAIN_TIMEOUT
+        );
+    }
 
     unsafe {
         spdk_rpc_finish();
@@ -70,6 +70,25 @@ impl InjectIoCtx {
         }
     }
 
+    /// Creates a context for an I/O which carries no data, e.g. a flush.
+    #[inline(always)]
+    pub fn without_iovs<D: Into<InjectIoDevice>>(
+        domain: FaultDomain,
+        dev: D,
+        io_type: IoType,
+        offset: u64,
+        num_blocks: u64,
+    ) -> Self {
+        Self {
+            domain,
+            dev: dev.into(),
+            range: offset .. offset + num_blocks,
+            io_type,
+            iovs: std::ptr::null_mut(),
+            iovs_len: 0,
+        }
+    }
+
     /// TODO
     #[inline(always)]
     pub fn is_valid(&self) -> bool {
@@ -104,6 +123,7 @@ impl InjectIoCtx {
             FaultIoOperation::ReadWrite => {
                 self.io_type == IoType::Read || self.io_type == IoType::Write
             }
+            FaultIoOperation::Flush => self.io_type == IoType::Flush,
         }
     }
 
@@ -382,6 +382,7 @@ fn parse_fault_io_type(
         "read" | "r" | "Read" => FaultIoOperation::Read,
         "write" | "w" | "Write" => FaultIoOperation::Write,
         "read_write" | "rw" | "ReadWrite" => FaultIoOperation::ReadWrite,
+        "flush" | "f" | "Flush" => FaultIoOperation::Flush,
         _ => {
             return Err(FaultInjectionError::UnknownParameter {
                 name: k.to_string(),
@@ -77,12 +77,38 @@ pub use thread::Mthread;
 
 use crate::subsys::NvmfError;
 pub use snapshot::{
+    AncestryNode,
     CloneParams,
     CloneXattrs,
+    ClusterRange,
+    DestroyPlan,
+    DestroyTreeReport,
+    PoolTopology,
+    PruneReport,
+    RestoreXattrs,
+    SnapshotBatchOutcome,
+    SnapshotCacheXattrs,
     SnapshotDescriptor,
+    SnapshotDescriptorError,
+    SnapshotGroupXattrs,
+    SnapshotHookId,
+    SnapshotLabel,
+    SnapshotLabelXattrs,
     SnapshotOps,
+    SnapshotPage,
     SnapshotParams,
+    SnapshotPostCreateHook,
+    SnapshotPreCreateHook,
+    SnapshotReplicationXattrs,
+    SnapshotRetentionXattrs,
+    SnapshotUsageBreakdown,
     SnapshotXattrs,
+    TopologyEdge,
+    TopologyEdgeKind,
+    TopologyNode,
+    TopologyNodeKind,
+    TreeUsageSummary,
+    UNCATEGORIZED_RETENTION_CLASS,
 };
 
 use spdk_rs::libspdk::SPDK_NVME_SC_CAPACITY_EXCEEDED;
@@ -276,6 +302,10 @@ pub enum CoreError {
     },
     #[snafu(display("Reset failed"))]
     ResetFailed {},
+    #[snafu(display("Flush failed with status {:?}", status))]
+    FlushFailed {
+        status: IoCompletionStatus,
+    },
     #[snafu(display(
         "Write zeroes failed at offset {} length {}",
         offset,
@@ -285,6 +315,11 @@ pub enum CoreError {
         offset: u64,
         len: u64,
     },
+    #[snafu(display("Unmap failed at offset {} length {}", offset, len))]
+    UnmapFailed {
+        offset: u64,
+        len: u64,
+    },
     #[snafu(display("NVMe Admin command {:x}h failed: {}", opcode, source))]
     NvmeAdminFailed {
         source: Errno,
@@ -414,9 +449,15 @@ impl ToErrno for CoreError {
             | Self::ResetFailed {
                 ..
             }
+            | Self::FlushFailed {
+                ..
+            }
             | Self::WriteZeroesFailed {
                 ..
             }
+            | Self::UnmapFailed {
+                ..
+            }
             | Self::NvmeIoPassthruFailed {
                 ..
             }
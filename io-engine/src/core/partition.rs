@@ -81,6 +81,13 @@ pub fn calc_data_partition(
     Some((data_start, data_end, req_blocks))
 }
 
+/// Returns the number of the first block past the data partition of a device
+/// of the given geometry, i.e. where its backup GPT tables begin.
+pub fn calc_data_end(num_blocks: u64, block_size: u64) -> u64 {
+    let gpt_blocks = bytes_to_alinged_blocks(GPT_TABLE_SIZE, block_size);
+    num_blocks.saturating_sub(gpt_blocks + 1)
+}
+
 /// Converts an offset in bytes into offset in number of aligned blocks for the
 /// given block size.
 pub fn bytes_to_alinged_blocks(size: u64, block_size: u64) -> u64 {
@@ -625,6 +625,30 @@ impl Reactor {
     {
         Self::spawn_at(&spdk_rs::Thread::primary(), f)
     }
+
+    /// Spawns a future on the reactor of the given core, and returns a
+    /// receiver for its output.
+    pub fn spawn_at_core<F>(
+        core: u32,
+        f: F,
+    ) -> Result<OnceShotRecv<F::Output>, CoreError>
+    where
+        F: Future + 'static,
+        F::Output: Send + Debug,
+    {
+        let reactor =
+            Reactors::get_by_core(core).ok_or(CoreError::NotSupported {
+                source: Errno::ENODEV,
+            })?;
+
+        let (s, r) = futures::channel::oneshot::channel::<F::Output>();
+        reactor.send_future(async move {
+            if let Err(e) = s.send(f.await) {
+                error!("Failed to send response future result {:?}", e);
+            }
+        });
+        Ok(r)
+    }
 }
 
 /// This implements the poll() method of the for the reactor future. Only the
@@ -3,24 +3,192 @@
 //! runtime to do whatever it needs to do. The tokio threads are
 //! unaffinitized such that they do not run on any of our reactors.
 
-use crate::core::Reactor;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{
+            AtomicBool,
+            AtomicU64,
+            AtomicUsize,
+            Ordering::{Relaxed, SeqCst},
+        },
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{core::Reactor, subsys::config::opts::try_from_env};
 use futures::{channel::oneshot, Future};
-use once_cell::sync::Lazy;
-use tokio::task::JoinHandle;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use snafu::Snafu;
+use tokio::{
+    sync::{watch, Notify, Semaphore},
+    task::{JoinError, JoinHandle},
+};
 
 use super::Mthread;
 
-/// spawn a future on the tokio runtime.
+/// spawn a future on the tokio runtime. The future is dropped without being
+/// polled if the runtime has been shut down.
 pub fn spawn(f: impl Future<Output = ()> + Send + 'static) {
     RUNTIME.spawn(f);
 }
 
+/// spawn a future on the tokio runtime and return its handle, which can be
+/// used to abort the task or to await its output later.
+///
+/// The handle is a `Send` future completed from a runtime thread: it must
+/// not be awaited from within a reactor, as the awaiting future would then
+/// be woken up outside of its reactor. A reactor must await the task through
+/// a hop back onto the reactor instead, as `spawn_await` does.
+///
+/// If the runtime has been shut down, the future is dropped and the handle
+/// resolves to a cancelled task error.
+pub fn spawn_handle<F, R>(f: F) -> JoinHandle<R>
+where
+    F: Future<Output = R> + Send + 'static,
+    R: Send + 'static,
+{
+    RUNTIME.spawn_handle(f)
+}
+
+/// Shutdown coordination of the cooperative tasks running on the tokio
+/// runtime. Such tasks may touch SPDK objects owned by the reactors, so they
+/// must have stopped before the reactors are torn down.
+struct Shutdown {
+    /// Set once the shutdown has been signalled.
+    signal: watch::Sender<bool>,
+    /// Number of cooperative tasks still running.
+    active: AtomicUsize,
+    /// Notified whenever a cooperative task completes.
+    completed: Notify,
+}
+
+static SHUTDOWN: Lazy<Shutdown> = Lazy::new(|| Shutdown {
+    signal: watch::channel(false).0,
+    active: AtomicUsize::new(0),
+    completed: Notify::new(),
+});
+
+/// Handed to the cooperative tasks to observe the shutdown signal.
+#[derive(Clone)]
+pub struct ShutdownToken(watch::Receiver<bool>);
+
+impl ShutdownToken {
+    /// Determines if the shutdown has been signalled.
+    pub fn is_shutdown(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once the shutdown has been signalled.
+    pub async fn shutdown(&mut self) {
+        self.0.wait_for(|s| *s).await.ok();
+    }
+}
+
+/// Accounts for a running cooperative task, until dropped.
+struct CooperativeTask;
+
+impl Drop for CooperativeTask {
+    fn drop(&mut self) {
+        SHUTDOWN.active.fetch_sub(1, SeqCst);
+        SHUTDOWN.completed.notify_waiters();
+    }
+}
+
+/// Spawn a cooperative task on the tokio runtime. The task must stop once
+/// the given token signals the shutdown, and is waited for by
+/// `await_tasks_drained` before the reactors are torn down.
+pub fn spawn_cooperative<F, Fut>(f: F)
+where
+    F: FnOnce(ShutdownToken) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    SHUTDOWN.active.fetch_add(1, SeqCst);
+    let task = CooperativeTask;
+    let fut = f(ShutdownToken(SHUTDOWN.signal.subscribe()));
+
+    RUNTIME.spawn(async move {
+        let _task = task;
+        fut.await;
+    });
+}
+
+/// Signal the shutdown to the cooperative tasks.
+pub fn signal_shutdown() {
+    SHUTDOWN.signal.send_replace(true);
+}
+
+/// Signal the shutdown to the cooperative tasks and wait for all of them to
+/// complete, for at most the given timeout.
+/// Returns false if some tasks are still running after the timeout.
+pub async fn await_tasks_drained(timeout: Duration) -> bool {
+    signal_shutdown();
+
+    let (s, r) = oneshot::channel();
+
+    RUNTIME.spawn(async move {
+        let drained = tokio::time::timeout(timeout, async {
+            loop {
+                let completed = SHUTDOWN.completed.notified();
+                if SHUTDOWN.active.load(SeqCst) == 0 {
+                    break;
+                }
+                completed.await;
+            }
+        })
+        .await
+        .is_ok();
+
+        if let Ok(r) = Reactor::spawn_at_primary(async move {
+            s.send(drained).ok();
+        }) {
+            r.await.ok();
+        }
+    });
+
+    r.await.unwrap_or(false)
+}
+
 /// Spawn a future on the tokio runtime and await its completion.
 pub async fn spawn_await(f: impl Future<Output = ()> + Send + 'static) {
+    spawn_await_output(f).await.ok();
+}
+
+/// Spawn a future on the tokio runtime and await its completion, returning
+/// its output. The output is handed back to the caller from the primary
+/// reactor. Fails if the output is lost on the way, e.g. if the runtime has
+/// been shut down.
+pub async fn spawn_await_output<R>(
+    f: impl Future<Output = R> + Send + 'static,
+) -> Result<R, RuntimeError>
+where
+    R: Send + 'static,
+{
+    let (s, r) = oneshot::channel();
+
+    RUNTIME.spawn(async move {
+        let res = f.await;
+
+        if let Ok(r) = Reactor::spawn_at_primary(async move {
+            s.send(res).ok();
+        }) {
+            r.await.ok();
+        }
+    });
+    r.await.map_err(|_| RuntimeError::OutputLost {})
+}
+
+/// Returns a receiver which completes once the given duration has elapsed.
+/// The duration is timed on the tokio runtime, so that the calling reactor
+/// is not blocked meanwhile. The receiver can be dropped before it
+/// completes, e.g. to stop waiting for a timeout.
+pub fn timer(duration: Duration) -> oneshot::Receiver<()> {
     let (s, r) = oneshot::channel();
 
     RUNTIME.spawn(async move {
-        f.await;
+        tokio::time::sleep(duration).await;
 
         if let Ok(r) = Reactor::spawn_at_primary(async move {
             s.send(()).ok();
@@ -28,16 +196,21 @@ pub async fn spawn_await(f: impl Future<Output = ()> + Send + 'static) {
             r.await.ok();
         }
     });
-    r.await.ok();
+    r
 }
 
-/// block on the given future until it completes
-pub fn block_on(f: impl Future<Output = ()> + Send + 'static) {
-    RUNTIME.block_on(f);
+/// block on the given future until it completes and return its output
+pub fn block_on<R>(f: impl Future<Output = R> + Send + 'static) -> R
+where
+    R: Send + 'static,
+{
+    RUNTIME.block_on(f)
 }
 
 /// spawn a future that might block on a separate worker thread the
-/// number of threads available is determined by max_blocking_threads
+/// number of threads available is determined by max_blocking_threads.
+/// If the runtime has been shut down, the closure is not run and the handle
+/// resolves to a cancelled task error.
 pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
 where
     F: FnOnce() -> R + Send + 'static,
@@ -46,37 +219,616 @@ where
     RUNTIME.spawn_blocking(f)
 }
 
+/// Bounds the number of blocking tasks of a given category running at once,
+/// so that a single category cannot take up all the blocking threads.
+#[derive(Debug, Clone)]
+pub struct BlockingLimiter {
+    name: String,
+    semaphore: Arc<Semaphore>,
+}
+
+impl BlockingLimiter {
+    /// Creates a new limiter allowing at most `limit` blocking tasks to run
+    /// at once.
+    pub fn new(name: &str, limit: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            semaphore: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+
+    /// Name of the limiter.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of blocking tasks which may start without waiting.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+/// Run a blocking closure on a separate worker thread, as `spawn_blocking`
+/// does, once the limiter allows it, and await its result. Fails if the
+/// limiter is closed, or if the task panics or is cancelled, e.g. as the
+/// runtime has been shut down.
+pub async fn run_blocking_limited<F, R>(
+    limiter: &BlockingLimiter,
+    f: F,
+) -> Result<R, RuntimeError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let permit =
+        limiter
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| RuntimeError::LimiterClosed {
+                name: limiter.name.clone(),
+            })?;
+
+    spawn_blocking(move || {
+        // The permit is only released once the work has been done, even if
+        // the caller stops waiting for it.
+        let _permit = permit;
+        f()
+    })
+    .await
+    .map_err(|source| RuntimeError::BlockingTaskFailed {
+        name: limiter.name.clone(),
+        source,
+    })
+}
+
+/// Bounds the number of futures of a given subsystem in flight on the
+/// runtime, so that a burst of work cannot pile up unbounded tasks.
+#[derive(Debug, Clone)]
+pub struct SpawnLimiter {
+    name: String,
+    semaphore: Arc<Semaphore>,
+}
+
+/// Spawn limiters of the subsystems, by name.
+static SPAWN_LIMITERS: Lazy<Mutex<HashMap<String, SpawnLimiter>>> =
+    Lazy::new(Default::default);
+
+impl SpawnLimiter {
+    /// Creates a new limiter allowing at most `limit` futures in flight at
+    /// once.
+    pub fn new(name: &str, limit: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            semaphore: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+
+    /// Returns the limiter of the given subsystem, shared by all of its
+    /// callers, creating it with the given limit on first use. The limit of
+    /// an existing limiter is left unchanged.
+    pub fn subsystem(name: &str, limit: usize) -> Self {
+        SPAWN_LIMITERS
+            .lock()
+            .entry(name.to_string())
+            .or_insert_with(|| Self::new(name, limit))
+            .clone()
+    }
+
+    /// Name of the limiter.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Number of futures which may be spawned without waiting.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+/// Spawn a future on the tokio runtime, as `spawn` does, once the limiter
+/// allows it: waits while the limit of futures in flight is reached. The
+/// future is accounted for until it completes, not until this returns.
+pub async fn spawn_bounded(
+    limiter: &SpawnLimiter,
+    f: impl Future<Output = ()> + Send + 'static,
+) {
+    let permit = limiter
+        .semaphore
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("Spawn limiter semaphore closed");
+
+    spawn(async move {
+        let _permit = permit;
+        f.await
+    });
+}
+
+/// Options of the tokio runtime.
+///
+/// The options are taken, in order of precedence, from an explicit call to
+/// `init`, from the `RUNTIME_WORKER_THREADS` and
+/// `RUNTIME_MAX_BLOCKING_THREADS` environment variables, or from the
+/// defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeOpts {
+    /// number of worker threads running the futures
+    pub worker_threads: usize,
+    /// maximum number of threads running blocking tasks
+    pub max_blocking_threads: usize,
+}
+
+impl Default for RuntimeOpts {
+    fn default() -> Self {
+        Self {
+            worker_threads: try_from_env("RUNTIME_WORKER_THREADS", 4),
+            max_blocking_threads: try_from_env(
+                "RUNTIME_MAX_BLOCKING_THREADS",
+                6,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(suffix(false)))]
+pub enum RuntimeError {
+    #[snafu(display("Runtime has already been initialised with {:?}", opts))]
+    AlreadyInitialised { opts: RuntimeOpts },
+    #[snafu(display("Runtime panic handler has already been set"))]
+    PanicHandlerAlreadySet {},
+    #[snafu(display(
+        "Runtime is shut down or primary reactor is gone, the output of the \
+        future is lost"
+    ))]
+    OutputLost {},
+    #[snafu(display("Blocking limiter '{}' is closed", name))]
+    LimiterClosed { name: String },
+    #[snafu(display("Blocking task of '{}' failed: {}", name, source))]
+    BlockingTaskFailed { name: String, source: JoinError },
+}
+
+/// Options the runtime is built with.
+static RUNTIME_OPTS: OnceCell<RuntimeOpts> = OnceCell::new();
+
+/// Initialises the runtime options, taking precedence over the environment.
+/// Must be called before the runtime is first used, which builds it: fails
+/// if the runtime has already been built or initialised.
+pub fn init(opts: RuntimeOpts) -> Result<(), RuntimeError> {
+    RUNTIME_OPTS
+        .set(opts)
+        .map_err(|_| RuntimeError::AlreadyInitialised {
+            opts: *RUNTIME_OPTS.get().unwrap(),
+        })
+}
+
+/// A task of the runtime which panicked.
+#[derive(Debug, Clone)]
+pub struct TaskPanic {
+    /// Type name of the future or closure run by the task.
+    pub task: &'static str,
+    /// Whether the task is a blocking one.
+    pub blocking: bool,
+    /// Message the task panicked with.
+    pub message: String,
+}
+
+/// Handler called whenever a task of the runtime panics.
+pub type PanicHandler = Box<dyn Fn(&TaskPanic) + Send + Sync>;
+
+static PANIC_HANDLER: OnceCell<PanicHandler> = OnceCell::new();
+
+/// Sets the handler called whenever a task of the runtime panics, e.g. to
+/// count the panics or to mark the node unhealthy. The handler is called
+/// from the thread of the task, after the panic has been logged; the panic
+/// is then resumed so that the handle of the task still reports it.
+///
+/// The handler is only ever called in builds which unwind on panic: builds
+/// with `panic = "abort"`, such as the dev profile, abort the process at the
+/// panic instead, before the runtime gets to see it.
+/// Fails if the handler has already been set.
+pub fn set_panic_handler(
+    handler: impl Fn(&TaskPanic) + Send + Sync + 'static,
+) -> Result<(), RuntimeError> {
+    PANIC_HANDLER
+        .set(Box::new(handler))
+        .map_err(|_| RuntimeError::PanicHandlerAlreadySet {})
+}
+
+/// Logs the panic of a task and calls the panic handler, if any.
+#[cfg(panic = "unwind")]
+fn task_panicked(
+    task: &'static str,
+    blocking: bool,
+    payload: &(dyn std::any::Any + Send),
+) {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|m| m.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+
+    error!(
+        "Runtime {} '{task}' panicked: {message}",
+        if blocking { "blocking task" } else { "future" }
+    );
+
+    if let Some(handler) = PANIC_HANDLER.get() {
+        handler(&TaskPanic {
+            task,
+            blocking,
+            message,
+        });
+    }
+}
+
+/// Runs the future of a task, reporting its panic before resuming it.
+#[cfg(panic = "unwind")]
+async fn run_future<F: Future>(f: F) -> F::Output {
+    use futures::FutureExt;
+
+    match std::panic::AssertUnwindSafe(f).catch_unwind().await {
+        Ok(output) => output,
+        Err(payload) => {
+            task_panicked(std::any::type_name::<F>(), false, payload.as_ref());
+            std::panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Runs the future of a task: a panic aborts the process, there is nothing
+/// to report.
+#[cfg(not(panic = "unwind"))]
+async fn run_future<F: Future>(f: F) -> F::Output {
+    f.await
+}
+
+/// Runs the closure of a blocking task, reporting its panic before resuming
+/// it.
+#[cfg(panic = "unwind")]
+fn run_blocking<F: FnOnce() -> R, R>(f: F) -> R {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(output) => output,
+        Err(payload) => {
+            task_panicked(std::any::type_name::<F>(), true, payload.as_ref());
+            std::panic::resume_unwind(payload)
+        }
+    }
+}
+
+/// Runs the closure of a blocking task: a panic aborts the process, there is
+/// nothing to report.
+#[cfg(not(panic = "unwind"))]
+fn run_blocking<F: FnOnce() -> R, R>(f: F) -> R {
+    f()
+}
+
+/// Shuts the runtime down: new tasks are no longer accepted, and the tasks
+/// in flight, including the blocking ones, are waited for at most the given
+/// timeout. Returns false if some tasks are still running after the timeout.
+///
+/// This blocks the calling thread, which must therefore be neither a reactor
+/// nor a runtime thread.
+pub fn shutdown(timeout: Duration) -> bool {
+    RUNTIME.shutdown_timeout(timeout)
+}
+
+/// Waits for the tasks in flight to complete, for at most the given timeout,
+/// while still accepting new tasks, e.g. so that the work they depend on
+/// can be torn down before the runtime is shut down. Returns false if some
+/// tasks are still running after the timeout.
+///
+/// This blocks the calling thread as `shutdown` does.
+pub fn drain(timeout: Duration) -> bool {
+    RUNTIME.drain_timeout(timeout)
+}
+
+/// Waits for the tasks in flight as `drain` does, from a separate thread.
+/// See `shutdown_await`.
+pub async fn drain_await(timeout: Duration) -> bool {
+    off_reactor(move || drain(timeout)).await
+}
+
+/// Shuts the runtime down as `shutdown` does, from a separate thread, so
+/// that the calling reactor keeps polling the futures the tasks in flight
+/// hop back onto meanwhile.
+pub async fn shutdown_await(timeout: Duration) -> bool {
+    off_reactor(move || shutdown(timeout)).await
+}
+
+/// Runs the given blocking wait on the tasks of the runtime from a separate
+/// thread, and hands its result back to the primary reactor.
+async fn off_reactor(wait: impl FnOnce() -> bool + Send + 'static) -> bool {
+    let (s, r) = oneshot::channel();
+
+    std::thread::Builder::new()
+        .name("runtime_shutdown".to_string())
+        .spawn(move || {
+            Mthread::unaffinitize();
+            let drained = wait();
+
+            Reactor::spawn_at_primary(async move {
+                s.send(drained).ok();
+            })
+            .ok();
+        })
+        .expect("Failed to spawn the runtime shutdown thread");
+
+    r.await.unwrap_or(false)
+}
+
+/// Returns the type names of the futures and closures of the tasks in
+/// flight on the runtime, along with the number of tasks of each.
+pub fn tasks_in_flight() -> Vec<(&'static str, usize)> {
+    RUNTIME.in_flight.task_names()
+}
+
+/// Determines if the runtime has been shut down.
+pub fn is_shut_down() -> bool {
+    RUNTIME.in_flight.closed.load(SeqCst)
+}
+
+/// Counters of the tasks of the runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Number of futures spawned and not yet completed.
+    pub active_tasks: usize,
+    /// Number of blocking tasks running on a blocking thread.
+    pub active_blocking_tasks: usize,
+    /// Number of blocking tasks waiting for a blocking thread.
+    pub queued_blocking_tasks: usize,
+    /// Number of futures and blocking tasks completed since start.
+    pub completed_tasks: u64,
+    /// Maximum number of blocking threads: blocking tasks are queued once
+    /// that many of them are running.
+    pub max_blocking_threads: usize,
+}
+
+/// Returns the counters of the tasks of the runtime.
+pub fn metrics() -> RuntimeMetrics {
+    let in_flight = &RUNTIME.in_flight;
+    let blocking = in_flight.blocking.load(Relaxed);
+    let running = in_flight.running_blocking.load(Relaxed);
+
+    RuntimeMetrics {
+        active_tasks: in_flight.futures.load(Relaxed),
+        active_blocking_tasks: running,
+        queued_blocking_tasks: blocking.saturating_sub(running),
+        completed_tasks: in_flight.completed.load(Relaxed),
+        max_blocking_threads: RUNTIME_OPTS
+            .get()
+            .map_or(0, |opts| opts.max_blocking_threads.max(1)),
+    }
+}
+
+/// Interval at which the tasks in flight are checked during the shutdown.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Accounting of the tasks in flight on a runtime.
+#[derive(Debug, Default)]
+struct InFlight {
+    /// Set once the runtime has been shut down.
+    closed: AtomicBool,
+    /// Number of futures in flight.
+    futures: AtomicUsize,
+    /// Number of blocking tasks in flight, queued or running.
+    blocking: AtomicUsize,
+    /// Number of blocking tasks running.
+    running_blocking: AtomicUsize,
+    /// Number of tasks completed.
+    completed: AtomicU64,
+    /// Type names of the tasks in flight, by task id.
+    tasks: Mutex<HashMap<u64, &'static str>>,
+    /// Id of the next task.
+    next_id: AtomicU64,
+}
+
+impl InFlight {
+    fn counter(&self, blocking: bool) -> &AtomicUsize {
+        if blocking {
+            &self.blocking
+        } else {
+            &self.futures
+        }
+    }
+
+    /// Returns the type names of the tasks in flight, sorted, along with the
+    /// number of such tasks.
+    fn task_names(&self) -> Vec<(&'static str, usize)> {
+        let mut names = std::collections::BTreeMap::new();
+        for name in self.tasks.lock().values() {
+            *names.entry(*name).or_insert(0) += 1;
+        }
+        names.into_iter().collect()
+    }
+}
+
+/// Accounts for a task in flight, until dropped.
+struct InFlightTask {
+    in_flight: Arc<InFlight>,
+    id: u64,
+    blocking: bool,
+    /// Cleared if the task is not run, so that it is not counted as
+    /// completed.
+    run: bool,
+}
+
+impl InFlightTask {
+    fn new(
+        in_flight: &Arc<InFlight>,
+        task: &'static str,
+        blocking: bool,
+    ) -> Self {
+        let id = in_flight.next_id.fetch_add(1, Relaxed);
+        in_flight.tasks.lock().insert(id, task);
+        in_flight.counter(blocking).fetch_add(1, SeqCst);
+        Self {
+            in_flight: in_flight.clone(),
+            id,
+            blocking,
+            run: true,
+        }
+    }
+}
+
+impl Drop for InFlightTask {
+    fn drop(&mut self) {
+        self.in_flight.tasks.lock().remove(&self.id);
+        // The in flight counters are sequentially consistent as the shutdown
+        // relies on them, whereas the metrics only are relaxed.
+        self.in_flight.counter(self.blocking).fetch_sub(1, SeqCst);
+        if self.run {
+            self.in_flight.completed.fetch_add(1, Relaxed);
+        }
+    }
+}
+
+/// Accounts for a blocking task running on a blocking thread, until dropped,
+/// even if the task panics.
+struct RunningBlockingTask {
+    in_flight: Arc<InFlight>,
+}
+
+impl RunningBlockingTask {
+    fn new(in_flight: Arc<InFlight>) -> Self {
+        in_flight.running_blocking.fetch_add(1, Relaxed);
+        Self {
+            in_flight,
+        }
+    }
+}
+
+impl Drop for RunningBlockingTask {
+    fn drop(&mut self) {
+        self.in_flight.running_blocking.fetch_sub(1, Relaxed);
+    }
+}
+
 pub struct Runtime {
     rt: tokio::runtime::Runtime,
+    in_flight: Arc<InFlight>,
 }
 
 static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    let opts = RUNTIME_OPTS.get_or_init(RuntimeOpts::default);
+    info!("Building runtime with {opts:?}");
+
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
-        .worker_threads(4)
-        .max_blocking_threads(6)
+        .worker_threads(opts.worker_threads.max(1))
+        .max_blocking_threads(opts.max_blocking_threads.max(1))
         .on_thread_start(Mthread::unaffinitize)
         .build()
         .unwrap();
 
-    Runtime {
-        rt,
-    }
+    Runtime::new(rt)
 });
 
 impl Runtime {
     pub fn new(rt: tokio::runtime::Runtime) -> Self {
         Self {
             rt,
+            in_flight: Arc::new(InFlight::default()),
         }
     }
-    fn block_on(&self, f: impl Future<Output = ()> + Send + 'static) {
-        self.rt.block_on(f);
+
+    /// Accounts for a new task, unless the runtime has been shut down.
+    fn track(
+        &self,
+        task: &'static str,
+        blocking: bool,
+    ) -> Option<InFlightTask> {
+        let mut in_flight = InFlightTask::new(&self.in_flight, task, blocking);
+        // Checked once accounted for, so that a task is either refused or
+        // waited for by a concurrent shutdown.
+        if self.in_flight.closed.load(SeqCst) {
+            warn!(
+                "Runtime is shut down, not running the {} '{task}'",
+                if blocking { "blocking task" } else { "future" }
+            );
+            in_flight.run = false;
+            return None;
+        }
+        Some(in_flight)
+    }
+
+    /// Returns the handle of a task which has been cancelled.
+    fn cancelled<R>(&self) -> JoinHandle<R>
+    where
+        R: Send + 'static,
+    {
+        let handle = self.rt.handle().spawn(std::future::pending());
+        handle.abort();
+        handle
+    }
+
+    /// Shuts the runtime down, see `shutdown`.
+    pub fn shutdown_timeout(&self, timeout: Duration) -> bool {
+        self.in_flight.closed.store(true, SeqCst);
+        let drained = self.drain_timeout(timeout);
+        if drained {
+            info!("Runtime shut down");
+        }
+        drained
+    }
+
+    /// Waits for the tasks in flight to complete, see `drain`.
+    fn drain_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let futures = self.in_flight.futures.load(SeqCst);
+            let blocking = self.in_flight.blocking.load(SeqCst);
+            if futures == 0 && blocking == 0 {
+                return true;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                let tasks = self
+                    .in_flight
+                    .task_names()
+                    .into_iter()
+                    .map(|(task, count)| format!("{task} (x{count})"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                warn!(
+                    "Runtime still has {futures} future(s) and {blocking} \
+                    blocking task(s) running after {timeout:?}: {tasks}"
+                );
+                return false;
+            }
+            std::thread::sleep(SHUTDOWN_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    fn block_on<R>(&self, f: impl Future<Output = R> + Send + 'static) -> R
+    where
+        R: Send + 'static,
+    {
+        self.rt.block_on(f)
     }
 
     fn spawn(&self, f: impl Future<Output = ()> + Send + 'static) {
-        let handle = self.rt.handle().clone();
-        handle.spawn(f);
+        self.spawn_handle(f);
+    }
+
+    fn spawn_handle<F, R>(&self, f: F) -> JoinHandle<R>
+    where
+        F: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let Some(task) = self.track(std::any::type_name::<F>(), false) else {
+            return self.cancelled();
+        };
+
+        self.rt.handle().spawn(async move {
+            let _task = task;
+            run_future(f).await
+        })
     }
 
     pub fn spawn_blocking<F, R>(&self, f: F) -> JoinHandle<R>
@@ -84,10 +836,17 @@ impl Runtime {
         F: FnOnce() -> R + Send + 'static,
         R: Send + 'static,
     {
+        let Some(task) = self.track(std::any::type_name::<F>(), true) else {
+            return self.cancelled();
+        };
+
+        let in_flight = self.in_flight.clone();
         let handle = self.rt.handle().clone();
-        handle.spawn_blocking(|| {
+        handle.spawn_blocking(move || {
+            let _task = task;
             Mthread::unaffinitize();
-            f()
+            let _running = RunningBlockingTask::new(in_flight);
+            run_blocking(f)
         })
     }
 }
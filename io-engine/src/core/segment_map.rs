@@ -1,6 +1,8 @@
 use bit_vec::{BitBlock, BitVec};
 use std::fmt::{Debug, Formatter};
 
+use super::ClusterRange;
+
 // Returns ceil of an integer division.
 fn div_ceil(a: u64, b: u64) -> u64 {
     (a + b - 1) / b
@@ -102,6 +104,26 @@ impl<B: BitBlock> SegmentMap<B> {
     pub(crate) fn size_blks(&self) -> u64 {
         self.num_blocks
     }
+
+    /// Returns the ranges of the device covered by dirty segments, in bytes.
+    pub(crate) fn dirty_ranges(&self) -> Vec<ClusterRange> {
+        let size = self.num_blocks * self.block_len;
+        let mut ranges: Vec<ClusterRange> = Vec::new();
+        for (idx, _) in self.segments.iter().enumerate().filter(|(_, d)| *d) {
+            let offset = idx as u64 * self.segment_size;
+            let length = self.segment_size.min(size - offset);
+            match ranges.last_mut() {
+                Some(last) if last.offset + last.length == offset => {
+                    last.length += length;
+                }
+                _ => ranges.push(ClusterRange {
+                    offset,
+                    length,
+                }),
+            }
+        }
+        ranges
+    }
 }
 
 impl From<SegmentMap> for BitVec {
@@ -1,14 +1,21 @@
-use crate::{lvs::Lvol, subsys::NvmfReq};
+use crate::{
+    lvs::{Lvol, Lvs},
+    subsys::NvmfReq,
+};
 use async_trait::async_trait;
-use futures::channel::oneshot;
+use chrono::{DateTime, ParseError, SecondsFormat, Utc};
+use futures::{channel::oneshot, future::LocalBoxFuture};
 use nix::errno::Errno;
 use serde::{Deserialize, Serialize};
+use snafu::Snafu;
 use spdk_rs::libspdk::{spdk_lvol, spdk_xattr_descriptor};
 use std::{
+    collections::{HashMap, HashSet},
     ffi::{c_void, CString},
     fmt::Debug,
+    sync::Arc,
 };
-use strum::EnumCount;
+use strum::{EnumCount, IntoEnumIterator};
 use strum_macros::{EnumCount as EnumCountMacro, EnumIter};
 
 /// Snapshot Captures all the Snapshot information for Lvol.
@@ -21,6 +28,23 @@ pub struct SnapshotParams {
     snapshot_uuid: Option<String>,
     create_time: Option<String>,
     discarded_snapshot: bool,
+    #[serde(default)]
+    group_id: Option<String>,
+    #[serde(default)]
+    group_member_count: Option<u32>,
+    /// Retention class of the snapshot, e.g. hourly, daily or monthly, for
+    /// retention policies to be applied per class.
+    #[serde(default)]
+    retention_class: Option<String>,
+    /// Create the snapshot blob with only its uuid and parent id attributes,
+    /// and write the remaining attributes once the snapshot has been taken,
+    /// off the I/O critical path.
+    /// Until they are written, the snapshot is reported as invalid. Should
+    /// writing them fail, the snapshot is destroyed. Should the io-engine
+    /// crash in between, the snapshot stays invalid and must be cleaned up,
+    /// e.g. by listing the invalid snapshots.
+    #[serde(default)]
+    defer_xattrs: bool,
 }
 
 /// Implement Snapshot Common Function.
@@ -42,6 +66,10 @@ impl SnapshotParams {
             snapshot_uuid,
             create_time,
             discarded_snapshot,
+            group_id: None,
+            group_member_count: None,
+            retention_class: None,
+            defer_xattrs: false,
         }
     }
 }
@@ -57,6 +85,14 @@ pub struct CloneParams {
     pub source_uuid: Option<String>,
     /// Timestamp when the clone is created.
     pub clone_create_time: Option<String>,
+    /// Cluster size of the clone, if different from the source snapshot.
+    pub cluster_size_override: Option<u32>,
+    /// Copy all the data of the source snapshot into the clone and detach
+    /// the clone from the snapshot once created.
+    pub thick: bool,
+    /// Read the clone through in the background once created, to warm up
+    /// the caches with the data of the source snapshot.
+    pub prefetch: bool,
 }
 
 impl CloneParams {
@@ -71,6 +107,9 @@ impl CloneParams {
             clone_uuid,
             source_uuid,
             clone_create_time,
+            cluster_size_override: None,
+            thick: false,
+            prefetch: false,
         }
     }
 
@@ -113,6 +152,47 @@ impl CloneParams {
     pub fn set_clone_create_time(&mut self, time: String) {
         self.clone_create_time = Some(time);
     }
+
+    /// Get clone cluster size override.
+    pub fn cluster_size_override(&self) -> Option<u32> {
+        self.cluster_size_override
+    }
+
+    /// Set clone cluster size override.
+    pub fn set_cluster_size_override(&mut self, cluster_size: u32) {
+        self.cluster_size_override = Some(cluster_size);
+    }
+
+    /// Check if the clone is to be thick provisioned.
+    pub fn thick(&self) -> bool {
+        self.thick
+    }
+
+    /// Set whether the clone is to be thick provisioned.
+    pub fn set_thick(&mut self, thick: bool) {
+        self.thick = thick;
+    }
+
+    /// Check if the clone is to be warmed up once created.
+    pub fn prefetch(&self) -> bool {
+        self.prefetch
+    }
+
+    /// Set whether the clone is to be warmed up once created.
+    pub fn set_prefetch(&mut self, prefetch: bool) {
+        self.prefetch = prefetch;
+    }
+}
+
+/// Whether a snapshot can be restored, and how.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RestoreEligibility {
+    /// The snapshot can be restored in-place, onto its source replica.
+    InPlace,
+    /// The snapshot can only be restored to a new replica.
+    NewReplicaOnly { reason: String },
+    /// The snapshot cannot be restored.
+    Ineligible { reason: String },
 }
 
 /// Snapshot Descriptor to respond back as part of listsnapshot.
@@ -126,6 +206,7 @@ pub struct VolumeSnapshotDescriptor {
                           * clone feature. */
     // set to false, if any of the snapshotdescriptor is not filled properly
     pub valid_snapshot: bool,
+    pub restore_eligibility: RestoreEligibility,
 }
 
 impl VolumeSnapshotDescriptor {
@@ -136,6 +217,7 @@ impl VolumeSnapshotDescriptor {
         snap_param: SnapshotParams,
         num_clones: u64,
         valid_snapshot: bool,
+        restore_eligibility: RestoreEligibility,
     ) -> Self {
         Self {
             snapshot_lvol,
@@ -144,6 +226,7 @@ impl VolumeSnapshotDescriptor {
             snap_param,
             num_clones,
             valid_snapshot,
+            restore_eligibility,
         }
     }
     /// Get snapshot lvol.
@@ -174,10 +257,245 @@ impl VolumeSnapshotDescriptor {
     pub fn valid_snapshot(&self) -> bool {
         self.valid_snapshot
     }
+
+    /// Get whether the snapshot can be restored, and how.
+    pub fn restore_eligibility(&self) -> &RestoreEligibility {
+        &self.restore_eligibility
+    }
+
+    /// Get the estimated number of blob layers a read of the snapshot
+    /// consults. It is computed on request, as it walks the whole snapshot
+    /// chain.
+    pub fn read_amplification(&self) -> f64 {
+        self.snapshot_lvol.read_amplification_factor()
+    }
+
+    /// Get the retention class of the snapshot, snapshots without a class
+    /// belonging to the uncategorized class.
+    pub fn retention_class(&self) -> String {
+        self.snap_param
+            .retention_class()
+            .unwrap_or_else(|| UNCATEGORIZED_RETENTION_CLASS.to_string())
+    }
+}
+
+/// Namespace of the snapshot uuids derived by `derive_snapshot_uuid`.
+/// It must never change, or derived uuids would no longer match the ones of
+/// previously created snapshots.
+const SNAPSHOT_UUID_NAMESPACE: uuid::Uuid =
+    uuid::uuid!("675cb319-cfcb-4679-8288-fa3edc906718");
+
+/// Derive the uuid of a snapshot from the uuid of its source lvol and the
+/// transaction id of the snapshot request.
+///
+/// The uuid is a version 5 (SHA-1 name based) uuid of the
+/// `"<source_uuid>/<txn_id>"` name within a fixed io-engine namespace, so the
+/// same source and transaction always yield the same snapshot uuid. This makes
+/// retried snapshot requests idempotent without the caller providing the
+/// snapshot uuid.
+pub fn derive_snapshot_uuid(source_uuid: &str, txn_id: &str) -> String {
+    uuid::Uuid::new_v5(
+        &SNAPSHOT_UUID_NAMESPACE,
+        format!("{source_uuid}/{txn_id}").as_bytes(),
+    )
+    .to_string()
+}
+
+/// A page of the snapshots of this node.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotPage {
+    /// The snapshots of the requested page.
+    pub snapshots: Vec<VolumeSnapshotDescriptor>,
+    /// Number of snapshots matching the filter, across all the pages.
+    pub total: usize,
+}
+
+/// Outcome of pruning the snapshots of a replica.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Number of snapshots which have been destroyed.
+    pub destroyed: u64,
+    /// Number of snapshots which have been marked as discarded as they still
+    /// have clones depending on them.
+    pub discarded: u64,
 }
 
+/// Outcome of creating snapshots of several lvols as a batch.
+#[derive(Debug)]
+pub struct SnapshotBatchOutcome<L, E> {
+    /// Transaction id shared by all the snapshots of the batch.
+    pub txn_id: String,
+    /// Result of each snapshot, in the order of the given lvols.
+    pub results: Vec<Result<L, E>>,
+}
+
+impl<L, E> SnapshotBatchOutcome<L, E> {
+    /// Number of snapshots which have been created.
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|r| r.is_ok()).count()
+    }
+
+    /// Determines if all the snapshots of the batch have been created.
+    pub fn is_complete(&self) -> bool {
+        self.results.iter().all(|r| r.is_ok())
+    }
+
+    /// Determines if only some of the snapshots of the batch have been
+    /// created.
+    pub fn is_partial_failure(&self) -> bool {
+        !self.is_complete() && self.succeeded() > 0
+    }
+}
+
+/// Effect of destroying a snapshot, clone or replica, as planned by
+/// `SnapshotOps::plan_destroy` without destroying anything.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DestroyPlan {
+    /// The lvols which would be deleted.
+    pub destroyed: Vec<TopologyNode>,
+    /// The snapshots which would only be marked as discarded, as clones
+    /// still depend on them.
+    pub discarded: Vec<TopologyNode>,
+    /// Estimated disk space reclaimed, in bytes, i.e. the space allocated by
+    /// the lvols which would be deleted, bar the clusters of a deleted
+    /// snapshot which are merged into the blob built upon it.
+    pub reclaimed_bytes: u64,
+}
+
+/// Outcome of destroying a replica along with all its snapshots and clones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DestroyTreeReport {
+    /// The lvols which have been destroyed, in the order they were destroyed.
+    pub destroyed: Vec<TopologyNode>,
+}
+
+impl DestroyTreeReport {
+    /// Number of lvols of the given kind which have been destroyed.
+    pub fn count(&self, kind: TopologyNodeKind) -> usize {
+        self.destroyed.iter().filter(|n| n.kind == kind).count()
+    }
+}
+
+/// Disk space usage of a snapshot, split between the space it alone holds
+/// and the space the lvols built upon it still read through to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SnapshotUsageBreakdown {
+    /// Disk space allocated by the snapshot which none of its children reads
+    /// through to, i.e. the space freed if the snapshot is deleted, in bytes.
+    pub exclusive_bytes: u64,
+    /// Disk space allocated by the snapshot which at least one of its
+    /// children, being a newer snapshot, the replica itself or a clone,
+    /// still reads through to, in bytes.
+    pub shared_bytes: u64,
+    /// Disk space holding the data visible through the snapshot, whether
+    /// allocated by the snapshot itself or by its ancestors, in bytes.
+    pub referenced_bytes: u64,
+}
+
+/// Range of an lvol, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClusterRange {
+    /// Offset of the range.
+    pub offset: u64,
+    /// Length of the range.
+    pub length: u64,
+}
+
+/// Disk space usage of a whole snapshot/clone tree.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreeUsageSummary {
+    /// Disk space allocated by all the lvols of the tree, in bytes.
+    pub total_allocated_bytes: u64,
+    /// Disk space allocated by the lvols which other lvols of the tree are
+    /// built upon, and thus shared by their descendants, in bytes.
+    pub shared_bytes: u64,
+    /// Disk space allocated by each leaf of the tree alone, in bytes, indexed
+    /// by the leaf uuid.
+    pub leaf_unique_bytes: HashMap<String, u64>,
+}
+
+/// Kind of a lvol in the topology of a pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyNodeKind {
+    Replica,
+    Snapshot,
+    Clone,
+}
+
+/// Relationship between two lvols in the topology of a pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TopologyEdgeKind {
+    /// The snapshot has been taken from the replica or clone.
+    Parent,
+    /// The clone has been created from the snapshot.
+    Source,
+}
+
+/// A replica, snapshot or clone of a pool.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TopologyNode {
+    pub uuid: String,
+    pub name: String,
+    pub kind: TopologyNodeKind,
+    /// Disk space allocated by the lvol, in bytes.
+    pub allocated_bytes: u64,
+    /// The snapshot has been marked as discarded.
+    pub discarded: bool,
+    /// The lvol refers to a parent or source lvol which cannot be found in
+    /// the pool.
+    pub orphaned: bool,
+}
+
+/// Edge from a lvol to the lvol it has been created from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TopologyEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: TopologyEdgeKind,
+    /// The lvol pointed to cannot be found in the pool.
+    pub dangling: bool,
+}
+
+/// Graph of all the replicas, snapshots and clones of a pool.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PoolTopology {
+    pub pool_uuid: String,
+    pub pool_name: String,
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+}
+
+/// An lvol in the ancestry of a snapshot or clone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AncestryNode {
+    pub uuid: String,
+    pub name: String,
+    pub kind: TopologyNodeKind,
+    /// Disk space allocated by the lvol, in bytes.
+    pub allocated_bytes: u64,
+}
+
+/// Hook called before a snapshot is created, with the lvol to snapshot and
+/// the parameters of the snapshot. An error aborts the snapshot creation.
+pub type SnapshotPreCreateHook = Arc<
+    dyn Fn(Lvol, SnapshotParams) -> LocalBoxFuture<'static, Result<(), String>>
+        + Send
+        + Sync,
+>;
+
+/// Hook called once a snapshot has been created, with the snapshot lvol.
+pub type SnapshotPostCreateHook =
+    Arc<dyn Fn(Lvol) -> LocalBoxFuture<'static, ()> + Send + Sync>;
+
+/// Identifies a registered snapshot creation hook, so that it can be
+/// unregistered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SnapshotHookId(pub(crate) u64);
+
 /// Snapshot attributes used to store its properties.
-#[derive(Debug, EnumCountMacro, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumCountMacro, EnumIter)]
 pub enum SnapshotXattrs {
     TxId,
     EntityId,
@@ -189,6 +507,10 @@ pub enum SnapshotXattrs {
     /// delete of the last valid replica(clone) can delete the snapshot marked
     /// as discarded.
     DiscardedSnapshot,
+    /// Checksum of the immutable attributes of the snapshot, to detect their
+    /// corruption. Snapshots created by older versions have none, and are
+    /// left unchecked.
+    Checksum,
 }
 
 impl SnapshotXattrs {
@@ -200,6 +522,213 @@ impl SnapshotXattrs {
             Self::SnapshotUuid => "uuid",
             Self::SnapshotCreateTime => "io-engine.snapshot_create_time",
             Self::DiscardedSnapshot => "io-engine.discarded_snapshot",
+            Self::Checksum => "io-engine.checksum",
+        }
+    }
+
+    /// Checks if the attribute may be missing from a valid snapshot.
+    pub fn is_optional(&self) -> bool {
+        matches!(self, Self::Checksum)
+    }
+
+    /// Checks if the attribute is covered by the checksum. The discarded
+    /// flag is not, as it changes during the lifetime of the snapshot.
+    fn is_checksummed(&self) -> bool {
+        !matches!(self, Self::DiscardedSnapshot | Self::Checksum)
+    }
+}
+
+/// Format a snapshot create time as stored in the snapshot attributes, i.e.
+/// in RFC3339 format.
+pub fn format_snapshot_create_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+/// Compute the checksum of the snapshot attributes, given a function
+/// returning the value of each of them.
+pub fn snapshot_xattrs_checksum(
+    value: impl Fn(&SnapshotXattrs) -> Option<String>,
+) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    for attr in SnapshotXattrs::iter().filter(|a| a.is_checksummed()) {
+        hasher.update(attr.name());
+        hasher.update([0]);
+        hasher.update(value(&attr).unwrap_or_default());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Problem found while building the descriptor of a snapshot from its
+/// attributes.
+#[derive(Debug, Snafu, Clone)]
+#[snafu(context(suffix(false)))]
+pub enum SnapshotDescriptorError {
+    #[snafu(display(
+        "Snapshot {} is missing the attribute {}",
+        name,
+        attr.name()
+    ))]
+    MissingXattr { name: String, attr: SnapshotXattrs },
+    #[snafu(display(
+        "Snapshot {} has an invalid value '{}' for the attribute {}",
+        name,
+        value,
+        attr.name()
+    ))]
+    InvalidXattr {
+        name: String,
+        attr: SnapshotXattrs,
+        value: String,
+    },
+    #[snafu(display(
+        "Snapshot {} is not a snapshot of replica {}",
+        name,
+        parent
+    ))]
+    ParentMismatch { name: String, parent: String },
+    #[snafu(display(
+        "Snapshot {} attributes do not match their checksum",
+        name
+    ))]
+    ChecksumMismatch { name: String },
+}
+
+/// Optional snapshot attributes used to store the consistency group a
+/// snapshot belongs to, when snapshots are taken as a group.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum SnapshotGroupXattrs {
+    GroupId,
+    GroupMemberCount,
+}
+
+impl SnapshotGroupXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::GroupId => "io-engine.group_id",
+            Self::GroupMemberCount => "io-engine.group_member_count",
+        }
+    }
+}
+
+/// Optional snapshot attributes used to record the replication of a snapshot
+/// to remote sites.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum SnapshotReplicationXattrs {
+    /// Comma separated list of the targets the snapshot was replicated to.
+    ReplicatedTo,
+}
+
+impl SnapshotReplicationXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::ReplicatedTo => "usr.replicated_to",
+        }
+    }
+}
+
+/// Retention class of the snapshots which have not been given one.
+pub const UNCATEGORIZED_RETENTION_CLASS: &str = "uncategorized";
+
+/// Optional snapshot attributes used to store the retention class of a
+/// snapshot.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum SnapshotRetentionXattrs {
+    RetentionClass,
+}
+
+impl SnapshotRetentionXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::RetentionClass => "io-engine.retention_class",
+        }
+    }
+}
+
+/// Snapshot attributes caching values which are otherwise expensive to
+/// compute.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum SnapshotCacheXattrs {
+    /// Number of clones created from the snapshot.
+    CloneRefcount,
+}
+
+impl SnapshotCacheXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::CloneRefcount => "usr.clone_refcount",
+        }
+    }
+}
+
+/// Magic number of the snapshot labels, i.e. "SNAP".
+pub const SNAPSHOT_LABEL_MAGIC: u32 = 0x534e_4150;
+
+/// Version of the schema of the snapshot labels.
+pub const SNAPSHOT_LABEL_VERSION: u32 = 1;
+
+/// Self-describing label of a snapshot taken as a recovery point. It holds
+/// enough to identify the snapshot and its source should its other
+/// attributes be lost.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotLabel {
+    /// Magic number, `SNAPSHOT_LABEL_MAGIC`.
+    pub magic: u32,
+    /// Version of the label schema.
+    pub version: u32,
+    /// Uuid of the replica the snapshot has been taken from.
+    pub source_uuid: String,
+    /// Creation time of the snapshot, as stored in its attributes.
+    pub create_time: String,
+}
+
+impl SnapshotLabel {
+    /// Creates the label of a snapshot of the given source.
+    pub fn new(source_uuid: String, create_time: String) -> Self {
+        Self {
+            magic: SNAPSHOT_LABEL_MAGIC,
+            version: SNAPSHOT_LABEL_VERSION,
+            source_uuid,
+            create_time,
+        }
+    }
+
+    /// Checks that the label carries the magic number and a known schema
+    /// version.
+    pub fn is_valid(&self) -> bool {
+        self.magic == SNAPSHOT_LABEL_MAGIC
+            && self.version == SNAPSHOT_LABEL_VERSION
+    }
+}
+
+/// Optional snapshot attribute used to store the label of a snapshot.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum SnapshotLabelXattrs {
+    Label,
+}
+
+impl SnapshotLabelXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::Label => "io-engine.snapshot_label",
+        }
+    }
+}
+
+/// Replica attribute recording the snapshot a restore was started from. It is
+/// only removed once the restore completes, so that a replica left partially
+/// restored, e.g. by a crash, can be told apart.
+#[derive(Debug, EnumCountMacro, EnumIter)]
+pub enum RestoreXattrs {
+    InProgress,
+}
+
+impl RestoreXattrs {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Self::InProgress => "io-engine.restore_in_progress",
         }
     }
 }
@@ -237,15 +766,85 @@ pub trait SnapshotOps {
         snap_param: SnapshotParams,
     ) -> Result<Lvol, Self::Error>;
 
+    /// Create snapshots of the given lvols concurrently, with the parameters
+    /// at the same index. All the snapshots share a single transaction id,
+    /// and form a consistency group unless a group is already given.
+    /// Snapshots successfully created are kept if others fail.
+    async fn create_snapshots_batch(
+        volumes: &[Self::Lvol],
+        params: Vec<SnapshotParams>,
+    ) -> SnapshotBatchOutcome<Self::Lvol, Self::Error>;
+
+    /// Create a snapshot as `create_snapshot` does, labelled as a recovery
+    /// point. The label is persisted along with the snapshot attributes; the
+    /// snapshot is destroyed if its label cannot be written.
+    async fn create_snapshot_labeled(
+        &self,
+        snap_param: SnapshotParams,
+    ) -> Result<Lvol, Self::Error>;
+
+    /// Read the label of a snapshot created by `create_snapshot_labeled`.
+    /// Returns None if the snapshot has no label, or an invalid one.
+    fn read_snapshot_label(&self) -> Option<SnapshotLabel>;
+
+    /// Rename this snapshot. The new name must not be empty, and must not be
+    /// used by any other lvol of the pool.
+    async fn rename_snapshot(&self, new_name: &str) -> Result<(), Self::Error>;
+
+    /// Register a hook called before each snapshot is created. A hook
+    /// returning an error aborts the snapshot creation.
+    fn register_pre_create_hook(hook: SnapshotPreCreateHook) -> SnapshotHookId;
+
+    /// Register a hook called after each snapshot has been created.
+    /// Remote snapshots complete outside of the io-engine, so this hook is
+    /// not called for them.
+    fn register_post_create_hook(
+        hook: SnapshotPostCreateHook,
+    ) -> SnapshotHookId;
+
+    /// Unregister the snapshot creation hook with the given id. Returns
+    /// false if no such hook is registered.
+    fn unregister_create_hook(id: SnapshotHookId) -> bool;
+
     /// Create a snapshot in Remote.
     async fn create_snapshot_remote(
         &self,
         nvmf_req: &NvmfReq,
         snapshot_params: SnapshotParams,
     );
-    /// Destroy snapshot.
+    /// Destroy snapshot. A snapshot which volumes still depend upon is only
+    /// marked as discarded, to be destroyed once they are gone.
     async fn destroy_snapshot(mut self) -> Result<(), Self::Error>;
 
+    /// List the snapshots of this replica created before the cutoff time,
+    /// oldest first, which are candidates for retention, i.e. which are not
+    /// discarded yet. If a retention class is given, only the snapshots of
+    /// that class are listed.
+    fn list_retention_candidates(
+        &self,
+        cutoff: DateTime<Utc>,
+        retention_class: Option<&str>,
+    ) -> Vec<VolumeSnapshotDescriptor>;
+
+    /// Destroy all snapshots of this replica created before the cutoff time,
+    /// oldest first. Snapshots which still have clones are marked as
+    /// discarded instead, so that the chain of the clones is not broken.
+    /// If a retention class is given, only the snapshots of that class are
+    /// pruned.
+    async fn prune_snapshots_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        retention_class: Option<&str>,
+    ) -> Result<PruneReport, Self::Error>;
+
+    /// Destroy or discard the snapshots with the given UUIDs, concurrently.
+    /// As for a single snapshot, a snapshot is only marked as discarded if it
+    /// still has clones, otherwise it is destroyed.
+    /// Returns the result of each snapshot, in the order of the given UUIDs.
+    async fn discard_snapshots_batch(
+        uuids: &[String],
+    ) -> Vec<Result<(), Self::Error>>;
+
     /// List Snapshot details based on source UUID from which snapshot is
     /// created.
     fn list_snapshot_by_source_uuid(&self) -> Vec<VolumeSnapshotDescriptor>;
@@ -253,11 +852,150 @@ pub trait SnapshotOps {
     /// List Single snapshot details based on snapshot UUID.
     fn list_snapshot_by_snapshot_uuid(&self) -> Vec<VolumeSnapshotDescriptor>;
 
+    /// List the snapshots this clone depends on, i.e. its source snapshot
+    /// and all the ancestors of that snapshot, ordered from the root of the
+    /// chain to the source snapshot. Empty if this lvol is not a clone.
+    fn list_snapshots_for_clone(&self) -> Vec<VolumeSnapshotDescriptor>;
+
     /// List All Snapshot.
     fn list_all_snapshots(
         parent_lvol: Option<&Lvol>,
     ) -> Vec<VolumeSnapshotDescriptor>;
 
+    /// List a page of at most `limit` snapshots, starting at `offset`,
+    /// optionally restricted to the snapshots of the given entity.
+    fn list_snapshots_page(
+        entity_id: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> SnapshotPage;
+
+    /// List the snapshots of the given entity, oldest first.
+    fn list_snapshots_by_entity_id(
+        entity_id: &str,
+    ) -> Vec<VolumeSnapshotDescriptor>;
+
+    /// Get the uuids of all the snapshots and clones reachable from this
+    /// lvol, walking the snapshot tree both up and down.
+    fn reachable_blobs(&self) -> HashSet<String>;
+
+    /// Compute the disk space usage of the whole snapshot/clone tree the
+    /// given lvol belongs to, walking the tree once.
+    fn tree_usage_summary(root: &Lvol) -> TreeUsageSummary;
+
+    /// Build the graph of all the replicas, snapshots and clones of the given
+    /// pool, with their parent and source relationships. Lvols referring to
+    /// an lvol missing from the pool are reported as orphaned.
+    fn pool_topology(lvs: &Lvs) -> PoolTopology;
+
+    /// List the ancestry of this lvol, from itself up to the root, following
+    /// the parent of the snapshots and the source snapshot of the clones.
+    /// The walk stops at the first lvol whose ancestor no longer exists, and
+    /// fails if the links loop back, which only corrupted attributes can do.
+    fn ancestry(&self) -> Result<Vec<AncestryNode>, Self::Error>;
+
+    /// Plan the destruction of this lvol without mutating anything, as
+    /// `destroy_snapshot` would carry it out for a snapshot and
+    /// `destroy_replica` for a clone or a replica.
+    fn plan_destroy(&self) -> Result<DestroyPlan, Self::Error>;
+
+    /// Destroy the given replica along with all the snapshots and clones
+    /// built upon it, leaves first. Stops at the first lvol which fails to
+    /// be destroyed, reporting the lvols already destroyed.
+    async fn destroy_replica_tree(
+        root: &Lvol,
+    ) -> Result<DestroyTreeReport, Self::Error>;
+
+    /// List all snapshots of the given pool.
+    fn list_snapshots_in_pool(
+        lvs: &Lvs,
+        parent_lvol: Option<&Lvol>,
+    ) -> Vec<VolumeSnapshotDescriptor>;
+
+    /// Find the snapshot uuids claimed by more than one snapshot, returning
+    /// each duplicated snapshot uuid with the uuids of the lvols claiming it.
+    fn find_duplicate_snapshot_uuids() -> Vec<(String, Vec<String>)>;
+
+    /// List all snapshots with invalid or partial metadata, i.e. snapshots
+    /// which are missing some of their attributes.
+    fn list_invalid_snapshots() -> Vec<VolumeSnapshotDescriptor>;
+
+    /// List all snapshots with valid metadata whose parent lvol no longer
+    /// exists, e.g. as their source replica has been destroyed. Unlike
+    /// invalid snapshots, these are not missing any attributes.
+    fn list_rooted_orphan_snapshots() -> Vec<VolumeSnapshotDescriptor>;
+
+    /// List all snapshots whose parent lvol no longer exists anywhere on
+    /// this node, i.e. whose space has leaked and can be reclaimed.
+    /// Discarded snapshots are retained on purpose for their clones, and thus
+    /// are not reported.
+    fn list_orphaned_snapshots() -> Vec<VolumeSnapshotDescriptor>;
+
+    /// Get the snapshot attributes missing from this snapshot.
+    fn missing_snapshot_xattrs(&self) -> Vec<SnapshotXattrs>;
+
+    /// List the snapshots on this node belonging to the given consistency
+    /// group.
+    fn list_snapshot_group(group_id: &str) -> Vec<VolumeSnapshotDescriptor>;
+
+    /// Check if all the expected members of the given consistency group have
+    /// been found on this node.
+    fn is_snapshot_group_complete(group_id: &str) -> bool;
+
+    /// Get the most recent snapshot of this replica which has been replicated
+    /// to the given target, i.e. the base of the next incremental
+    /// replication to that target.
+    fn replication_base(&self, target: &str) -> Option<Self::Lvol>;
+
+    /// Record that this snapshot has been successfully replicated to the
+    /// given target.
+    async fn mark_replicated(&self, target: &str) -> Result<(), Self::Error>;
+
+    /// Estimate the average number of blob layers a read of this lvol has to
+    /// consult, walking down its snapshot chain until the block is found.
+    /// This is an approximate model, which assumes that reads are spread
+    /// uniformly over the lvol, and that the clusters allocated by each
+    /// layer are spread uniformly and independently of the other layers.
+    /// An lvol without any snapshot has a factor of 1.
+    fn read_amplification_factor(&self) -> f64;
+
+    /// Split the disk space allocated by this snapshot between the space
+    /// exclusive to it and the space shared with its children, clones
+    /// included, along with the space referenced through its ancestors.
+    fn snapshot_usage_breakdown(&self) -> SnapshotUsageBreakdown;
+
+    /// Get the ranges of this lvol which may have changed since the given
+    /// snapshot was taken, the snapshot being an ancestor of this lvol. The
+    /// ranges do not extend past the size of this lvol.
+    fn snapshot_diff(
+        &self,
+        base: &Lvol,
+    ) -> Result<Vec<ClusterRange>, Self::Error>;
+
+    /// Get the logical size a replica must have to hold the data of this
+    /// snapshot when it is restored.
+    fn required_restore_size(&self) -> u64;
+
+    /// Check that a replica of the given size can hold the data of this
+    /// snapshot, before restoring the snapshot onto it.
+    fn validate_restore_target_size(
+        &self,
+        target_size: u64,
+    ) -> Result<(), Self::Error>;
+
+    /// Roll this replica back to the given snapshot, which must have been
+    /// taken from it and be restorable in-place. The snapshots taken after
+    /// the given one are preserved. The replica must be neither shared nor
+    /// claimed, and is claimed for the duration of the restore.
+    async fn restore_from_snapshot(
+        &self,
+        snapshot: &Self::Lvol,
+    ) -> Result<(), Self::Error>;
+
+    /// Get the UUID of the snapshot this replica was being restored from, if
+    /// that restore did not complete.
+    fn restore_in_progress(&self) -> Option<String>;
+
     /// Create snapshot clone.
     async fn create_clone(
         &self,
@@ -274,6 +1012,8 @@ pub trait SnapshotOps {
     fn list_all_clones() -> Vec<Self::Lvol>;
 
     /// Prepare Snapshot Config for Block/Nvmf Device, before snapshot create.
+    /// When `snap_uuid` is empty, the snapshot uuid is derived from the lvol
+    /// uuid and `txn_id` with `derive_snapshot_uuid`.
     fn prepare_snap_config(
         &self,
         snap_name: &str,
@@ -298,6 +1038,13 @@ pub trait SnapshotOps {
         cstrs: &mut Vec<CString>,
     ) -> Result<(), Self::Error>;
 
+    /// Get the name/value pairs of the attributes a snapshot created with the
+    /// given parameters would carry, without creating it.
+    fn preview_snapshot_xattrs(
+        &self,
+        params: &SnapshotParams,
+    ) -> Result<Vec<(String, String)>, Self::Error>;
+
     /// create replica snapshot inner function to call spdk snapshot create
     /// function.
     unsafe fn create_snapshot_inner(
@@ -355,6 +1102,20 @@ pub trait SnapshotOps {
         parent: Option<&Lvol>,
     ) -> Option<VolumeSnapshotDescriptor>;
 
+    /// Same as `snapshot_descriptor`, but fails with the first attribute
+    /// which is missing or cannot be parsed, instead of flagging the
+    /// snapshot as invalid. A snapshot is valid if, and only if, this
+    /// succeeds.
+    fn try_snapshot_descriptor(
+        &self,
+        parent: Option<&Lvol>,
+    ) -> Result<VolumeSnapshotDescriptor, SnapshotDescriptorError>;
+
+    /// Check the attributes of this snapshot against their checksum.
+    /// Snapshots without a checksum, created by older versions, pass
+    /// unchecked.
+    fn verify_snapshot_integrity(&self) -> Result<(), Self::Error>;
+
     /// Return bool value to indicate, if the snapshot is marked as discarded.
     fn is_discarded_snapshot(&self) -> bool;
 
@@ -411,8 +1172,11 @@ pub trait SnapshotDescriptor {
     /// Set snapshot uuid of the snapshot.
     fn set_snapshot_uuid(&mut self, snapshot_uuid: String);
 
-    /// Get snapshot create time.
-    fn create_time(&self) -> Option<String>;
+    /// Get snapshot create time, failing if it cannot be parsed.
+    fn create_time(&self) -> Result<Option<DateTime<Utc>>, ParseError>;
+
+    /// Get snapshot create time, as it was given or stored.
+    fn create_time_str(&self) -> Option<String>;
 
     /// Set snapshot create time.
     fn set_create_time(&mut self, time: String);
@@ -422,6 +1186,32 @@ pub trait SnapshotDescriptor {
 
     /// Set discarded_snapshot
     fn set_discarded_snapshot(&mut self, discarded: bool);
+
+    /// Get the consistency group id of the snapshot.
+    fn group_id(&self) -> Option<String>;
+
+    /// Set the consistency group id of the snapshot.
+    fn set_group_id(&mut self, group_id: String);
+
+    /// Get the expected number of members of the snapshot consistency group.
+    fn group_member_count(&self) -> Option<u32>;
+
+    /// Set the expected number of members of the snapshot consistency group.
+    fn set_group_member_count(&mut self, count: u32);
+
+    /// Get the retention class of the snapshot.
+    fn retention_class(&self) -> Option<String>;
+
+    /// Set the retention class of the snapshot.
+    fn set_retention_class(&mut self, retention_class: String);
+
+    /// Check if the snapshot attributes are written after the snapshot has
+    /// been taken.
+    fn defer_xattrs(&self) -> bool;
+
+    /// Set whether the snapshot attributes are written after the snapshot
+    /// has been taken.
+    fn set_defer_xattrs(&mut self, defer: bool);
 }
 
 /// Trait to give interface for all Snapshot Parameters.
@@ -470,8 +1260,16 @@ impl SnapshotDescriptor for SnapshotParams {
     fn set_snapshot_uuid(&mut self, snapshot_uuid: String) {
         self.snapshot_uuid = Some(snapshot_uuid);
     }
-    /// Get snapshot create time.
-    fn create_time(&self) -> Option<String> {
+    /// Get snapshot create time, failing if it cannot be parsed.
+    fn create_time(&self) -> Result<Option<DateTime<Utc>>, ParseError> {
+        self.create_time
+            .as_deref()
+            .map(str::parse::<DateTime<Utc>>)
+            .transpose()
+    }
+
+    /// Get snapshot create time, as it was given or stored.
+    fn create_time_str(&self) -> Option<String> {
         self.create_time.clone()
     }
 
@@ -489,4 +1287,46 @@ impl SnapshotDescriptor for SnapshotParams {
     fn set_discarded_snapshot(&mut self, discarded: bool) {
         self.discarded_snapshot = discarded;
     }
+
+    /// Get the consistency group id of the snapshot.
+    fn group_id(&self) -> Option<String> {
+        self.group_id.clone()
+    }
+
+    /// Set the consistency group id of the snapshot.
+    fn set_group_id(&mut self, group_id: String) {
+        self.group_id = Some(group_id);
+    }
+
+    /// Get the expected number of members of the snapshot consistency group.
+    fn group_member_count(&self) -> Option<u32> {
+        self.group_member_count
+    }
+
+    /// Set the expected number of members of the snapshot consistency group.
+    fn set_group_member_count(&mut self, count: u32) {
+        self.group_member_count = Some(count);
+    }
+
+    /// Get the retention class of the snapshot.
+    fn retention_class(&self) -> Option<String> {
+        self.retention_class.clone()
+    }
+
+    /// Set the retention class of the snapshot.
+    fn set_retention_class(&mut self, retention_class: String) {
+        self.retention_class = Some(retention_class);
+    }
+
+    /// Check if the snapshot attributes are written after the snapshot has
+    /// been taken.
+    fn defer_xattrs(&self) -> bool {
+        self.defer_xattrs
+    }
+
+    /// Set whether the snapshot attributes are written after the snapshot
+    /// has been taken.
+    fn set_defer_xattrs(&mut self, defer: bool) {
+        self.defer_xattrs = defer;
+    }
 }
@@ -8,7 +8,7 @@ use events_api::event::{
 
 use crate::{
     core::{snapshot::CloneParams, MayastorEnvironment},
-    eventing::Event,
+    eventing::{Event, EventWithMeta},
 };
 
 impl Event for CloneParams {
@@ -29,3 +29,30 @@ impl Event for CloneParams {
         }
     }
 }
+
+impl EventWithMeta for CloneParams {
+    fn event(&self, action: EventAction, meta: EventMeta) -> EventMessage {
+        EventMessage {
+            category: EventCategory::Clone as i32,
+            action: action as i32,
+            target: self.clone_uuid().unwrap_or_default(),
+            metadata: Some(meta),
+        }
+    }
+}
+
+/// Clone provisioning state change event meta.
+pub(crate) fn clone_state_change_event_meta(
+    params: &CloneParams,
+    previous: &str,
+    next: &str,
+) -> EventMeta {
+    let event_source =
+        EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_clone_data(
+                params.source_uuid().unwrap_or_default(),
+                params.clone_create_time().unwrap_or_default(),
+            )
+            .with_state_change_data(previous.to_string(), next.to_string());
+    EventMeta::from_source(event_source)
+}
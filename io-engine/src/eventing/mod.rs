@@ -1,4 +1,4 @@
-mod clone_events;
+pub(crate) mod clone_events;
 pub(crate) mod host_events;
 pub(crate) mod io_engine_events;
 mod nexus_child_events;
@@ -37,14 +37,21 @@ impl Event for Lvol {
     }
 }
 
-/// Replica state change event meta.
+/// Replica state change event meta, carrying the nexus and the child whose
+/// state changed, along with the fault reason if the child became faulted.
 pub(crate) fn state_change_event_meta(
+    child: &NexusChild,
     previous: ChildState,
     next: ChildState,
 ) -> EventMeta {
-    let event_source =
+    let mut event_source =
         EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_target_data("nexus", child.nexus_name())
+            .with_nexus_child_data(child.uri())
             .with_state_change_data(previous.to_string(), next.to_string());
+    if let ChildState::Faulted(reason) = next {
+        event_source = event_source.with_error_details(reason.to_string());
+    }
     EventMeta::from_source(event_source)
 }
 
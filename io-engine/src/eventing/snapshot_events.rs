@@ -7,7 +7,13 @@ use events_api::event::{
 };
 
 use crate::{
-    core::{MayastorEnvironment, SnapshotDescriptor, SnapshotParams},
+    core::{
+        logical_volume::LogicalVolume,
+        snapshot::VolumeSnapshotDescriptor,
+        MayastorEnvironment,
+        SnapshotDescriptor,
+        SnapshotParams,
+    },
     eventing::Event,
 };
 
@@ -18,7 +24,7 @@ impl Event for SnapshotParams {
         )
         .with_snapshot_data(
             self.parent_id().unwrap_or_default(),
-            self.create_time().unwrap_or_default(),
+            self.create_time_str().unwrap_or_default(),
             self.entity_id().unwrap_or_default(),
         );
 
@@ -30,3 +36,33 @@ impl Event for SnapshotParams {
         }
     }
 }
+
+// Snapshot event messages from the descriptor of an existing snapshot, which
+// carry the snapshot details as they were persisted along with the pool the
+// snapshot belongs to and the snapshot name.
+impl Event for VolumeSnapshotDescriptor {
+    fn event(&self, event_action: EventAction) -> EventMessage {
+        let params = self.snapshot_params();
+        let snapshot_lvol = self.snapshot_lvol();
+        let event_source = EventSource::new(
+            MayastorEnvironment::global_or_default().node_name,
+        )
+        .with_snapshot_data(
+            params.parent_id().unwrap_or_default(),
+            params.create_time_str().unwrap_or_default(),
+            params.entity_id().unwrap_or_default(),
+        )
+        .with_replica_data(
+            &snapshot_lvol.pool_name(),
+            &snapshot_lvol.pool_uuid(),
+            &params.name().unwrap_or_default(),
+        );
+
+        EventMessage {
+            category: EventCategory::Snapshot as i32,
+            action: event_action as i32,
+            target: params.snapshot_uuid().unwrap_or_default(),
+            metadata: Some(EventMeta::from_source(event_source)),
+        }
+    }
+}
@@ -305,6 +305,18 @@ impl From<LvsError> for tonic::Status {
             LvsError::ResourceLockFailed {
                 ..
             } => Status::aborted(e.to_string()),
+            LvsError::SnapshotLimitExceeded {
+                ..
+            } => Status::resource_exhausted(e.to_string()),
+            LvsError::SnapshotTxnConflict {
+                ..
+            } => Status::already_exists(e.to_string()),
+            LvsError::SnapshotRestoreRefused {
+                ..
+            } => Status::failed_precondition(e.to_string()),
+            LvsError::CloneDetachCancelled {
+                ..
+            } => Status::cancelled(e.to_string()),
             _ => Status::internal(e.verbose()),
         }
     }
@@ -33,10 +33,14 @@ fn map_fault_reason(r: FaultReason) -> ChildStateReason {
         FaultReason::NoSpace => NoSpace,
         FaultReason::TimedOut => TimedOut,
         FaultReason::IoError => IoFailure,
+        FaultReason::MediaError => MediaFailure,
+        FaultReason::TransportError => TransportFailure,
         FaultReason::Offline => ByClient,
         FaultReason::RebuildFailed => RebuildFailed,
         FaultReason::AdminCommandFailed => AdminFailed,
         FaultReason::OfflinePermanent => ByClient,
+        FaultReason::DataCorruption => RebuildFailed,
+        FaultReason::LabelMismatch => ConfigInvalid,
     }
 }
 
@@ -53,6 +57,7 @@ fn map_child_state(child: &NexusChild) -> (ChildState, ChildStateReason) {
         ChildStateClient::Init => (Degraded, Init),
         ChildStateClient::ConfigInvalid => (Faulted, ConfigInvalid),
         ChildStateClient::Open => (Online, None),
+        ChildStateClient::OpenReadOnly => (Degraded, None),
         ChildStateClient::Closed => (Degraded, Closed),
         ChildStateClient::Faulted(r) => (
             match r {
@@ -135,19 +135,25 @@ impl From<NexusStatus> for NexusState {
     }
 }
 
-fn map_fault_reason(r: FaultReason) -> ChildStateReason {
-    use ChildStateReason::*;
-
-    match r {
-        FaultReason::Unknown => None,
-        FaultReason::CantOpen => CannotOpen,
-        FaultReason::NoSpace => NoSpace,
-        FaultReason::TimedOut => TimedOut,
-        FaultReason::IoError => IoFailure,
-        FaultReason::Offline => ByClient,
-        FaultReason::RebuildFailed => RebuildFailed,
-        FaultReason::AdminCommandFailed => AdminFailed,
-        FaultReason::OfflinePermanent => ByClient,
+impl From<FaultReason> for ChildStateReason {
+    fn from(r: FaultReason) -> Self {
+        use ChildStateReason::*;
+
+        match r {
+            FaultReason::Unknown => None,
+            FaultReason::CantOpen => CannotOpen,
+            FaultReason::NoSpace => NoSpace,
+            FaultReason::TimedOut => TimedOut,
+            FaultReason::IoError => IoFailure,
+            FaultReason::MediaError => MediaFailure,
+            FaultReason::TransportError => TransportFailure,
+            FaultReason::Offline => ByClient,
+            FaultReason::RebuildFailed => RebuildFailed,
+            FaultReason::AdminCommandFailed => AdminFailed,
+            FaultReason::OfflinePermanent => ByClient,
+            FaultReason::DataCorruption => RebuildFailed,
+            FaultReason::LabelMismatch => ConfigInvalid,
+        }
     }
 }
 
@@ -159,6 +165,7 @@ fn map_child_state(child: &NexusChild) -> (ChildState, ChildStateReason) {
         ChildStateClient::Init => (Degraded, Init),
         ChildStateClient::ConfigInvalid => (Faulted, ConfigInvalid),
         ChildStateClient::Open => (Online, None),
+        ChildStateClient::OpenReadOnly => (Degraded, None),
         ChildStateClient::Closed => (Degraded, Closed),
         ChildStateClient::Faulted(r) => (
             match r {
@@ -166,9 +173,9 @@ fn map_child_state(child: &NexusChild) -> (ChildState, ChildStateReason) {
                 FaultReason::Offline => Degraded,
                 _ => Faulted,
             },
-            map_fault_reason(r),
+            r.into(),
         ),
-        ChildStateClient::Faulting(r) => (Unknown, map_fault_reason(r)),
+        ChildStateClient::Faulting(r) => (Unknown, r.into()),
         ChildStateClient::OutOfSync => (Degraded, OutOfSync),
     }
 }
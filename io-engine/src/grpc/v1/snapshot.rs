@@ -47,6 +47,25 @@ pub struct SnapshotService {
     replica_svc: super::replica::ReplicaService,
 }
 
+/// Get the create time of a snapshot, reporting none if it cannot be parsed.
+fn snapshot_create_time(
+    snapshot_name: &str,
+    params: &SnapshotParams,
+) -> Option<DateTime<Utc>> {
+    match params.create_time() {
+        Ok(time) => time,
+        Err(error) => {
+            warn!(
+                snapshot = snapshot_name,
+                create_time = params.create_time_str(),
+                %error,
+                "Failed to parse snapshot create time"
+            );
+            None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ReplicaSnapshotDescriptor {
     pub snapshot_lvol: Lvol,
@@ -139,6 +158,7 @@ impl From<ReplicaSnapshotDescriptor> for SnapshotInfo {
                         curr_attr_val.parse().unwrap_or_default(),
                     );
                 }
+                SnapshotXattrs::Checksum => {}
             }
         }
 
@@ -149,9 +169,8 @@ impl From<ReplicaSnapshotDescriptor> for SnapshotInfo {
             snapshot_name: snap_lvol.name(),
             snapshot_size: usage.allocated_bytes,
             num_clones: 0, //TODO: Need to implement along with clone
-            timestamp: snapshot_param
-                .create_time()
-                .map(|s| s.parse::<DateTime<Utc>>().unwrap_or_default().into()),
+            timestamp: snapshot_create_time(&snap_lvol.name(), &snapshot_param)
+                .map(Into::into),
             source_uuid: r.replica_uuid,
             source_size: r.replica_size,
             pool_uuid: snap_lvol.pool_uuid(),
@@ -179,10 +198,11 @@ impl From<VolumeSnapshotDescriptor> for SnapshotInfo {
             snapshot_name: s.snapshot_params().name().unwrap_or_default(),
             snapshot_size: usage.allocated_bytes,
             num_clones: s.num_clones(),
-            timestamp: s
-                .snapshot_params()
-                .create_time()
-                .map(|s| s.parse::<DateTime<Utc>>().unwrap_or_default().into()),
+            timestamp: snapshot_create_time(
+                &s.snapshot_lvol().name(),
+                s.snapshot_params(),
+            )
+            .map(Into::into),
             source_uuid: s.source_uuid(),
             source_size: s.snapshot_lvol().size(),
             pool_uuid: s.snapshot_lvol().pool_uuid(),
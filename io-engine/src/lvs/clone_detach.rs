@@ -0,0 +1,100 @@
+//! Tracking of the detach of the clones from their source snapshots: the
+//! clusters a clone still shares with its snapshot are copied into it one at
+//! a time, so that the copy can be followed and cancelled while it runs.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+/// Progress of the detach of a clone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloneDetachProgress {
+    /// Number of shared clusters copied so far.
+    pub clusters_copied: u64,
+    /// Number of clusters the clone shared with its snapshot.
+    pub clusters_total: u64,
+}
+
+/// Detach of a clone, shared by the detaching future and the registry.
+#[derive(Debug)]
+struct CloneDetach {
+    cancelled: AtomicBool,
+    clusters_copied: AtomicU64,
+    clusters_total: u64,
+}
+
+/// Detaches in progress, by clone uuid.
+static DETACHES: Lazy<Mutex<HashMap<String, Arc<CloneDetach>>>> =
+    Lazy::new(Default::default);
+
+/// Registration of a running detach, which is forgotten once dropped.
+pub(crate) struct CloneDetachGuard {
+    uuid: String,
+    detach: Arc<CloneDetach>,
+}
+
+impl CloneDetachGuard {
+    /// Registers the detach of the clone with the given uuid, unless the
+    /// clone is already being detached.
+    pub(crate) fn new(uuid: &str, clusters_total: u64) -> Option<Self> {
+        let mut detaches = DETACHES.lock();
+        if detaches.contains_key(uuid) {
+            return None;
+        }
+        let detach = Arc::new(CloneDetach {
+            cancelled: AtomicBool::new(false),
+            clusters_copied: AtomicU64::new(0),
+            clusters_total,
+        });
+        detaches.insert(uuid.to_string(), detach.clone());
+        Some(Self {
+            uuid: uuid.to_string(),
+            detach,
+        })
+    }
+
+    /// Checks whether the detach has been cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.detach.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Accounts for one more shared cluster copied into the clone.
+    pub(crate) fn cluster_copied(&self) {
+        self.detach.clusters_copied.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for CloneDetachGuard {
+    fn drop(&mut self) {
+        DETACHES.lock().remove(&self.uuid);
+    }
+}
+
+/// Returns the progress of the detach of the clone with the given uuid, if
+/// it is being detached.
+pub fn clone_detach_progress(uuid: &str) -> Option<CloneDetachProgress> {
+    DETACHES.lock().get(uuid).map(|d| CloneDetachProgress {
+        clusters_copied: d.clusters_copied.load(Ordering::Relaxed),
+        clusters_total: d.clusters_total,
+    })
+}
+
+/// Cancels the detach of the clone with the given uuid, which then remains
+/// dependent on its snapshot. Returns false if the clone is not being
+/// detached.
+pub fn cancel_clone_detach(uuid: &str) -> bool {
+    match DETACHES.lock().get(uuid) {
+        Some(detach) => {
+            detach.cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,207 @@
+//! Warm-up of the clones: a freshly created clone serves its reads from its
+//! source snapshot until written, so the first accesses to cold data are
+//! slow. The warm-up reads the clone through, cluster by cluster, to fault
+//! that data in beforehand.
+//!
+//! The io-engine keeps no cache of the data of its own: the reads go down to
+//! the device of the pool, and warm whatever lies below it, e.g. the read
+//! cache of a storage array, or a cloud volume restored from a snapshot,
+//! whose blocks are only fetched from the object store on their first
+//! access. On a local device without any such cache, the warm-up buys
+//! nothing, hence it is only run on request.
+//!
+//! Only the clusters the clone has not allocated yet are read, as the others
+//! are served by the clone itself. The warm-up is driven from the tokio
+//! runtime, the clone being read through a single handle on the primary
+//! reactor.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crossbeam::atomic::AtomicCell;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+use super::Lvol;
+use crate::core::{
+    runtime,
+    CoreError,
+    LogicalVolume,
+    Reactor,
+    UntypedBdev,
+    UntypedBdevHandle,
+};
+
+/// State of the warm-up of a clone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClonePrefetchState {
+    /// The clone is being read through.
+    Running,
+    /// The whole clone has been read through.
+    Completed,
+    /// The warm-up has been stopped, e.g. as the clone has been destroyed.
+    Cancelled,
+    /// A read of the clone failed.
+    Failed,
+}
+
+/// Progress of the warm-up of a clone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClonePrefetchProgress {
+    /// State of the warm-up.
+    pub state: ClonePrefetchState,
+    /// Number of clusters warmed up so far, the clusters allocated by the
+    /// clone itself being warm already.
+    pub clusters_read: u64,
+    /// Number of clusters of the clone.
+    pub clusters_total: u64,
+}
+
+/// Warm-up of a clone, shared by its task and the registry.
+#[derive(Debug)]
+struct ClonePrefetch {
+    state: AtomicCell<ClonePrefetchState>,
+    clusters_read: AtomicU64,
+    clusters_total: u64,
+}
+
+impl ClonePrefetch {
+    fn progress(&self) -> ClonePrefetchProgress {
+        ClonePrefetchProgress {
+            state: self.state.load(),
+            clusters_read: self.clusters_read.load(Ordering::Relaxed),
+            clusters_total: self.clusters_total,
+        }
+    }
+
+    /// Moves a running warm-up to the given final state.
+    fn finish(&self, state: ClonePrefetchState) {
+        self.state
+            .compare_exchange(ClonePrefetchState::Running, state)
+            .ok();
+    }
+}
+
+/// Warm-ups of the clones, by clone uuid. A warm-up is kept until its clone
+/// is destroyed, so that its outcome remains observable.
+static PREFETCHES: Lazy<Mutex<HashMap<String, Arc<ClonePrefetch>>>> =
+    Lazy::new(Default::default);
+
+/// Starts the warm-up of the given clone in the background. Any previous
+/// warm-up of the clone is cancelled.
+pub(crate) fn start_clone_prefetch(clone: &Lvol) {
+    let uuid = clone.uuid();
+    let cluster_size = clone.usage().cluster_size.max(1);
+    let size = clone.size();
+    let prefetch = Arc::new(ClonePrefetch {
+        state: AtomicCell::new(ClonePrefetchState::Running),
+        clusters_read: AtomicU64::new(0),
+        clusters_total: (size + cluster_size - 1) / cluster_size,
+    });
+
+    if let Some(previous) =
+        PREFETCHES.lock().insert(uuid.clone(), prefetch.clone())
+    {
+        previous.finish(ClonePrefetchState::Cancelled);
+    }
+    info!(
+        clone = clone.name(),
+        clusters = prefetch.clusters_total,
+        "Starting clone warm-up"
+    );
+
+    runtime::spawn_cooperative(move |token| async move {
+        let warm_up = {
+            let (uuid, prefetch) = (uuid.clone(), prefetch.clone());
+            Reactor::spawn_at_primary(async move {
+                warm_up(&uuid, &prefetch, cluster_size, &token).await
+            })
+        };
+        let result = match warm_up {
+            Ok(r) => r.await.unwrap_or(Ok(())),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            warn!(clone = uuid, %error, "Clone warm-up failed");
+            prefetch.finish(ClonePrefetchState::Failed);
+        }
+        prefetch.finish(ClonePrefetchState::Completed);
+        debug!(
+            clone = uuid,
+            progress = ?prefetch.progress(),
+            "Clone warm-up done"
+        );
+    });
+}
+
+/// Reads through the clusters of the clone with the given uuid which the
+/// clone has not allocated, discarding the data. Stops as soon as the
+/// warm-up is no longer running: the clone is then released after at most
+/// one more read, so that it can be destroyed.
+async fn warm_up(
+    uuid: &str,
+    prefetch: &ClonePrefetch,
+    cluster_size: u64,
+    token: &runtime::ShutdownToken,
+) -> Result<(), CoreError> {
+    let Some(bdev) = UntypedBdev::lookup_by_uuid_str(uuid) else {
+        prefetch.finish(ClonePrefetchState::Cancelled);
+        return Ok(());
+    };
+    let Ok(clone) = Lvol::try_from(bdev) else {
+        prefetch.finish(ClonePrefetchState::Cancelled);
+        return Ok(());
+    };
+    let owned = clone.blob_allocated_clusters(clone.blob_checked());
+    let size = clone.size();
+
+    let hdl = UntypedBdevHandle::open_with_bdev(&bdev, false)?;
+    let mut buf = hdl.dma_malloc(cluster_size).map_err(|_| {
+        CoreError::DmaAllocationFailed {
+            size: cluster_size,
+        }
+    })?;
+
+    for cluster in 0 .. prefetch.clusters_total {
+        if token.is_shutdown() {
+            prefetch.finish(ClonePrefetchState::Cancelled);
+        }
+        if prefetch.state.load() != ClonePrefetchState::Running {
+            break;
+        }
+
+        if !owned.get(cluster as usize).copied().unwrap_or(false) {
+            let offset = cluster * cluster_size;
+            let len = cluster_size.min(size - offset);
+            if buf.len() != len {
+                buf = hdl.dma_malloc(len).map_err(|_| {
+                    CoreError::DmaAllocationFailed {
+                        size: len,
+                    }
+                })?;
+            }
+            hdl.read_at(offset, &mut buf).await?;
+        }
+        prefetch.clusters_read.fetch_add(1, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Cancels the warm-up of the clone with the given uuid, if any, and
+/// forgets about it.
+pub(crate) fn cancel_clone_prefetch(uuid: &str) {
+    if let Some(prefetch) = PREFETCHES.lock().remove(uuid) {
+        prefetch.finish(ClonePrefetchState::Cancelled);
+    }
+}
+
+/// Returns the progress of the warm-up of the clone with the given uuid, if
+/// it has been warmed up.
+pub fn clone_prefetch_progress(uuid: &str) -> Option<ClonePrefetchProgress> {
+    PREFETCHES.lock().get(uuid).map(|p| p.progress())
+}
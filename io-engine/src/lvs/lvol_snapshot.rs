@@ -1,151 +1,742 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     convert::TryFrom,
-    ffi::{c_ushort, c_void, CString},
+    ffi::{c_ushort, c_void, CStr, CString},
     os::raw::c_char,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
 };
 
 use async_trait::async_trait;
-use chrono::Utc;
-use futures::{channel::oneshot, future::join_all};
+use chrono::{DateTime, Utc};
+use futures::{
+    channel::oneshot,
+    future::join_all,
+    stream,
+    Future,
+    Stream,
+    StreamExt,
+};
 use nix::errno::Errno;
+use once_cell::sync::Lazy;
 use strum::{EnumCount, IntoEnumIterator};
 
 use events_api::event::EventAction;
 
 use spdk_rs::libspdk::{
     spdk_blob,
+    spdk_blob_calc_used_clusters,
+    spdk_blob_get_next_allocated_io_unit,
+    spdk_blob_get_next_unallocated_io_unit,
+    spdk_blob_get_num_clusters,
+    spdk_blob_get_xattr_names,
     spdk_blob_reset_used_clusters_cache,
+    spdk_bs_get_cluster_size,
+    spdk_bs_get_io_unit_size,
     spdk_lvol,
     spdk_xattr_descriptor,
+    spdk_xattr_names,
+    spdk_xattr_names_free,
+    spdk_xattr_names_get_count,
+    spdk_xattr_names_get_name,
     vbdev_lvol_create_clone_ext,
     vbdev_lvol_create_snapshot_ext,
+    vbdev_lvol_rename,
 };
 
 use crate::{
     core::{
         logical_volume::LogicalVolume,
+        runtime::{self, SpawnLimiter},
         snapshot::{
+            derive_snapshot_uuid,
+            format_snapshot_create_time,
+            snapshot_xattrs_checksum,
+            AncestryNode,
             CloneParams,
+            ClusterRange,
+            DestroyPlan,
+            DestroyTreeReport,
             LvolResult,
+            PoolTopology,
+            PruneReport,
+            RestoreEligibility,
+            SnapshotBatchOutcome,
             SnapshotDescriptor,
+            SnapshotDescriptorError,
+            SnapshotHookId,
+            SnapshotPage,
+            SnapshotPostCreateHook,
+            SnapshotPreCreateHook,
+            SnapshotUsageBreakdown,
+            TopologyEdge,
+            TopologyEdgeKind,
+            TopologyNode,
+            TopologyNodeKind,
+            TreeUsageSummary,
             VolumeSnapshotDescriptor,
         },
         Bdev,
         CloneXattrs,
+        Reactor,
+        RestoreXattrs,
+        Share,
+        SnapshotCacheXattrs,
+        SnapshotGroupXattrs,
+        SnapshotLabel,
+        SnapshotLabelXattrs,
         SnapshotOps,
         SnapshotParams,
+        SnapshotReplicationXattrs,
+        SnapshotRetentionXattrs,
         SnapshotXattrs,
         UntypedBdev,
+        UntypedBdevHandle,
+    },
+    eventing::{
+        clone_events::clone_state_change_event_meta,
+        Event,
+        EventWithMeta,
     },
-    eventing::Event,
     ffihelper::{cb_arg, done_cb, IntoCString},
-    subsys::NvmfReq,
+    subsys::{Config, NvmfReq},
 };
 
-use super::{BsError, Lvol, LvsError, LvsLvol};
+use super::{
+    clone_prefetch::start_clone_prefetch,
+    BsError,
+    Lvol,
+    Lvs,
+    LvsError,
+    LvsLvol,
+};
 
-/// TODO
-pub trait AsyncParentIterator {
-    type Item;
-    fn parent(&mut self) -> Option<Self::Item>;
-}
+impl Lvol {
+    /// Looks up the snapshot with the given UUID.
+    fn lookup_snapshot(uuid: &str) -> Result<Lvol, LvsError> {
+        UntypedBdev::lookup_by_uuid_str(uuid)
+            .and_then(|b| Lvol::try_from(b).ok())
+            .filter(|l| l.is_snapshot())
+            .ok_or_else(|| LvsError::Invalid {
+                source: BsError::LvolNotFound {},
+                msg: format!("snapshot {uuid} not found"),
+            })
+    }
 
-/// Iterator over Lvol Blobstore for Snapshot.
-pub struct LvolSnapshotIter {
-    inner_blob: *mut spdk_blob,
-    inner_lvol: Lvol,
-}
+    /// Reads the given attributes of the blob in a single pass over its xattr
+    /// set, keyed by attribute name. The values of other attributes are not
+    /// read. Attributes without a value, or which fail to parse, are left out
+    /// of the map, as `get_blob_xattr` does.
+    fn read_snapshot_xattrs(
+        blob: *mut spdk_blob,
+        wanted: &[&str],
+    ) -> HashMap<String, String> {
+        let mut xattrs = HashMap::new();
+        if blob.is_null() || wanted.is_empty() {
+            return xattrs;
+        }
 
-impl LvolSnapshotIter {
-    pub fn new(lvol: Lvol) -> Self {
-        Self {
-            inner_blob: lvol.bs_iter_first(),
-            inner_lvol: lvol,
+        let mut names: *mut spdk_xattr_names = std::ptr::null_mut();
+        let rc = unsafe { spdk_blob_get_xattr_names(blob, &mut names) };
+        if rc != 0 || names.is_null() {
+            warn!(rc, "Failed to read the blob attribute names");
+            return xattrs;
+        }
+
+        let count = unsafe { spdk_xattr_names_get_count(names) };
+        let mut remaining = wanted.len();
+        for index in 0 .. count {
+            if remaining == 0 {
+                break;
+            }
+            let name = unsafe {
+                let name = spdk_xattr_names_get_name(names, index);
+                if name.is_null() {
+                    continue;
+                }
+                CStr::from_ptr(name)
+            };
+            let Some(attr) =
+                wanted.iter().find(|w| w.as_bytes() == name.to_bytes())
+            else {
+                continue;
+            };
+            remaining -= 1;
+            if let Some(value) = Self::get_blob_xattr_cstr(blob, name) {
+                xattrs.insert(attr.to_string(), value);
+            }
         }
+        unsafe { spdk_xattr_names_free(names) };
+
+        xattrs
     }
-}
 
-/// Iterator implementation for LvolSnapshot.
-impl AsyncParentIterator for LvolSnapshotIter {
-    type Item = VolumeSnapshotDescriptor;
-    fn parent(&mut self) -> Option<Self::Item> {
-        if self.inner_blob.is_null() {
-            None
-        } else {
-            let parent_blob =
-                unsafe { self.inner_lvol.bs_iter_parent(self.inner_blob) }?;
-            let uuid = Lvol::get_blob_xattr(
-                parent_blob,
-                SnapshotXattrs::SnapshotUuid.name(),
-            )?;
-            let snap_lvol = UntypedBdev::lookup_by_uuid_str(&uuid)
-                .and_then(|bdev| Lvol::try_from(bdev).ok())?;
-            self.inner_blob = parent_blob;
-            self.inner_lvol = snap_lvol.clone();
-            snap_lvol.snapshot_descriptor(None)
+    /// Reads all the snapshot attributes of the blob, as
+    /// `read_snapshot_xattrs` does.
+    fn read_all_snapshot_xattrs(
+        blob: *mut spdk_blob,
+    ) -> HashMap<String, String> {
+        let wanted = SnapshotXattrs::iter()
+            .map(|attr| attr.name())
+            .collect::<Vec<_>>();
+        Self::read_snapshot_xattrs(blob, &wanted)
+    }
+
+    /// Finds the snapshot of this lvol created by the given transaction, if
+    /// any. Only the snapshot chain of this lvol is walked, reading the
+    /// transaction id and the parent of each snapshot alone. Discarded
+    /// snapshots are ignored, as they are pending destruction.
+    fn find_snapshot_by_txn_id(&self, txn_id: &str) -> Option<Lvol> {
+        let uuid = self.uuid();
+        let mut blob = self.bs_iter_first();
+
+        while let Some(parent) = unsafe { self.bs_iter_parent(blob) } {
+            blob = parent;
+            let xattr = |attr: SnapshotXattrs| {
+                Lvol::get_blob_xattr(parent, attr.name())
+            };
+            if xattr(SnapshotXattrs::TxId).as_deref() != Some(txn_id)
+                || xattr(SnapshotXattrs::ParentId).as_ref() != Some(&uuid)
+            {
+                continue;
+            }
+            let snapshot = xattr(SnapshotXattrs::SnapshotUuid)
+                .and_then(|uuid| UntypedBdev::lookup_by_uuid_str(&uuid))
+                .and_then(|bdev| Lvol::try_from(bdev).ok());
+            if let Some(snapshot) = snapshot {
+                if !snapshot.is_discarded_snapshot() {
+                    return Some(snapshot);
+                }
+            }
         }
+        None
     }
-}
 
-#[async_trait(?Send)]
-impl SnapshotOps for Lvol {
-    type Error = LvsError;
-    type SnapshotIter = LvolSnapshotIter;
-    type Lvol = Lvol;
+    /// Checks if the attribute identifies the snapshot, and thus must be
+    /// stored when the snapshot is created, even with deferred attributes.
+    fn is_snapshot_identity_xattr(attr: &SnapshotXattrs) -> bool {
+        matches!(
+            attr,
+            SnapshotXattrs::SnapshotUuid | SnapshotXattrs::ParentId
+        )
+    }
 
-    /// Prepare Snapshot Config for Block/Nvmf Device, before snapshot create.
-    fn prepare_snap_config(
+    /// Writes the snapshot attributes which have been deferred when the
+    /// snapshot was created.
+    async fn write_deferred_snapshot_xattrs(
         &self,
-        snap_name: &str,
-        entity_id: &str,
-        txn_id: &str,
-        snap_uuid: &str,
-    ) -> Option<SnapshotParams> {
-        // snap_name
-        let snap_name = if snap_name.is_empty() {
-            return None;
-        } else {
-            snap_name.to_string()
+        params: &SnapshotParams,
+    ) -> Result<(), LvsError> {
+        let create_time = self.snapshot_create_time_value(params)?;
+        let mut attrs = SnapshotXattrs::iter()
+            .filter(|attr| !Self::is_snapshot_identity_xattr(attr))
+            .map(|attr| {
+                let value = match attr {
+                    SnapshotXattrs::TxId => params.txn_id(),
+                    SnapshotXattrs::EntityId => params.entity_id(),
+                    SnapshotXattrs::SnapshotCreateTime => create_time.clone(),
+                    SnapshotXattrs::DiscardedSnapshot => {
+                        Some(params.discarded_snapshot().to_string())
+                    }
+                    SnapshotXattrs::Checksum => {
+                        Some(Self::snapshot_params_checksum(params))
+                    }
+                    SnapshotXattrs::ParentId | SnapshotXattrs::SnapshotUuid => {
+                        None
+                    }
+                };
+                (attr.name(), value.unwrap_or_default())
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(group_id) = params.group_id() {
+            attrs.push((SnapshotGroupXattrs::GroupId.name(), group_id));
+            attrs.push((
+                SnapshotGroupXattrs::GroupMemberCount.name(),
+                params.group_member_count().unwrap_or_default().to_string(),
+            ));
+        }
+        for (attr, av) in Self::snapshot_retention_xattr_values(params) {
+            attrs.push((attr.name(), av));
+        }
+
+        // Only sync the blob metadata once, with the last attribute.
+        let last = attrs.len() - 1;
+        for (idx, (name, value)) in attrs.into_iter().enumerate() {
+            self.set_blob_attr(name, value, idx == last).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks whether this snapshot can be restored onto its source replica,
+    /// only to a new replica, or not at all.
+    fn restore_eligibility(
+        &self,
+        params: &SnapshotParams,
+        valid_snapshot: bool,
+        source: Option<&Lvol>,
+    ) -> RestoreEligibility {
+        if params.discarded_snapshot() {
+            return RestoreEligibility::Ineligible {
+                reason: "snapshot is discarded".to_string(),
+            };
+        }
+        if !valid_snapshot {
+            return RestoreEligibility::Ineligible {
+                reason: format!(
+                    "snapshot metadata is incomplete, missing {:?}",
+                    self.missing_snapshot_xattrs()
+                ),
+            };
+        }
+        match source {
+            None => RestoreEligibility::NewReplicaOnly {
+                reason: "source replica no longer exists".to_string(),
+            },
+            Some(source) if source.size() != self.required_restore_size() => {
+                RestoreEligibility::NewReplicaOnly {
+                    reason: format!(
+                        "source replica has been resized from {} to {} bytes",
+                        self.required_restore_size(),
+                        source.size()
+                    ),
+                }
+            }
+            Some(_) => RestoreEligibility::InPlace,
+        }
+    }
+
+    /// Gets the number of clones of this snapshot cached in its attributes.
+    fn cached_clone_count(&self) -> Option<u64> {
+        Lvol::get_blob_xattr(
+            self.blob_checked(),
+            SnapshotCacheXattrs::CloneRefcount.name(),
+        )
+        .and_then(|count| count.parse().ok())
+    }
+
+    /// Gets the number of clones of this snapshot.
+    pub fn num_clones(&self) -> u64 {
+        Self::with_clone_index(|index| {
+            index.get(&self.uuid()).map_or(0, |c| c.len() as u64)
+        })
+    }
+
+    /// Gets the number of clones on the chain from the root replica down to
+    /// this lvol. The walk stops as soon as the depth exceeds `limit`, so
+    /// that it is bounded by the limit rather than by the depth of the tree,
+    /// or as soon as it comes back to an lvol it went through, should the
+    /// parent attributes form a cycle.
+    fn clone_chain_depth(&self, limit: u32) -> u32 {
+        let mut depth = 0;
+        let mut visited = HashSet::new();
+        let mut lvol = self.clone();
+        while depth <= limit {
+            if !visited.insert(lvol.uuid()) {
+                warn!(
+                    lvol = self.name(),
+                    uuid = lvol.uuid(),
+                    "Cycle in the ancestry of the lvol"
+                );
+                break;
+            }
+            let next = if lvol.is_snapshot() {
+                Lvol::get_blob_xattr(
+                    lvol.blob_checked(),
+                    SnapshotXattrs::ParentId.name(),
+                )
+                .and_then(|parent| UntypedBdev::lookup_by_uuid_str(&parent))
+                .and_then(|b| Lvol::try_from(b).ok())
+            } else {
+                let source = lvol.is_snapshot_clone();
+                if source.is_some() {
+                    depth += 1;
+                }
+                source
+            };
+            match next {
+                Some(next) => lvol = next,
+                None => break,
+            }
+        }
+        depth
+    }
+
+    /// Checks that a snapshot of this lvol, or a clone of this snapshot if
+    /// `clone` is set, would not exceed the configured clone chain depth.
+    fn check_clone_chain_depth(&self, clone: bool) -> Result<(), LvsError> {
+        let limit = Config::get().snapshot_opts.max_clone_chain_depth;
+        if limit == 0 {
+            return Ok(());
+        }
+        if self.clone_chain_depth(limit) + clone as u32 > limit {
+            return Err(LvsError::CloneChainTooDeep {
+                name: self.name(),
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that this lvol has fewer snapshots than the configured limit,
+    /// so that one more snapshot can be taken.
+    fn check_snapshot_limit(&self) -> Result<(), LvsError> {
+        let opts = &Config::get().snapshot_opts;
+        let limit = opts.max_snapshots_per_volume;
+        if limit == 0 {
+            return Ok(());
+        }
+        let count = self
+            .list_snapshot_by_source_uuid()
+            .iter()
+            .filter(|s| {
+                !opts.limit_excludes_discarded
+                    || !s.snapshot_params().discarded_snapshot()
+            })
+            .count() as u32;
+        if count >= limit {
+            return Err(LvsError::SnapshotLimitExceeded {
+                name: self.name(),
+                count,
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Gets the uuids of the lvols whose usage cache is reset when a snapshot
+    /// is destroyed after its parent. Only built for the tests.
+    #[cfg(feature = "io-engine-testing")]
+    pub fn wildcard_snapshot_tree_uuids() -> Vec<String> {
+        wildcard_snapshot_tree().iter().map(|l| l.uuid()).collect()
+    }
+
+    /// Gets which clusters of the given blob of this lvol's pool are
+    /// allocated by the blob itself, regardless of its ancestors.
+    pub(super) fn blob_allocated_clusters(
+        &self,
+        blob: *mut spdk_blob,
+    ) -> Vec<bool> {
+        let bs = self.lvs().blob_store();
+        let (io_units_per_cluster, num_clusters) = unsafe {
+            (
+                spdk_bs_get_cluster_size(bs) / spdk_bs_get_io_unit_size(bs),
+                spdk_blob_get_num_clusters(blob),
+            )
         };
-        let entity_id = if entity_id.is_empty() {
-            return None;
-        } else {
-            entity_id.to_string()
+        let mut allocated = vec![false; num_clusters as usize];
+        let end = num_clusters * io_units_per_cluster;
+        let mut offset = 0;
+        while offset < end {
+            let start =
+                unsafe { spdk_blob_get_next_allocated_io_unit(blob, offset) };
+            if start >= end {
+                break;
+            }
+            let stop =
+                unsafe { spdk_blob_get_next_unallocated_io_unit(blob, start) }
+                    .min(end);
+            let first = start / io_units_per_cluster;
+            let last = (stop + io_units_per_cluster - 1) / io_units_per_cluster;
+            allocated[first as usize .. last as usize].fill(true);
+            offset = stop;
+        }
+        allocated
+    }
+
+    /// Gets the disk space freed by destroying this snapshot, in bytes. The
+    /// blobstore merges the clusters of a deleted snapshot into the blob
+    /// built upon it, so only the clusters which that blob has allocated
+    /// itself are freed, or all of them if no blob is built upon the
+    /// snapshot anymore. The given lvols, destroyed beforehand, are not
+    /// accounted as built upon the snapshot.
+    fn snapshot_reclaimed_bytes(&self, destroyed: &[String]) -> u64 {
+        let blob = self.blob_checked();
+        let children = self
+            .lvs()
+            .lvols()
+            .into_iter()
+            .flatten()
+            .filter(|l| !destroyed.contains(&l.uuid()))
+            .map(|l| l.blob_checked())
+            .filter(|b| unsafe { self.bs_iter_parent(*b) } == Some(blob))
+            .map(|b| self.blob_allocated_clusters(b))
+            .collect::<Vec<_>>();
+
+        let freed = self
+            .blob_allocated_clusters(blob)
+            .into_iter()
+            .enumerate()
+            .filter(|(cluster, allocated)| {
+                *allocated
+                    && children.iter().all(|child| {
+                        child.get(*cluster).copied().unwrap_or(false)
+                    })
+            })
+            .count() as u64;
+        freed * self.usage().cluster_size
+    }
+
+    /// Drops the index of the clones of the snapshots. Must be called as soon
+    /// as a clone is created or destroyed, or its source attribute is set,
+    /// before yielding to any other future, so that no stale clone is ever
+    /// seen.
+    pub(crate) fn invalidate_clone_index() {
+        *CLONE_INDEX.lock() = None;
+    }
+
+    /// Runs the given function with the index of the clones of the
+    /// snapshots, building it first if needed.
+    fn with_clone_index<R>(
+        f: impl FnOnce(&HashMap<String, Vec<String>>) -> R,
+    ) -> R {
+        let mut index = CLONE_INDEX.lock();
+        let index = index.get_or_insert_with(|| {
+            let mut index: HashMap<String, Vec<String>> = HashMap::new();
+            let Some(bdev) = UntypedBdev::bdev_first() else {
+                return index;
+            };
+            for lvol in bdev
+                .into_iter()
+                .filter(|b| b.driver() == "lvol")
+                .map(|b| Lvol::try_from(b).unwrap())
+            {
+                if let Some(source_uuid) = Lvol::get_blob_xattr(
+                    lvol.blob_checked(),
+                    CloneXattrs::SourceUuid.name(),
+                ) {
+                    index.entry(source_uuid).or_default().push(lvol.uuid());
+                }
+            }
+            index
+        });
+        f(index)
+    }
+
+    /// Destroys this snapshot if it has been discarded and no clone refers to
+    /// it anymore, e.g. once its last clone is gone, rather than leaving it
+    /// to the garbage collection of the next pool import. Returns whether the
+    /// snapshot has been destroyed.
+    pub(crate) async fn destroy_discarded_unreferenced(
+        &self,
+    ) -> Result<bool, LvsError> {
+        if !self.is_snapshot()
+            || !self.is_discarded_snapshot()
+            || self.has_snapshot_dependents()
+        {
+            return Ok(false);
+        }
+
+        self.reset_snapshot_tree_usage_cache(false);
+        let name = self.clone().destroy().await?;
+        info!(
+            snapshot = name,
+            "Destroyed discarded snapshot without clones"
+        );
+        Ok(true)
+    }
+
+    /// Updates the number of clones of this snapshot cached in its
+    /// attributes. Falls back to counting the clones when nothing is cached
+    /// yet. The cache is best effort, failures are only logged.
+    pub(crate) async fn update_clone_refcount(&self, added: bool) {
+        let count = match self.cached_clone_count() {
+            Some(count) if added => count + 1,
+            Some(count) => count.saturating_sub(1),
+            None => self.list_clones_by_snapshot_uuid().len() as u64,
         };
+        if let Err(error) = self
+            .set_blob_attr(
+                SnapshotCacheXattrs::CloneRefcount.name(),
+                count.to_string(),
+                true,
+            )
+            .await
+        {
+            warn!(
+                snapshot = self.name(),
+                %error,
+                "Failed to update snapshot clone refcount"
+            );
+        }
+    }
 
-        // txn_id
-        let txn_id = if txn_id.is_empty() {
-            return None;
-        } else {
-            txn_id.to_string()
+    /// Checks if volumes depend on the data of this snapshot without
+    /// originating from its source replica, i.e. the clones of this snapshot
+    /// and of its descendants, and their own snapshots, which remain after
+    /// their clone is gone. Such a snapshot cannot be destroyed yet.
+    fn has_snapshot_dependents(&self) -> bool {
+        let uuid = self.uuid();
+        if Self::with_clone_index(|index| {
+            index.get(&uuid).map_or(false, |clones| !clones.is_empty())
+        }) {
+            return true;
+        }
+
+        // Index the blob children of the lvols of the pool with a single
+        // scan, and walk the descendants of this snapshot from there.
+        let source = |lvol: &Lvol| {
+            if lvol.is_snapshot() {
+                Self::get_blob_xattr(
+                    lvol.blob_checked(),
+                    SnapshotXattrs::ParentId.name(),
+                )
+                .unwrap_or_default()
+            } else {
+                lvol.uuid()
+            }
         };
-        // snapshot_uuid
-        let snap_uuid: Option<String> = if snap_uuid.is_empty() {
-            None
-        } else {
-            Some(snap_uuid.to_string())
+        let self_source = source(self);
+
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return false;
         };
-        // Current Lvol uuid is the parent for the snapshot.
-        let parent_id = Some(self.uuid());
-        Some(SnapshotParams::new(
-            Some(entity_id),
-            parent_id,
-            Some(txn_id),
-            Some(snap_name),
-            snap_uuid,
-            Some(Utc::now().to_string()),
-            false,
-        ))
+        let mut children: HashMap<String, Vec<Lvol>> = HashMap::new();
+        for lvol in bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.lvs().uuid() == self.lvs().uuid())
+        {
+            let parent = unsafe { lvol.bs_iter_parent(lvol.blob_checked()) }
+                .and_then(|blob| {
+                    Self::get_blob_xattr(
+                        blob,
+                        SnapshotXattrs::SnapshotUuid.name(),
+                    )
+                });
+            if let Some(parent) = parent {
+                children.entry(parent).or_default().push(lvol);
+            }
+        }
+
+        let mut pending = vec![uuid];
+        while let Some(parent) = pending.pop() {
+            for child in children.remove(&parent).unwrap_or_default() {
+                if source(&child) != self_source {
+                    return true;
+                }
+                pending.push(child.uuid());
+            }
+        }
+        false
     }
 
-    /// Prepare snapshot xattrs.
-    fn prepare_snapshot_xattrs(
+    /// Makes a newly created clone thick, by copying all of the data it
+    /// shares with its snapshot and detaching it from the snapshot. On
+    /// failure the clone is still a thin clone of the snapshot, which the
+    /// caller is to destroy.
+    async fn make_clone_thick(
         &self,
-        attr_descrs: &mut [spdk_xattr_descriptor; SnapshotXattrs::COUNT],
-        params: SnapshotParams,
-        cstrs: &mut Vec<CString>,
+        clone_param: &CloneParams,
     ) -> Result<(), LvsError> {
-        for (idx, attr) in SnapshotXattrs::iter().enumerate() {
+        let state_change = |previous: &str, next: &str| {
+            EventWithMeta::event(
+                clone_param,
+                EventAction::StateChange,
+                clone_state_change_event_meta(clone_param, previous, next),
+            )
+            .generate();
+        };
+
+        state_change("Thin", "Copying");
+        match self.detach_clone().await {
+            Ok(()) => {
+                state_change("Copying", "Thick");
+                Ok(())
+            }
+            Err(error) => {
+                error!(
+                    clone = self.name(),
+                    %error,
+                    "Failed to make clone thick"
+                );
+                state_change("Copying", "Thin");
+                Err(error)
+            }
+        }
+    }
+
+    /// Reconciles the cached clone counts of the snapshots of the given pool
+    /// with their actual clones, to correct any drift, e.g. after a crash.
+    pub(crate) async fn reconcile_clone_refcounts(lvs: &Lvs) {
+        for snapshot in Lvol::list_snapshots_in_pool(lvs, None) {
+            let snap = snapshot.snapshot_lvol();
+            let actual = snap.list_clones_by_snapshot_uuid().len() as u64;
+            let cached = snap.cached_clone_count();
+            if cached == Some(actual) {
+                continue;
+            }
+            info!(
+                snapshot = snap.name(),
+                ?cached,
+                actual,
+                "Reconciling snapshot clone refcount"
+            );
+            if let Err(error) = snap
+                .set_blob_attr(
+                    SnapshotCacheXattrs::CloneRefcount.name(),
+                    actual.to_string(),
+                    true,
+                )
+                .await
+            {
+                warn!(
+                    snapshot = snap.name(),
+                    %error,
+                    "Failed to reconcile snapshot clone refcount"
+                );
+            }
+        }
+    }
+
+    /// Computes the checksum of the snapshot attributes from the snapshot
+    /// parameters.
+    fn snapshot_params_checksum(params: &SnapshotParams) -> String {
+        snapshot_xattrs_checksum(|attr| match attr {
+            SnapshotXattrs::TxId => params.txn_id(),
+            SnapshotXattrs::EntityId => params.entity_id(),
+            SnapshotXattrs::ParentId => params.parent_id(),
+            SnapshotXattrs::SnapshotUuid => params.snapshot_uuid(),
+            SnapshotXattrs::SnapshotCreateTime => params
+                .create_time()
+                .ok()
+                .flatten()
+                .map(format_snapshot_create_time),
+            SnapshotXattrs::DiscardedSnapshot | SnapshotXattrs::Checksum => {
+                None
+            }
+        })
+    }
+
+    /// Gets the value of the snapshot create time attribute, in RFC3339
+    /// format, failing if the create time given cannot be parsed.
+    fn snapshot_create_time_value(
+        &self,
+        params: &SnapshotParams,
+    ) -> Result<Option<String>, LvsError> {
+        params
+            .create_time()
+            .map(|t| t.map(format_snapshot_create_time))
+            .map_err(|error| LvsError::SnapshotConfigFailed {
+                name: self.as_bdev().name().to_string(),
+                msg: format!(
+                    "invalid create_time {}: {error}",
+                    params.create_time_str().unwrap_or_default()
+                ),
+            })
+    }
+
+    /// Gets the values of the snapshot attributes from the snapshot
+    /// parameters, failing if any mandatory one is missing.
+    fn snapshot_xattr_values(
+        &self,
+        params: &SnapshotParams,
+    ) -> Result<Vec<(SnapshotXattrs, String)>, LvsError> {
+        let mut values = Vec::with_capacity(SnapshotXattrs::COUNT);
+        for attr in SnapshotXattrs::iter() {
             // Get attribute value from snapshot params.
             let av = match attr {
                 SnapshotXattrs::TxId => match params.txn_id() {
@@ -185,7 +776,7 @@ impl SnapshotOps for Lvol {
                     }
                 },
                 SnapshotXattrs::SnapshotCreateTime => {
-                    match params.create_time() {
+                    match self.snapshot_create_time_value(params)? {
                         Some(v) => v,
                         None => {
                             return Err(LvsError::SnapshotConfigFailed {
@@ -198,272 +789,114 @@ impl SnapshotOps for Lvol {
                 SnapshotXattrs::DiscardedSnapshot => {
                     params.discarded_snapshot().to_string()
                 }
+                SnapshotXattrs::Checksum => {
+                    Self::snapshot_params_checksum(params)
+                }
             };
-            let attr_name = attr.name().to_string().into_cstring();
-            let attr_val = av.into_cstring();
-            attr_descrs[idx].name = attr_name.as_ptr() as *mut c_char;
-            attr_descrs[idx].value = attr_val.as_ptr() as *mut c_void;
-            attr_descrs[idx].value_len = attr_val.to_bytes().len() as c_ushort;
-
-            cstrs.push(attr_val);
-            cstrs.push(attr_name);
+            values.push((attr, av));
         }
+        Ok(values)
+    }
 
-        Ok(())
-    }
-
-    /// create replica snapshot inner function to call spdk snapshot create
-    /// function.
-    unsafe fn create_snapshot_inner(
-        &self,
-        snap_param: &SnapshotParams,
-        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
-        cb_arg: *mut c_void,
-    ) -> Result<(), LvsError> {
-        let mut attr_descrs: [spdk_xattr_descriptor; SnapshotXattrs::COUNT] =
-            [spdk_xattr_descriptor::default(); SnapshotXattrs::COUNT];
-
-        // Vector to keep allocated CStrings before snapshot  creation
-        // is complete to guarantee validity of attribute buffers
-        // stored inside CStrings.
-        let mut cstrs: Vec<CString> = Vec::new();
-
-        self.prepare_snapshot_xattrs(
-            &mut attr_descrs,
-            snap_param.clone(),
-            &mut cstrs,
-        )?;
-
-        let c_snapshot_name = snap_param.name().unwrap().into_cstring();
-
-        // No need to flush blob's buffers explicitly as SPDK always
-        // synchronizes blob when taking a snapshot.
-        unsafe {
-            vbdev_lvol_create_snapshot_ext(
-                self.as_inner_ptr(),
-                c_snapshot_name.as_ptr(),
-                attr_descrs.as_mut_ptr(),
-                SnapshotXattrs::COUNT as u32,
-                Some(cb),
-                cb_arg,
-            )
+    /// Gets the values of the consistency group attributes from the snapshot
+    /// parameters; they are only stored for group snapshots.
+    fn snapshot_group_xattr_values(
+        params: &SnapshotParams,
+    ) -> Vec<(SnapshotGroupXattrs, String)> {
+        let Some(group_id) = params.group_id() else {
+            return Vec::new();
         };
-        Ok(())
-    }
-
-    /// Creates a snapshot.
-    async fn do_create_snapshot(
-        &self,
-        snap_param: SnapshotParams,
-        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
-        cb_arg: *mut c_void,
-        receiver: oneshot::Receiver<LvolResult>,
-    ) -> Result<Lvol, LvsError> {
-        unsafe {
-            self.create_snapshot_inner(&snap_param, cb, cb_arg)?;
-        }
-
-        // Wait till operation succeeds, if requested.
-        let res = receiver.await.expect("Snapshot done callback disappeared");
-
-        match res {
-            Ok(lvol_ptr) => {
-                snap_param.event(EventAction::Create).generate();
-                Ok(Lvol::from_inner_ptr(lvol_ptr))
-            }
-            Err(e) => Err(LvsError::SnapshotCreate {
-                source: BsError::from_errno(e),
-                msg: snap_param.name().unwrap(),
-            }),
-        }
-    }
-
-    /// Creates a remote snapshot.
-    async fn do_create_snapshot_remote(
-        &self,
-        snap_param: SnapshotParams,
-        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
-        cb_arg: *mut c_void,
-    ) -> Result<(), LvsError> {
-        unsafe {
-            self.create_snapshot_inner(&snap_param, cb, cb_arg)?;
-        }
-        snap_param.event(EventAction::Create).generate();
-        Ok(())
+        SnapshotGroupXattrs::iter()
+            .map(|attr| {
+                let av = match attr {
+                    SnapshotGroupXattrs::GroupId => group_id.clone(),
+                    SnapshotGroupXattrs::GroupMemberCount => params
+                        .group_member_count()
+                        .unwrap_or_default()
+                        .to_string(),
+                };
+                (attr, av)
+            })
+            .collect()
     }
 
-    /// Prepare clone config for snapshot.
-    fn prepare_clone_config(
-        &self,
-        clone_name: &str,
-        clone_uuid: &str,
-        source_uuid: &str,
-    ) -> Option<CloneParams> {
-        // clone_name
-        let clone_name = if clone_name.is_empty() {
-            return None;
-        } else {
-            clone_name.to_string()
-        };
-        // clone_uuid
-        let clone_uuid = if clone_uuid.is_empty() {
-            return None;
-        } else {
-            clone_uuid.to_string()
-        };
-        // source_uuid
-        let source_uuid = if source_uuid.is_empty() {
-            return None;
-        } else {
-            source_uuid.to_string()
+    /// Gets the retention attribute values of a snapshot created with the
+    /// given parameters. The retention class is only stored for the
+    /// snapshots given one.
+    fn snapshot_retention_xattr_values(
+        params: &SnapshotParams,
+    ) -> Vec<(SnapshotRetentionXattrs, String)> {
+        let Some(retention_class) = params.retention_class() else {
+            return Vec::new();
         };
-        Some(CloneParams::new(
-            Some(clone_name),
-            Some(clone_uuid),
-            Some(source_uuid),
-            Some(Utc::now().to_string()),
-        ))
-    }
-
-    /// Prepare clone xattrs.
-    fn prepare_clone_xattrs(
-        &self,
-        attr_descrs: &mut [spdk_xattr_descriptor; CloneXattrs::COUNT],
-        params: CloneParams,
-        cstrs: &mut Vec<CString>,
-    ) -> Result<(), LvsError> {
-        for (idx, attr) in CloneXattrs::iter().enumerate() {
-            // Get attribute value from CloneParams.
-            let av = match attr {
-                CloneXattrs::SourceUuid => match params.source_uuid() {
-                    Some(v) => v,
-                    None => {
-                        return Err(LvsError::CloneConfigFailed {
-                            name: self.as_bdev().name().to_string(),
-                            msg: "source uuid not provided".to_string(),
-                        })
+        SnapshotRetentionXattrs::iter()
+            .map(|attr| {
+                let av = match attr {
+                    SnapshotRetentionXattrs::RetentionClass => {
+                        retention_class.clone()
                     }
-                },
-                CloneXattrs::CloneCreateTime => {
-                    match params.clone_create_time() {
-                        Some(v) => v,
-                        None => {
-                            return Err(LvsError::CloneConfigFailed {
-                                name: self.as_bdev().name().to_string(),
-                                msg: "create_time not provided".to_string(),
-                            })
-                        }
-                    }
-                }
-                CloneXattrs::CloneUuid => match params.clone_uuid() {
-                    Some(v) => v,
-                    None => {
-                        return Err(LvsError::CloneConfigFailed {
-                            name: self.as_bdev().name().to_string(),
-                            msg: "clone_uuid not provided".to_string(),
-                        })
-                    }
-                },
-            };
-            let attr_name = attr.name().to_string().into_cstring();
-            let attr_val = av.into_cstring();
-            attr_descrs[idx].name = attr_name.as_ptr() as *mut c_char;
-            attr_descrs[idx].value = attr_val.as_ptr() as *mut c_void;
-            attr_descrs[idx].value_len = attr_val.to_bytes().len() as c_ushort;
-
-            cstrs.push(attr_val);
-            cstrs.push(attr_name);
-        }
-        Ok(())
-    }
-
-    /// Create clone inner function to call spdk clone function.
-    unsafe fn create_clone_inner(
-        &self,
-        clone_param: &CloneParams,
-        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
-        cb_arg: *mut c_void,
-    ) -> Result<(), LvsError> {
-        let mut attr_descrs: [spdk_xattr_descriptor; CloneXattrs::COUNT] =
-            [spdk_xattr_descriptor::default(); CloneXattrs::COUNT];
-
-        // Vector to keep allocated CStrings before snapshot  creation
-        // is complete to guarantee validity of attribute buffers
-        // stored inside CStrings.
-        let mut cstrs: Vec<CString> = Vec::new();
-
-        self.prepare_clone_xattrs(
-            &mut attr_descrs,
-            clone_param.clone(),
-            &mut cstrs,
-        )?;
-
-        let c_clone_name =
-            clone_param.clone_name().unwrap_or_default().into_cstring();
-
-        unsafe {
-            vbdev_lvol_create_clone_ext(
-                self.as_inner_ptr(),
-                c_clone_name.as_ptr(),
-                attr_descrs.as_mut_ptr(),
-                CloneXattrs::COUNT as u32,
-                Some(cb),
-                cb_arg,
-            )
-        };
-        Ok(())
+                };
+                (attr, av)
+            })
+            .collect()
     }
 
-    /// Creates a clone.
-    async fn do_create_clone(
-        &self,
-        clone_param: CloneParams,
-        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
-        cb_arg: *mut c_void,
-        receiver: oneshot::Receiver<LvolResult>,
-    ) -> Result<Lvol, LvsError> {
-        unsafe {
-            self.create_clone_inner(&clone_param, cb, cb_arg)?;
-        }
-        // Wait till operation succeeds, if requested.
-        let res = receiver
-            .await
-            .expect("Snapshot Clone done callback disappeared");
-
-        match res {
-            Ok(lvol_ptr) => {
-                clone_param.event(EventAction::Create).generate();
-                Ok(Lvol::from_inner_ptr(lvol_ptr))
-            }
-            Err(err) => Err(LvsError::SnapshotCloneCreate {
-                source: BsError::from_errno(err),
-                msg: clone_param.clone_name().unwrap_or_default(),
-            }),
-        }
+    /// Gets the targets this snapshot has been replicated to.
+    fn replicated_to(&self) -> Vec<String> {
+        Lvol::get_blob_xattr(
+            self.blob_checked(),
+            SnapshotReplicationXattrs::ReplicatedTo.name(),
+        )
+        .map(|targets| {
+            targets
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
     }
 
-    /// Common API to set SnapshotDescriptor for ListReplicaSnapshot.
-    fn snapshot_descriptor(
+    /// Build the descriptor of this snapshot, along with the first problem
+    /// found in its attributes, if any. Fails only if the snapshot does not
+    /// belong to the given parent.
+    fn build_snapshot_descriptor(
         &self,
         parent: Option<&Lvol>,
-    ) -> Option<VolumeSnapshotDescriptor> {
-        let mut valid_snapshot = true;
+    ) -> Result<
+        (VolumeSnapshotDescriptor, Option<SnapshotDescriptorError>),
+        SnapshotDescriptorError,
+    > {
+        let mut error = None;
         let mut snapshot_param: SnapshotParams = Default::default();
+        let mut xattrs = Self::read_all_snapshot_xattrs(self.blob_checked());
+        let checksum =
+            snapshot_xattrs_checksum(|attr| xattrs.get(attr.name()).cloned());
         for attr in SnapshotXattrs::iter() {
-            let curr_attr_val =
-                match Self::get_blob_xattr(self.blob_checked(), attr.name()) {
-                    Some(val) => val,
-                    None => {
-                        valid_snapshot = false;
-                        continue;
-                    }
-                };
+            let curr_attr_val = match xattrs.remove(attr.name()) {
+                Some(val) => val,
+                None if attr.is_optional() => continue,
+                None => {
+                    error.get_or_insert(
+                        SnapshotDescriptorError::MissingXattr {
+                            name: self.name(),
+                            attr,
+                        },
+                    );
+                    continue;
+                }
+            };
             match attr {
                 SnapshotXattrs::ParentId => {
                     if let Some(parent_lvol) = parent {
                         // Skip snapshots if it's parent is not matched.
                         if curr_attr_val != parent_lvol.uuid() {
-                            return None;
+                            return Err(
+                                SnapshotDescriptorError::ParentMismatch {
+                                    name: self.name(),
+                                    parent: parent_lvol.uuid(),
+                                },
+                            );
                         }
                     }
                     snapshot_param.set_parent_id(curr_attr_val);
@@ -481,193 +914,2176 @@ impl SnapshotOps for Lvol {
                     snapshot_param.set_create_time(curr_attr_val);
                 }
                 SnapshotXattrs::DiscardedSnapshot => {
-                    snapshot_param.set_discarded_snapshot(
-                        curr_attr_val.parse().unwrap_or_default(),
-                    );
+                    match curr_attr_val.parse() {
+                        Ok(discarded) => {
+                            snapshot_param.set_discarded_snapshot(discarded)
+                        }
+                        Err(_) => {
+                            error.get_or_insert(
+                                SnapshotDescriptorError::InvalidXattr {
+                                    name: self.name(),
+                                    attr,
+                                    value: curr_attr_val,
+                                },
+                            );
+                        }
+                    }
+                }
+                SnapshotXattrs::Checksum => {
+                    if curr_attr_val != checksum {
+                        error.get_or_insert(
+                            SnapshotDescriptorError::ChecksumMismatch {
+                                name: self.name(),
+                            },
+                        );
+                    }
                 }
             }
         }
-        // set remaining snapshot parameters for snapshot list
-        snapshot_param.set_name(self.name());
-        // set parent replica uuid and size of the snapshot
-        let parent_uuid = if let Some(parent_lvol) = parent {
-            parent_lvol.uuid()
-        } else {
-            match Bdev::lookup_by_uuid_str(
-                snapshot_param.parent_id().unwrap_or_default().as_str(),
-            )
-            .and_then(|b| Lvol::try_from(b).ok())
-            {
-                Some(parent) => parent.uuid(),
-                None => String::default(),
-            }
-        };
-        let snapshot_descriptor = VolumeSnapshotDescriptor::new(
-            self.to_owned(),
+        // consistency group attributes are optional.
+        for attr in SnapshotGroupXattrs::iter() {
+            let Some(curr_attr_val) = xattrs.remove(attr.name()) else {
+                continue;
+            };
+            match attr {
+                SnapshotGroupXattrs::GroupId => {
+                    snapshot_param.set_group_id(curr_attr_val);
+                }
+                SnapshotGroupXattrs::GroupMemberCount => {
+                    if let Ok(count) = curr_attr_val.parse() {
+                        snapshot_param.set_group_member_count(count);
+                    }
+                }
+            }
+        }
+        // the retention class is optional.
+        for attr in SnapshotRetentionXattrs::iter() {
+            let Some(curr_attr_val) = xattrs.remove(attr.name()) else {
+                continue;
+            };
+            match attr {
+                SnapshotRetentionXattrs::RetentionClass => {
+                    snapshot_param.set_retention_class(curr_attr_val);
+                }
+            }
+        }
+        // set remaining snapshot parameters for snapshot list
+        snapshot_param.set_name(self.name());
+        // set parent replica uuid and size of the snapshot
+        let parent_lvol = match parent {
+            Some(parent_lvol) => Some(parent_lvol.clone()),
+            None => Bdev::lookup_by_uuid_str(
+                snapshot_param.parent_id().unwrap_or_default().as_str(),
+            )
+            .and_then(|b| Lvol::try_from(b).ok()),
+        };
+        let parent_uuid =
+            parent_lvol.as_ref().map(|p| p.uuid()).unwrap_or_default();
+        let valid_snapshot = error.is_none();
+        let restore_eligibility = self.restore_eligibility(
+            &snapshot_param,
+            valid_snapshot,
+            parent_lvol.as_ref(),
+        );
+        let snapshot_descriptor = VolumeSnapshotDescriptor::new(
+            self.to_owned(),
             parent_uuid,
             self.usage().allocated_bytes,
             snapshot_param,
-            self.list_clones_by_snapshot_uuid().len() as u64,
+            self.num_clones(),
             valid_snapshot,
+            restore_eligibility,
+        );
+        Ok((snapshot_descriptor, error))
+    }
+}
+
+/// Maximum number of snapshots destroyed or discarded concurrently.
+fn discard_concurrency() -> usize {
+    Config::get().snapshot_opts.discard_concurrency.max(1)
+}
+
+/// Runs a snapshot destroy on the primary reactor once the spawn limiter of
+/// the snapshot subsystem allows it, so that all the callers together keep
+/// at most `discard_concurrency` destroys in flight.
+async fn destroy_bounded<F, Fut>(op: F) -> Result<(), LvsError>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), LvsError>> + 'static,
+{
+    let limiter = SpawnLimiter::subsystem("snapshot", discard_concurrency());
+    let (s, r) = oneshot::channel();
+
+    runtime::spawn_bounded(&limiter, async move {
+        if let Ok(r) = Reactor::spawn_at_primary(async move {
+            s.send(op().await).ok();
+        }) {
+            r.await.ok();
+        }
+    })
+    .await;
+
+    r.await.unwrap_or_else(|_| {
+        Err(LvsError::Invalid {
+            source: BsError::Generic {
+                source: Errno::ECANCELED,
+            },
+            msg: "snapshot destroy cancelled by the shutdown".to_string(),
+        })
+    })
+}
+
+/// Hooks registered by other subsystems around the snapshot creation.
+#[derive(Default)]
+struct SnapshotCreateHooks {
+    next_id: u64,
+    pre: Vec<(SnapshotHookId, SnapshotPreCreateHook)>,
+    post: Vec<(SnapshotHookId, SnapshotPostCreateHook)>,
+}
+
+impl SnapshotCreateHooks {
+    fn next_id(&mut self) -> SnapshotHookId {
+        self.next_id += 1;
+        SnapshotHookId(self.next_id)
+    }
+}
+
+static SNAPSHOT_CREATE_HOOKS: Lazy<parking_lot::Mutex<SnapshotCreateHooks>> =
+    Lazy::new(Default::default);
+
+/// Uuids of the clones of each snapshot, indexed by snapshot uuid. Built
+/// lazily with a single scan of the lvols, and dropped whenever clones come
+/// and go so that it is rebuilt on next use.
+static CLONE_INDEX: Lazy<
+    parking_lot::Mutex<Option<HashMap<String, Vec<String>>>>,
+> = Lazy::new(Default::default);
+
+/// TODO
+pub trait AsyncParentIterator {
+    type Item;
+    fn parent(&mut self) -> Option<Self::Item>;
+}
+
+/// Iterator over Lvol Blobstore for Snapshot.
+pub struct LvolSnapshotIter {
+    inner_blob: *mut spdk_blob,
+    inner_lvol: Lvol,
+}
+
+impl LvolSnapshotIter {
+    pub fn new(lvol: Lvol) -> Self {
+        Self {
+            inner_blob: lvol.bs_iter_first(),
+            inner_lvol: lvol,
+        }
+    }
+}
+
+/// Iterator implementation for LvolSnapshot.
+impl AsyncParentIterator for LvolSnapshotIter {
+    type Item = VolumeSnapshotDescriptor;
+    fn parent(&mut self) -> Option<Self::Item> {
+        if self.inner_blob.is_null() {
+            None
+        } else {
+            let parent_blob =
+                unsafe { self.inner_lvol.bs_iter_parent(self.inner_blob) }?;
+            let uuid = Lvol::get_blob_xattr(
+                parent_blob,
+                SnapshotXattrs::SnapshotUuid.name(),
+            )?;
+            let snap_lvol = UntypedBdev::lookup_by_uuid_str(&uuid)
+                .and_then(|bdev| Lvol::try_from(bdev).ok())?;
+            self.inner_blob = parent_blob;
+            self.inner_lvol = snap_lvol.clone();
+            snap_lvol.snapshot_descriptor(None)
+        }
+    }
+}
+
+/// Stream adapter for LvolSnapshotIter, yielding the snapshots of the blob
+/// parent chain one at a time. The stream ends at the root of the chain, or
+/// as soon as a parent cannot be resolved into a snapshot.
+impl Stream for LvolSnapshotIter {
+    type Item = VolumeSnapshotDescriptor;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let next = self.parent();
+        if next.is_none() {
+            // Keep the stream terminated, even if the parent chain could
+            // be resolved later on.
+            self.inner_blob = std::ptr::null_mut();
+        }
+        Poll::Ready(next)
+    }
+}
+
+#[async_trait(?Send)]
+impl SnapshotOps for Lvol {
+    type Error = LvsError;
+    type SnapshotIter = LvolSnapshotIter;
+    type Lvol = Lvol;
+
+    /// Prepare Snapshot Config for Block/Nvmf Device, before snapshot create.
+    fn prepare_snap_config(
+        &self,
+        snap_name: &str,
+        entity_id: &str,
+        txn_id: &str,
+        snap_uuid: &str,
+    ) -> Option<SnapshotParams> {
+        // snap_name
+        let snap_name = if snap_name.is_empty() {
+            return None;
+        } else {
+            snap_name.to_string()
+        };
+        let entity_id = if entity_id.is_empty() {
+            return None;
+        } else {
+            entity_id.to_string()
+        };
+
+        // txn_id
+        let txn_id = if txn_id.is_empty() {
+            return None;
+        } else {
+            txn_id.to_string()
+        };
+        // snapshot_uuid, derived from the lvol and the transaction when not
+        // provided so that retries of the same request are idempotent.
+        let snap_uuid = if snap_uuid.is_empty() {
+            derive_snapshot_uuid(&self.uuid(), &txn_id)
+        } else {
+            snap_uuid.to_string()
+        };
+        // Current Lvol uuid is the parent for the snapshot.
+        let parent_id = Some(self.uuid());
+        Some(SnapshotParams::new(
+            Some(entity_id),
+            parent_id,
+            Some(txn_id),
+            Some(snap_name),
+            Some(snap_uuid),
+            Some(format_snapshot_create_time(Utc::now())),
+            false,
+        ))
+    }
+
+    /// Prepare snapshot xattrs.
+    fn prepare_snapshot_xattrs(
+        &self,
+        attr_descrs: &mut [spdk_xattr_descriptor; SnapshotXattrs::COUNT],
+        params: SnapshotParams,
+        cstrs: &mut Vec<CString>,
+    ) -> Result<(), LvsError> {
+        for (idx, (attr, av)) in
+            self.snapshot_xattr_values(&params)?.into_iter().enumerate()
+        {
+            let attr_name = attr.name().to_string().into_cstring();
+            let attr_val = av.into_cstring();
+            attr_descrs[idx].name = attr_name.as_ptr() as *mut c_char;
+            attr_descrs[idx].value = attr_val.as_ptr() as *mut c_void;
+            attr_descrs[idx].value_len = attr_val.to_bytes().len() as c_ushort;
+
+            cstrs.push(attr_val);
+            cstrs.push(attr_name);
+        }
+
+        Ok(())
+    }
+
+    /// Estimate the average number of blob layers a read of this lvol has to
+    /// consult.
+    fn read_amplification_factor(&self) -> f64 {
+        let mut factor = 0.0;
+        // Probability for a read to reach the current layer, i.e. for the
+        // block not to be allocated in any of the layers above.
+        let mut reach = 1.0;
+        let mut blob = Some(self.blob_checked());
+
+        while let Some(curr_blob) = blob.filter(|b| !b.is_null()) {
+            factor += reach;
+            let (num_clusters, num_allocated) = unsafe {
+                (
+                    spdk_blob_get_num_clusters(curr_blob),
+                    spdk_blob_calc_used_clusters(curr_blob),
+                )
+            };
+            if num_clusters == 0 {
+                break;
+            }
+            let allocated = num_allocated.min(num_clusters) as f64;
+            reach *= 1.0 - allocated / num_clusters as f64;
+            blob = unsafe { self.bs_iter_parent(curr_blob) };
+        }
+        factor.max(1.0)
+    }
+
+    /// Split the disk space allocated by this snapshot between the space
+    /// exclusive to it and the space shared with its children. A cluster of
+    /// the snapshot is shared as long as one of the blobs built directly
+    /// upon it, be it a newer snapshot, the replica or a clone, has not
+    /// allocated the cluster itself and thus still reads it from the
+    /// snapshot.
+    fn snapshot_usage_breakdown(&self) -> SnapshotUsageBreakdown {
+        let mut breakdown = SnapshotUsageBreakdown::default();
+        let Some(lvols) = self.lvs().lvols() else {
+            return breakdown;
+        };
+        let blob = self.blob_checked();
+        let cluster_size = self.usage().cluster_size;
+
+        let children = lvols
+            .map(|l| l.blob_checked())
+            .filter(|b| unsafe { self.bs_iter_parent(*b) } == Some(blob))
+            .map(|b| self.blob_allocated_clusters(b))
+            .collect::<Vec<_>>();
+
+        let own = self.blob_allocated_clusters(blob);
+        for (cluster, _) in own.iter().enumerate().filter(|(_, a)| **a) {
+            let shared = children
+                .iter()
+                .any(|child| cluster < child.len() && !child[cluster]);
+            if shared {
+                breakdown.shared_bytes += cluster_size;
+            } else {
+                breakdown.exclusive_bytes += cluster_size;
+            }
+        }
+
+        // A cluster is referenced if the snapshot or any of its ancestors
+        // has allocated it.
+        let mut referenced = own;
+        let mut parent = unsafe { self.bs_iter_parent(blob) };
+        while let Some(parent_blob) = parent {
+            let allocated = self.blob_allocated_clusters(parent_blob);
+            for (r, a) in referenced.iter_mut().zip(allocated) {
+                *r |= a;
+            }
+            parent = unsafe { self.bs_iter_parent(parent_blob) };
+        }
+        breakdown.referenced_bytes =
+            referenced.iter().filter(|r| **r).count() as u64 * cluster_size;
+        breakdown
+    }
+
+    /// Get the ranges of this lvol which may have changed since the given
+    /// snapshot was taken. These are the clusters allocated by any of the
+    /// blobs of the chain from this lvol down to the snapshot, excluded,
+    /// along with the clusters beyond the end of the snapshot. The last
+    /// cluster of the lvol may extend past its size, the range covering it
+    /// is cut at the size of the lvol.
+    fn snapshot_diff(
+        &self,
+        base: &Lvol,
+    ) -> Result<Vec<ClusterRange>, LvsError> {
+        let base_blob = base.blob_checked();
+        let mut chain = Vec::new();
+        let mut blob = Some(self.blob_checked());
+        while let Some(curr_blob) = blob.filter(|b| *b != base_blob) {
+            chain.push(curr_blob);
+            blob = unsafe { self.bs_iter_parent(curr_blob) };
+        }
+        if blob.is_none() || chain.is_empty() {
+            return Err(LvsError::NotAnAncestor {
+                name: self.name(),
+                base: base.name(),
+            });
+        }
+
+        let mut changed = self.blob_allocated_clusters(self.blob_checked());
+        let base_clusters = unsafe { spdk_blob_get_num_clusters(base_blob) };
+        for cluster in changed.iter_mut().skip(base_clusters as usize) {
+            *cluster = true;
+        }
+        for curr_blob in chain.into_iter().skip(1) {
+            let allocated = self.blob_allocated_clusters(curr_blob);
+            for (c, a) in changed.iter_mut().zip(allocated) {
+                *c |= a;
+            }
+        }
+
+        let cluster_size = self.usage().cluster_size;
+        let size = self.size();
+        let mut ranges: Vec<ClusterRange> = Vec::new();
+        for (cluster, _) in changed.iter().enumerate().filter(|(_, c)| **c) {
+            let offset = cluster as u64 * cluster_size;
+            if offset >= size {
+                break;
+            }
+            let length = cluster_size.min(size - offset);
+            match ranges.last_mut() {
+                Some(last) if last.offset + last.length == offset => {
+                    last.length += length;
+                }
+                _ => ranges.push(ClusterRange {
+                    offset,
+                    length,
+                }),
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// Get the logical size a replica must have to hold the data of this
+    /// snapshot when it is restored, i.e. the size of its source replica at
+    /// the time the snapshot was taken.
+    fn required_restore_size(&self) -> u64 {
+        self.size()
+    }
+
+    /// Check that a replica of the given size can hold the data of this
+    /// snapshot, before restoring the snapshot onto it.
+    fn validate_restore_target_size(
+        &self,
+        target_size: u64,
+    ) -> Result<(), LvsError> {
+        let required = self.required_restore_size();
+        if target_size < required {
+            return Err(LvsError::RestoreTargetTooSmall {
+                name: self.name(),
+                required,
+                available: target_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// Roll this replica back to the given snapshot: the clusters which may
+    /// have changed since the snapshot was taken are copied back from the
+    /// snapshot, so that they are allocated by the replica afterwards. The
+    /// snapshots taken after the given one are left untouched, and thus
+    /// preserved.
+    /// The replica is claimed while it is restored, as any write meanwhile
+    /// could be overwritten by the copy, and the snapshot being restored is
+    /// recorded on it until the copy completes.
+    async fn restore_from_snapshot(
+        &self,
+        snapshot: &Lvol,
+    ) -> Result<(), LvsError> {
+        let refused = |reason: String| LvsError::SnapshotRestoreRefused {
+            name: self.name(),
+            snapshot: snapshot.name(),
+            reason,
+        };
+        let failed = |msg: String| LvsError::SnapshotRestore {
+            name: self.name(),
+            snapshot: snapshot.name(),
+            msg,
+        };
+
+        if !snapshot.is_snapshot() {
+            return Err(refused("not a snapshot".to_string()));
+        }
+        snapshot.validate_restore_target_size(self.size())?;
+        let descr = snapshot
+            .snapshot_descriptor(Some(self))
+            .ok_or_else(|| refused("snapshot was not taken from it".into()))?;
+        match descr.restore_eligibility() {
+            RestoreEligibility::InPlace => {}
+            RestoreEligibility::NewReplicaOnly {
+                reason,
+            }
+            | RestoreEligibility::Ineligible {
+                reason,
+            } => return Err(refused(reason.clone())),
+        }
+        // Also checks that the snapshot is an ancestor of the replica.
+        let ranges = self.snapshot_diff(snapshot)?;
+
+        let preserved = self
+            .list_snapshot_by_source_uuid()
+            .into_iter()
+            .map(|s| s.snapshot_lvol().clone())
+            .skip_while(|s| s.uuid() != snapshot.uuid())
+            .skip(1)
+            .map(|s| s.name())
+            .collect::<Vec<_>>();
+        // Writes to the replica while it is restored could be overwritten by
+        // the copy: refuse a replica in use, and claim it meanwhile.
+        if self.shared().is_some() {
+            return Err(refused("replica is shared".into()));
+        }
+        let bdev = self.as_bdev();
+        if bdev.is_claimed() {
+            return Err(refused("replica is in use".into()));
+        }
+        let desc =
+            Arc::new(bdev.open(true).map_err(|e| failed(e.to_string()))?);
+        if !desc.claim() {
+            return Err(refused("replica is in use".into()));
+        }
+
+        info!(
+            replica = self.name(),
+            snapshot = snapshot.name(),
+            ?preserved,
+            "Restoring replica from snapshot"
+        );
+
+        let copy = async {
+            // Recorded first, so that an interrupted restore is not mistaken
+            // for a consistent replica.
+            self.set_blob_attr(
+                RestoreXattrs::InProgress.name(),
+                snapshot.uuid(),
+                true,
+            )
+            .await?;
+
+            let src =
+                UntypedBdevHandle::open_with_bdev(&snapshot.as_bdev(), false)
+                    .map_err(|e| failed(e.to_string()))?;
+            let dst = UntypedBdevHandle::try_from(desc.clone())
+                .map_err(|e| failed(e.to_string()))?;
+            let cluster_size = self.usage().cluster_size;
+            let size = self.size();
+            for range in ranges {
+                let end = (range.offset + range.length).min(size);
+                let mut offset = range.offset;
+                while offset < end {
+                    let len = cluster_size.min(end - offset);
+                    let mut buf = src
+                        .dma_malloc(len)
+                        .map_err(|e| failed(e.to_string()))?;
+                    src.read_at(offset, &mut buf)
+                        .await
+                        .map_err(|e| failed(e.to_string()))?;
+                    dst.write_at(offset, &buf)
+                        .await
+                        .map_err(|e| failed(e.to_string()))?;
+                    offset += len;
+                }
+            }
+            Ok::<(), LvsError>(())
+        };
+        let copied = copy.await;
+        desc.unclaim();
+        drop(desc);
+        self.reset_snapshot_tree_usage_cache(true);
+        copied?;
+
+        self.remove_blob_attr(RestoreXattrs::InProgress.name(), true)
+            .await?;
+        descr.event(EventAction::StateChange).generate();
+        info!(
+            replica = self.name(),
+            snapshot = snapshot.name(),
+            "Replica restored from snapshot"
         );
-        Some(snapshot_descriptor)
+        Ok(())
+    }
+
+    /// Get the UUID of the snapshot this replica was being restored from, if
+    /// that restore did not complete.
+    fn restore_in_progress(&self) -> Option<String> {
+        Lvol::get_blob_xattr(
+            self.blob_checked(),
+            RestoreXattrs::InProgress.name(),
+        )
+    }
+
+    /// Get the name/value pairs of the attributes a snapshot created with the
+    /// given parameters would carry, without creating it.
+    fn preview_snapshot_xattrs(
+        &self,
+        params: &SnapshotParams,
+    ) -> Result<Vec<(String, String)>, LvsError> {
+        let xattrs = self
+            .snapshot_xattr_values(params)?
+            .into_iter()
+            .map(|(attr, av)| (attr.name().to_string(), av));
+        let group_xattrs = Self::snapshot_group_xattr_values(params)
+            .into_iter()
+            .map(|(attr, av)| (attr.name().to_string(), av));
+        let retention_xattrs = Self::snapshot_retention_xattr_values(params)
+            .into_iter()
+            .map(|(attr, av)| (attr.name().to_string(), av));
+        Ok(xattrs.chain(group_xattrs).chain(retention_xattrs).collect())
+    }
+
+    /// create replica snapshot inner function to call spdk snapshot create
+    /// function.
+    unsafe fn create_snapshot_inner(
+        &self,
+        snap_param: &SnapshotParams,
+        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
+        cb_arg: *mut c_void,
+    ) -> Result<(), LvsError> {
+        let mut attr_descrs: [spdk_xattr_descriptor; SnapshotXattrs::COUNT] =
+            [spdk_xattr_descriptor::default(); SnapshotXattrs::COUNT];
+
+        // Vector to keep allocated CStrings before snapshot  creation
+        // is complete to guarantee validity of attribute buffers
+        // stored inside CStrings.
+        let mut cstrs: Vec<CString> = Vec::new();
+
+        self.prepare_snapshot_xattrs(
+            &mut attr_descrs,
+            snap_param.clone(),
+            &mut cstrs,
+        )?;
+
+        // With deferred attributes, the snapshot is only created with the
+        // attributes identifying it, the others are written afterwards.
+        let mut attr_descrs = if snap_param.defer_xattrs() {
+            SnapshotXattrs::iter()
+                .zip(attr_descrs)
+                .filter(|(attr, _)| Self::is_snapshot_identity_xattr(attr))
+                .map(|(_, descr)| descr)
+                .collect::<Vec<_>>()
+        } else {
+            attr_descrs.to_vec()
+        };
+
+        // Consistency group attributes are only stored for group snapshots,
+        // and the retention class only for the snapshots given one.
+        if !snap_param.defer_xattrs() {
+            let optional_xattrs = Self::snapshot_group_xattr_values(snap_param)
+                .into_iter()
+                .map(|(attr, av)| (attr.name(), av))
+                .chain(
+                    Self::snapshot_retention_xattr_values(snap_param)
+                        .into_iter()
+                        .map(|(attr, av)| (attr.name(), av)),
+                );
+            for (name, av) in optional_xattrs {
+                let attr_name = name.to_string().into_cstring();
+                let attr_val = av.into_cstring();
+                attr_descrs.push(spdk_xattr_descriptor {
+                    name: attr_name.as_ptr() as *mut c_char,
+                    value: attr_val.as_ptr() as *mut c_void,
+                    value_len: attr_val.to_bytes().len() as c_ushort,
+                });
+
+                cstrs.push(attr_val);
+                cstrs.push(attr_name);
+            }
+        }
+
+        let c_snapshot_name = snap_param.name().unwrap().into_cstring();
+
+        // No need to flush blob's buffers explicitly as SPDK always
+        // synchronizes blob when taking a snapshot.
+        unsafe {
+            vbdev_lvol_create_snapshot_ext(
+                self.as_inner_ptr(),
+                c_snapshot_name.as_ptr(),
+                attr_descrs.as_mut_ptr(),
+                attr_descrs.len() as u32,
+                Some(cb),
+                cb_arg,
+            )
+        };
+        Ok(())
+    }
+
+    /// Creates a snapshot.
+    ///
+    /// The creation is idempotent per transaction: if a snapshot of this lvol
+    /// carrying the same transaction id already exists, e.g. as the request
+    /// is retried after a timeout, that snapshot is returned instead of
+    /// creating a duplicate. The retry must carry the same snapshot name and
+    /// uuid, else it conflicts with the snapshot already created. The window
+    /// of idempotency lasts as long as that snapshot remains in the snapshot
+    /// chain of this lvol, and has not been discarded. A snapshot with
+    /// deferred attributes only joins the window once its attributes have
+    /// been written.
+    async fn do_create_snapshot(
+        &self,
+        snap_param: SnapshotParams,
+        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
+        cb_arg: *mut c_void,
+        receiver: oneshot::Receiver<LvolResult>,
+    ) -> Result<Lvol, LvsError> {
+        if let Some(snapshot) = snap_param
+            .txn_id()
+            .and_then(|txn_id| self.find_snapshot_by_txn_id(&txn_id))
+        {
+            // The FFI call is not issued, so complete the callback here for
+            // its argument to be released.
+            unsafe {
+                cb(cb_arg, std::ptr::null_mut(), -(Errno::EEXIST as i32))
+            };
+            receiver.await.ok();
+            if snap_param.name().as_deref() != Some(snapshot.name().as_str())
+                || snap_param.snapshot_uuid().as_deref()
+                    != Some(snapshot.uuid().as_str())
+            {
+                return Err(LvsError::SnapshotTxnConflict {
+                    name: self.name(),
+                    txn_id: snap_param.txn_id().unwrap_or_default(),
+                    snapshot: snapshot.name(),
+                });
+            }
+            info!(
+                txn_id = ?snap_param.txn_id(),
+                snapshot = %snapshot.name(),
+                "Snapshot of {} already created by the transaction",
+                self.name()
+            );
+            return Ok(snapshot);
+        }
+
+        if let Err(error) = self.check_clone_chain_depth(false) {
+            // The FFI call is not issued, so complete the callback here for
+            // its argument to be released.
+            unsafe {
+                cb(cb_arg, std::ptr::null_mut(), -(Errno::EMLINK as i32))
+            };
+            receiver.await.ok();
+            return Err(error);
+        }
+        if let Err(error) = self.check_snapshot_limit() {
+            unsafe {
+                cb(cb_arg, std::ptr::null_mut(), -(Errno::EDQUOT as i32))
+            };
+            receiver.await.ok();
+            return Err(error);
+        }
+
+        // Clone the hooks, so that none is called with the registry locked.
+        let pre_hooks = SNAPSHOT_CREATE_HOOKS
+            .lock()
+            .pre
+            .iter()
+            .map(|(_, hook)| hook.clone())
+            .collect::<Vec<_>>();
+        for hook in pre_hooks {
+            if let Err(msg) = hook(self.clone(), snap_param.clone()).await {
+                // The FFI call is not issued, so complete the callback here
+                // for its argument to be released.
+                unsafe {
+                    cb(cb_arg, std::ptr::null_mut(), -(Errno::ECANCELED as i32))
+                };
+                receiver.await.ok();
+                return Err(LvsError::SnapshotHookFailed {
+                    name: snap_param.name().unwrap_or_default(),
+                    msg,
+                });
+            }
+        }
+
+        unsafe {
+            self.create_snapshot_inner(&snap_param, cb, cb_arg)?;
+        }
+
+        // Wait till operation succeeds, if requested.
+        let res = receiver.await.expect("Snapshot done callback disappeared");
+
+        match res {
+            Ok(lvol_ptr) => {
+                let snapshot = Lvol::from_inner_ptr(lvol_ptr);
+                if snap_param.defer_xattrs() {
+                    if let Err(error) = snapshot
+                        .write_deferred_snapshot_xattrs(&snap_param)
+                        .await
+                    {
+                        // The snapshot blob has been created: report it, and
+                        // do not leave the partial snapshot behind. Its
+                        // destruction reports its deletion in turn.
+                        snap_param.event(EventAction::Create).generate();
+                        let name = snapshot.name();
+                        if let Err(error) = snapshot.destroy().await {
+                            error!(
+                                snapshot = name,
+                                %error,
+                                "Failed to destroy partial snapshot"
+                            );
+                        }
+                        return Err(error);
+                    }
+                }
+                // Build the event from the persisted snapshot, so that event
+                // consumers get a self-contained snapshot record.
+                match snapshot.snapshot_descriptor(None) {
+                    Some(descr) => descr.event(EventAction::Create).generate(),
+                    None => snap_param.event(EventAction::Create).generate(),
+                }
+                let post_hooks = SNAPSHOT_CREATE_HOOKS
+                    .lock()
+                    .post
+                    .iter()
+                    .map(|(_, hook)| hook.clone())
+                    .collect::<Vec<_>>();
+                for hook in post_hooks {
+                    hook(snapshot.clone()).await;
+                }
+                Ok(snapshot)
+            }
+            Err(e) => Err(LvsError::SnapshotCreate {
+                source: BsError::from_errno(e),
+                msg: snap_param.name().unwrap(),
+            }),
+        }
+    }
+
+    /// Creates a remote snapshot.
+    async fn do_create_snapshot_remote(
+        &self,
+        mut snap_param: SnapshotParams,
+        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
+        cb_arg: *mut c_void,
+    ) -> Result<(), LvsError> {
+        // The remote snapshot completes in the callback, so there is no place
+        // to write deferred attributes in.
+        snap_param.set_defer_xattrs(false);
+        unsafe {
+            self.create_snapshot_inner(&snap_param, cb, cb_arg)?;
+        }
+        snap_param.event(EventAction::Create).generate();
+        Ok(())
+    }
+
+    /// Prepare clone config for snapshot.
+    fn prepare_clone_config(
+        &self,
+        clone_name: &str,
+        clone_uuid: &str,
+        source_uuid: &str,
+    ) -> Option<CloneParams> {
+        // clone_name
+        let clone_name = if clone_name.is_empty() {
+            return None;
+        } else {
+            clone_name.to_string()
+        };
+        // clone_uuid
+        let clone_uuid = if clone_uuid.is_empty() {
+            return None;
+        } else {
+            clone_uuid.to_string()
+        };
+        // source_uuid
+        let source_uuid = if source_uuid.is_empty() {
+            return None;
+        } else {
+            source_uuid.to_string()
+        };
+        Some(CloneParams::new(
+            Some(clone_name),
+            Some(clone_uuid),
+            Some(source_uuid),
+            Some(Utc::now().to_string()),
+        ))
+    }
+
+    /// Prepare clone xattrs.
+    fn prepare_clone_xattrs(
+        &self,
+        attr_descrs: &mut [spdk_xattr_descriptor; CloneXattrs::COUNT],
+        params: CloneParams,
+        cstrs: &mut Vec<CString>,
+    ) -> Result<(), LvsError> {
+        for (idx, attr) in CloneXattrs::iter().enumerate() {
+            // Get attribute value from CloneParams.
+            let av = match attr {
+                CloneXattrs::SourceUuid => match params.source_uuid() {
+                    Some(v) => v,
+                    None => {
+                        return Err(LvsError::CloneConfigFailed {
+                            name: self.as_bdev().name().to_string(),
+                            msg: "source uuid not provided".to_string(),
+                        })
+                    }
+                },
+                CloneXattrs::CloneCreateTime => {
+                    match params.clone_create_time() {
+                        Some(v) => v,
+                        None => {
+                            return Err(LvsError::CloneConfigFailed {
+                                name: self.as_bdev().name().to_string(),
+                                msg: "create_time not provided".to_string(),
+                            })
+                        }
+                    }
+                }
+                CloneXattrs::CloneUuid => match params.clone_uuid() {
+                    Some(v) => v,
+                    None => {
+                        return Err(LvsError::CloneConfigFailed {
+                            name: self.as_bdev().name().to_string(),
+                            msg: "clone_uuid not provided".to_string(),
+                        })
+                    }
+                },
+            };
+            let attr_name = attr.name().to_string().into_cstring();
+            let attr_val = av.into_cstring();
+            attr_descrs[idx].name = attr_name.as_ptr() as *mut c_char;
+            attr_descrs[idx].value = attr_val.as_ptr() as *mut c_void;
+            attr_descrs[idx].value_len = attr_val.to_bytes().len() as c_ushort;
+
+            cstrs.push(attr_val);
+            cstrs.push(attr_name);
+        }
+        Ok(())
+    }
+
+    /// Create clone inner function to call spdk clone function.
+    unsafe fn create_clone_inner(
+        &self,
+        clone_param: &CloneParams,
+        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
+        cb_arg: *mut c_void,
+    ) -> Result<(), LvsError> {
+        let mut attr_descrs: [spdk_xattr_descriptor; CloneXattrs::COUNT] =
+            [spdk_xattr_descriptor::default(); CloneXattrs::COUNT];
+
+        // Vector to keep allocated CStrings before snapshot  creation
+        // is complete to guarantee validity of attribute buffers
+        // stored inside CStrings.
+        let mut cstrs: Vec<CString> = Vec::new();
+
+        self.prepare_clone_xattrs(
+            &mut attr_descrs,
+            clone_param.clone(),
+            &mut cstrs,
+        )?;
+
+        let c_clone_name =
+            clone_param.clone_name().unwrap_or_default().into_cstring();
+
+        unsafe {
+            vbdev_lvol_create_clone_ext(
+                self.as_inner_ptr(),
+                c_clone_name.as_ptr(),
+                attr_descrs.as_mut_ptr(),
+                CloneXattrs::COUNT as u32,
+                Some(cb),
+                cb_arg,
+            )
+        };
+        Ok(())
+    }
+
+    /// Creates a clone.
+    async fn do_create_clone(
+        &self,
+        clone_param: CloneParams,
+        cb: unsafe extern "C" fn(*mut c_void, *mut spdk_lvol, i32),
+        cb_arg: *mut c_void,
+        receiver: oneshot::Receiver<LvolResult>,
+    ) -> Result<Lvol, LvsError> {
+        if let Err(error) = self.check_clone_chain_depth(true) {
+            // The FFI call is not issued, so complete the callback here for
+            // its argument to be released.
+            unsafe {
+                cb(cb_arg, std::ptr::null_mut(), -(Errno::EMLINK as i32))
+            };
+            receiver.await.ok();
+            return Err(error);
+        }
+
+        if let Some(cluster_size) = clone_param.cluster_size_override() {
+            let pool_cluster_size = self.usage().cluster_size;
+            let cluster_size = cluster_size as u64;
+            if !cluster_size.is_power_of_two()
+                || cluster_size < pool_cluster_size
+                || cluster_size % pool_cluster_size != 0
+            {
+                return Err(LvsError::CloneConfigFailed {
+                    name: self.as_bdev().name().to_string(),
+                    msg: format!(
+                        "cluster size {cluster_size} is not a power-of-two \
+                        multiple of the pool cluster size {pool_cluster_size}"
+                    ),
+                });
+            }
+            // The blobstore uses a single cluster size for all of its blobs.
+            if cluster_size != pool_cluster_size {
+                return Err(LvsError::CloneConfigFailed {
+                    name: self.as_bdev().name().to_string(),
+                    msg: format!(
+                        "cluster size {cluster_size} differs from the pool \
+                        cluster size {pool_cluster_size}, which is not \
+                        supported by the blobstore"
+                    ),
+                });
+            }
+        }
+
+        unsafe {
+            self.create_clone_inner(&clone_param, cb, cb_arg)?;
+        }
+        // Wait till operation succeeds, if requested.
+        let res = receiver
+            .await
+            .expect("Snapshot Clone done callback disappeared");
+
+        match res {
+            Ok(lvol_ptr) => {
+                Self::invalidate_clone_index();
+                Event::event(&clone_param, EventAction::Create).generate();
+                self.update_clone_refcount(true).await;
+                let clone = Lvol::from_inner_ptr(lvol_ptr);
+                if clone_param.thick() {
+                    if let Err(error) =
+                        clone.make_clone_thick(&clone_param).await
+                    {
+                        // The clone is not handed out: do not leave the
+                        // partial thin clone behind.
+                        let name = clone.name();
+                        if let Err(error) = clone.destroy().await {
+                            error!(
+                                clone = name,
+                                %error,
+                                "Failed to destroy partial thick clone"
+                            );
+                        }
+                        return Err(error);
+                    }
+                } else if clone_param.prefetch() {
+                    // A thick clone holds all of its data already.
+                    start_clone_prefetch(&clone);
+                }
+                Ok(clone)
+            }
+            Err(err) => Err(LvsError::SnapshotCloneCreate {
+                source: BsError::from_errno(err),
+                msg: clone_param.clone_name().unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Common API to set SnapshotDescriptor for ListReplicaSnapshot.
+    fn snapshot_descriptor(
+        &self,
+        parent: Option<&Lvol>,
+    ) -> Option<VolumeSnapshotDescriptor> {
+        self.build_snapshot_descriptor(parent)
+            .ok()
+            .map(|(descriptor, _)| descriptor)
+    }
+
+    /// Same as `snapshot_descriptor`, but fails with the first attribute
+    /// which is missing or cannot be parsed.
+    fn try_snapshot_descriptor(
+        &self,
+        parent: Option<&Lvol>,
+    ) -> Result<VolumeSnapshotDescriptor, SnapshotDescriptorError> {
+        match self.build_snapshot_descriptor(parent)? {
+            (descriptor, None) => Ok(descriptor),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Create snapshots of the given lvols concurrently, sharing a single
+    /// transaction id.
+    async fn create_snapshots_batch(
+        volumes: &[Lvol],
+        mut params: Vec<SnapshotParams>,
+    ) -> SnapshotBatchOutcome<Lvol, LvsError> {
+        let txn_id = params
+            .iter()
+            .find_map(|p| p.txn_id())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        if volumes.len() != params.len() {
+            let msg = format!(
+                "{} snapshot parameters given for {} replicas",
+                params.len(),
+                volumes.len()
+            );
+            return SnapshotBatchOutcome {
+                txn_id,
+                results: volumes
+                    .iter()
+                    .map(|v| {
+                        Err(LvsError::SnapshotConfigFailed {
+                            name: v.name(),
+                            msg: msg.clone(),
+                        })
+                    })
+                    .collect(),
+            };
+        }
+
+        let member_count = volumes.len() as u32;
+        for p in params.iter_mut() {
+            p.set_txn_id(txn_id.clone());
+            if p.group_id().is_none() {
+                p.set_group_id(txn_id.clone());
+                p.set_group_member_count(member_count);
+            }
+        }
+
+        let results = join_all(
+            volumes
+                .iter()
+                .zip(params)
+                .map(|(volume, p)| volume.create_snapshot(p)),
+        )
+        .await;
+
+        let outcome = SnapshotBatchOutcome {
+            txn_id,
+            results,
+        };
+        if !outcome.is_complete() {
+            warn!(
+                txn_id = %outcome.txn_id,
+                created = outcome.succeeded(),
+                total = outcome.results.len(),
+                "Snapshot batch partially failed"
+            );
+        }
+        outcome
+    }
+
+    /// Rename this snapshot. The new name is persisted in the blobstore
+    /// metadata.
+    /// Create a snapshot labelled as a recovery point.
+    async fn create_snapshot_labeled(
+        &self,
+        snap_param: SnapshotParams,
+    ) -> Result<Lvol, LvsError> {
+        let snapshot = self.create_snapshot(snap_param).await?;
+
+        let label = SnapshotLabel::new(
+            self.uuid(),
+            Lvol::get_blob_xattr(
+                snapshot.blob_checked(),
+                SnapshotXattrs::SnapshotCreateTime.name(),
+            )
+            .unwrap_or_default(),
+        );
+        let res = match serde_json::to_string(&label) {
+            Ok(value) => {
+                snapshot
+                    .set_blob_attr(
+                        SnapshotLabelXattrs::Label.name(),
+                        value,
+                        true,
+                    )
+                    .await
+            }
+            Err(error) => Err(LvsError::SnapshotConfigFailed {
+                name: snapshot.name(),
+                msg: format!("cannot serialize the snapshot label: {error}"),
+            }),
+        };
+
+        if let Err(error) = res {
+            let name = snapshot.name();
+            if let Err(e) = snapshot.destroy_snapshot().await {
+                error!(
+                    "Failed to destroy snapshot {name} after its label could \
+                    not be written: {e}"
+                );
+            }
+            return Err(error);
+        }
+        Ok(snapshot)
+    }
+
+    /// Read the label of this snapshot, if any.
+    fn read_snapshot_label(&self) -> Option<SnapshotLabel> {
+        Lvol::get_blob_xattr(
+            self.blob_checked(),
+            SnapshotLabelXattrs::Label.name(),
+        )
+        .and_then(|label| serde_json::from_str::<SnapshotLabel>(&label).ok())
+        .filter(|label| label.is_valid())
+    }
+
+    async fn rename_snapshot(&self, new_name: &str) -> Result<(), LvsError> {
+        extern "C" fn rename_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).ok();
+        }
+
+        let name = self.name();
+        let invalid = |msg: &str| LvsError::SnapshotConfigFailed {
+            name: name.clone(),
+            msg: msg.to_string(),
+        };
+        if !self.is_snapshot() {
+            return Err(invalid("not a snapshot"));
+        }
+        if new_name.is_empty() {
+            return Err(invalid("snapshot name not provided"));
+        }
+        if new_name == name {
+            return Ok(());
+        }
+        if self
+            .lvs()
+            .lvols()
+            .map_or(false, |mut lvols| lvols.any(|l| l.name() == new_name))
+        {
+            return Err(LvsError::SnapshotRename {
+                source: BsError::VolAlreadyExists {},
+                name,
+                new_name: new_name.to_string(),
+            });
+        }
+
+        let (s, r) = oneshot::channel::<i32>();
+        let c_name = new_name.into_cstring();
+        unsafe {
+            vbdev_lvol_rename(
+                self.as_inner_ptr(),
+                c_name.as_ptr(),
+                Some(rename_cb),
+                cb_arg(s),
+            );
+        }
+
+        match r.await.expect("Snapshot rename callback disappeared") {
+            0 => {
+                info!(snapshot = name, new_name, "Snapshot renamed");
+                if let Some(descr) = self.snapshot_descriptor(None) {
+                    descr.event(EventAction::Rename).generate();
+                }
+                Ok(())
+            }
+            errno => Err(LvsError::SnapshotRename {
+                source: BsError::from_errno(Errno::from_i32(errno.abs())),
+                name,
+                new_name: new_name.to_string(),
+            }),
+        }
+    }
+
+    /// Register a hook called before each snapshot is created.
+    fn register_pre_create_hook(hook: SnapshotPreCreateHook) -> SnapshotHookId {
+        let mut hooks = SNAPSHOT_CREATE_HOOKS.lock();
+        let id = hooks.next_id();
+        hooks.pre.push((id, hook));
+        id
+    }
+
+    /// Register a hook called after each snapshot has been created.
+    fn register_post_create_hook(
+        hook: SnapshotPostCreateHook,
+    ) -> SnapshotHookId {
+        let mut hooks = SNAPSHOT_CREATE_HOOKS.lock();
+        let id = hooks.next_id();
+        hooks.post.push((id, hook));
+        id
+    }
+
+    /// Unregister the snapshot creation hook with the given id.
+    fn unregister_create_hook(id: SnapshotHookId) -> bool {
+        let mut hooks = SNAPSHOT_CREATE_HOOKS.lock();
+        let count = hooks.pre.len() + hooks.post.len();
+        hooks.pre.retain(|(hook_id, _)| *hook_id != id);
+        hooks.post.retain(|(hook_id, _)| *hook_id != id);
+        hooks.pre.len() + hooks.post.len() != count
+    }
+
+    /// Create Snapshot Common API for Local Device.
+    async fn create_snapshot(
+        &self,
+        snap_param: SnapshotParams,
+    ) -> Result<Lvol, LvsError> {
+        extern "C" fn snapshot_create_done_cb(
+            arg: *mut c_void,
+            lvol_ptr: *mut spdk_lvol,
+            errno: i32,
+        ) {
+            let res = if errno == 0 {
+                Ok(lvol_ptr)
+            } else {
+                assert!(errno < 0);
+                let e = Errno::from_i32(-errno);
+                error!("Create snapshot failed with errno {errno}: {e}");
+                Err(e)
+            };
+
+            done_cb(arg, res);
+        }
+
+        let (s, r) = oneshot::channel::<LvolResult>();
+
+        self.do_create_snapshot(
+            snap_param,
+            snapshot_create_done_cb,
+            cb_arg(s),
+            r,
+        )
+        .await
+    }
+
+    /// Create a snapshot in Remote.
+    async fn create_snapshot_remote(
+        &self,
+        nvmf_req: &NvmfReq,
+        snapshot_params: SnapshotParams,
+    ) {
+        extern "C" fn snapshot_done_cb(
+            nvmf_req_ptr: *mut c_void,
+            _lvol_ptr: *mut spdk_lvol,
+            errno: i32,
+        ) {
+            let nvmf_req = NvmfReq::from(nvmf_req_ptr);
+
+            match errno {
+                0 => nvmf_req.complete(),
+                _ => {
+                    error!("vbdev_lvol_create_snapshot_ext errno {}", errno);
+                    nvmf_req.complete_error(errno);
+                }
+            };
+        }
+
+        info!(
+            volume = self.name(),
+            ?snapshot_params,
+            "Creating a remote snapshot"
+        );
+
+        if let Err(error) = self
+            .do_create_snapshot_remote(
+                snapshot_params,
+                snapshot_done_cb,
+                nvmf_req.0.as_ptr().cast(),
+            )
+            .await
+        {
+            error!(
+                ?error,
+                volume = self.name(),
+                "Failed to create remote snapshot"
+            );
+        }
+    }
+
+    /// Get a Snapshot Iterator.
+    async fn snapshot_iter(self) -> LvolSnapshotIter {
+        LvolSnapshotIter::new(self)
+    }
+
+    /// Destroy snapshot.
+    async fn destroy_snapshot(mut self) -> Result<(), Self::Error> {
+        if !self.has_snapshot_dependents() {
+            self.destroy().await?;
+        } else {
+            self.set_blob_attr(
+                SnapshotXattrs::DiscardedSnapshot.name(),
+                true.to_string(),
+                true,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// List the snapshots of this replica created before the cutoff time,
+    /// oldest first, which are candidates for retention.
+    fn list_retention_candidates(
+        &self,
+        cutoff: DateTime<Utc>,
+        retention_class: Option<&str>,
+    ) -> Vec<VolumeSnapshotDescriptor> {
+        let mut candidates = self
+            .list_snapshot_by_source_uuid()
+            .into_iter()
+            .filter(|s| !s.snapshot_params().discarded_snapshot())
+            .filter(|s| {
+                retention_class.map_or(true, |c| s.retention_class() == c)
+            })
+            .filter_map(|s| {
+                let params = s.snapshot_params();
+                match params.create_time() {
+                    Ok(Some(t)) if t < cutoff => Some((t, s)),
+                    Ok(_) => None,
+                    Err(error) => {
+                        warn!(
+                            snapshot = s.snapshot_lvol().name(),
+                            create_time = params.create_time_str(),
+                            %error,
+                            "Skipping snapshot with invalid create time"
+                        );
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|(t, _)| *t);
+        candidates.into_iter().map(|(_, s)| s).collect()
+    }
+
+    /// Destroy all snapshots of this replica created before the cutoff time,
+    /// oldest first. Snapshots which still have clones are marked as
+    /// discarded instead.
+    async fn prune_snapshots_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        retention_class: Option<&str>,
+    ) -> Result<PruneReport, Self::Error> {
+        let candidates = self
+            .list_retention_candidates(cutoff, retention_class)
+            .into_iter()
+            .map(|s| s.snapshot_lvol().clone())
+            .collect::<Vec<_>>();
+
+        let mut report = PruneReport::default();
+        for snapshot in candidates {
+            let has_clones =
+                !snapshot.list_clones_by_snapshot_uuid().is_empty();
+            snapshot.destroy_snapshot().await?;
+            if has_clones {
+                report.discarded += 1;
+            } else {
+                report.destroyed += 1;
+            }
+        }
+
+        info!(
+            replica = self.name(),
+            %cutoff,
+            ?retention_class,
+            ?report,
+            "Pruned replica snapshots"
+        );
+        Ok(report)
+    }
+
+    /// Destroy or discard the snapshots with the given UUIDs, concurrently.
+    async fn discard_snapshots_batch(
+        uuids: &[String],
+    ) -> Vec<Result<(), Self::Error>> {
+        stream::iter(uuids.to_vec())
+            .map(|uuid| {
+                destroy_bounded(move || async move {
+                    Lvol::lookup_snapshot(&uuid)?.destroy_snapshot().await
+                })
+            })
+            .buffered(discard_concurrency())
+            .collect()
+            .await
+    }
+
+    /// List Snapshot details based on source UUID from which snapshot is
+    /// created.
+    fn list_snapshot_by_source_uuid(&self) -> Vec<VolumeSnapshotDescriptor> {
+        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
+        let mut lvol_snap_iter = LvolSnapshotIter::new(self.clone());
+        while let Some(volume_snap_descr) = lvol_snap_iter.parent() {
+            // break the blob iteration if source uuid is not matched.
+            // it will happen when clone snapshot list is done through
+            // source clone uuid.
+            if volume_snap_descr.source_uuid() != self.uuid() {
+                break;
+            }
+            snapshot_list.push(volume_snap_descr.clone());
+        }
+        snapshot_list
+    }
+
+    /// List Single snapshot details based on snapshot UUID.
+    fn list_snapshot_by_snapshot_uuid(&self) -> Vec<VolumeSnapshotDescriptor> {
+        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
+        if let Some(snapshot) = self.snapshot_descriptor(None) {
+            snapshot_list.push(snapshot)
+        }
+        snapshot_list
+    }
+
+    /// List the snapshots this clone depends on, ordered from the root of the
+    /// chain to the source snapshot.
+    fn list_snapshots_for_clone(&self) -> Vec<VolumeSnapshotDescriptor> {
+        // Walk from the source snapshot rather than from the clone, so that
+        // the snapshots taken of the clone itself are left out.
+        let Some(source) = self.is_snapshot_clone() else {
+            return Vec::new();
+        };
+        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> =
+            source.snapshot_descriptor(None).into_iter().collect();
+        let mut lvol_snap_iter = LvolSnapshotIter::new(source);
+        while let Some(volume_snap_descr) = lvol_snap_iter.parent() {
+            snapshot_list.push(volume_snap_descr);
+        }
+        snapshot_list.reverse();
+        snapshot_list
+    }
+
+    /// List All Snapshot.
+    fn list_all_snapshots(
+        parent_lvol: Option<&Lvol>,
+    ) -> Vec<VolumeSnapshotDescriptor> {
+        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
+
+        let bdev = match UntypedBdev::bdev_first() {
+            Some(b) => b,
+            None => return Vec::new(), /* No devices available, provide no
+                                       snapshots */
+        };
+
+        let lvol_devices = bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .collect::<Vec<Lvol>>();
+
+        for snapshot_lvol in lvol_devices {
+            // skip lvol if it is not snapshot.
+            if !snapshot_lvol.is_snapshot() {
+                continue;
+            }
+            match snapshot_lvol.snapshot_descriptor(parent_lvol) {
+                Some(snapshot_descriptor) => {
+                    snapshot_list.push(snapshot_descriptor)
+                }
+                None => continue,
+            }
+        }
+        snapshot_list
+    }
+
+    /// List a page of at most `limit` snapshots, starting at `offset`,
+    /// optionally restricted to the snapshots of the given entity. Only the
+    /// entity id of the snapshots is read to filter and count them, the
+    /// descriptors being built for the snapshots of the page alone.
+    fn list_snapshots_page(
+        entity_id: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> SnapshotPage {
+        let mut page = SnapshotPage::default();
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return page;
+        };
+
+        for snapshot_lvol in bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.is_snapshot())
+        {
+            if let Some(entity_id) = entity_id {
+                let snapshot_entity_id = Lvol::get_blob_xattr(
+                    snapshot_lvol.blob_checked(),
+                    SnapshotXattrs::EntityId.name(),
+                );
+                if snapshot_entity_id.as_deref() != Some(entity_id) {
+                    continue;
+                }
+            }
+            let index = page.total;
+            page.total += 1;
+            if index < offset || page.snapshots.len() >= limit {
+                continue;
+            }
+            if let Some(descriptor) = snapshot_lvol.snapshot_descriptor(None) {
+                page.snapshots.push(descriptor);
+            }
+        }
+        page
+    }
+
+    /// List the snapshots of the given entity, sorted by their creation
+    /// time, oldest first; snapshots without a valid creation time come
+    /// first. Only the entity id of the snapshots is read to filter them, the
+    /// descriptors being built for the matching snapshots alone.
+    fn list_snapshots_by_entity_id(
+        entity_id: &str,
+    ) -> Vec<VolumeSnapshotDescriptor> {
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return Vec::new();
+        };
+
+        let mut snapshots = bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| {
+                l.is_snapshot()
+                    && Lvol::get_blob_xattr(
+                        l.blob_checked(),
+                        SnapshotXattrs::EntityId.name(),
+                    )
+                    .as_deref()
+                        == Some(entity_id)
+            })
+            .filter_map(|l| l.snapshot_descriptor(None))
+            .collect::<Vec<_>>();
+
+        snapshots
+            .sort_by_key(|s| s.snapshot_params().create_time().ok().flatten());
+        snapshots
+    }
+
+    /// Get the uuids of all the snapshots and clones reachable from this
+    /// lvol. The snapshot tree of the pool is indexed with a single scan of
+    /// its lvols, and then walked from this lvol.
+    fn reachable_blobs(&self) -> HashSet<String> {
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return HashSet::new();
+        };
+
+        // Index the edges of the snapshot tree: a snapshot is linked to the
+        // lvol it was taken from, and a clone to its source snapshot.
+        let lvs_uuid = self.lvs().uuid();
+        let mut edges: HashMap<String, Vec<String>> = HashMap::new();
+        for lvol in bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.lvs().uuid() == lvs_uuid)
+        {
+            let blob = lvol.blob_checked();
+            let parent = if lvol.is_snapshot() {
+                Lvol::get_blob_xattr(blob, SnapshotXattrs::ParentId.name())
+            } else {
+                Lvol::get_blob_xattr(blob, CloneXattrs::SourceUuid.name())
+            };
+            if let Some(parent) = parent {
+                edges.entry(parent.clone()).or_default().push(lvol.uuid());
+                edges.entry(lvol.uuid()).or_default().push(parent);
+            }
+        }
+
+        let origin = self.uuid();
+        let mut reachable = HashSet::new();
+        let mut pending = vec![origin.clone()];
+        while let Some(uuid) = pending.pop() {
+            for next in edges.remove(&uuid).unwrap_or_default() {
+                if next != origin && reachable.insert(next.clone()) {
+                    pending.push(next);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Compute the disk space usage of the whole snapshot/clone tree the
+    /// given lvol belongs to. The clusters allocated by a blob are never
+    /// shared with its ancestors, so every lvol of the tree is accounted for
+    /// exactly once: lvols with descendants count as shared space, and the
+    /// leaves as space unique to them.
+    fn tree_usage_summary(root: &Lvol) -> TreeUsageSummary {
+        let mut members: Vec<Lvol> = root
+            .reachable_blobs()
+            .iter()
+            .filter_map(|uuid| UntypedBdev::lookup_by_uuid_str(uuid))
+            .filter_map(|bdev| Lvol::try_from(bdev).ok())
+            .collect();
+        members.push(root.clone());
+
+        let parents: HashSet<String> = members
+            .iter()
+            .filter_map(|lvol| {
+                let blob = lvol.blob_checked();
+                if lvol.is_snapshot() {
+                    Lvol::get_blob_xattr(blob, SnapshotXattrs::ParentId.name())
+                } else {
+                    Lvol::get_blob_xattr(blob, CloneXattrs::SourceUuid.name())
+                }
+            })
+            .collect();
+
+        let mut summary = TreeUsageSummary::default();
+        for lvol in &members {
+            let allocated = lvol.usage().allocated_bytes;
+            summary.total_allocated_bytes += allocated;
+            if parents.contains(&lvol.uuid()) {
+                summary.shared_bytes += allocated;
+            } else {
+                summary.leaf_unique_bytes.insert(lvol.uuid(), allocated);
+            }
+        }
+        summary
+    }
+
+    /// Build the graph of all the replicas, snapshots and clones of the given
+    /// pool, with their parent and source relationships. Lvols referring to
+    /// an lvol missing from the pool are reported as orphaned.
+    fn pool_topology(lvs: &Lvs) -> PoolTopology {
+        let mut topology = PoolTopology {
+            pool_uuid: lvs.uuid(),
+            pool_name: lvs.name().to_string(),
+            ..Default::default()
+        };
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return topology;
+        };
+
+        let lvs_uuid = lvs.uuid();
+        for lvol in bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.lvs().uuid() == lvs_uuid)
+        {
+            let xattrs = Self::read_snapshot_xattrs(
+                lvol.blob_checked(),
+                &[
+                    SnapshotXattrs::ParentId.name(),
+                    CloneXattrs::SourceUuid.name(),
+                ],
+            );
+            let (kind, edge) = if lvol.is_snapshot() {
+                (
+                    TopologyNodeKind::Snapshot,
+                    xattrs
+                        .get(SnapshotXattrs::ParentId.name())
+                        .map(|p| (p.clone(), TopologyEdgeKind::Parent)),
+                )
+            } else if let Some(source) =
+                xattrs.get(CloneXattrs::SourceUuid.name())
+            {
+                (
+                    TopologyNodeKind::Clone,
+                    Some((source.clone(), TopologyEdgeKind::Source)),
+                )
+            } else {
+                (TopologyNodeKind::Replica, None)
+            };
+            let discarded = xattrs
+                .get(SnapshotXattrs::DiscardedSnapshot.name())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_default();
+
+            topology.nodes.push(TopologyNode {
+                uuid: lvol.uuid(),
+                name: lvol.name(),
+                kind,
+                allocated_bytes: lvol.usage().allocated_bytes,
+                discarded,
+                orphaned: false,
+            });
+            if let Some((to, kind)) = edge {
+                topology.edges.push(TopologyEdge {
+                    from: lvol.uuid(),
+                    to,
+                    kind,
+                    dangling: false,
+                });
+            }
+        }
+
+        // Edges can only be resolved once all the lvols of the pool are known.
+        let uuids: HashSet<String> =
+            topology.nodes.iter().map(|n| n.uuid.clone()).collect();
+        let mut orphans = HashSet::new();
+        for edge in &mut topology.edges {
+            if !uuids.contains(&edge.to) {
+                edge.dangling = true;
+                orphans.insert(edge.from.clone());
+            }
+        }
+        for node in &mut topology.nodes {
+            node.orphaned = orphans.contains(&node.uuid);
+        }
+
+        topology
     }
 
-    /// Create Snapshot Common API for Local Device.
-    async fn create_snapshot(
-        &self,
-        snap_param: SnapshotParams,
-    ) -> Result<Lvol, LvsError> {
-        extern "C" fn snapshot_create_done_cb(
-            arg: *mut c_void,
-            lvol_ptr: *mut spdk_lvol,
-            errno: i32,
-        ) {
-            let res = if errno == 0 {
-                Ok(lvol_ptr)
+    /// List the ancestry of this lvol, from itself up to the root, following
+    /// the parent of the snapshots and the source snapshot of the clones.
+    fn ancestry(&self) -> Result<Vec<AncestryNode>, LvsError> {
+        let mut ancestry = Vec::new();
+        let mut visited = HashSet::new();
+        let mut next = Some(self.clone());
+
+        while let Some(lvol) = next.take() {
+            let uuid = lvol.uuid();
+            if !visited.insert(uuid.clone()) {
+                return Err(LvsError::AncestryCycle {
+                    name: self.name(),
+                    uuid,
+                });
+            }
+            let kind = if lvol.is_snapshot() {
+                next = Lvol::get_blob_xattr(
+                    lvol.blob_checked(),
+                    SnapshotXattrs::ParentId.name(),
+                )
+                .and_then(|parent| UntypedBdev::lookup_by_uuid_str(&parent))
+                .and_then(|bdev| Lvol::try_from(bdev).ok());
+                TopologyNodeKind::Snapshot
+            } else if Lvol::get_blob_xattr(
+                lvol.blob_checked(),
+                CloneXattrs::SourceUuid.name(),
+            )
+            .is_some()
+            {
+                next = lvol.is_snapshot_clone();
+                TopologyNodeKind::Clone
             } else {
-                assert!(errno < 0);
-                let e = Errno::from_i32(-errno);
-                error!("Create snapshot failed with errno {errno}: {e}");
-                Err(e)
+                TopologyNodeKind::Replica
             };
 
-            done_cb(arg, res);
+            ancestry.push(AncestryNode {
+                uuid,
+                name: lvol.name(),
+                kind,
+                allocated_bytes: lvol.usage().allocated_bytes,
+            });
         }
+        Ok(ancestry)
+    }
 
-        let (s, r) = oneshot::channel::<LvolResult>();
+    /// Plan the destruction of this lvol without mutating anything.
+    fn plan_destroy(&self) -> Result<DestroyPlan, LvsError> {
+        let node = |lvol: &Lvol, kind| TopologyNode {
+            uuid: lvol.uuid(),
+            name: lvol.name(),
+            kind,
+            allocated_bytes: lvol.usage().allocated_bytes,
+            discarded: lvol.is_snapshot() && lvol.is_discarded_snapshot(),
+            orphaned: false,
+        };
+        let mut plan = DestroyPlan::default();
 
-        self.do_create_snapshot(
-            snap_param,
-            snapshot_create_done_cb,
-            cb_arg(s),
-            r,
-        )
-        .await
+        if self.is_snapshot() {
+            // Mirrors destroy_snapshot.
+            let snapshot = node(self, TopologyNodeKind::Snapshot);
+            if self.has_snapshot_dependents() {
+                plan.discarded.push(snapshot);
+                return Ok(plan);
+            }
+            plan.destroyed.push(snapshot);
+            plan.reclaimed_bytes = self.snapshot_reclaimed_bytes(&[]);
+        } else if let Some(source) = self.is_snapshot_clone() {
+            // Mirrors destroy_replica: the source snapshot goes along with
+            // its last clone if it has been discarded.
+            let clone = node(self, TopologyNodeKind::Clone);
+            plan.reclaimed_bytes = clone.allocated_bytes;
+            plan.destroyed.push(clone);
+            let uuid = self.uuid();
+            if source.is_discarded_snapshot()
+                && source
+                    .list_clones_by_snapshot_uuid()
+                    .iter()
+                    .all(|c| c.uuid() == uuid)
+            {
+                plan.destroyed
+                    .push(node(&source, TopologyNodeKind::Snapshot));
+                plan.reclaimed_bytes +=
+                    source.snapshot_reclaimed_bytes(&[uuid]);
+            }
+        } else {
+            let replica = node(self, TopologyNodeKind::Replica);
+            plan.reclaimed_bytes = replica.allocated_bytes;
+            plan.destroyed.push(replica);
+        }
+
+        Ok(plan)
     }
 
-    /// Create a snapshot in Remote.
-    async fn create_snapshot_remote(
-        &self,
-        nvmf_req: &NvmfReq,
-        snapshot_params: SnapshotParams,
-    ) {
-        extern "C" fn snapshot_done_cb(
-            nvmf_req_ptr: *mut c_void,
-            _lvol_ptr: *mut spdk_lvol,
-            errno: i32,
-        ) {
-            let nvmf_req = NvmfReq::from(nvmf_req_ptr);
+    /// Destroy the given replica along with all the snapshots and clones
+    /// built upon it. The lvols are destroyed leaves first, every blob after
+    /// the blobs backed by it, so that no snapshot is ever destroyed while
+    /// another blob still reads through to it: the replica and the clones
+    /// go before their snapshots. Nothing is destroyed if any lvol of the
+    /// tree is claimed, e.g. shared or opened by a nexus. The destruction
+    /// stops at the first lvol which fails to be destroyed, reporting the
+    /// lvols already destroyed along with the error.
+    async fn destroy_replica_tree(
+        root: &Lvol,
+    ) -> Result<DestroyTreeReport, Self::Error> {
+        let topology = Self::pool_topology(&root.lvs());
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+        for edge in topology.edges {
+            children.entry(edge.to).or_default().push(edge.from);
+        }
+        let mut nodes: HashMap<String, TopologyNode> = topology
+            .nodes
+            .into_iter()
+            .map(|n| (n.uuid.clone(), n))
+            .collect();
+
+        // Collect the lvols of the tree.
+        let mut members = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![root.uuid()];
+        while let Some(uuid) = pending.pop() {
+            if visited.insert(uuid.clone()) {
+                pending.extend(children.remove(&uuid).unwrap_or_default());
+                members.push(uuid);
+            }
+        }
 
-            match errno {
-                0 => nvmf_req.complete(),
-                _ => {
-                    error!("vbdev_lvol_create_snapshot_ext errno {}", errno);
-                    nvmf_req.complete_error(errno);
+        // Check all the lvols up front, so that the tree is either destroyed
+        // or left untouched as a whole, unless an lvol fails to be destroyed.
+        let mut lvols = HashMap::with_capacity(members.len());
+        for uuid in &members {
+            let lvol = UntypedBdev::lookup_by_uuid_str(uuid)
+                .and_then(|b| Lvol::try_from(b).ok())
+                .ok_or_else(|| LvsError::Invalid {
+                    source: BsError::LvolNotFound {},
+                    msg: format!("lvol {uuid} not found"),
+                })?;
+            let bdev = lvol.as_bdev();
+            if bdev.is_claimed() {
+                return Err(LvsError::TreeNodeHeld {
+                    name: lvol.name(),
+                    holder: bdev
+                        .first_claim_module_name()
+                        .unwrap_or_else(|| "Orphaned".into()),
+                });
+            }
+            lvols.insert(uuid.clone(), lvol);
+        }
+
+        // Order the lvols leaves first, from the blobs backing them: a blob
+        // is destroyed once all the blobs of the tree backed by it are.
+        let mut blob_parent = HashMap::new();
+        let mut backed: HashMap<String, usize> = HashMap::new();
+        for (uuid, lvol) in &lvols {
+            let parent = unsafe { lvol.bs_iter_parent(lvol.blob_checked()) }
+                .and_then(|blob| {
+                    Self::get_blob_xattr(
+                        blob,
+                        SnapshotXattrs::SnapshotUuid.name(),
+                    )
+                })
+                .filter(|parent| lvols.contains_key(parent));
+            if let Some(parent) = parent {
+                *backed.entry(parent.clone()).or_default() += 1;
+                blob_parent.insert(uuid.clone(), parent);
+            }
+        }
+        let mut leaves = members
+            .iter()
+            .filter(|uuid| !backed.contains_key(*uuid))
+            .cloned()
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(members.len());
+        while let Some(uuid) = leaves.pop_front() {
+            if let Some(parent) = blob_parent.get(&uuid) {
+                let count = backed.get_mut(parent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    leaves.push_back(parent.clone());
                 }
+            }
+            order.push(uuid);
+        }
+
+        let mut report = DestroyTreeReport::default();
+        let (root_uuid, root_name) = (root.uuid(), root.name());
+        for uuid in order {
+            let lvol = lvols.remove(&uuid).unwrap();
+            let name = lvol.name();
+            // The replica takes with it its source snapshot if it was the
+            // last clone of a discarded snapshot.
+            let res = if uuid == root_uuid {
+                lvol.destroy_replica().await.map(|_| ())
+            } else {
+                lvol.destroy().await.map(|_| ())
             };
+            if let Err(error) = res {
+                error!(
+                    replica = root_name,
+                    lvol = name,
+                    destroyed = report.destroyed.len(),
+                    %error,
+                    "Failed to destroy replica tree"
+                );
+                return Err(LvsError::TreeDestroyIncomplete {
+                    name: root_name,
+                    failed: name,
+                    report,
+                    source: Box::new(error),
+                });
+            }
+            if let Some(node) = nodes.remove(&uuid) {
+                report.destroyed.push(node);
+            }
         }
 
         info!(
-            volume = self.name(),
-            ?snapshot_params,
-            "Creating a remote snapshot"
+            replica = root_name,
+            destroyed = report.destroyed.len(),
+            "Destroyed replica tree"
         );
-
-        if let Err(error) = self
-            .do_create_snapshot_remote(
-                snapshot_params,
-                snapshot_done_cb,
-                nvmf_req.0.as_ptr().cast(),
-            )
-            .await
-        {
-            error!(
-                ?error,
-                volume = self.name(),
-                "Failed to create remote snapshot"
-            );
-        }
-    }
-
-    /// Get a Snapshot Iterator.
-    async fn snapshot_iter(self) -> LvolSnapshotIter {
-        LvolSnapshotIter::new(self)
+        Ok(report)
     }
 
-    /// Destroy snapshot.
-    async fn destroy_snapshot(mut self) -> Result<(), Self::Error> {
-        if self.list_clones_by_snapshot_uuid().is_empty() {
-            self.destroy().await?;
-        } else {
-            self.set_blob_attr(
-                SnapshotXattrs::DiscardedSnapshot.name(),
-                true.to_string(),
-                true,
-            )
-            .await?;
-        }
+    /// List all snapshots of the given pool.
+    fn list_snapshots_in_pool(
+        lvs: &Lvs,
+        parent_lvol: Option<&Lvol>,
+    ) -> Vec<VolumeSnapshotDescriptor> {
+        let Some(lvols) = lvs.lvols() else {
+            return Vec::new();
+        };
 
-        Ok(())
+        lvols
+            .filter(|l| l.is_snapshot())
+            .filter_map(|l| l.snapshot_descriptor(parent_lvol))
+            .collect()
     }
 
-    /// List Snapshot details based on source UUID from which snapshot is
-    /// created.
-    fn list_snapshot_by_source_uuid(&self) -> Vec<VolumeSnapshotDescriptor> {
-        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
-        let mut lvol_snap_iter = LvolSnapshotIter::new(self.clone());
-        while let Some(volume_snap_descr) = lvol_snap_iter.parent() {
-            // break the blob iteration if source uuid is not matched.
-            // it will happen when clone snapshot list is done through
-            // source clone uuid.
-            if volume_snap_descr.source_uuid() != self.uuid() {
-                break;
+    /// Find the snapshot uuids claimed by more than one snapshot, returning
+    /// each duplicated snapshot uuid with the uuids of the lvols claiming it.
+    fn find_duplicate_snapshot_uuids() -> Vec<(String, Vec<String>)> {
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return Vec::new();
+        };
+
+        let mut claims: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for lvol in bdev
+            .into_iter()
+            .filter(|b| b.driver() == "lvol")
+            .map(|b| Lvol::try_from(b).unwrap())
+            .filter(|l| l.is_snapshot())
+        {
+            if let Some(snap_uuid) = Lvol::get_blob_xattr(
+                lvol.blob_checked(),
+                SnapshotXattrs::SnapshotUuid.name(),
+            ) {
+                claims.entry(snap_uuid).or_default().push(lvol.uuid());
             }
-            snapshot_list.push(volume_snap_descr.clone());
         }
-        snapshot_list
+
+        claims
+            .into_iter()
+            .filter(|(_, lvols)| lvols.len() > 1)
+            .collect()
     }
 
-    /// List Single snapshot details based on snapshot UUID.
-    fn list_snapshot_by_snapshot_uuid(&self) -> Vec<VolumeSnapshotDescriptor> {
-        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
-        if let Some(snapshot) = self.snapshot_descriptor(None) {
-            snapshot_list.push(snapshot)
-        }
-        snapshot_list
+    /// List all snapshots with invalid or partial metadata.
+    fn list_invalid_snapshots() -> Vec<VolumeSnapshotDescriptor> {
+        Self::list_all_snapshots(None)
+            .into_iter()
+            .filter(|s| !s.valid_snapshot())
+            .inspect(|s| {
+                warn!(
+                    snapshot = s.snapshot_lvol().name(),
+                    missing = ?s.snapshot_lvol().missing_snapshot_xattrs(),
+                    "Snapshot has invalid metadata"
+                );
+            })
+            .collect()
     }
 
-    /// List All Snapshot.
-    fn list_all_snapshots(
-        parent_lvol: Option<&Lvol>,
-    ) -> Vec<VolumeSnapshotDescriptor> {
-        let mut snapshot_list: Vec<VolumeSnapshotDescriptor> = Vec::new();
+    /// List all snapshots with valid metadata whose parent lvol no longer
+    /// exists.
+    fn list_rooted_orphan_snapshots() -> Vec<VolumeSnapshotDescriptor> {
+        Self::list_all_snapshots(None)
+            .into_iter()
+            .filter(|s| s.valid_snapshot() && s.source_uuid().is_empty())
+            .collect()
+    }
 
-        let bdev = match UntypedBdev::bdev_first() {
-            Some(b) => b,
-            None => return Vec::new(), /* No devices available, provide no
-                                       snapshots */
+    /// List all snapshots whose parent lvol no longer exists. The lvols are
+    /// scanned once, collecting their uuids along with the parent of the
+    /// snapshots, so that the descriptors are built for the orphans alone.
+    fn list_orphaned_snapshots() -> Vec<VolumeSnapshotDescriptor> {
+        let Some(bdev) = UntypedBdev::bdev_first() else {
+            return Vec::new();
         };
 
-        let lvol_devices = bdev
+        let mut uuids = HashSet::new();
+        let mut snapshots = Vec::new();
+        for lvol in bdev
             .into_iter()
             .filter(|b| b.driver() == "lvol")
             .map(|b| Lvol::try_from(b).unwrap())
-            .collect::<Vec<Lvol>>();
-
-        for snapshot_lvol in lvol_devices {
-            // skip lvol if it is not snapshot.
-            if !snapshot_lvol.is_snapshot() {
+        {
+            uuids.insert(lvol.uuid());
+            if !lvol.is_snapshot() || lvol.is_discarded_snapshot() {
                 continue;
             }
-            match snapshot_lvol.snapshot_descriptor(parent_lvol) {
-                Some(snapshot_descriptor) => {
-                    snapshot_list.push(snapshot_descriptor)
-                }
-                None => continue,
+            // A snapshot without a parent has invalid metadata instead.
+            if let Some(parent) = Lvol::get_blob_xattr(
+                lvol.blob_checked(),
+                SnapshotXattrs::ParentId.name(),
+            ) {
+                snapshots.push((lvol, parent));
             }
         }
-        snapshot_list
+
+        snapshots
+            .into_iter()
+            .filter(|(_, parent)| !uuids.contains(parent))
+            .filter_map(|(snapshot, parent)| {
+                warn!(
+                    snapshot = snapshot.name(),
+                    parent, "Snapshot parent no longer exists"
+                );
+                snapshot.snapshot_descriptor(None)
+            })
+            .collect()
+    }
+
+    /// Get the snapshot attributes missing from this snapshot.
+    fn missing_snapshot_xattrs(&self) -> Vec<SnapshotXattrs> {
+        let xattrs = Self::read_all_snapshot_xattrs(self.blob_checked());
+        SnapshotXattrs::iter()
+            .filter(|attr| !attr.is_optional())
+            .filter(|attr| !xattrs.contains_key(attr.name()))
+            .collect()
+    }
+
+    /// List the snapshots on this node belonging to the given consistency
+    /// group.
+    fn list_snapshot_group(group_id: &str) -> Vec<VolumeSnapshotDescriptor> {
+        Self::list_all_snapshots(None)
+            .into_iter()
+            .filter(|s| {
+                s.snapshot_params().group_id().as_deref() == Some(group_id)
+            })
+            .collect()
+    }
+
+    /// Check if all the expected members of the given consistency group have
+    /// been found on this node.
+    fn is_snapshot_group_complete(group_id: &str) -> bool {
+        let members = Self::list_snapshot_group(group_id);
+        match members
+            .first()
+            .and_then(|s| s.snapshot_params().group_member_count())
+        {
+            Some(expected) => members.len() == expected as usize,
+            None => false,
+        }
+    }
+
+    /// Get the most recent snapshot of this replica which has been replicated
+    /// to the given target.
+    fn replication_base(&self, target: &str) -> Option<Self::Lvol> {
+        self.list_snapshot_by_source_uuid()
+            .into_iter()
+            .map(|s| s.snapshot_lvol().clone())
+            .find(|snap| snap.replicated_to().iter().any(|t| t == target))
+    }
+
+    /// Record that this snapshot has been replicated to the given target.
+    async fn mark_replicated(&self, target: &str) -> Result<(), Self::Error> {
+        if !self.is_snapshot() {
+            return Err(LvsError::Invalid {
+                source: BsError::InvalidArgument {},
+                msg: format!("{} is not a snapshot", self.name()),
+            });
+        }
+        if target.is_empty() || target.contains(',') {
+            return Err(LvsError::Invalid {
+                source: BsError::InvalidArgument {},
+                msg: format!("invalid replication target '{target}'"),
+            });
+        }
+
+        let mut targets = self.replicated_to();
+        if targets.iter().any(|t| t == target) {
+            return Ok(());
+        }
+        targets.push(target.to_string());
+
+        self.set_blob_attr(
+            SnapshotReplicationXattrs::ReplicatedTo.name(),
+            targets.join(","),
+            true,
+        )
+        .await?;
+        info!(
+            snapshot = self.name(),
+            replication_target = target,
+            "Snapshot marked as replicated"
+        );
+        Ok(())
     }
 
     /// Create snapshot clone.
@@ -700,24 +3116,14 @@ impl SnapshotOps for Lvol {
 
     /// List clones based on snapshot_uuid.
     fn list_clones_by_snapshot_uuid(&self) -> Vec<Lvol> {
-        let bdev = match UntypedBdev::bdev_first() {
-            Some(b) => b,
-            None => return Vec::new(), /* No devices available, no clones */
-        };
-        bdev.into_iter()
-            .filter(|b| b.driver() == "lvol")
-            .map(|b| Lvol::try_from(b).unwrap())
-            .filter_map(|b| {
-                let snap_lvol = b.is_snapshot_clone();
-                if snap_lvol.is_some()
-                    && snap_lvol.unwrap().uuid() == self.uuid()
-                {
-                    Some(b)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<Lvol>>()
+        let clones = Self::with_clone_index(|index| {
+            index.get(&self.uuid()).cloned().unwrap_or_default()
+        });
+        clones
+            .iter()
+            .filter_map(|uuid| UntypedBdev::lookup_by_uuid_str(uuid))
+            .filter_map(|b| Lvol::try_from(b).ok())
+            .collect()
     }
 
     /// List All Clones.
@@ -733,6 +3139,23 @@ impl SnapshotOps for Lvol {
             .collect::<Vec<Lvol>>()
     }
 
+    /// Check the attributes of this snapshot against their checksum.
+    fn verify_snapshot_integrity(&self) -> Result<(), LvsError> {
+        let xattrs = Self::read_all_snapshot_xattrs(self.blob_checked());
+        let Some(stored) = xattrs.get(SnapshotXattrs::Checksum.name()) else {
+            // Created by an older version, nothing to check against.
+            return Ok(());
+        };
+        let checksum =
+            snapshot_xattrs_checksum(|attr| xattrs.get(attr.name()).cloned());
+        if *stored != checksum {
+            return Err(LvsError::SnapshotChecksumMismatch {
+                name: self.name(),
+            });
+        }
+        Ok(())
+    }
+
     /// Check if the snapshot has been discarded.
     fn is_discarded_snapshot(&self) -> bool {
         Lvol::get_blob_xattr(
@@ -760,21 +3183,44 @@ impl SnapshotOps for Lvol {
             .filter(|b| {
                 b.is_snapshot()
                     && b.is_discarded_snapshot()
-                    && b.list_clones_by_snapshot_uuid().is_empty()
+                    && !b.has_snapshot_dependents()
             })
             .collect::<Vec<Lvol>>();
         for snap in &snap_list {
             snap.reset_snapshot_tree_usage_cache(false);
         }
-        let futures = snap_list.into_iter().map(|s| s.destroy());
-        let result = join_all(futures).await;
-        for r in result {
-            match r {
-                Ok(r) => {
-                    debug!("Pending discarded snapshot {r:?} destroy success")
-                }
-                _ => warn!("Pending discarded snapshot destroy failed"),
-            }
+
+        // Bound the number of concurrent destroys, not to stall the reactor
+        // with hundreds of blobstore operations, e.g. on pool import.
+        let total = snap_list.len();
+        let failures = stream::iter(snap_list)
+            .map(|snap| (snap.name(), snap.uuid()))
+            .map(|(name, uuid)| async move {
+                destroy_bounded(move || async move {
+                    Lvol::lookup_snapshot(&uuid)?.destroy().await.map(|_| ())
+                })
+                .await
+                .err()
+                .map(|error| (name, error))
+            })
+            .buffer_unordered(discard_concurrency())
+            .filter_map(|failure| async move { failure })
+            .collect::<Vec<_>>()
+            .await;
+
+        if failures.is_empty() {
+            debug!(total, "Destroyed pending discarded snapshots");
+        } else {
+            let failures = failures
+                .iter()
+                .map(|(name, error)| format!("{name}: {error}"))
+                .collect::<Vec<_>>();
+            warn!(
+                total,
+                failed = failures.len(),
+                ?failures,
+                "Failed to destroy some pending discarded snapshots"
+            );
         }
     }
 
@@ -847,8 +3293,7 @@ impl SnapshotOps for Lvol {
                 }
             } else {
                 reset_snapshot_tree_usage_cache_with_wildcard(
-                    self,
-                    snapshot_parent_uuid,
+                    &snapshot_parent_uuid,
                 );
             }
         }
@@ -875,48 +3320,83 @@ fn reset_snapshot_tree_usage_cache_with_parent_uuid(lvol: &Lvol) {
 
 /// When snapshot is destroyed, if snapshot parent not exist, reset cache of
 /// linked snapshot and clone tree based on wildcard search through complete
-/// bdev by matching parent uuid got from snapshot attribute.
-/// todo: need more optimization to adding new function in spdk to relate
-/// snapshot and clone blobs.
-fn reset_snapshot_tree_usage_cache_with_wildcard(
-    lvol: &Lvol,
-    snapshot_parent_uuid: String,
-) {
-    let mut successor_clones: Vec<Lvol> = vec![];
-
-    let mut successor_snapshots = Lvol::list_all_snapshots(None)
-        .iter()
-        .map(|v| v.snapshot_lvol())
-        .filter_map(|l| {
-            let uuid = Lvol::get_blob_xattr(
-                lvol.blob_checked(),
-                SnapshotXattrs::ParentId.name(),
-            );
-            match uuid {
-                Some(uuid) if uuid == snapshot_parent_uuid => Some(l.clone()),
-                _ => None,
-            }
-        })
-        .collect::<Vec<Lvol>>();
-
-    while !successor_snapshots.is_empty() || !successor_clones.is_empty() {
-        if let Some(snapshot) = successor_snapshots.pop() {
-            unsafe {
-                spdk_blob_reset_used_clusters_cache(snapshot.blob_checked());
-            }
-            let new_clone_list = snapshot.list_clones_by_snapshot_uuid();
-            successor_clones.extend(new_clone_list);
+/// bdev.
+fn reset_snapshot_tree_usage_cache_with_wildcard(snapshot_parent_uuid: &str) {
+    let tree = wildcard_snapshot_tree();
+    for lvol in &tree {
+        unsafe {
+            spdk_blob_reset_used_clusters_cache(lvol.blob_checked());
         }
+    }
+    debug!(
+        parent = snapshot_parent_uuid,
+        reset = tree.len(),
+        "Reset usage cache of snapshot tree"
+    );
+}
 
-        if let Some(clone) = successor_clones.pop() {
-            unsafe {
-                spdk_blob_reset_used_clusters_cache(clone.blob_checked());
-            }
-            let new_snap_list = Lvol::list_all_snapshots(Some(&clone))
-                .iter()
-                .map(|v| v.snapshot_lvol().clone())
-                .collect::<Vec<Lvol>>();
-            successor_snapshots.extend(new_snap_list);
+/// Gets the lvols of the snapshot and clone tree whose usage cache is reset
+/// when the parent of a destroyed snapshot is gone, each lvol once. The
+/// links between the snapshots and the clones are gathered with a single
+/// scan of the lvols, the tree being then walked in memory.
+fn wildcard_snapshot_tree() -> Vec<Lvol> {
+    let Some(bdev) = UntypedBdev::bdev_first() else {
+        return Vec::new();
+    };
+
+    let mut lvols: HashMap<String, Lvol> = HashMap::new();
+    // Snapshots with valid attributes, indexed by parent uuid.
+    let mut children: HashMap<String, Vec<Lvol>> = HashMap::new();
+    // The parent being gone, the tree cannot be located from it: every
+    // snapshot is a root of the walk.
+    let mut pending: VecDeque<(Lvol, bool)> = VecDeque::new();
+    for lvol in bdev
+        .into_iter()
+        .filter(|b| b.driver() == "lvol")
+        .map(|b| Lvol::try_from(b).unwrap())
+    {
+        lvols.insert(lvol.uuid(), lvol.clone());
+        if !lvol.is_snapshot() || lvol.snapshot_descriptor(None).is_none() {
+            continue;
+        }
+        if let Some(parent) = Lvol::get_blob_xattr(
+            lvol.blob_checked(),
+            SnapshotXattrs::ParentId.name(),
+        ) {
+            children.entry(parent).or_default().push(lvol.clone());
+        }
+        pending.push_back((lvol, true));
+    }
+    let clones = Lvol::with_clone_index(|index| index.clone());
+
+    // Walk down from the snapshots to their clones, and from the clones to
+    // their snapshots.
+    let mut visited = HashSet::new();
+    let mut tree = Vec::new();
+    while let Some((lvol, is_snapshot)) = pending.pop_front() {
+        let uuid = lvol.uuid();
+        if !visited.insert((uuid.clone(), is_snapshot)) {
+            continue;
+        }
+        if is_snapshot {
+            pending.extend(
+                clones
+                    .get(&uuid)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|clone| lvols.get(clone))
+                    .map(|clone| (clone.clone(), false)),
+            );
+        } else {
+            pending.extend(
+                children
+                    .get(&uuid)
+                    .into_iter()
+                    .flatten()
+                    .map(|snapshot| (snapshot.clone(), true)),
+            );
         }
+        tree.push(lvol);
     }
+    tree
 }
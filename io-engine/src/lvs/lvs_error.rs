@@ -5,7 +5,7 @@ use super::PropName;
 
 use crate::{
     bdev_api::BdevError,
-    core::{CoreError, ToErrno},
+    core::{CoreError, DestroyTreeReport, ToErrno},
 };
 
 /// LVS import error reason.
@@ -238,11 +238,151 @@ pub enum LvsError {
         source: BsError,
         msg: String,
     },
+    #[snafu(display(
+        "Snapshot {} aborted by a pre-create hook: {}",
+        name,
+        msg
+    ))]
+    SnapshotHookFailed {
+        name: String,
+        msg: String,
+    },
+    #[snafu(display(
+        "Transaction {} already created snapshot {} of {}, with another \
+        name or uuid",
+        txn_id,
+        snapshot,
+        name
+    ))]
+    SnapshotTxnConflict {
+        name: String,
+        txn_id: String,
+        snapshot: String,
+    },
+    #[snafu(display("Failed to rename snapshot {} to {}", name, new_name))]
+    SnapshotRename {
+        source: BsError,
+        name: String,
+        new_name: String,
+    },
     #[snafu(display("SnapshotClone {} creation failed", msg))]
     SnapshotCloneCreate {
         source: BsError,
         msg: String,
     },
+    #[snafu(display(
+        "Replica of {} bytes is too small to restore snapshot {}, \
+        {} bytes are required",
+        available,
+        name,
+        required
+    ))]
+    RestoreTargetTooSmall {
+        name: String,
+        required: u64,
+        available: u64,
+    },
+    #[snafu(display(
+        "Source snapshot {} of clone {} no longer exists",
+        source_uuid,
+        name
+    ))]
+    CloneSourceMissing {
+        name: String,
+        source_uuid: String,
+    },
+    #[snafu(display(
+        "Lvol {} is held by {}, refusing to destroy its tree",
+        name,
+        holder
+    ))]
+    TreeNodeHeld {
+        name: String,
+        holder: String,
+    },
+    #[snafu(display(
+        "Failed to destroy lvol {} of the tree of replica {}, after {} lvols \
+        were destroyed: {}",
+        failed,
+        name,
+        report.destroyed.len(),
+        source
+    ))]
+    TreeDestroyIncomplete {
+        name: String,
+        failed: String,
+        report: DestroyTreeReport,
+        source: Box<LvsError>,
+    },
+    #[snafu(display(
+        "Clone chain of {} would exceed the limit of {} clones",
+        name,
+        limit
+    ))]
+    CloneChainTooDeep {
+        name: String,
+        limit: u32,
+    },
+    #[snafu(display(
+        "Replica {} already has {} snapshots, the limit is {}",
+        name,
+        count,
+        limit
+    ))]
+    SnapshotLimitExceeded {
+        name: String,
+        count: u32,
+        limit: u32,
+    },
+    #[snafu(display(
+        "Attributes of snapshot {} do not match their checksum",
+        name
+    ))]
+    SnapshotChecksumMismatch {
+        name: String,
+    },
+    #[snafu(display("Snapshot {} is not an ancestor of {}", base, name))]
+    NotAnAncestor {
+        name: String,
+        base: String,
+    },
+    #[snafu(display(
+        "Replica {} cannot be restored from snapshot {}: {}",
+        name,
+        snapshot,
+        reason
+    ))]
+    SnapshotRestoreRefused {
+        name: String,
+        snapshot: String,
+        reason: String,
+    },
+    #[snafu(display("Ancestry of lvol {} loops back to lvol {}", name, uuid))]
+    AncestryCycle {
+        name: String,
+        uuid: String,
+    },
+    #[snafu(display(
+        "Failed to restore replica {} from snapshot {}: {}",
+        name,
+        snapshot,
+        msg
+    ))]
+    SnapshotRestore {
+        name: String,
+        snapshot: String,
+        msg: String,
+    },
+    #[snafu(display("Failed to detach clone {}: {}", name, msg))]
+    CloneDetach {
+        source: BsError,
+        name: String,
+        msg: String,
+    },
+    #[snafu(display("Detach of clone {} was cancelled", name))]
+    CloneDetachCancelled {
+        name: String,
+    },
     #[snafu(display("Flush Failed for replica {}", name))]
     FlushFailed {
         name: String,
@@ -339,6 +479,15 @@ impl ToErrno for LvsError {
             Self::SnapshotCreate {
                 source, ..
             } => source.to_errno(),
+            Self::SnapshotRename {
+                source, ..
+            } => source.to_errno(),
+            Self::SnapshotHookFailed {
+                ..
+            } => Errno::ECANCELED,
+            Self::SnapshotTxnConflict {
+                ..
+            } => Errno::EEXIST,
             Self::FlushFailed {
                 ..
             } => Errno::EIO,
@@ -354,9 +503,48 @@ impl ToErrno for LvsError {
             Self::SnapshotCloneCreate {
                 source, ..
             } => source.to_errno(),
+            Self::CloneDetach {
+                source, ..
+            } => source.to_errno(),
+            Self::CloneDetachCancelled {
+                ..
+            } => Errno::ECANCELED,
+            Self::CloneSourceMissing {
+                ..
+            } => Errno::ENOENT,
+            Self::TreeNodeHeld {
+                ..
+            } => Errno::EBUSY,
+            Self::TreeDestroyIncomplete {
+                source, ..
+            } => source.to_errno(),
+            Self::CloneChainTooDeep {
+                ..
+            } => Errno::EMLINK,
+            Self::SnapshotLimitExceeded {
+                ..
+            } => Errno::EDQUOT,
+            Self::SnapshotChecksumMismatch {
+                ..
+            } => Errno::EBADMSG,
+            Self::NotAnAncestor {
+                ..
+            } => Errno::EINVAL,
+            Self::SnapshotRestoreRefused {
+                ..
+            } => Errno::EINVAL,
+            Self::SnapshotRestore {
+                ..
+            } => Errno::EIO,
+            Self::AncestryCycle {
+                ..
+            } => Errno::ELOOP,
             Self::CloneConfigFailed {
                 ..
             } => Errno::EINVAL,
+            Self::RestoreTargetTooSmall {
+                ..
+            } => Errno::ENOSPC,
             Self::WipeFailed {
                 ..
             } => Errno::EINVAL,
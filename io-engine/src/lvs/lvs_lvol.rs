@@ -12,6 +12,7 @@ use std::{
     os::raw::c_char,
     pin::Pin,
     ptr::NonNull,
+    sync::Arc,
 };
 
 use spdk_rs::libspdk::{
@@ -22,19 +23,27 @@ use spdk_rs::libspdk::{
     spdk_blob_get_xattr_value,
     spdk_blob_is_read_only,
     spdk_blob_is_thin_provisioned,
+    spdk_blob_remove_xattr,
     spdk_blob_set_xattr,
     spdk_blob_sync_md,
     spdk_bs_get_cluster_size,
     spdk_bs_get_parent_blob,
     spdk_bs_iter_next,
     spdk_lvol,
+    spdk_lvol_decouple_parent,
     vbdev_lvol_destroy,
     vbdev_lvol_get_from_bdev,
     vbdev_lvol_resize,
     LVS_CLEAR_WITH_UNMAP,
 };
 
-use super::{BsError, Lvs, LvsError};
+use super::{
+    clone_detach::CloneDetachGuard,
+    clone_prefetch::cancel_clone_prefetch,
+    BsError,
+    Lvs,
+    LvsError,
+};
 
 use crate::{
     bdev::PtplFileOps,
@@ -51,6 +60,7 @@ use crate::{
         SnapshotXattrs,
         ToErrno,
         UntypedBdev,
+        UntypedBdevHandle,
         UpdateProps,
     },
     eventing::Event,
@@ -180,6 +190,8 @@ impl Share for Lvol {
         mut self: Pin<&mut Self>,
         props: Option<NvmfShareProps>,
     ) -> Result<Self::Output, Self::Error> {
+        self.verify_clone_parent()?;
+
         let allowed_hosts = props
             .as_ref()
             .map(|s| s.allowed_hosts().clone())
@@ -383,13 +395,21 @@ impl Lvol {
 
     /// Common API to get the xattr from blob.
     pub fn get_blob_xattr(blob: *mut spdk_blob, attr: &str) -> Option<String> {
+        Self::get_blob_xattr_cstr(blob, &attr.into_cstring())
+    }
+
+    /// Gets the xattr from blob, given its name as a C string, e.g. as read
+    /// from the xattr names of the blob.
+    pub(crate) fn get_blob_xattr_cstr(
+        blob: *mut spdk_blob,
+        attribute: &CStr,
+    ) -> Option<String> {
         if blob.is_null() {
             return None;
         }
         let blob_inner = blob;
         let mut val: *const libc::c_char = std::ptr::null::<libc::c_char>();
         let mut size: u64 = 0;
-        let attribute = attr.into_cstring();
 
         unsafe {
             let r = spdk_blob_get_xattr_value(
@@ -439,7 +459,7 @@ impl Lvol {
             std::str::from_utf8(sl).map_or_else(
                 |error| {
                     warn!(
-                        attribute = attr,
+                        ?attribute,
                         ?error,
                         "Failed to parse attribute, default to empty string"
                     );
@@ -450,6 +470,44 @@ impl Lvol {
         }
     }
 
+    /// Estimate the additional pool space a snapshot of this lvol will consume
+    /// once `projected_write_bytes` have been written to the lvol after the
+    /// snapshot is taken.
+    ///
+    /// The model assumes writes are spread uniformly over the lvol's address
+    /// space. Once the snapshot is taken, every cluster that is allocated at
+    /// that point is owned by the snapshot, so the first write hitting such a
+    /// cluster allocates a new one for the lvol (copy-on-write). Writes which
+    /// hit unallocated clusters would allocate space anyway and are therefore
+    /// not accounted as snapshot overhead. Repeated writes to the same cluster
+    /// are not accounted more than once, so the estimate never exceeds the
+    /// currently allocated space of the lvol.
+    pub fn estimate_snapshot_growth(&self, projected_write_bytes: u64) -> u64 {
+        let usage = self.usage();
+        if usage.cluster_size == 0
+            || usage.num_clusters == 0
+            || projected_write_bytes == 0
+        {
+            return 0;
+        }
+
+        let clusters_written = std::cmp::min(
+            (projected_write_bytes + usage.cluster_size - 1)
+                / usage.cluster_size,
+            usage.num_clusters,
+        );
+        // Portion of the written clusters which are expected to hit clusters
+        // owned by the snapshot.
+        let cow_clusters = (clusters_written as u128
+            * usage.num_allocated_clusters as u128
+            + usage.num_clusters as u128
+            - 1)
+            / usage.num_clusters as u128;
+
+        std::cmp::min(cow_clusters as u64, usage.num_allocated_clusters)
+            * usage.cluster_size
+    }
+
     /// Low-level function to set blob attributes.
     pub async fn set_blob_attr<A: AsRef<str>>(
         &self,
@@ -487,6 +545,7 @@ impl Lvol {
                 name: self.name(),
             });
         }
+        Self::clone_xattr_changed(attr.as_ref());
 
         if !sync_metadata {
             return Ok(());
@@ -514,6 +573,60 @@ impl Lvol {
             }
         }
     }
+
+    /// Drops the index of the clones if the attribute it is built from has
+    /// changed, so that the clone is indexed under its current snapshot.
+    fn clone_xattr_changed(attr: &str) {
+        if attr == CloneXattrs::SourceUuid.name() {
+            Self::invalidate_clone_index();
+        }
+    }
+
+    /// Low-level function to remove a blob attribute, which is not an error
+    /// if the attribute is not set.
+    pub async fn remove_blob_attr<A: AsRef<str>>(
+        &self,
+        attr: A,
+        sync_metadata: bool,
+    ) -> Result<(), LvsError> {
+        let attr_name = attr.as_ref().into_cstring();
+        let r = unsafe {
+            spdk_blob_remove_xattr(self.blob_checked(), attr_name.as_ptr())
+        };
+        if r != 0 && r != -libc::ENOENT {
+            error!(
+                lvol = self.name(),
+                attr = attr.as_ref(),
+                errno = r,
+                "Failed to remove blob attribute"
+            );
+            return Err(LvsError::SetProperty {
+                source: BsError::from_i32(r),
+                prop: attr.as_ref().to_owned(),
+                name: self.name(),
+            });
+        }
+        Self::clone_xattr_changed(attr.as_ref());
+
+        if !sync_metadata {
+            return Ok(());
+        }
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            spdk_blob_sync_md(
+                self.blob_checked(),
+                Some(Self::blob_sync_cb),
+                cb_arg(s),
+            );
+        };
+        r.await.expect("sync callback is gone").to_result(|e| {
+            LvsError::SyncProperty {
+                source: BsError::from_i32(e),
+                name: self.name(),
+            }
+        })
+    }
 }
 
 pub struct LvolPtpl {
@@ -577,6 +690,11 @@ pub trait LvsLvol: LogicalVolume + Share {
     /// snapshot. if it is clone, return the snapshot lvol.
     fn is_snapshot_clone(&self) -> Option<Lvol>;
 
+    /// Check that the source snapshot of this lvol still exists, if this lvol
+    /// has been created as a clone. A clone whose source snapshot is gone
+    /// must not be exposed for I/O, as its unwritten blocks cannot be read.
+    fn verify_clone_parent(&self) -> Result<(), LvsError>;
+
     /// Get/Read a property of this lvol from the in-memory metadata copy.
     async fn get(&self, prop: PropName) -> Result<PropValue, LvsError>;
 
@@ -657,6 +775,17 @@ pub trait LvsLvol: LogicalVolume + Share {
     /// upon if required size is more or less than current size of
     /// the replica.
     async fn resize_replica(&mut self, resize_to: u64) -> Result<(), LvsError>;
+
+    /// Detach a clone from its source snapshot by copying all the clusters
+    /// it still shares with the snapshot into the clone. Once detached, the
+    /// lvol is no longer a clone and the snapshot can be deleted on its own.
+    /// The clusters are copied one at a time: the progress of the copy is
+    /// given by `clone_detach_progress()`, and `cancel_clone_detach()` stops
+    /// it, the clone then remaining dependent on its snapshot.
+    /// A clone which is claimed, e.g. by a nexus or a share, is refused, and
+    /// the clone is claimed for the duration of the copy, so that no write
+    /// can race with it.
+    async fn detach_clone(&self) -> Result<(), LvsError>;
 }
 
 ///  LogicalVolume implement Generic interface for Lvol.
@@ -833,6 +962,24 @@ impl LvsLvol for Lvol {
         None
     }
 
+    /// Check that the source snapshot of this lvol still exists, if this lvol
+    /// has been created as a clone.
+    fn verify_clone_parent(&self) -> Result<(), LvsError> {
+        let Some(source_uuid) = Lvol::get_blob_xattr(
+            self.blob_checked(),
+            CloneXattrs::SourceUuid.name(),
+        ) else {
+            return Ok(());
+        };
+        match self.is_snapshot_clone() {
+            Some(snapshot) if snapshot.is_snapshot() => Ok(()),
+            _ => Err(LvsError::CloneSourceMissing {
+                name: self.name(),
+                source_uuid,
+            }),
+        }
+    }
+
     /// Get/Read a property of this lvol from the in-memory metadata copy.
     async fn get(&self, prop: PropName) -> Result<PropValue, LvsError> {
         let blob = self.blob_checked();
@@ -905,33 +1052,39 @@ impl LvsLvol for Lvol {
             sender.send(errno).unwrap();
         }
         self.reset_snapshot_tree_usage_cache(!self.is_snapshot());
+        let source_snapshot = self.is_snapshot_clone();
         // We must always unshare before destroying bdev.
         let _ = Pin::new(&mut self).unshare().await;
 
         let name = self.name();
         let ptpl = self.ptpl();
+        cancel_clone_prefetch(&self.uuid());
 
         let (s, r) = pair::<i32>();
         unsafe {
             vbdev_lvol_destroy(self.as_inner_ptr(), Some(destroy_cb), cb_arg(s))
         };
 
-        r.await
-            .expect("lvol destroy callback is gone")
-            .to_result(|e| {
-                warn!("error while destroying lvol {name}");
-                LvsError::RepDestroy {
-                    source: BsError::from_i32(e),
-                    name: name.clone(),
-                    msg: "error while destroying lvol".into(),
-                }
-            })?;
+        let errno = r.await.expect("lvol destroy callback is gone");
+        Lvol::invalidate_clone_index();
+        errno.to_result(|e| {
+            warn!("error while destroying lvol {name}");
+            LvsError::RepDestroy {
+                source: BsError::from_i32(e),
+                name: name.clone(),
+                msg: "error while destroying lvol".into(),
+            }
+        })?;
         if let Err(error) = ptpl.destroy() {
             tracing::error!(
                 "{name}: Failed to clean up persistence through power loss for replica: {error}",
             );
         }
 
+        if let Some(snapshot) = source_snapshot {
+            snapshot.update_clone_refcount(false).await;
+        }
+
         info!("destroyed lvol {name}");
         event.generate();
         Ok(name)
@@ -1106,11 +1259,7 @@ impl LvsLvol for Lvol {
         // clone from the snapshot, destroy the snapshot
         // if it is already marked as discarded snapshot.
         if let Some(snapshot_lvol) = snapshot_lvol {
-            if snapshot_lvol.list_clones_by_snapshot_uuid().is_empty()
-                && snapshot_lvol.is_discarded_snapshot()
-            {
-                snapshot_lvol.destroy().await?;
-            }
+            snapshot_lvol.destroy_discarded_unreferenced().await?;
         }
         Ok(name)
     }
@@ -1151,6 +1300,211 @@ impl LvsLvol for Lvol {
             }
         }
     }
+
+    /// Detach a clone from its source snapshot by copying all the clusters
+    /// it still shares with the snapshot into the clone.
+    async fn detach_clone(&self) -> Result<(), LvsError> {
+        extern "C" fn decouple_cb(sender: *mut c_void, errno: i32) {
+            let sender =
+                unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
+            sender.send(errno).unwrap();
+        }
+
+        let name = self.name();
+        let Some(snapshot) = self.is_snapshot_clone() else {
+            return Err(LvsError::CloneDetach {
+                source: BsError::InvalidArgument {},
+                name,
+                msg: "lvol is not a snapshot clone".into(),
+            });
+        };
+
+        // The clusters not allocated by the clone are read from the first
+        // blob of its parent chain which allocates them, which is not
+        // necessarily the source snapshot itself: all of them are copied.
+        let own = self.blob_allocated_clusters(self.blob_checked());
+        let mut inherited = vec![false; own.len()];
+        let mut parents = 0;
+        let mut parent = unsafe { self.bs_iter_parent(self.blob_checked()) };
+        while let Some(blob) = parent {
+            for (cluster, allocated) in
+                self.blob_allocated_clusters(blob).into_iter().enumerate()
+            {
+                if let Some(inherited) = inherited.get_mut(cluster) {
+                    *inherited |= allocated;
+                }
+            }
+            parents += 1;
+            parent = unsafe { self.bs_iter_parent(blob) };
+        }
+        let shared = inherited
+            .into_iter()
+            .zip(own)
+            .enumerate()
+            .filter(|(_, (inherited, own))| *inherited && !*own)
+            .map(|(cluster, _)| cluster as u64)
+            .collect::<Vec<_>>();
+
+        let Some(detach) =
+            CloneDetachGuard::new(&self.uuid(), shared.len() as u64)
+        else {
+            return Err(LvsError::CloneDetach {
+                source: BsError::VolBusy {},
+                name,
+                msg: "clone is already being detached".into(),
+            });
+        };
+
+        let failed = |msg: String| LvsError::CloneDetach {
+            source: BsError::Generic {
+                source: Errno::EIO,
+            },
+            name: name.clone(),
+            msg,
+        };
+        let busy = || LvsError::CloneDetach {
+            source: BsError::VolBusy {},
+            name: name.clone(),
+            msg: "clone is in use".into(),
+        };
+
+        // Claim the clone for the duration of the copy: a clone in use is
+        // refused, and no writer can open it meanwhile, whose writes could
+        // otherwise be overwritten by the copy.
+        let bdev = self.as_bdev();
+        if bdev.is_claimed() {
+            return Err(busy());
+        }
+        let desc =
+            Arc::new(bdev.open(true).map_err(|e| failed(e.to_string()))?);
+        if !desc.claim() {
+            return Err(busy());
+        }
+
+        info!(
+            clone = name,
+            snapshot = snapshot.name(),
+            clusters = shared.len(),
+            "Detaching clone from its snapshot"
+        );
+        self.reset_snapshot_tree_usage_cache(true);
+
+        // Read the shared clusters through the clone and write them back, so
+        // that the clone allocates its own copy of them.
+        let copy = async {
+            let hdl = UntypedBdevHandle::try_from(desc.clone())
+                .map_err(|e| failed(e.to_string()))?;
+            let cluster_size = self.usage().cluster_size;
+            let size = self.size();
+            for cluster in shared {
+                if detach.is_cancelled() {
+                    warn!(
+                        clone = name,
+                        snapshot = snapshot.name(),
+                        "Detach of clone cancelled"
+                    );
+                    return Err(LvsError::CloneDetachCancelled {
+                        name: name.clone(),
+                    });
+                }
+                let offset = cluster * cluster_size;
+                if offset >= size {
+                    break;
+                }
+                let len = cluster_size.min(size - offset);
+                let mut buf =
+                    hdl.dma_malloc(len).map_err(|e| failed(e.to_string()))?;
+                hdl.read_at(offset, &mut buf)
+                    .await
+                    .map_err(|e| failed(e.to_string()))?;
+                hdl.write_at(offset, &buf)
+                    .await
+                    .map_err(|e| failed(e.to_string()))?;
+                detach.cluster_copied();
+            }
+            Ok(())
+        };
+        let copied = copy.await;
+        desc.unclaim();
+        drop(desc);
+        copied?;
+
+        // Nothing is left to copy: this only removes the dependency on each
+        // of the parents of the clone in turn.
+        for _ in 0 .. parents {
+            if unsafe { self.bs_iter_parent(self.blob_checked()) }.is_none() {
+                break;
+            }
+            let (s, r) = pair::<i32>();
+            unsafe {
+                spdk_lvol_decouple_parent(
+                    self.as_inner_ptr(),
+                    Some(decouple_cb),
+                    cb_arg(s),
+                )
+            };
+
+            r.await.expect("lvol decouple callback is gone").to_result(
+                |e| LvsError::CloneDetach {
+                    source: BsError::from_i32(e),
+                    name: name.clone(),
+                    msg: "failed to decouple the clone from the snapshot"
+                        .into(),
+                },
+            )?;
+        }
+
+        // The data no longer depends on the snapshot: drop the clone
+        // attributes so the lvol is not reported as a clone anymore.
+        Lvol::invalidate_clone_index();
+        for attr in [CloneXattrs::SourceUuid, CloneXattrs::CloneCreateTime] {
+            let attr_name = attr.name().into_cstring();
+            let r = unsafe {
+                spdk_blob_remove_xattr(self.blob_checked(), attr_name.as_ptr())
+            };
+            if r != 0 && r != -libc::ENOENT {
+                return Err(LvsError::CloneDetach {
+                    source: BsError::from_i32(r),
+                    name,
+                    msg: format!("failed to remove attribute {}", attr.name()),
+                });
+            }
+        }
+
+        let (s, r) = pair::<i32>();
+        unsafe {
+            spdk_blob_sync_md(
+                self.blob_checked(),
+                Some(Self::blob_sync_cb),
+                cb_arg(s),
+            );
+        };
+        r.await.expect("sync callback is gone").to_result(|e| {
+            LvsError::SyncProperty {
+                source: BsError::from_i32(e),
+                name: name.clone(),
+            }
+        })?;
+
+        snapshot.update_clone_refcount(false).await;
+
+        info!(
+            clone = name,
+            snapshot = snapshot.name(),
+            "Detached clone from its snapshot"
+        );
+
+        // The clone no longer holds its snapshot: a discarded snapshot left
+        // without clones is reclaimed right away.
+        if let Err(error) = snapshot.destroy_discarded_unreferenced().await {
+            warn!(
+                snapshot = snapshot.name(),
+                %error,
+                "Failed to destroy discarded snapshot"
+            );
+        }
+        Ok(())
+    }
 }
 
 extern "C" fn lvol_resize_cb(cb_arg: *mut c_void, errno: i32) {
@@ -306,6 +306,8 @@ impl Lvs {
                 name: name.into(),
                 reason: ImportErrorReason::None,
             })?;
+        // The lvols of the pool may be clones.
+        Lvol::invalidate_clone_index();
 
         if name != lvs.name() {
             warn!(
@@ -385,6 +387,29 @@ impl Lvs {
         // Try to destroy the pending snapshots without catching
         // the error.
         Lvol::destroy_pending_discarded_snapshot().await;
+        Lvol::reconcile_clone_refcounts(&pool).await;
+        // Duplicate snapshot uuids break the uuid based traversal of the
+        // snapshot trees, report them loudly.
+        for (snap_uuid, lvols) in Lvol::find_duplicate_snapshot_uuids() {
+            tracing::error!(
+                pool = args.name,
+                snapshot_uuid = snap_uuid,
+                ?lvols,
+                "Snapshot uuid is claimed by multiple lvols"
+            );
+        }
+        // A replica whose restore did not complete holds a mix of its own
+        // data and of the snapshot data.
+        for lvol in pool.lvols().into_iter().flatten() {
+            if let Some(snapshot_uuid) = lvol.restore_in_progress() {
+                tracing::error!(
+                    pool = args.name,
+                    replica = lvol.name(),
+                    snapshot_uuid,
+                    "Replica restore from snapshot did not complete"
+                );
+            }
+        }
         // if the uuid is provided for the import request check
         // for the pool uuid to make sure it is the correct one
         if let Some(uuid) = args.uuid {
@@ -603,12 +628,12 @@ impl Lvs {
             )
         };
 
-        r.await
-            .expect("callback gone while exporting lvs")
-            .to_result(|e| LvsError::Export {
-                source: BsError::from_i32(e),
-                name: pool.clone(),
-            })?;
+        let errno = r.await.expect("callback gone while exporting lvs");
+        Lvol::invalidate_clone_index();
+        errno.to_result(|e| LvsError::Export {
+            source: BsError::from_i32(e),
+            name: pool.clone(),
+        })?;
 
         info!("{}: lvs exported successfully", self_str);
 
@@ -699,12 +724,12 @@ impl Lvs {
             )
         };
 
-        r.await
-            .expect("callback gone while destroying lvs")
-            .to_result(|e| LvsError::Export {
-                source: BsError::from_i32(e),
-                name: pool.clone(),
-            })?;
+        let errno = r.await.expect("callback gone while destroying lvs");
+        Lvol::invalidate_clone_index();
+        errno.to_result(|e| LvsError::Export {
+            source: BsError::from_i32(e),
+            name: pool.clone(),
+        })?;
 
         info!("{}: lvs destroyed successfully", self_str);
 
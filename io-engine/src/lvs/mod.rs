@@ -19,6 +19,16 @@ use crate::{
         ReplicaOps,
     },
 };
+pub use clone_detach::{
+    cancel_clone_detach,
+    clone_detach_progress,
+    CloneDetachProgress,
+};
+pub use clone_prefetch::{
+    clone_prefetch_progress,
+    ClonePrefetchProgress,
+    ClonePrefetchState,
+};
 pub use lvol_snapshot::LvolSnapshotIter;
 pub use lvs_bdev::LvsBdev;
 pub use lvs_error::{BsError, ImportErrorReason, LvsError};
@@ -27,6 +37,8 @@ pub use lvs_lvol::{Lvol, LvsLvol, PropName, PropValue};
 pub use lvs_store::Lvs;
 use std::{convert::TryFrom, pin::Pin};
 
+mod clone_detach;
+mod clone_prefetch;
 mod lvol_snapshot;
 mod lvs_bdev;
 mod lvs_error;
@@ -17,7 +17,12 @@ pub use nexus_rebuild::{NexusRebuildJob, NexusRebuildJobStarter};
 use rebuild_descriptor::RebuildDescriptor;
 pub(crate) use rebuild_error::{RebuildError, SnapshotRebuildError};
 use rebuild_job::RebuildOperation;
-pub use rebuild_job::{RebuildJob, RebuildJobOptions, RebuildVerifyMode};
+pub use rebuild_job::{
+    RebuildJob,
+    RebuildJobOptions,
+    RebuildProgressListener,
+    RebuildVerifyMode,
+};
 use rebuild_job_backend::{
     RebuildFBendChan,
     RebuildJobBackendManager,
@@ -27,7 +32,7 @@ pub use rebuild_map::RebuildMap;
 pub use rebuild_state::RebuildState;
 use rebuild_state::RebuildStates;
 pub(crate) use rebuild_stats::HistoryRecord;
-pub use rebuild_stats::RebuildStats;
+pub use rebuild_stats::{RebuildProgress, RebuildStats, RebuildVerifyOutcome};
 use rebuild_task::{RebuildTasks, TaskResult};
 pub use snapshot_rebuild::SnapshotRebuildJob;
 
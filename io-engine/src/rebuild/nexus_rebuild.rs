@@ -4,6 +4,7 @@ use spdk_rs::LbaRange;
 use std::ops::{Deref, Range};
 
 use crate::{
+    bdev::nexus::nexus_lookup,
     core::{DescriptorGuard, UntypedBdev},
     gen_rebuild_instances,
     rebuild::{
@@ -19,6 +20,7 @@ use crate::{
         RebuildMap,
         RebuildState,
     },
+    sleep::mayastor_sleep,
 };
 
 use super::{
@@ -163,6 +165,29 @@ impl Deref for NexusRebuildDescriptor {
     }
 }
 
+impl NexusRebuildDescriptor {
+    /// Waits for the copy of the given number of blocks to be admitted by the
+    /// throttles of the source and destination children, if they are
+    /// throttled. The copy is a read of the source and a write of the
+    /// destination, so it waits for the slower of both.
+    async fn throttle_copy(&self, num_blocks: u64) {
+        let len = num_blocks * self.block_size;
+        let delay = nexus_lookup(&self.nexus_name)
+            .and_then(|n| {
+                [&self.src_uri, &self.dst_uri]
+                    .into_iter()
+                    .filter_map(|uri| n.child(uri).ok())
+                    .map(|c| c.throttle_delay(len))
+                    .max()
+            })
+            .unwrap_or_default();
+
+        if !delay.is_zero() {
+            mayastor_sleep(delay).await.ok();
+        }
+    }
+}
+
 /// A nexus-specific rebuild job which is responsible for rebuilding
 /// the common `RebuildDescriptor` with the addition of the nexus guard
 /// as a means of locking the range which is being rebuilt ensuring
@@ -348,6 +373,10 @@ impl RebuildTaskCopier for NexusRebuildDescriptor {
         // partition.
         let r = LbaRange::new(blk - self.range.start, len);
 
+        // Throttle the copy before locking the range, so that the front end
+        // I/Os to the range are not held meanwhile.
+        self.throttle_copy(len).await;
+
         // Wait for LBA range to be locked.
         // This prevents other I/Os being issued to this LBA range whilst it is
         // being rebuilt.
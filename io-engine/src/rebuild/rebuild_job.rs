@@ -8,6 +8,7 @@ use super::{
     RebuildError,
     RebuildJobBackendManager,
     RebuildJobRequest,
+    RebuildProgress,
     RebuildState,
     RebuildStates,
     RebuildStats,
@@ -46,6 +47,31 @@ impl RebuildJobOptions {
     }
 }
 
+/// Listener of the progress of a rebuild job, see
+/// `RebuildJob::add_progress_listener`. The listener returns false once it is
+/// no longer interested in the progress, which unregisters it.
+pub struct RebuildProgressListener(
+    Box<dyn Fn(&RebuildProgress) -> bool + Send>,
+);
+
+impl RebuildProgressListener {
+    /// Creates a new progress listener calling the given function.
+    pub fn new(f: impl Fn(&RebuildProgress) -> bool + Send + 'static) -> Self {
+        Self(Box::new(f))
+    }
+
+    /// Calls the listener with the given progress update.
+    pub(super) fn call(&self, progress: &RebuildProgress) -> bool {
+        (self.0)(progress)
+    }
+}
+
+impl std::fmt::Debug for RebuildProgressListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("RebuildProgressListener")
+    }
+}
+
 /// Operations used to control the state of the job.
 #[derive(Debug)]
 pub(super) enum RebuildOperation {
@@ -91,6 +117,8 @@ pub struct RebuildJob {
     notify_chan: crossbeam::channel::Receiver<RebuildState>,
     /// Channel used to Notify when rebuild completes.
     complete_chan: Weak<parking_lot::Mutex<Vec<oneshot::Sender<RebuildState>>>>,
+    /// Listeners of the rebuild progress.
+    progress_listeners: Weak<parking_lot::Mutex<Vec<RebuildProgressListener>>>,
 }
 
 impl RebuildJob {
@@ -110,6 +138,7 @@ impl RebuildJob {
             comms: RebuildFBendChan::from(&manager.info_chan),
             complete_chan: Arc::downgrade(&manager.complete_chan),
             notify_chan: manager.notify_chan.1.clone(),
+            progress_listeners: Arc::downgrade(&manager.progress_listeners),
         };
 
         // Kick off the rebuild task where it will "live" and await for
@@ -132,6 +161,7 @@ impl RebuildJob {
             comms: RebuildFBendChan::from(&manager.info_chan),
             complete_chan: Arc::downgrade(&manager.complete_chan),
             notify_chan: manager.notify_chan.1.clone(),
+            progress_listeners: Arc::downgrade(&manager.progress_listeners),
         }
     }
 
@@ -221,6 +251,7 @@ impl RebuildJob {
             final_stats,
             state: self.state(),
             end_time: Utc::now(),
+            verify_outcome: None,
         })
     }
 
@@ -247,6 +278,16 @@ impl RebuildJob {
         self.notify_chan.clone()
     }
 
+    /// Registers a listener of the rebuild progress. The listener is called
+    /// from the reactor running the job whenever the progress (in %) or the
+    /// state of the job changes, until the job ends. It is dropped right away
+    /// if the job has already ended.
+    pub fn add_progress_listener(&self, listener: RebuildProgressListener) {
+        if let Some(listeners) = self.progress_listeners.upgrade() {
+            listeners.lock().push(listener);
+        }
+    }
+
     /// Get the uri of the rebuild source.
     pub fn src_uri(&self) -> &str {
         &self.src_uri
@@ -12,6 +12,8 @@ use futures::{channel::oneshot, FutureExt, StreamExt};
 use super::{
     RebuildDescriptor,
     RebuildError,
+    RebuildProgress,
+    RebuildProgressListener,
     RebuildState,
     RebuildStates,
     RebuildStats,
@@ -103,6 +105,11 @@ pub(super) struct RebuildJobManager {
         Arc<parking_lot::Mutex<Vec<oneshot::Sender<RebuildState>>>>,
     /// Channel to share information between frontend and backend.
     pub(super) info_chan: RebuildFBendChan,
+    /// Listeners of the rebuild progress.
+    pub(super) progress_listeners:
+        Arc<parking_lot::Mutex<Vec<RebuildProgressListener>>>,
+    /// Last progress in % published to the listeners.
+    last_progress: Option<u64>,
     /// Job serial number.
     serial: u64,
 }
@@ -164,6 +171,8 @@ impl RebuildJobManager {
             states: Default::default(),
             complete_chan: Default::default(),
             info_chan: RebuildFBendChan::new(),
+            progress_listeners: Default::default(),
+            last_progress: None,
             serial,
         }
     }
@@ -251,6 +260,9 @@ impl RebuildJobBackendManager {
     }
     /// Calls the job's registered notify fn callback and notify sender channel
     fn on_state_change(&mut self) {
+        // Publish the new state before the backend processes it, so that the
+        // listeners see it ahead of anything the backend does on completion.
+        self.publish_progress(true);
         self.backend.on_state_change();
 
         if let Err(e) = self.notify_chan.0.send(self.state()) {
@@ -336,6 +348,24 @@ impl RebuildJobBackendManager {
         }
     }
 
+    /// Publishes the progress of the job to its listeners, unless the progress
+    /// in % has not changed since the last update and `force` is not set.
+    /// Listeners which are no longer interested are dropped.
+    fn publish_progress(&mut self, force: bool) {
+        if self.progress_listeners.lock().is_empty() {
+            return;
+        }
+
+        let stats = self.stats();
+        if !force && self.last_progress == Some(stats.progress) {
+            return;
+        }
+        self.last_progress = Some(stats.progress);
+
+        let update = RebuildProgress::new(self.state(), &stats);
+        self.progress_listeners.lock().retain(|l| l.call(&update));
+    }
+
     /// Reply back to the requester with the generic rebuild stats.
     async fn reply_stats(
         &mut self,
@@ -436,6 +466,7 @@ impl RebuildJobBackendManager {
                         let state = self.states.read().clone();
                         match state.pending {
                             None | Some(RebuildState::Running) => {
+                                self.publish_progress(false);
                                 self.start_task_by_id(r.id);
                             }
                             _ => {
@@ -1,7 +1,7 @@
 use bit_vec::BitVec;
 use std::fmt::{Debug, Formatter};
 
-use crate::core::SegmentMap;
+use crate::core::{ClusterRange, SegmentMap};
 
 /// Map of segments to be rebuilt.
 pub struct RebuildMap {
@@ -49,6 +49,16 @@ impl RebuildMap {
         }
     }
 
+    /// Marks the given range of logical blocks as dirty.
+    ///
+    /// # Arguments
+    ///
+    /// * `lbn`: First logical block number.
+    /// * `lbn_cnt`: Number of logical blocks.
+    pub(crate) fn blks_dirty(&mut self, lbn: u64, lbn_cnt: u64) {
+        self.segments.set(lbn, lbn_cnt, true);
+    }
+
     /// Marks the given logical block as clean (e.g. already transferred).
     ///
     /// # Arguments
@@ -62,6 +72,11 @@ impl RebuildMap {
     pub(crate) fn count_dirty_blks(&self) -> u64 {
         self.segments.count_dirty_blks()
     }
+
+    /// Returns the ranges of the device to be transferred, in bytes.
+    pub(crate) fn dirty_ranges(&self) -> Vec<ClusterRange> {
+        self.segments.dirty_ranges()
+    }
 }
 
 impl From<RebuildMap> for BitVec {
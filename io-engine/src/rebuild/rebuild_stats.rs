@@ -1,6 +1,6 @@
 use super::RebuildState;
 use chrono::{DateTime, Utc};
-use std::ops::Deref;
+use std::{ops::Deref, time::Duration};
 
 /// Rebuild statistics.
 #[derive(Debug, Clone)]
@@ -50,6 +50,49 @@ impl Default for RebuildStats {
     }
 }
 
+/// Progress update of a rebuild job, published to the listeners of the job
+/// as the rebuild advances and whenever its state changes.
+#[derive(Debug, Clone)]
+pub struct RebuildProgress {
+    /// State of the rebuild job.
+    pub state: RebuildState,
+    /// Rebuild progress in %.
+    pub progress: u64,
+    /// Number of bytes recovered.
+    pub bytes_recovered: u64,
+    /// Total number of bytes to recover.
+    pub bytes_total: u64,
+    /// Estimated time until the rebuild completes, extrapolated from the
+    /// rate of recovery so far. Not known until some data is recovered.
+    pub eta: Option<Duration>,
+}
+
+impl RebuildProgress {
+    /// Creates a progress update from the current statistics of a job.
+    pub(super) fn new(state: RebuildState, stats: &RebuildStats) -> Self {
+        let elapsed = (Utc::now() - stats.start_time).to_std().ok();
+        let blocks_left = stats.blocks_total - stats.blocks_recovered;
+
+        let eta = match elapsed {
+            _ if state.done() => None,
+            Some(elapsed) if stats.blocks_recovered > 0 => {
+                Some(elapsed.mul_f64(
+                    blocks_left as f64 / stats.blocks_recovered as f64,
+                ))
+            }
+            _ => None,
+        };
+
+        Self {
+            state,
+            progress: stats.progress,
+            bytes_recovered: stats.blocks_recovered * stats.block_size,
+            bytes_total: stats.blocks_total * stats.block_size,
+            eta,
+        }
+    }
+}
+
 /// A rebuild record is a lightweight extract of rebuild job that is maintained
 /// for the statistics.
 #[derive(Debug, Clone)]
@@ -65,6 +108,25 @@ pub struct HistoryRecord {
     pub state: RebuildState,
     /// End time of this rebuild.
     pub end_time: DateTime<Utc>,
+    /// Outcome of the verification of the rebuilt child, if it was verified.
+    pub verify_outcome: Option<RebuildVerifyOutcome>,
+}
+
+/// Outcome of the verification of a rebuilt child against its rebuild source.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RebuildVerifyOutcome {
+    /// The data of the rebuilt child matches the source.
+    Passed {
+        /// Number of blocks compared.
+        blocks_verified: u64,
+    },
+    /// The data of the rebuilt child differs from the source.
+    Mismatch {
+        /// Offset of the first mismatching segment.
+        offset_blk: u64,
+    },
+    /// The verification could not be carried out.
+    Failed { reason: String },
 }
 
 impl Deref for HistoryRecord {
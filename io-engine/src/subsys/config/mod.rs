@@ -24,12 +24,14 @@ use crate::{
     jsonrpc::{jsonrpc_register, Code, RpcErrorCode},
     subsys::config::opts::{
         BdevOpts,
+        ErrStoreOpts,
         GetOpts,
         IoBufOpts,
         NexusOpts,
         NvmeBdevOpts,
         NvmfTgtConfig,
         PosixSocketOpts,
+        SnapshotOpts,
     },
 };
 
@@ -133,6 +135,10 @@ pub struct Config {
     pub socket_opts: PosixSocketOpts,
     /// iobuf specific options
     pub iobuf_opts: IoBufOpts,
+    /// snapshot and clone specific options
+    pub snapshot_opts: SnapshotOpts,
+    /// nexus child error store specific options
+    pub err_store_opts: ErrStoreOpts,
 }
 
 impl Config {
@@ -196,6 +202,8 @@ impl Config {
             nexus_opts: self.nexus_opts.get(),
             socket_opts: self.socket_opts.get(),
             iobuf_opts: self.iobuf_opts.get(),
+            snapshot_opts: self.snapshot_opts.get(),
+            err_store_opts: self.err_store_opts.get(),
         }
     }
 
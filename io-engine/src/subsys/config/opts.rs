@@ -4,6 +4,7 @@
 //! from trait, and we are not allowed to skip or use different types.
 
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 
 use spdk_rs::{
     ffihelper::copy_str_with_null,
@@ -52,6 +53,61 @@ pub struct NexusOpts {
     /// NOTE: we do not (yet) differentiate between
     /// the nexus and replica nvmf target
     pub nvmf_replica_port: u16,
+    /// maximum number of concurrently active nexus rebuilds on this node,
+    /// any further rebuilds are queued until a slot frees up (0: no limit,
+    /// the default)
+    pub max_concurrent_rebuilds: usize,
+    /// maximum size in bytes of a single I/O submitted to a nexus child by
+    /// the nexus itself, e.g. by rebuilds (0: no limit, the default)
+    pub max_child_io_size: u64,
+    /// number of times the opening of a nexus child device is retried
+    /// before the child is faulted (0: no retry)
+    pub child_open_retries: u32,
+    /// delay in milliseconds before the first retry to open a nexus child
+    /// device, doubled on every further retry
+    pub child_open_retry_delay_ms: u64,
+    /// timeout in milliseconds of a single I/O submitted to a nexus child by
+    /// the nexus itself, e.g. by rebuilds (0: no timeout)
+    pub child_io_timeout_ms: u64,
+    /// fault a nexus child with an I/O error when one of its I/Os times out
+    pub child_io_timeout_fault: bool,
+    /// number of bytes a nexus child may be smaller than the nexus: the
+    /// missing tail is presented as a padded region which reads as zeroes and
+    /// rejects writes (0: children must be at least as large as the nexus)
+    pub child_size_tolerance: u64,
+    /// maximum number of bytes per second transferred by the nexus itself,
+    /// e.g. by rebuilds, to a throttled nexus child (0: no limit)
+    pub child_throttle_bytes_per_sec: u64,
+    /// maximum number of I/Os per second submitted by the nexus itself to a
+    /// throttled nexus child (0: no limit)
+    pub child_throttle_iops: u64,
+    /// verification of a rebuilt nexus child against its rebuild source,
+    /// once the rebuild has completed
+    pub rebuild_post_verify: PostRebuildVerify,
+}
+
+/// Verification of a rebuilt nexus child against its rebuild source, carried
+/// out once the rebuild has completed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    Display,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum PostRebuildVerify {
+    /// The rebuilt child is not verified.
+    None,
+    /// Evenly spaced segments of the rebuilt child are verified.
+    Sampled,
+    /// The whole rebuilt child is verified.
+    Full,
 }
 
 /// Default nvmf port used for replicas.
@@ -67,6 +123,31 @@ impl Default for NexusOpts {
             nvmf_discovery_enable: true,
             nvmf_nexus_port: NVMF_PORT_NEXUS,
             nvmf_replica_port: NVMF_PORT_REPLICA,
+            max_concurrent_rebuilds: try_from_env(
+                "NEXUS_MAX_CONCURRENT_REBUILDS",
+                0,
+            ),
+            max_child_io_size: try_from_env("NEXUS_MAX_CHILD_IO_SIZE", 0),
+            child_open_retries: try_from_env("NEXUS_CHILD_OPEN_RETRIES", 0),
+            child_open_retry_delay_ms: try_from_env(
+                "NEXUS_CHILD_OPEN_RETRY_DELAY_MS",
+                100,
+            ),
+            child_io_timeout_ms: try_from_env("NEXUS_CHILD_IO_TIMEOUT_MS", 0),
+            child_io_timeout_fault: try_from_env(
+                "NEXUS_CHILD_IO_TIMEOUT_FAULT",
+                true,
+            ),
+            child_size_tolerance: try_from_env("NEXUS_CHILD_SIZE_TOLERANCE", 0),
+            child_throttle_bytes_per_sec: try_from_env(
+                "NEXUS_CHILD_THROTTLE_BPS",
+                0,
+            ),
+            child_throttle_iops: try_from_env("NEXUS_CHILD_THROTTLE_IOPS", 0),
+            rebuild_post_verify: try_from_env(
+                "NEXUS_REBUILD_POST_VERIFY",
+                PostRebuildVerify::None,
+            ),
         }
     }
 }
@@ -77,6 +158,81 @@ impl GetOpts for NexusOpts {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrStoreOpts {
+    /// keep a history of the failed I/Os of every nexus child
+    pub enabled: bool,
+    /// number of failed I/Os of a nexus child within the retention window
+    /// at which the child is faulted (0: never fault)
+    pub max_errors: usize,
+    /// length in milliseconds of the window over which the failed I/Os of a
+    /// nexus child are counted
+    pub retention_ms: u64,
+}
+
+impl Default for ErrStoreOpts {
+    fn default() -> Self {
+        Self {
+            enabled: try_from_env("NEXUS_ERR_STORE_ENABLED", true),
+            max_errors: try_from_env("NEXUS_ERR_STORE_MAX_ERRORS", 0),
+            retention_ms: try_from_env("NEXUS_ERR_STORE_RETENTION_MS", 10000),
+        }
+    }
+}
+
+impl GetOpts for ErrStoreOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotOpts {
+    /// maximum number of clones on the chain from a replica down to any of
+    /// its snapshots or clones, i.e. how many times a snapshot of a clone
+    /// can be cloned again (0: no limit, the default)
+    pub max_clone_chain_depth: u32,
+    /// maximum number of discarded snapshots destroyed concurrently, e.g.
+    /// when garbage collecting them on pool import
+    pub discard_concurrency: usize,
+    /// maximum number of snapshots of a single replica (0: no limit)
+    pub max_snapshots_per_volume: u32,
+    /// whether the discarded snapshots, which are only retained for their
+    /// clones, are left out of the snapshot count of a replica
+    pub limit_excludes_discarded: bool,
+}
+
+impl Default for SnapshotOpts {
+    fn default() -> Self {
+        Self {
+            max_clone_chain_depth: try_from_env(
+                "SNAPSHOT_MAX_CLONE_CHAIN_DEPTH",
+                0,
+            ),
+            discard_concurrency: try_from_env(
+                "SNAPSHOT_DISCARD_CONCURRENCY",
+                8,
+            ),
+            max_snapshots_per_volume: try_from_env(
+                "SNAPSHOT_MAX_PER_VOLUME",
+                0,
+            ),
+            limit_excludes_discarded: try_from_env(
+                "SNAPSHOT_LIMIT_EXCLUDES_DISCARDED",
+                false,
+            ),
+        }
+    }
+}
+
+impl GetOpts for SnapshotOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+}
+
 /// Length of target Command Retry Delay configuration array.
 /// Must be equal to the size of `spdk_nvmf_target_opts.crdt`.
 pub const TARGET_CRDT_LEN: usize = 3;
@@ -153,7 +309,7 @@ pub struct NvmfTcpTransportOpts {
 }
 
 /// try to read an env variable or returns the default when not found
-fn try_from_env<T>(name: &str, default: T) -> T
+pub(crate) fn try_from_env<T>(name: &str, default: T) -> T
 where
     T: FromStr + Display + Copy,
     <T as FromStr>::Err: Debug + Display,
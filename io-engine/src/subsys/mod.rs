@@ -2,7 +2,13 @@
 //! Main file to register additional subsystems
 
 pub use config::{
-    opts::{NexusOpts, NvmeBdevOpts},
+    opts::{
+        ErrStoreOpts,
+        NexusOpts,
+        NvmeBdevOpts,
+        PostRebuildVerify,
+        SnapshotOpts,
+    },
     pool::PoolConfig,
     Config,
     ConfigSubsystem,
@@ -1,5 +1,5 @@
 use io_engine::{
-    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildError, Error},
     core::{MayastorCliArgs, Protocol},
 };
 
@@ -13,17 +13,25 @@ static BDEVNAME1: &str = "aio:///tmp/disk1.img?blk_size=512";
 static DISKNAME2: &str = "/tmp/disk2.img";
 static BDEVNAME2: &str = "aio:///tmp/disk2.img?blk_size=512";
 
+static DISKNAME3: &str = "/tmp/disk3.img";
+static BDEVNAME3: &str = "aio:///tmp/disk3.img?blk_size=4096";
+
 pub mod common;
 use common::MayastorTest;
 
 fn test_start() {
-    common::delete_file(&[DISKNAME1.into(), DISKNAME2.into()]);
+    common::delete_file(&[
+        DISKNAME1.into(),
+        DISKNAME2.into(),
+        DISKNAME3.into(),
+    ]);
     common::truncate_file(DISKNAME1, FILE_SIZE);
     common::truncate_file(DISKNAME2, FILE_SIZE);
+    common::truncate_file(DISKNAME3, FILE_SIZE);
 }
 
 fn test_finish() {
-    let disks = [DISKNAME1.into(), DISKNAME2.into()];
+    let disks = [DISKNAME1.into(), DISKNAME2.into(), DISKNAME3.into()];
     common::delete_file(&disks);
 }
 
@@ -104,6 +112,24 @@ async fn add_child() {
     })
     .await;
 
+    // Test adding a child with a mismatched block size
+    ms.spawn(async {
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let res = nexus.as_mut().add_child(BDEVNAME3, false).await;
+        assert!(matches!(
+            res,
+            Err(Error::OpenChild {
+                source: ChildError::BlockSizeMismatch {
+                    child: 4096,
+                    parent: 512,
+                },
+                ..
+            })
+        ));
+        assert_eq!(nexus.child_count(), 1);
+    })
+    .await;
+
     // Unshare nexus
     ms.spawn(async {
         let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
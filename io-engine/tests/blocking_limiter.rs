@@ -0,0 +1,46 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use futures::future::join_all;
+use io_engine::core::{
+    runtime::{run_blocking_limited, BlockingLimiter},
+    MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn blocking_limiter_bounds_concurrency() {
+    let _ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let limiter = BlockingLimiter::new("test", 2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let results = join_all((0 .. 6).map(|i| {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        run_blocking_limited(&limiter, move || {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            running.fetch_sub(1, Ordering::SeqCst);
+            i
+        })
+    }))
+    .await;
+
+    let results = results
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Blocking tasks failed");
+    assert_eq!(results, (0 .. 6).collect::<Vec<_>>());
+    assert!(max_running.load(Ordering::SeqCst) <= 2);
+    assert_eq!(limiter.available(), 2);
+}
@@ -0,0 +1,58 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, Error, FaultReason},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "CanRemoveNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=20";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=20";
+static CHILD_3: &str = "malloc:///malloc2?blk_size=512&size_mb=20";
+
+#[tokio::test]
+async fn nexus_child_can_remove() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // An out-of-sync child, left without a rebuild, does not hold a copy of
+        // the data.
+        nexus.as_mut().add_child(CHILD_3, true).await.unwrap();
+        nexus.check_child_remove_operation(CHILD_1).unwrap();
+        nexus.check_child_remove_operation(CHILD_3).unwrap();
+
+        nexus
+            .as_mut()
+            .fault_child(CHILD_2, FaultReason::OfflinePermanent)
+            .await
+            .unwrap();
+        nexus.check_child_remove_operation(CHILD_2).unwrap();
+        assert!(matches!(
+            nexus.check_child_remove_operation(CHILD_1),
+            Err(Error::RemoveLastHealthyChild { .. })
+        ));
+        nexus.check_child_remove_operation(CHILD_3).unwrap();
+
+        // The last child can never be removed, healthy or not.
+        nexus.as_mut().remove_child(CHILD_3).await.unwrap();
+        nexus.as_mut().remove_child(CHILD_2).await.unwrap();
+        assert!(matches!(
+            nexus.check_child_remove_operation(CHILD_1),
+            Err(Error::RemoveLastChild { .. })
+        ));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,55 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "CompareNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_compare() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child1 = nexus.child(CHILD_1).unwrap();
+        let child2 = nexus.child(CHILD_2).unwrap();
+        let alignment = child1.get_device().unwrap().alignment();
+
+        let mut buf = DmaBuf::new(256 * 1024, alignment).unwrap();
+        buf.as_mut_slice().fill(0x5a);
+        child1.write_at(0, &buf).await.unwrap();
+        child2.write_at(0, &buf).await.unwrap();
+        assert_eq!(
+            child1.compare_at(child2, 0, 256 * 1024).await.unwrap(),
+            None
+        );
+
+        // The mismatch is found past the first compared segment.
+        let mut block = DmaBuf::new(512, alignment).unwrap();
+        block.as_mut_slice().fill(0x5a);
+        block.as_mut_slice()[7] = 0;
+        child2.write_at(200 * 1024, &block).await.unwrap();
+        assert_eq!(
+            child1.compare_at(child2, 0, 256 * 1024).await.unwrap(),
+            Some(200 * 1024 + 7)
+        );
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+
+use io_engine::{
+    bdev::nexus::{
+        nexus_create,
+        nexus_lookup_mut,
+        ChildState,
+        ErrorCounts,
+        FaultReason,
+        NexusErrStore,
+    },
+    core::{IoType, MayastorCliArgs},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ErrStoreNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_err_store_survives_reopen() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_2).unwrap();
+        assert_eq!(child.io_error_count(), 0);
+        child.record_io_error(IoType::Write, 0, 8);
+        child.record_io_error(IoType::Read, 16, 8);
+        assert_eq!(child.io_error_count(), 2);
+
+        let counts = ErrorCounts {
+            read: 1,
+            write: 1,
+            total: 2,
+        };
+        let stats = child.io_error_stats(Duration::from_secs(60)).unwrap();
+        assert_eq!(stats.total, counts);
+        assert_eq!(stats.recent, counts);
+        std::thread::sleep(Duration::from_millis(10));
+        let stats = child.io_error_stats(Duration::from_millis(5)).unwrap();
+        assert_eq!(stats.total, counts);
+        assert_eq!(stats.recent, ErrorCounts::default());
+
+        // Transient fault: the child can be onlined again.
+        nexus
+            .as_mut()
+            .fault_child(CHILD_2, FaultReason::Offline)
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // Wait for the faulted child's device to be retired.
+    let mut retired = false;
+    for _ in 0 .. 50 {
+        retired = ms
+            .spawn(async {
+                let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+                nexus.child(CHILD_2).unwrap().get_device().is_err()
+            })
+            .await;
+        if retired {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(retired);
+
+    ms.spawn(async {
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().online_child(CHILD_2).await.unwrap();
+
+        // The error history must survive the reopen.
+        let child = nexus.child(CHILD_2).unwrap();
+        assert_eq!(child.state(), ChildState::Open);
+        assert_eq!(child.io_error_count(), 2);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
+
+#[test]
+fn nexus_child_err_store_total_past_capacity() {
+    let mut store = NexusErrStore::new(4);
+    for i in 0 .. 10 {
+        let io_op = if i % 2 == 0 {
+            IoType::Read
+        } else {
+            IoType::Write
+        };
+        store.add_record(io_op, i, 8, Instant::now());
+    }
+
+    // Only the last records are kept, but all the failures are counted.
+    assert_eq!(store.len(), 4);
+    let stats = store.stats(None);
+    assert_eq!(
+        stats.total,
+        ErrorCounts {
+            read: 5,
+            write: 5,
+            total: 10,
+        }
+    );
+    assert_eq!(
+        stats.recent,
+        ErrorCounts {
+            read: 2,
+            write: 2,
+            total: 4,
+        }
+    );
+}
@@ -0,0 +1,68 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildState, FaultReason},
+    core::{IoType, MayastorCliArgs},
+    subsys::{Config, ErrStoreOpts},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ErrThresholdNexus";
+static NEXUS_SIZE: u64 = 4 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_err_threshold() {
+    Config::get_or_init(|| Config {
+        err_store_opts: ErrStoreOpts {
+            max_errors: 3,
+            retention_ms: 60000,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_2).unwrap();
+        child.record_io_error(IoType::Write, 0, 8);
+        child.record_io_error(IoType::Write, 8, 8);
+        assert!(child.is_healthy());
+        assert!(child.io_error_rate() > 0.0);
+        child.record_io_error(IoType::Read, 16, 8);
+    })
+    .await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        assert_eq!(
+            nexus.child(CHILD_2).unwrap().state(),
+            ChildState::Faulted(FaultReason::IoError)
+        );
+
+        // The last healthy child is never faulted.
+        let child = nexus.child(CHILD_1).unwrap();
+        for offset in 0 .. 3 {
+            child.record_io_error(IoType::Write, offset * 8, 8);
+        }
+    })
+    .await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        assert!(nexus.child(CHILD_1).unwrap().is_healthy());
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
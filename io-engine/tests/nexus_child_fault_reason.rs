@@ -0,0 +1,82 @@
+use io_engine::{
+    bdev::nexus::FaultReason,
+    core::{IoCompletionStatus, LvolFailure, NvmeStatus},
+};
+use spdk_rs::libspdk::{
+    SPDK_NVME_SC_ABORTED_SQ_DELETION,
+    SPDK_NVME_SC_COMMAND_TRANSIENT_TRANSPORT_ERROR,
+    SPDK_NVME_SC_INTERNAL_DEVICE_ERROR,
+    SPDK_NVME_SC_UNRECOVERED_READ_ERROR,
+};
+
+pub mod common;
+
+use common::compose::rpc::v1::nexus::ChildStateReason;
+
+#[test]
+fn nexus_child_fault_reason_classification() {
+    let reason = |status| FaultReason::from_io_status(status);
+
+    assert_eq!(
+        reason(IoCompletionStatus::NvmeError(NvmeStatus::Media(
+            SPDK_NVME_SC_UNRECOVERED_READ_ERROR
+        ))),
+        FaultReason::MediaError
+    );
+    assert_eq!(
+        reason(IoCompletionStatus::NvmeError(
+            NvmeStatus::DATA_TRANSFER_ERROR
+        )),
+        FaultReason::TransportError
+    );
+    assert_eq!(
+        reason(IoCompletionStatus::NvmeError(NvmeStatus::Generic(
+            SPDK_NVME_SC_ABORTED_SQ_DELETION
+        ))),
+        FaultReason::TransportError
+    );
+    assert_eq!(
+        reason(IoCompletionStatus::NvmeError(NvmeStatus::Generic(
+            SPDK_NVME_SC_COMMAND_TRANSIENT_TRANSPORT_ERROR
+        ))),
+        FaultReason::TransportError
+    );
+    assert_eq!(
+        reason(IoCompletionStatus::NvmeError(NvmeStatus::Generic(
+            SPDK_NVME_SC_INTERNAL_DEVICE_ERROR
+        ))),
+        FaultReason::IoError
+    );
+    assert_eq!(
+        reason(IoCompletionStatus::LvolError(LvolFailure::NoSpace)),
+        FaultReason::NoSpace
+    );
+
+    // Only transport errors may go away.
+    assert!(!FaultReason::MediaError.is_recoverable());
+    assert!(FaultReason::TransportError.is_recoverable());
+    for r in [
+        FaultReason::IoError,
+        FaultReason::MediaError,
+        FaultReason::TransportError,
+    ] {
+        assert!(r.is_io_failure());
+    }
+}
+
+#[test]
+fn nexus_child_fault_reason_grpc() {
+    // Each I/O failure is reported with its own reason.
+    assert_eq!(
+        ChildStateReason::from(FaultReason::IoError),
+        ChildStateReason::IoFailure
+    );
+    assert_eq!(
+        ChildStateReason::from(FaultReason::MediaError),
+        ChildStateReason::MediaFailure
+    );
+    assert_eq!(
+        ChildStateReason::from(FaultReason::TransportError),
+        ChildStateReason::TransportFailure
+    );
+}
@@ -0,0 +1,63 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, FaultReason, NexusChild},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "HealthOrderNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=20";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=20";
+static CHILD_3: &str = "malloc:///malloc2?blk_size=512&size_mb=20";
+static CHILD_4: &str = "malloc:///malloc3?blk_size=512&size_mb=20";
+
+#[tokio::test]
+async fn nexus_child_health_order() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        // The children are added out of order of their uris.
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[
+                CHILD_3.to_string(),
+                CHILD_1.to_string(),
+                CHILD_2.to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        let order = |children: Vec<&NexusChild>| {
+            children
+                .iter()
+                .map(|c| c.uri().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Equally healthy children are ordered by uri.
+        assert_eq!(
+            order(nexus.children_by_health().await),
+            [CHILD_1, CHILD_2, CHILD_3]
+        );
+
+        // Faulted children come first, then out-of-sync ones.
+        nexus.as_mut().add_child(CHILD_4, true).await.unwrap();
+        nexus
+            .as_mut()
+            .fault_child(CHILD_2, FaultReason::OfflinePermanent)
+            .await
+            .unwrap();
+        assert_eq!(
+            order(nexus.children_by_health().await),
+            [CHILD_2, CHILD_4, CHILD_1, CHILD_3]
+        );
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,61 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildIoError},
+    core::{Cores, MayastorCliArgs},
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "IoOnCoreNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_io_on_core() {
+    let ms = common::MayastorTest::new(MayastorCliArgs {
+        reactor_mask: "0x3".into(),
+        ..Default::default()
+    });
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_1).unwrap();
+        let alignment = child.get_device().unwrap().alignment();
+        let other = Cores::count()
+            .into_iter()
+            .find(|c| *c != Cores::current())
+            .unwrap();
+
+        let mut wbuf = DmaBuf::new(4096, alignment).unwrap();
+        wbuf.as_mut_slice().fill(0xa5);
+        let (n, wbuf) = child.write_at_on(other, 0, wbuf).await.unwrap();
+        assert_eq!(n, 4096);
+
+        // The buffers are handed back once the I/O completes.
+        let rbuf = DmaBuf::new(4096, alignment).unwrap();
+        let (n, rbuf) =
+            child.read_at_on(Cores::current(), 0, rbuf).await.unwrap();
+        assert_eq!(n, 4096);
+        assert_eq!(wbuf.as_slice(), rbuf.as_slice());
+
+        let rbuf = DmaBuf::new(4096, alignment).unwrap();
+        let (_, rbuf) = child.read_at_on(other, 0, rbuf).await.unwrap();
+        assert_eq!(wbuf.as_slice(), rbuf.as_slice());
+
+        // A core without a reactor is rejected.
+        assert!(matches!(
+            child.read_at_on(1024, 0, rbuf).await,
+            Err(ChildIoError::InvalidCore {
+                core: 1024,
+                ..
+            })
+        ));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,121 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildIoError},
+    constants::NVME_NQN_PREFIX,
+    core::MayastorCliArgs,
+    subsys::{Config, NexusOpts},
+};
+use spdk_rs::DmaBuf;
+use std::time::{Duration, Instant};
+
+pub mod common;
+
+use common::{
+    compose::{
+        rpc::v0::{
+            mayastor::{BdevShareRequest, BdevUri},
+            GrpcConnect,
+        },
+        Builder,
+    },
+    MayastorTest,
+};
+
+static NEXUS_NAME: &str = "TimeoutNexus";
+static NEXUS_SIZE: u64 = 50 * 1024 * 1024;
+static LOCAL_CHILD: &str = "malloc:///malloc0?blk_size=512&size_mb=100";
+
+#[tokio::test]
+async fn nexus_child_io_timeout() {
+    common::composer_init();
+
+    // Time out child I/Os well before the NVMe layer does.
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            child_io_timeout_ms: 1000,
+            child_io_timeout_fault: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_dbg("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let grpc = GrpcConnect::new(&test);
+    let mut hdl = grpc.grpc_handle("ms1").await.unwrap();
+    hdl.bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=100".into(),
+        })
+        .await
+        .unwrap();
+    hdl.bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let remote_child =
+        format!("nvmf://{}:8420/{NVME_NQN_PREFIX}:disk0", hdl.endpoint.ip());
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    let c = remote_child.clone();
+    ms.spawn(async move {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[LOCAL_CHILD.to_string(), c],
+        )
+        .await
+        .unwrap();
+    })
+    .await;
+
+    // Make the I/Os to the remote child hang.
+    test.pause("ms1").await.unwrap();
+
+    let c = remote_child.clone();
+    ms.spawn(async move {
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(&c).unwrap();
+        let alignment = child.get_device().unwrap().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+
+        // The hung read times out and is aborted, without waiting for the
+        // target.
+        let start = Instant::now();
+        assert!(matches!(
+            child.read_at(0, &mut buf).await,
+            Err(ChildIoError::Timeout { .. })
+        ));
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        // The I/Os of the other children are not affected.
+        let local = nexus.child(LOCAL_CHILD).unwrap();
+        assert_eq!(local.read_at(0, &mut buf).await.unwrap(), 4096);
+    })
+    .await;
+
+    test.thaw("ms1").await.unwrap();
+
+    ms.spawn(async move {
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,104 @@
+use futures::StreamExt;
+use io_engine::{
+    bdev::{
+        device_create,
+        device_lookup,
+        nexus::{
+            nexus_create,
+            nexus_lookup_mut,
+            ChildRebuildUpdate,
+            NexusLabel,
+            LABEL_OFFSET,
+        },
+    },
+    core::MayastorCliArgs,
+    rebuild::RebuildState,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "LabelNexus";
+static NEXUS_SIZE: u64 = 4 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+static FOREIGN: &str = "malloc:///malloc2?blk_size=512&size_mb=10";
+static BLANK: &str = "malloc:///malloc3?blk_size=512&size_mb=10";
+static FOREIGN_2: &str = "malloc:///malloc4?blk_size=512&size_mb=10";
+
+/// Writes the label of another nexus into the given device.
+async fn write_foreign_label(uri: &str) {
+    let name = device_create(uri).await.unwrap();
+    let desc = device_lookup(&name).unwrap().open(true).unwrap();
+    let hdl = desc.get_io_handle_nonblock().await.unwrap();
+
+    let mut buf = DmaBuf::new(512, hdl.get_device().alignment()).unwrap();
+    buf.as_mut_slice().fill(0);
+    NexusLabel::new(uuid::Uuid::new_v4(), 1).encode(buf.as_mut_slice());
+    hdl.write_at(LABEL_OFFSET, &buf).await.unwrap();
+}
+
+#[tokio::test]
+async fn nexus_child_label() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        write_foreign_label(FOREIGN).await;
+
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let label = nexus.label();
+        assert_eq!(label.generation, 1);
+        for uri in [CHILD_1, CHILD_2] {
+            let child = nexus.child(uri).unwrap();
+            assert_eq!(child.read_label().await.unwrap(), Some(label));
+        }
+
+        // A child labelled by another nexus is rejected.
+        assert!(nexus.as_mut().add_child(FOREIGN, true).await.is_err());
+        assert!(!nexus.contains_child_uri(FOREIGN));
+
+        // A blank child is accepted, to be rebuilt, and only gets labelled
+        // once rebuilt.
+        nexus.as_mut().add_child(BLANK, true).await.unwrap();
+        let child = nexus.child(BLANK).unwrap();
+        assert_eq!(child.read_label().await.unwrap(), None);
+
+        let mut updates = child.subscribe_rebuild().unwrap();
+        nexus.start_rebuild(BLANK).await.unwrap();
+        let mut finished = None;
+        while let Some(update) = updates.next().await {
+            if let ChildRebuildUpdate::Finished {
+                state, ..
+            } = update
+            {
+                finished = Some(state);
+            }
+        }
+        assert_eq!(finished, Some(RebuildState::Completed));
+        let child = nexus.child(BLANK).unwrap();
+        assert_eq!(child.read_label().await.unwrap(), Some(label));
+
+        nexus.destroy().await.unwrap();
+
+        // A nexus cannot be opened with a child labelled by another nexus.
+        write_foreign_label(FOREIGN_2).await;
+        assert!(nexus_create(
+            "LabelNexus2",
+            NEXUS_SIZE,
+            None,
+            &[FOREIGN_2.to_string()],
+        )
+        .await
+        .is_err());
+    })
+    .await;
+}
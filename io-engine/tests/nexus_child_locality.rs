@@ -0,0 +1,42 @@
+use io_engine::bdev::nexus::ChildLocality;
+
+#[test]
+fn child_locality_from_uri() {
+    for uri in [
+        "malloc:///malloc0?blk_size=512&size_mb=10",
+        "aio:///dev/sda?blk_size=512",
+        "bdev:///lvol0",
+        "pcie:///0000:00:04.0",
+        "nvmf://127.0.0.1:8420/nqn.2019-05.io.openebs:disk0",
+        "nvmf://localhost:8420/nqn.2019-05.io.openebs:disk0",
+        "nvmf://[::1]:8420/nqn.2019-05.io.openebs:disk0",
+    ] {
+        assert_eq!(ChildLocality::from_uri(uri), ChildLocality::Local, "{uri}");
+    }
+
+    assert_eq!(
+        ChildLocality::from_uri(
+            "nvmf://192.0.2.10:8420/nqn.2019-05.io.openebs:disk0"
+        ),
+        ChildLocality::RemoteTcp
+    );
+    assert_eq!(
+        ChildLocality::from_uri(
+            "nvmf+rdma://192.0.2.10:4420/nqn.2019-05.io.openebs:disk0"
+        ),
+        ChildLocality::RemoteRdma
+    );
+    assert_eq!(
+        ChildLocality::from_uri("iscsi://192.0.2.10:3260/iqn.2019-05.disk0/0"),
+        ChildLocality::RemoteIscsi
+    );
+    for uri in ["not a uri", "/dev/sda", "ftp://192.0.2.10/disk0"] {
+        assert_eq!(
+            ChildLocality::from_uri(uri),
+            ChildLocality::Unknown,
+            "{uri}"
+        );
+    }
+    assert!(!ChildLocality::RemoteTcp.is_local());
+    assert!(!ChildLocality::Unknown.is_local());
+}
@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, FaultReason},
+    bdev_api::bdev_create,
+    core::MayastorCliArgs,
+    sleep::mayastor_sleep,
+};
+
+pub mod common;
+use common::bdev_io;
+
+static NEXUS_NAME: &str = "OnlineInSyncNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static DISK_1: &str = "malloc:///disk1?blk_size=512&size_mb=12";
+static DISK_2: &str = "malloc:///disk2?blk_size=512&size_mb=12";
+// The children go through loopback devices, so that the disks and their data
+// outlive the child devices closed when offlining.
+static CHILD_1: &str = "bdev:///disk1";
+static CHILD_2: &str = "bdev:///disk2";
+
+/// Offlines the second child the way the nexus retires it.
+async fn offline_child() {
+    let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+    let device = nexus.child(CHILD_2).unwrap().get_device_name().unwrap();
+    nexus
+        .as_mut()
+        .fault_child(CHILD_2, FaultReason::Offline)
+        .await
+        .unwrap();
+
+    // let the retire routine detach the device before closing it
+    mayastor_sleep(Duration::from_millis(500)).await.unwrap();
+    nexus.close_child(&device).await.unwrap();
+}
+
+#[tokio::test]
+async fn nexus_child_online_in_sync() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        bdev_create(DISK_1).await.unwrap();
+        bdev_create(DISK_2).await.unwrap();
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        // Nothing written while offline: the child is brought back as is.
+        offline_child().await;
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus
+            .as_mut()
+            .online_child_ext(CHILD_2, true)
+            .await
+            .unwrap();
+        assert!(nexus.child(CHILD_2).unwrap().is_healthy());
+        assert!(nexus.rebuild_state(CHILD_2).is_err());
+        assert!(nexus.rebuild_history().is_empty());
+
+        // Written while offline: the child falls back to a rebuild.
+        offline_child().await;
+        bdev_io::write_blocks(NEXUS_NAME, 0, 8, 0xaa).await.unwrap();
+        nexus
+            .as_mut()
+            .online_child_ext(CHILD_2, true)
+            .await
+            .unwrap();
+
+        for _ in 0 .. 100 {
+            if nexus.child(CHILD_2).unwrap().is_healthy() {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(50)).await.unwrap();
+        }
+        assert!(nexus.child(CHILD_2).unwrap().is_healthy());
+        assert_eq!(nexus.rebuild_history().len(), 1);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,54 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::{MayastorCliArgs, UntypedBdevHandle},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "PaddedNexus";
+static NEXUS_SIZE: u64 = 32 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+// smaller than the nexus, but within the configured tolerance
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=30";
+
+#[tokio::test]
+async fn nexus_child_padded() {
+    // The tolerance is read once, when the configuration is initialized.
+    std::env::set_var("NEXUS_CHILD_SIZE_TOLERANCE", "8388608");
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+
+        let hdl = UntypedBdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xff);
+        hdl.write_at(NEXUS_SIZE - 4096, &buf).await.unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+        let tail = nexus.padded_tail_start() * nexus.block_len();
+        assert!(tail < NEXUS_SIZE);
+
+        // Writes to the padded tail are rejected, the rest is writable.
+        assert!(hdl.write_at(NEXUS_SIZE - 4096, &buf).await.is_err());
+        assert!(hdl.write_at(tail - 2048, &buf).await.is_err());
+        hdl.write_at(tail - 4096, &buf).await.unwrap();
+
+        // The padded tail reads as zeroes, whatever the children hold,
+        // wherever the read starts within it.
+        for offset in [tail, tail + 4096, NEXUS_SIZE - 4096] {
+            buf.fill(0xff);
+            hdl.read_at(offset, &mut buf).await.unwrap();
+            assert!(buf.as_slice().iter().all(|b| *b == 0));
+        }
+
+        // A read straddling the tail gets the data ahead of it.
+        hdl.read_at(tail - 2048, &mut buf).await.unwrap();
+        assert!(buf.as_slice()[.. 2048].iter().all(|b| *b == 0xff));
+        assert!(buf.as_slice()[2048 ..].iter().all(|b| *b == 0));
+    })
+    .await;
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::{
+        device_create,
+        nexus::{nexus_create, nexus_lookup_mut, FaultReason},
+    },
+    core::{MayastorCliArgs, UntypedBdev},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "PaddedTailNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=16";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=16";
+// Within the size tolerance of the nexus, but its data partition ends before
+// the last block the nexus already exposes.
+static SMALLER: &str = "malloc:///malloc2?blk_size=512&size_mb=15";
+static REPLACEMENT: &str = "malloc:///malloc3?blk_size=512&size_mb=16";
+
+#[tokio::test]
+async fn nexus_child_padded_tail_fixed() {
+    // The tolerance is read once, when the configuration is initialized.
+    std::env::set_var("NEXUS_CHILD_SIZE_TOLERANCE", "2097152");
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    let tail_start = ms
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                NEXUS_SIZE,
+                None,
+                &[CHILD_1.to_string(), CHILD_2.to_string()],
+            )
+            .await
+            .unwrap();
+            device_create(SMALLER).await.unwrap();
+            device_create(REPLACEMENT).await.unwrap();
+
+            let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+            // Both children cover the whole nexus: there is no padded tail.
+            assert_eq!(nexus.padded_tail_start(), nexus.num_blocks());
+
+            nexus
+                .as_mut()
+                .fault_child(CHILD_2, FaultReason::OfflinePermanent)
+                .await
+                .unwrap();
+            nexus.padded_tail_start()
+        })
+        .await;
+
+    // Wait for the faulted child's device to be retired.
+    let mut retired = false;
+    for _ in 0 .. 50 {
+        retired = ms
+            .spawn(async {
+                let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+                nexus.child(CHILD_2).unwrap().get_device().is_err()
+            })
+            .await;
+        if retired {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(retired);
+
+    ms.spawn(async move {
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // A smaller child would move the tail of the sized nexus over blocks
+        // already exposed as data.
+        let bdev = UntypedBdev::lookup_by_name("malloc2").unwrap();
+        assert!(nexus
+            .as_mut()
+            .replace_child(CHILD_2, bdev, true)
+            .await
+            .is_err());
+        assert_eq!(nexus.padded_tail_start(), tail_start);
+
+        let bdev = UntypedBdev::lookup_by_name("malloc3").unwrap();
+        nexus
+            .as_mut()
+            .replace_child(CHILD_2, bdev, true)
+            .await
+            .unwrap();
+        assert_eq!(nexus.padded_tail_start(), tail_start);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,133 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::{MayastorCliArgs, UntypedBdevHandle},
+};
+use once_cell::sync::OnceCell;
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+static NEXUS_NAME: &str = "PauseNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+static FRONT_END_NEXUS_NAME: &str = "PauseFrontEndNexus";
+static FRONT_END_CHILD_1: &str = "malloc:///malloc2?blk_size=512&size_mb=10";
+static FRONT_END_CHILD_2: &str = "malloc:///malloc3?blk_size=512&size_mb=10";
+
+static MAYASTOR: OnceCell<MayastorTest> = OnceCell::new();
+
+fn get_ms() -> &'static MayastorTest<'static> {
+    MAYASTOR.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+#[tokio::test]
+async fn nexus_child_pause_close_reopen() {
+    let ms = get_ms();
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let device = nexus.child(CHILD_2).unwrap().get_device_name().unwrap();
+
+        // Close the child while its I/O is paused.
+        nexus.as_mut().child_mut(CHILD_2).unwrap().pause_io();
+        nexus.close_child(&device).await.unwrap();
+        nexus.as_mut().online_child(CHILD_2).await.unwrap();
+
+        // The reopened child is no longer paused.
+        let child = nexus.child(CHILD_2).unwrap();
+        let alignment = child.get_device().unwrap().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+        assert_eq!(child.read_at(0, &mut buf).await.unwrap(), 4096);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn nexus_child_pause_front_end_io() {
+    static WRITTEN: AtomicBool = AtomicBool::new(false);
+    let ms = get_ms();
+
+    ms.spawn(async {
+        nexus_create(
+            FRONT_END_NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[FRONT_END_CHILD_1.to_string(), FRONT_END_CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let mut nexus = nexus_lookup_mut(FRONT_END_NEXUS_NAME).unwrap();
+        nexus
+            .as_mut()
+            .child_mut(FRONT_END_CHILD_2)
+            .unwrap()
+            .pause_io();
+    })
+    .await;
+
+    // A write to the nexus is held back while the I/O of a child is paused.
+    ms.send(async {
+        let hdl =
+            UntypedBdevHandle::open(FRONT_END_NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xa5);
+        hdl.write_at(0, &buf).await.unwrap();
+        WRITTEN.store(true, Ordering::SeqCst);
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!WRITTEN.load(Ordering::SeqCst));
+
+    ms.spawn(async {
+        let mut nexus = nexus_lookup_mut(FRONT_END_NEXUS_NAME).unwrap();
+        nexus
+            .as_mut()
+            .child_mut(FRONT_END_CHILD_2)
+            .unwrap()
+            .resume_io();
+    })
+    .await;
+
+    // The write completes once the child is resumed.
+    let mut written = false;
+    for _ in 0 .. 50 {
+        written = WRITTEN.load(Ordering::SeqCst);
+        if written {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(written);
+
+    ms.spawn(async {
+        let hdl = UntypedBdevHandle::open(FRONT_END_NEXUS_NAME, false, false)
+            .unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xa5));
+        drop(hdl);
+
+        let nexus = nexus_lookup_mut(FRONT_END_NEXUS_NAME).unwrap();
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,44 @@
+use io_engine::{
+    bdev::{
+        device_create,
+        device_destroy,
+        device_lookup,
+        nexus::{ChildIoError, ChildState, NexusChild},
+    },
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static CHILD: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_open_readonly() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let name = device_create(CHILD).await.unwrap();
+        let mut child = NexusChild::new(
+            CHILD.to_string(),
+            "ReadOnlyNexus".to_string(),
+            device_lookup(&name),
+        );
+
+        child.open_readonly().unwrap();
+        assert_eq!(child.state(), ChildState::OpenReadOnly);
+        assert!(child.claim_owner().is_none());
+
+        let alignment = child.get_device().unwrap().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+        assert_eq!(child.read_at(0, &mut buf).await.unwrap(), 4096);
+        assert!(matches!(
+            child.write_at(0, &buf).await,
+            Err(ChildIoError::ReadOnly { .. })
+        ));
+
+        drop(child);
+        device_destroy(CHILD).await.unwrap();
+    })
+    .await;
+}
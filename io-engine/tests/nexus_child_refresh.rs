@@ -0,0 +1,57 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildError},
+    core::{MayastorCliArgs, UntypedBdevHandle},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "RefreshNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_refresh_channel() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let hdl = UntypedBdevHandle::open(NEXUS_NAME, true, false).unwrap();
+        let mut buf = hdl.dma_malloc(4096).unwrap();
+        buf.fill(0xaa);
+
+        // The nexus I/O goes on through the refreshed channels.
+        nexus
+            .child(CHILD_2)
+            .unwrap()
+            .refresh_channel()
+            .await
+            .unwrap();
+        hdl.write_at(0, &buf).await.unwrap();
+        buf.fill(0);
+        hdl.read_at(0, &mut buf).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xaa));
+
+        // A closed child has no descriptor to refresh the channels from.
+        let device = nexus.child(CHILD_2).unwrap().get_device_name().unwrap();
+        nexus.close_child(&device).await.unwrap();
+        assert!(matches!(
+            nexus.child(CHILD_2).unwrap().refresh_channel().await,
+            Err(ChildError::NoDescriptor {})
+        ));
+        hdl.read_at(0, &mut buf).await.unwrap();
+
+        drop(hdl);
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::{
+        device_create,
+        nexus::{nexus_create, nexus_lookup_mut, ChildState, FaultReason},
+    },
+    core::{MayastorCliArgs, Share, UntypedBdev},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ReplaceNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+static REPLACEMENT: &str = "malloc:///malloc2?blk_size=512&size_mb=10";
+static TOO_SMALL: &str = "malloc:///malloc3?blk_size=512&size_mb=5";
+
+#[tokio::test]
+async fn nexus_child_replace_faulted() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+        device_create(REPLACEMENT).await.unwrap();
+        device_create(TOO_SMALL).await.unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // A healthy child cannot be replaced.
+        let bdev = UntypedBdev::lookup_by_name("malloc2").unwrap();
+        assert!(nexus
+            .as_mut()
+            .replace_child(CHILD_1, bdev, true)
+            .await
+            .is_err());
+
+        nexus
+            .as_mut()
+            .fault_child(CHILD_2, FaultReason::OfflinePermanent)
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // Wait for the faulted child's device to be retired.
+    let mut retired = false;
+    for _ in 0 .. 50 {
+        retired = ms
+            .spawn(async {
+                let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+                nexus.child(CHILD_2).unwrap().get_device().is_err()
+            })
+            .await;
+        if retired {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(retired);
+
+    ms.spawn(async {
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // The replacement must be large enough.
+        let bdev = UntypedBdev::lookup_by_name("malloc3").unwrap();
+        assert!(nexus
+            .as_mut()
+            .replace_child(CHILD_2, bdev, true)
+            .await
+            .is_err());
+
+        let bdev = UntypedBdev::lookup_by_name("malloc2").unwrap();
+        let uri = bdev.bdev_uri().unwrap().to_string();
+        nexus
+            .as_mut()
+            .replace_child(CHILD_2, bdev, true)
+            .await
+            .unwrap();
+
+        // The child is renamed after the replacement device, which needs to
+        // be rebuilt.
+        assert!(!nexus.contains_child_uri(CHILD_2));
+        let child = nexus.child(&uri).unwrap();
+        assert_eq!(child.uri(), uri);
+        assert_eq!(child.get_device_name().as_deref(), Some("malloc2"));
+        assert_eq!(child.state(), ChildState::Open);
+        assert!(child.is_opened_unsync());
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
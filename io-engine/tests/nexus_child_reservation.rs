@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{
+        nexus_create,
+        nexus_lookup,
+        nexus_lookup_mut,
+        ChildIoError,
+        ChildState,
+        FaultReason,
+        NexusNvmePreemption,
+        NvmeReservation,
+    },
+    constants::NVME_NQN_PREFIX,
+    core::MayastorCliArgs,
+};
+use once_cell::sync::OnceCell;
+
+pub mod common;
+
+use common::{
+    compose::{
+        rpc::v0::{
+            mayastor::{
+                CreateNexusV2Request,
+                CreatePoolRequest,
+                CreateReplicaRequest,
+            },
+            GrpcConnect,
+        },
+        Binary,
+        Builder,
+    },
+    MayastorTest,
+};
+
+static NEXUS_NAME: &str = "ReservationNexus";
+static CHILD: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+
+static REASSERT_NEXUS_NAME: &str = "ReassertNexus";
+static REASSERT_NEXUS_UUID: &str = "2b5c4a3e-7f57-4d0e-9d31-5c1e0f6e8a41";
+static LOCAL_CHILD: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+static POOL_NAME: &str = "tpool";
+static REPL_UUID: &str = "8f1c0e0d-4a4b-4f43-9a3e-1b6d5d2c7e90";
+static HOSTID0: &str = "53b35ce9-8e71-49a9-ab9b-cba7c5670fad";
+static HOSTID1: &str = "c1affd2d-ef79-4ba4-b5cf-8eb48f9c07d0";
+static HOSTID2: &str = "3f264cc3-1c95-44ca-bc1f-ed7fb68f3894";
+
+static MAYASTOR: OnceCell<MayastorTest> = OnceCell::new();
+
+fn get_ms() -> &'static MayastorTest<'static> {
+    MAYASTOR.get_or_init(|| {
+        std::env::set_var("MAYASTOR_NVMF_HOSTID", HOSTID0);
+        MayastorTest::new(MayastorCliArgs::default())
+    })
+}
+
+#[tokio::test]
+async fn nexus_child_reservation_unsupported() {
+    let ms = get_ms();
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, 32 * 1024 * 1024, None, &[CHILD.to_string()])
+            .await
+            .unwrap();
+
+        // Malloc devices have no NVMe reservation support.
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD).unwrap();
+        assert!(matches!(
+            child.reserve(0x1234, NvmeReservation::WriteExclusive).await,
+            Err(ChildIoError::Unsupported { .. })
+        ));
+        assert!(matches!(
+            child.release(0x1234).await,
+            Err(ChildIoError::Unsupported { .. })
+        ));
+    })
+    .await;
+}
+
+#[tokio::test]
+/// Reserve a remote child, and have another host preempt the reservation
+/// while the child is offline. The reservation then fails to be re-asserted
+/// as the child is onlined, which faults the child.
+async fn nexus_child_reservation_reassert_conflict() {
+    common::composer_init();
+
+    let test = Builder::new()
+        .name("nexus_child_resv_reassert_test")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_bin(
+            "ms1",
+            Binary::from_dbg("io-engine")
+                .with_env("MAYASTOR_NVMF_HOSTID", HOSTID1),
+        )
+        .add_container_bin(
+            "ms2",
+            Binary::from_dbg("io-engine")
+                .with_env("NEXUS_NVMF_RESV_ENABLE", "1")
+                .with_env("MAYASTOR_NVMF_HOSTID", HOSTID2),
+        )
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let grpc = GrpcConnect::new(&test);
+    let mut ms1 = grpc.grpc_handle("ms1").await.unwrap();
+    let mut ms2 = grpc.grpc_handle("ms2").await.unwrap();
+
+    ms1.mayastor
+        .create_pool(CreatePoolRequest {
+            name: POOL_NAME.to_string(),
+            disks: vec!["malloc:///disk0?size_mb=64".into()],
+        })
+        .await
+        .unwrap();
+    ms1.mayastor
+        .create_replica(CreateReplicaRequest {
+            uuid: REPL_UUID.to_string(),
+            pool: POOL_NAME.to_string(),
+            size: 32 * 1024 * 1024,
+            thin: false,
+            share: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let ms = get_ms();
+    let remote_child = format!(
+        "nvmf://{}:8420/{NVME_NQN_PREFIX}:{REPL_UUID}",
+        ms1.endpoint.ip()
+    );
+    let resv_key = 0xabcd_ef00_1234_5678;
+
+    let uri = remote_child.clone();
+    ms.spawn(async move {
+        nexus_create(
+            REASSERT_NEXUS_NAME,
+            32 * 1024 * 1024,
+            Some(REASSERT_NEXUS_UUID),
+            &[LOCAL_CHILD.to_string(), uri.clone()],
+        )
+        .await
+        .unwrap();
+
+        let mut nexus = nexus_lookup_mut(REASSERT_NEXUS_NAME).unwrap();
+        nexus
+            .child(&uri)
+            .unwrap()
+            .reserve(resv_key, NvmeReservation::ExclusiveAccess)
+            .await
+            .unwrap();
+        nexus
+            .as_mut()
+            .fault_child(&uri, FaultReason::Offline)
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // Wait for the faulted child's device to be retired.
+    let mut retired = false;
+    for _ in 0 .. 50 {
+        let uri = remote_child.clone();
+        retired = ms
+            .spawn(async move {
+                let nexus = nexus_lookup_mut(REASSERT_NEXUS_NAME).unwrap();
+                nexus.child(&uri).unwrap().get_device().is_err()
+            })
+            .await;
+        if retired {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(retired);
+
+    // Another host preempts the reservation of the offline child.
+    ms2.mayastor
+        .create_nexus_v2(CreateNexusV2Request {
+            name: REASSERT_NEXUS_NAME.to_string(),
+            uuid: REASSERT_NEXUS_UUID.to_string(),
+            size: 32 * 1024 * 1024,
+            min_cntl_id: 1,
+            max_cntl_id: 0xffef,
+            resv_key: 0xfeed_f00d_bead_5678,
+            preempt_key: 0,
+            children: vec![remote_child.clone()],
+            nexus_info_key: "".to_string(),
+            resv_type: Some(NvmeReservation::ExclusiveAccess as i32),
+            preempt_policy: NexusNvmePreemption::Holder as i32,
+        })
+        .await
+        .unwrap();
+
+    let uri = remote_child.clone();
+    ms.spawn(async move {
+        let mut nexus = nexus_lookup_mut(REASSERT_NEXUS_NAME).unwrap();
+
+        // The reservation is now held by another host: it cannot be
+        // re-asserted, which faults the child instead of leaving it open
+        // unfenced.
+        assert!(nexus.as_mut().online_child(&uri).await.is_err());
+        assert_eq!(
+            nexus.child(&uri).unwrap().state(),
+            ChildState::Faulted(FaultReason::AdminCommandFailed)
+        );
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ScrubNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_scrub() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            NEXUS_SIZE,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_1).unwrap();
+        let num_blocks = child.get_device().unwrap().num_blocks();
+
+        // A healthy child scrubs without errors.
+        assert_eq!(child.scrub(0, num_blocks).await.unwrap(), 0);
+        assert_eq!(child.io_error_count(), 0);
+
+        child.schedule_scrub(Duration::from_millis(100));
+    })
+    .await;
+
+    // Let a few scheduled passes run.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    ms.spawn(async {
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_1).unwrap();
+        assert!(child.is_healthy());
+        assert_eq!(child.io_error_count(), 0);
+
+        child.cancel_scrub_schedule();
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,196 @@
+#![cfg(feature = "io-engine-testing")]
+#![allow(deprecated)]
+
+use async_trait::async_trait;
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup},
+    core::{
+        BlockDevice,
+        BlockDeviceHandle,
+        CoreError,
+        IoCompletionCallback,
+        IoCompletionCallbackArg,
+        MayastorCliArgs,
+        ReadOptions,
+        SnapshotParams,
+    },
+};
+use spdk_rs::{DmaBuf, DmaError, IoVec};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ShortReadNexus";
+static CHILD: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+
+/// I/O handle completing every read with the given number of bytes less than
+/// requested.
+struct ShortReadHandle {
+    inner: Box<dyn BlockDeviceHandle>,
+    short_by: u64,
+}
+
+#[async_trait(?Send)]
+impl BlockDeviceHandle for ShortReadHandle {
+    fn get_device(&self) -> &dyn BlockDevice {
+        self.inner.get_device()
+    }
+
+    fn dma_malloc(&self, size: u64) -> Result<DmaBuf, DmaError> {
+        self.inner.dma_malloc(size)
+    }
+
+    async fn read_at(
+        &self,
+        offset: u64,
+        buffer: &mut DmaBuf,
+    ) -> Result<u64, CoreError> {
+        let n = self.inner.read_at(offset, buffer).await?;
+        Ok(n - self.short_by)
+    }
+
+    async fn write_at(
+        &self,
+        offset: u64,
+        buffer: &DmaBuf,
+    ) -> Result<u64, CoreError> {
+        self.inner.write_at(offset, buffer).await
+    }
+
+    fn readv_blocks(
+        &self,
+        iovs: &mut [IoVec],
+        offset_blocks: u64,
+        num_blocks: u64,
+        opts: ReadOptions,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner.readv_blocks(
+            iovs,
+            offset_blocks,
+            num_blocks,
+            opts,
+            cb,
+            cb_arg,
+        )
+    }
+
+    fn writev_blocks(
+        &self,
+        iovs: &[IoVec],
+        offset_blocks: u64,
+        num_blocks: u64,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner
+            .writev_blocks(iovs, offset_blocks, num_blocks, cb, cb_arg)
+    }
+
+    fn comparev_blocks(
+        &self,
+        iovs: &[IoVec],
+        offset_blocks: u64,
+        num_blocks: u64,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner
+            .comparev_blocks(iovs, offset_blocks, num_blocks, cb, cb_arg)
+    }
+
+    fn reset(
+        &self,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner.reset(cb, cb_arg)
+    }
+
+    fn unmap_blocks(
+        &self,
+        offset_blocks: u64,
+        num_blocks: u64,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner
+            .unmap_blocks(offset_blocks, num_blocks, cb, cb_arg)
+    }
+
+    fn write_zeroes(
+        &self,
+        offset_blocks: u64,
+        num_blocks: u64,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner
+            .write_zeroes(offset_blocks, num_blocks, cb, cb_arg)
+    }
+
+    async fn nvme_admin_custom(&self, opcode: u8) -> Result<(), CoreError> {
+        self.inner.nvme_admin_custom(opcode).await
+    }
+
+    async fn nvme_admin(
+        &self,
+        nvme_cmd: &spdk_rs::libspdk::spdk_nvme_cmd,
+        buffer: Option<&mut DmaBuf>,
+    ) -> Result<(), CoreError> {
+        self.inner.nvme_admin(nvme_cmd, buffer).await
+    }
+
+    async fn nvme_identify_ctrlr(&self) -> Result<DmaBuf, CoreError> {
+        self.inner.nvme_identify_ctrlr().await
+    }
+
+    async fn create_snapshot(
+        &self,
+        params: SnapshotParams,
+    ) -> Result<u64, CoreError> {
+        self.inner.create_snapshot(params).await
+    }
+
+    fn flush_io(
+        &self,
+        cb: IoCompletionCallback,
+        cb_arg: IoCompletionCallbackArg,
+    ) -> Result<(), CoreError> {
+        self.inner.flush_io(cb, cb_arg)
+    }
+}
+
+#[tokio::test]
+async fn nexus_child_short_read() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, 8 * 1024 * 1024, None, &[CHILD.to_string()])
+            .await
+            .unwrap();
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD).unwrap();
+        let alignment = child.get_device().unwrap().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+        buf.fill(0xaa);
+        assert_eq!(child.write_at(0, &buf).await.unwrap(), 4096);
+
+        // The caller sees the count the device actually read, and none of
+        // the stale content of the buffer past it.
+        let hdl = Box::new(ShortReadHandle {
+            inner: child.get_io_handle().unwrap(),
+            short_by: 1024,
+        });
+        buf.fill(0xff);
+        assert_eq!(child.read_at_with(hdl, 0, &mut buf).await.unwrap(), 3072);
+        assert!(buf.as_slice()[.. 3072].iter().all(|b| *b == 0xaa));
+        assert!(buf.as_slice()[3072 ..].iter().all(|b| *b == 0));
+
+        // A full read is reported as such.
+        assert_eq!(child.read_at(0, &mut buf).await.unwrap(), 4096);
+        assert!(buf.as_slice().iter().all(|b| *b == 0xaa));
+    })
+    .await;
+}
@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use io_engine::{
+    bdev::nexus::{
+        nexus_create,
+        nexus_lookup,
+        nexus_lookup_mut,
+        ChildThrottleLimits,
+    },
+    core::MayastorCliArgs,
+    sleep::mayastor_sleep,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ThrottleNexus";
+static CHILD: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn nexus_child_throttle() {
+    // The limits are read once, when the configuration is initialized.
+    std::env::set_var("NEXUS_CHILD_THROTTLE_IOPS", "10");
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, 32 * 1024 * 1024, None, &[CHILD.to_string()])
+            .await
+            .unwrap();
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD).unwrap();
+        assert_eq!(child.throttle(), None);
+
+        child.set_throttled(true);
+        assert_eq!(
+            child.throttle(),
+            Some(ChildThrottleLimits {
+                bytes_per_sec: 0,
+                iops: 10,
+            })
+        );
+
+        // The first second worth of I/Os goes through at once, the rest at
+        // the throttled rate.
+        let alignment = child.get_device().unwrap().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+        let start = Instant::now();
+        for _ in 0 .. 20 {
+            child.read_at(0, &mut buf).await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(900));
+
+        child.set_throttled(false);
+        assert_eq!(child.throttle(), None);
+
+        // A rebuild throttles its source and destination until it ends.
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+        let done = nexus.start_rebuild(CHILD_2).await.unwrap();
+        for uri in [CHILD, CHILD_2] {
+            assert!(nexus.child(uri).unwrap().throttle().is_some());
+        }
+
+        nexus.stop_rebuild(CHILD_2).await.unwrap();
+        done.await.unwrap();
+        for _ in 0 .. 100 {
+            if [CHILD, CHILD_2]
+                .iter()
+                .all(|uri| nexus.child(uri).unwrap().throttle().is_none())
+            {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+        }
+        for uri in [CHILD, CHILD_2] {
+            assert_eq!(nexus.child(uri).unwrap().throttle(), None);
+        }
+    })
+    .await;
+}
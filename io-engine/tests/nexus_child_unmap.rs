@@ -0,0 +1,46 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildIoError},
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+
+static NEXUS_NAME: &str = "UnmapNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_child_unmap() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        let child = nexus.child(CHILD_1).unwrap();
+        let alignment = child.get_device().unwrap().alignment();
+
+        let mut wbuf = DmaBuf::new(8192, alignment).unwrap();
+        wbuf.as_mut_slice().fill(0xa5);
+        assert_eq!(child.write_at(0, &wbuf).await.unwrap(), 8192);
+
+        // A malloc device reads back zeroes from unmapped blocks.
+        child.unmap(0, 4096).await.unwrap();
+        let mut rbuf = DmaBuf::new(8192, alignment).unwrap();
+        assert_eq!(child.read_at(0, &mut rbuf).await.unwrap(), 8192);
+        assert!(rbuf.as_slice()[.. 4096].iter().all(|b| *b == 0));
+        assert!(rbuf.as_slice()[4096 ..].iter().all(|b| *b == 0xa5));
+
+        // The range must be aligned on the block size.
+        assert!(matches!(
+            child.unmap(100, 4096).await,
+            Err(ChildIoError::UnmapError { .. })
+        ));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
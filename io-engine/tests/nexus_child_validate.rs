@@ -0,0 +1,51 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildError, ChildState},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ValidateNexus";
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn nexus_child_validate_descriptor() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            32 * 1024 * 1024,
+            None,
+            &[CHILD_1.to_string(), CHILD_2.to_string()],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // Healthy children pass the validation, and stay open.
+        nexus.validate_child_descriptors().await;
+        for uri in [CHILD_1, CHILD_2] {
+            let child = nexus.child(uri).unwrap();
+            assert!(child.validate_descriptor().await.is_ok());
+            assert_eq!(child.state(), ChildState::Open);
+        }
+
+        // A closed child has no descriptor left to validate, and is skipped
+        // by the nexus.
+        let device = nexus.child(CHILD_2).unwrap().get_device_name().unwrap();
+        nexus.close_child(&device).await.unwrap();
+        assert!(matches!(
+            nexus.child(CHILD_2).unwrap().validate_descriptor().await,
+            Err(ChildError::NoDescriptor {})
+        ));
+        nexus.validate_child_descriptors().await;
+        assert_eq!(nexus.child(CHILD_1).unwrap().state(), ChildState::Open);
+        assert_eq!(nexus.child(CHILD_2).unwrap().state(), ChildState::Closed);
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
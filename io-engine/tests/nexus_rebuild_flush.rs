@@ -0,0 +1,71 @@
+#![cfg(feature = "fault-injection")]
+
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ChildState, FaultReason},
+    core::{
+        fault_injection::{
+            add_fault_injection,
+            FaultDomain,
+            FaultIoOperation,
+            FaultIoStage,
+            InjectionBuilder,
+        },
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "RebuildFlushNexus";
+static NEXUS_SIZE: u64 = 10 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=10";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=10";
+
+#[tokio::test]
+async fn nexus_rebuild_flush_failure() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+
+        // Fail the flush of the rebuilt data.
+        let device = nexus.child(CHILD_2).unwrap().get_device_name().unwrap();
+        add_fault_injection(
+            InjectionBuilder::default()
+                .with_domain(FaultDomain::BlockDevice)
+                .with_device_name(device)
+                .with_io_operation(FaultIoOperation::Flush)
+                .with_io_stage(FaultIoStage::Submission)
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let done = nexus.start_rebuild(CHILD_2).await.unwrap();
+        done.await.unwrap();
+
+        // The child is faulted instead of being marked in sync.
+        for _ in 0 .. 100 {
+            if !nexus.child(CHILD_2).unwrap().state().is_open_or_init() {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(50)).await.unwrap();
+        }
+        let child = nexus.child(CHILD_2).unwrap();
+        assert_eq!(
+            child.state(),
+            ChildState::Faulted(FaultReason::RebuildFailed)
+        );
+        assert!(!child.is_healthy());
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
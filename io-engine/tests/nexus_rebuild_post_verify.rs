@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::MayastorCliArgs,
+    rebuild::RebuildVerifyOutcome,
+    subsys::{Config, NexusOpts, PostRebuildVerify},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "PostVerifyNexus";
+static NEXUS_SIZE: u64 = 32 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn nexus_rebuild_post_verify() {
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            rebuild_post_verify: PostRebuildVerify::Full,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+        nexus.start_rebuild(CHILD_2).await.unwrap();
+    })
+    .await;
+
+    // Wait for the rebuild and its verification to complete.
+    let mut history = Vec::new();
+    for _ in 0 .. 100 {
+        history = ms
+            .spawn(async {
+                nexus_lookup_mut(NEXUS_NAME).unwrap().rebuild_history()
+            })
+            .await;
+        if !history.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(history.len(), 1);
+    assert!(matches!(
+        history[0].verify_outcome,
+        Some(RebuildVerifyOutcome::Passed { .. })
+    ));
+
+    ms.spawn(async {
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        assert!(nexus.child(CHILD_2).unwrap().is_healthy());
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
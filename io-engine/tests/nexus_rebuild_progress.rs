@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use io_engine::{
+    bdev::nexus::{
+        nexus_create,
+        nexus_lookup_mut,
+        ChildRebuildUpdate,
+        ChildState,
+    },
+    core::MayastorCliArgs,
+    rebuild::RebuildState,
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "ProgressNexus";
+static NEXUS_SIZE: u64 = 32 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn nexus_rebuild_progress() {
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    let mut updates = ms
+        .spawn(async {
+            nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+                .await
+                .unwrap();
+
+            let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+            nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+            nexus.start_rebuild(CHILD_2).await.unwrap();
+            nexus.child(CHILD_2).unwrap().subscribe_rebuild().unwrap()
+        })
+        .await;
+
+    let mut last_progress = 0;
+    let mut finished = None;
+    while let Some(update) =
+        tokio::time::timeout(Duration::from_secs(10), updates.next())
+            .await
+            .unwrap()
+    {
+        match update {
+            ChildRebuildUpdate::Progress(p) => {
+                assert!(finished.is_none());
+                assert!(p.progress >= last_progress);
+                assert!(p.bytes_recovered <= p.bytes_total);
+                last_progress = p.progress;
+            }
+            ChildRebuildUpdate::Finished {
+                state,
+                child_state,
+            } => finished = Some((state, child_state)),
+        }
+    }
+    assert_eq!(last_progress, 100);
+    assert_eq!(finished, Some((RebuildState::Completed, ChildState::Open)));
+
+    ms.spawn(async {
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,50 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, queued_rebuild_count},
+    core::MayastorCliArgs,
+    subsys::{Config, NexusOpts},
+};
+
+pub mod common;
+
+static NEXUS_NAME: &str = "RebuildQueueNexus";
+static NEXUS_SIZE: u64 = 60 * 1024 * 1024;
+static CHILD_1: &str = "malloc:///malloc0?blk_size=512&size_mb=64";
+static CHILD_2: &str = "malloc:///malloc1?blk_size=512&size_mb=64";
+static CHILD_3: &str = "malloc:///malloc2?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn nexus_rebuild_queue_child_removed() {
+    Config::get_or_init(|| Config {
+        nexus_opts: NexusOpts {
+            max_concurrent_rebuilds: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+    let ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        nexus_create(NEXUS_NAME, NEXUS_SIZE, None, &[CHILD_1.to_string()])
+            .await
+            .unwrap();
+
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD_2, true).await.unwrap();
+        nexus.as_mut().add_child(CHILD_3, true).await.unwrap();
+
+        // Keep the first rebuild active, so that the second one is queued.
+        let _ = nexus.start_rebuild(CHILD_2).await.unwrap();
+        nexus.pause_rebuild(CHILD_2).await.unwrap();
+        let queued = nexus.start_rebuild(CHILD_3).await.unwrap();
+        assert_eq!(queued_rebuild_count(), 1);
+
+        // Removing the child drops its queued rebuild.
+        nexus.as_mut().remove_child(CHILD_3).await.unwrap();
+        assert_eq!(queued_rebuild_count(), 0);
+        assert!(queued.await.is_err());
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,34 @@
+use io_engine::core::runtime::{self, RuntimeError, RuntimeOpts};
+
+#[tokio::test]
+async fn runtime_init_precedence() {
+    // the environment takes precedence over the defaults
+    std::env::set_var("RUNTIME_WORKER_THREADS", "2");
+    std::env::set_var("RUNTIME_MAX_BLOCKING_THREADS", "3");
+    assert_eq!(
+        RuntimeOpts::default(),
+        RuntimeOpts {
+            worker_threads: 2,
+            max_blocking_threads: 3,
+        }
+    );
+
+    // and an explicit init over the environment
+    let opts = RuntimeOpts {
+        worker_threads: 1,
+        max_blocking_threads: 5,
+    };
+    runtime::init(opts).unwrap();
+    assert_eq!(runtime::spawn_handle(async { 7 }).await.unwrap(), 7);
+    assert_eq!(runtime::metrics().max_blocking_threads, 5);
+
+    // the runtime is built with the options of the first init
+    assert!(matches!(
+        runtime::init(RuntimeOpts {
+            worker_threads: 4,
+            max_blocking_threads: 6,
+        }),
+        Err(RuntimeError::AlreadyInitialised { opts: o }) if o == opts
+    ));
+    assert_eq!(runtime::metrics().max_blocking_threads, 5);
+}
@@ -0,0 +1,21 @@
+use io_engine::core::runtime::{self, RuntimeError, RuntimeOpts};
+
+#[tokio::test]
+async fn runtime_init_after_build() {
+    std::env::set_var("RUNTIME_MAX_BLOCKING_THREADS", "3");
+
+    // using the runtime builds it from the environment
+    assert_eq!(runtime::spawn_handle(async { 7 }).await.unwrap(), 7);
+    let built = RuntimeOpts::default();
+    assert_eq!(runtime::metrics().max_blocking_threads, 3);
+
+    // so it is too late to initialise it
+    assert!(matches!(
+        runtime::init(RuntimeOpts {
+            worker_threads: 1,
+            max_blocking_threads: 5,
+        }),
+        Err(RuntimeError::AlreadyInitialised { opts }) if opts == built
+    ));
+    assert_eq!(runtime::metrics().max_blocking_threads, 3);
+}
@@ -0,0 +1,36 @@
+use std::{sync::mpsc, time::Duration};
+
+use io_engine::core::{runtime, MayastorCliArgs};
+
+pub mod common;
+
+#[tokio::test]
+async fn runtime_task_metrics() {
+    let _ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    let before = runtime::metrics();
+    assert!(before.max_blocking_threads > 0);
+
+    // keep a future and a blocking task running until released
+    let (future_tx, future_rx) = tokio::sync::oneshot::channel::<()>();
+    let future = runtime::spawn_handle(async move {
+        future_rx.await.ok();
+    });
+    let (blocking_tx, blocking_rx) = mpsc::channel::<()>();
+    let blocking = runtime::spawn_blocking(move || {
+        blocking_rx.recv().ok();
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let running = runtime::metrics();
+    assert!(running.active_tasks > before.active_tasks);
+    assert!(running.active_blocking_tasks > before.active_blocking_tasks);
+
+    future_tx.send(()).unwrap();
+    blocking_tx.send(()).unwrap();
+    future.await.unwrap();
+    blocking.await.unwrap();
+
+    let after = runtime::metrics();
+    assert!(after.completed_tasks >= running.completed_tasks + 2);
+}
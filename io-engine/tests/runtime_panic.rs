@@ -0,0 +1,46 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use io_engine::core::{runtime, MayastorCliArgs};
+
+pub mod common;
+
+static PANICS: AtomicUsize = AtomicUsize::new(0);
+static MESSAGES: Mutex<Vec<(bool, String)>> = Mutex::new(Vec::new());
+
+#[tokio::test]
+async fn runtime_task_panic() {
+    let _ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    runtime::set_panic_handler(|panic| {
+        PANICS.fetch_add(1, Ordering::SeqCst);
+        MESSAGES
+            .lock()
+            .unwrap()
+            .push((panic.blocking, panic.message.clone()));
+    })
+    .unwrap();
+    assert!(runtime::set_panic_handler(|_| {}).is_err());
+
+    // the handle of the task still reports the panic
+    let err = runtime::spawn_handle(async { panic!("future panic") })
+        .await
+        .unwrap_err();
+    assert!(err.is_panic());
+
+    let err = runtime::spawn_blocking(|| -> u32 { panic!("blocking panic") })
+        .await
+        .unwrap_err();
+    assert!(err.is_panic());
+
+    // tasks which complete are left alone
+    assert_eq!(runtime::spawn_handle(async { 7 }).await.unwrap(), 7);
+    assert_eq!(runtime::spawn_blocking(|| 7).await.unwrap(), 7);
+
+    assert_eq!(PANICS.load(Ordering::SeqCst), 2);
+    let messages = MESSAGES.lock().unwrap();
+    assert!(messages.contains(&(false, "future panic".to_string())));
+    assert!(messages.contains(&(true, "blocking panic".to_string())));
+}
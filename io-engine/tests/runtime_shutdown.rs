@@ -0,0 +1,66 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use io_engine::core::{runtime, MayastorCliArgs};
+
+pub mod common;
+
+#[test]
+fn runtime_shutdown() {
+    let _ms = common::MayastorTest::new(MayastorCliArgs::default());
+
+    let blocking_done = Arc::new(AtomicBool::new(false));
+    let future_done = Arc::new(AtomicBool::new(false));
+
+    runtime::spawn_blocking({
+        let done = blocking_done.clone();
+        move || {
+            std::thread::sleep(Duration::from_millis(100));
+            done.store(true, Ordering::SeqCst);
+        }
+    });
+    runtime::spawn({
+        let done = future_done.clone();
+        async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            done.store(true, Ordering::SeqCst);
+        }
+    });
+    // never completes on its own
+    let stuck = runtime::spawn_handle(std::future::pending::<()>());
+
+    // the stuck task is reported once the timeout expires, after the other
+    // tasks have completed
+    assert!(!runtime::shutdown(Duration::from_secs(1)));
+    assert!(runtime::is_shut_down());
+    assert!(blocking_done.load(Ordering::SeqCst));
+    assert!(future_done.load(Ordering::SeqCst));
+    // the stuck task is named as still in flight
+    let tasks = runtime::tasks_in_flight();
+    assert!(
+        tasks.iter().any(|(task, _)| task.contains("Pending")),
+        "tasks in flight: {tasks:?}"
+    );
+    stuck.abort();
+
+    // new tasks are no longer run
+    let ran = Arc::new(AtomicBool::new(false));
+    runtime::spawn({
+        let ran = ran.clone();
+        async move {
+            ran.store(true, Ordering::SeqCst);
+        }
+    });
+    let handle = runtime::spawn_handle(async { 7 });
+    let err = futures::executor::block_on(handle).unwrap_err();
+    assert!(err.is_cancelled());
+    let handle = runtime::spawn_blocking(|| 7);
+    let err = futures::executor::block_on(handle).unwrap_err();
+    assert!(err.is_cancelled());
+    assert!(!ran.load(Ordering::SeqCst));
+}
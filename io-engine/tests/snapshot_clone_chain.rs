@@ -0,0 +1,97 @@
+pub mod common;
+
+use chrono::Utc;
+use io_engine::{
+    core::{
+        CloneParams,
+        LogicalVolume,
+        MayastorCliArgs,
+        SnapshotOps,
+        SnapshotParams,
+    },
+    lvs::{Lvol, Lvs, LvsError},
+    pool_backend::{PoolArgs, PoolBackend},
+    subsys::{Config, SnapshotOpts},
+};
+use io_engine_tests::MayastorTest;
+use uuid::Uuid;
+
+static LVOL_SIZE: u64 = 24 * 1024 * 1024;
+
+async fn snapshot(lvol: &Lvol, name: &str) -> Result<Lvol, LvsError> {
+    lvol.create_snapshot(SnapshotParams::new(
+        Some("chain_e1".to_string()),
+        Some(lvol.uuid()),
+        Some(Uuid::new_v4().to_string()),
+        Some(name.to_string()),
+        Some(Uuid::new_v4().to_string()),
+        Some(Utc::now().to_string()),
+        false,
+    ))
+    .await
+}
+
+async fn clone(snapshot: &Lvol, name: &str) -> Result<Lvol, LvsError> {
+    snapshot
+        .create_clone(CloneParams::new(
+            Some(name.to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot.uuid()),
+            Some(Utc::now().to_string()),
+        ))
+        .await
+}
+
+#[tokio::test]
+async fn snapshot_clone_chain_depth_limit() {
+    // The limit is off by default.
+    Config::get_or_init(|| Config {
+        snapshot_opts: SnapshotOpts {
+            max_clone_chain_depth: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .apply();
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(PoolArgs {
+            name: "chain_pool".to_string(),
+            disks: vec!["malloc:///chain_disk?size_mb=128".to_string()],
+            uuid: None,
+            cluster_size: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .expect("Failed to create test pool");
+        let lvol = pool
+            .create_lvol(
+                "chain_lvol",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        // One clone deep: within the limit.
+        let snap1 = snapshot(&lvol, "chain_snap1").await.unwrap();
+        let clone1 = clone(&snap1, "chain_clone1").await.unwrap();
+        let snap2 = snapshot(&clone1, "chain_snap2").await.unwrap();
+
+        // Two clones deep: rejected.
+        assert!(matches!(
+            clone(&snap2, "chain_clone2").await,
+            Err(LvsError::CloneChainTooDeep {
+                limit: 1,
+                ..
+            })
+        ));
+        assert!(snap2.list_clones_by_snapshot_uuid().is_empty());
+
+        pool.destroy().await.expect("Failed to destroy test pool");
+    })
+    .await;
+}
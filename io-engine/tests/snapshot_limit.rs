@@ -0,0 +1,89 @@
+pub mod common;
+
+use chrono::Utc;
+use io_engine::{
+    core::{
+        CloneParams,
+        LogicalVolume,
+        MayastorCliArgs,
+        SnapshotOps,
+        SnapshotParams,
+    },
+    lvs::{Lvol, Lvs, LvsError},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+use io_engine_tests::MayastorTest;
+use uuid::Uuid;
+
+static LVOL_SIZE: u64 = 24 * 1024 * 1024;
+
+async fn snapshot(lvol: &Lvol, name: &str) -> Result<Lvol, LvsError> {
+    lvol.create_snapshot(SnapshotParams::new(
+        Some("limit_e1".to_string()),
+        Some(lvol.uuid()),
+        Some(Uuid::new_v4().to_string()),
+        Some(name.to_string()),
+        Some(Uuid::new_v4().to_string()),
+        Some(Utc::now().to_string()),
+        false,
+    ))
+    .await
+}
+
+#[tokio::test]
+async fn snapshot_per_volume_limit() {
+    // The limit is read once, when the configuration is initialized.
+    std::env::set_var("SNAPSHOT_MAX_PER_VOLUME", "2");
+    std::env::set_var("SNAPSHOT_LIMIT_EXCLUDES_DISCARDED", "true");
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(PoolArgs {
+            name: "limit_pool".to_string(),
+            disks: vec!["malloc:///limit_disk?size_mb=128".to_string()],
+            uuid: None,
+            cluster_size: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .expect("Failed to create test pool");
+        let lvol = pool
+            .create_lvol(
+                "limit_lvol",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        snapshot(&lvol, "limit_snap1").await.unwrap();
+        let snap2 = snapshot(&lvol, "limit_snap2").await.unwrap();
+        assert!(matches!(
+            snapshot(&lvol, "limit_snap3").await,
+            Err(LvsError::SnapshotLimitExceeded {
+                count: 2,
+                limit: 2,
+                ..
+            })
+        ));
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 2);
+
+        // A discarded snapshot does not count towards the limit.
+        snap2
+            .create_clone(CloneParams::new(
+                Some("limit_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snap2.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .unwrap();
+        snap2.destroy_snapshot().await.unwrap();
+        snapshot(&lvol, "limit_snap3").await.unwrap();
+
+        pool.destroy().await.expect("Failed to destroy test pool");
+    })
+    .await;
+}
@@ -19,30 +19,68 @@ use common::{bdev_io, compose::MayastorTest};
 
 use io_engine::{
     bdev::{device_create, device_open},
+    constants::EVENTING_TARGET,
     core::{
         CloneParams,
         CloneXattrs,
+        ClusterRange,
         LogicalVolume,
         MayastorCliArgs,
+        SnapshotDescriptorError,
+        SnapshotHookId,
         SnapshotParams,
+        SnapshotPostCreateHook,
+        SnapshotPreCreateHook,
         SnapshotXattrs,
+        TopologyEdgeKind,
+        TopologyNodeKind,
         UntypedBdev,
     },
-    lvs::{Lvol, Lvs, LvsLvol},
+    eventing::Event,
+    lvs::{
+        cancel_clone_detach,
+        clone_detach_progress,
+        clone_prefetch_progress,
+        ClonePrefetchState,
+        Lvol,
+        LvolSnapshotIter,
+        Lvs,
+        LvsError,
+        LvsLvol,
+    },
     pool_backend::PoolArgs,
+    sleep::mayastor_sleep,
 };
 
-use chrono::Utc;
+use events_api::event::{EventAction, EventCategory};
+
+use chrono::{SecondsFormat, Utc};
+use futures::{FutureExt, StreamExt};
 use io_engine::{
     core::{
-        snapshot::VolumeSnapshotDescriptor,
+        snapshot::{derive_snapshot_uuid, VolumeSnapshotDescriptor},
         SnapshotDescriptor,
         SnapshotOps,
     },
     pool_backend::PoolBackend,
 };
 use log::info;
-use std::{convert::TryFrom, str};
+use std::{
+    convert::TryFrom,
+    fmt,
+    str,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+use tracing::{field::Field, instrument::WithSubscriber};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt},
+    Layer,
+};
 use uuid::Uuid;
 
 static MAYASTOR: OnceCell<MayastorTest> = OnceCell::new();
@@ -291,9 +329,10 @@ fn check_snapshot_descriptor(
         "Snapshot txn ID doesn't match"
     );
     assert_eq!(
-        params.create_time().unwrap(),
+        params.create_time().unwrap().unwrap(),
         snap_params
             .create_time()
+            .expect("Snapshot descriptor has an invalid create time")
             .expect("Snapshot descriptor has no snapshot createtime"),
         "Snapshot CreateTime doesn't match"
     );
@@ -504,6 +543,87 @@ async fn test_lvol_list_snapshot() {
     .await;
 }
 
+#[tokio::test]
+async fn test_lvol_snapshot_stream() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool53",
+            "malloc:///disk53?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol53",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        // A replica without snapshots streams nothing.
+        assert_eq!(LvolSnapshotIter::new(lvol.clone()).count().await, 0);
+
+        let mut snapshots = Vec::new();
+        for i in 1 ..= 3 {
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some("lvol53_e1".to_string()),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(format!("lvol53_snap{i}")),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+            snapshots.push(snapshot.uuid());
+        }
+
+        // The snapshot stream walks the same parent chain, from the latest
+        // snapshot to the oldest one.
+        let streamed: Vec<String> = LvolSnapshotIter::new(lvol.clone())
+            .map(|s| s.snapshot_lvol().uuid())
+            .collect()
+            .await;
+        assert_eq!(
+            streamed,
+            lvol.list_snapshot_by_source_uuid()
+                .iter()
+                .map(|s| s.snapshot_lvol().uuid())
+                .collect::<Vec<_>>()
+        );
+        snapshots.reverse();
+        assert_eq!(streamed, snapshots);
+
+        // The stream is lazy, and can be combined.
+        let first: Vec<String> = LvolSnapshotIter::new(lvol.clone())
+            .take(1)
+            .map(|s| s.snapshot_lvol().uuid())
+            .collect()
+            .await;
+        assert_eq!(first, snapshots[.. 1]);
+        let named = LvolSnapshotIter::new(lvol.clone())
+            .filter(|s| {
+                futures::future::ready(
+                    s.snapshot_params().name().as_deref()
+                        == Some("lvol53_snap2"),
+                )
+            })
+            .count()
+            .await;
+        assert_eq!(named, 1);
+
+        pool.destroy().await.expect("Failed to destroy test pool");
+    })
+    .await;
+}
+
 #[tokio::test]
 async fn test_list_all_snapshots() {
     let ms = get_ms();
@@ -1844,3 +1964,3172 @@ async fn test_clone_snapshot_usage_post_clone_destroy() {
     })
     .await;
 }
+
+/// Test subscriber layer collecting the events published on the eventing
+/// target, formatted as their recorded fields.
+#[derive(Clone, Default)]
+struct EventCapture(Arc<Mutex<Vec<String>>>);
+
+impl EventCapture {
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for EventCapture {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != EVENTING_TARGET {
+            return;
+        }
+        let mut fields = String::new();
+        event.record(&mut |field: &Field, value: &dyn fmt::Debug| {
+            fields.push_str(&format!("{}={value:?} ", field.name()));
+        });
+        self.0.lock().unwrap().push(fields);
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_create_event_payload() {
+    let ms = get_ms();
+    const LVOL_NAME: &str = "lvol18";
+
+    ms.spawn(async move {
+        // Create a pool and lvol.
+        let pool = create_test_pool(
+            "pool18",
+            "malloc:///disk18?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                LVOL_NAME,
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let entity_id = String::from("lvol18_e1");
+        let snap_name = String::from("lvol18_snap1");
+        let snapshot_uuid = Uuid::new_v4().to_string();
+        let snapshot_params = SnapshotParams::new(
+            Some(entity_id.clone()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snap_name.clone()),
+            Some(snapshot_uuid.clone()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        // Subscribe to the events published while the snapshot is created.
+        let capture = EventCapture::default();
+        let snapshot = lvol
+            .create_snapshot(snapshot_params)
+            .with_subscriber(
+                tracing_subscriber::registry().with(capture.clone()),
+            )
+            .await
+            .expect("Failed to create a snapshot");
+
+        let events = capture.events();
+        let published = events
+            .iter()
+            .filter(|e| e.contains(&snapshot_uuid))
+            .collect::<Vec<_>>();
+        assert_eq!(published.len(), 1, "events: {events:?}");
+        for field in [snap_name.as_str(), &entity_id, &lvol.uuid(), "pool18"] {
+            assert!(
+                published[0].contains(field),
+                "{field} missing from the create event {}",
+                published[0]
+            );
+        }
+
+        let descr = snapshot
+            .snapshot_descriptor(None)
+            .expect("Failed to get snapshot descriptor");
+        let event = Event::event(&descr, EventAction::Create);
+
+        assert_eq!(event.category, EventCategory::Snapshot as i32);
+        assert_eq!(event.action, EventAction::Create as i32);
+        assert_eq!(event.target, snapshot_uuid);
+
+        let details = event
+            .metadata
+            .and_then(|m| m.source)
+            .and_then(|s| s.event_details)
+            .expect("Event has no details");
+        let replica = details
+            .replica_details
+            .expect("Event has no snapshot name and pool details");
+        assert_eq!(replica.replica_name, snap_name);
+        assert_eq!(replica.pool_name, "pool18");
+        let snapshot_details = details
+            .snapshot_details
+            .expect("Event has no snapshot details");
+        assert_eq!(snapshot_details.parent_id, lvol.uuid());
+        assert_eq!(snapshot_details.entity_id, entity_id);
+
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_snapshots_in_pool() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        // Create two pools with a snapshotted lvol each.
+        let mut pools = Vec::new();
+        for idx in [19, 20] {
+            let pool = create_test_pool(
+                &format!("pool{idx}"),
+                format!("malloc:///disk{idx}?size_mb=64"),
+                None,
+            )
+            .await;
+            let lvol = pool
+                .create_lvol(
+                    &format!("lvol{idx}"),
+                    LVOL_SIZE,
+                    Some(&Uuid::new_v4().to_string()),
+                    false,
+                    None,
+                )
+                .await
+                .expect("Failed to create test lvol");
+
+            let snapshot_params = SnapshotParams::new(
+                Some(format!("lvol{idx}_e1")),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(format!("lvol{idx}_snap1")),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            );
+            lvol.create_snapshot(snapshot_params)
+                .await
+                .expect("Failed to create a snapshot");
+            pools.push((idx, pool));
+        }
+
+        // Every pool only reports its own snapshot.
+        for (idx, pool) in &pools {
+            let snapshots = Lvol::list_snapshots_in_pool(pool, None);
+            assert_eq!(snapshots.len(), 1, "Number of snapshots mismatch");
+            assert_eq!(
+                snapshots[0].snapshot_lvol().name(),
+                format!("lvol{idx}_snap1")
+            );
+        }
+        assert!(Lvol::list_all_snapshots(None).len() >= pools.len());
+
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_tree_usage_summary() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool21",
+            "malloc:///disk21?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol21",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol21_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol21_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot_lvol = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+
+        let clone_param = CloneParams::new(
+            Some("lvol21_snap1_clone_1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot_lvol.uuid()),
+            Some(Utc::now().to_string()),
+        );
+        let clone = snapshot_lvol
+            .create_clone(clone_param)
+            .await
+            .expect("Failed to create a clone");
+
+        // The summary is the same whichever lvol of the tree it starts from.
+        let summary = Lvol::tree_usage_summary(&lvol);
+        assert_eq!(summary, Lvol::tree_usage_summary(&clone));
+
+        // The snapshot is shared, the replica and the clone are the leaves.
+        assert_eq!(summary.leaf_unique_bytes.len(), 2);
+        assert!(summary.leaf_unique_bytes.contains_key(&lvol.uuid()));
+        assert!(summary.leaf_unique_bytes.contains_key(&clone.uuid()));
+        assert_eq!(summary.shared_bytes, snapshot_lvol.usage().allocated_bytes);
+        assert_eq!(
+            summary.total_allocated_bytes,
+            summary.shared_bytes
+                + summary.leaf_unique_bytes.values().sum::<u64>()
+        );
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_detach_clone() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool22",
+            "malloc:///disk22?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol22",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol22_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol22_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot_lvol = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+
+        let clone_param = CloneParams::new(
+            Some("lvol22_snap1_clone_1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot_lvol.uuid()),
+            Some(Utc::now().to_string()),
+        );
+        let clone = snapshot_lvol
+            .create_clone(clone_param)
+            .await
+            .expect("Failed to create a clone");
+        assert!(clone.is_snapshot_clone().is_some());
+
+        // A clone in use cannot be detached.
+        let desc = clone.as_bdev().open(true).unwrap();
+        assert!(desc.claim());
+        assert!(matches!(
+            clone.detach_clone().await,
+            Err(LvsError::CloneDetach { .. })
+        ));
+        desc.unclaim();
+        drop(desc);
+        assert!(clone.is_snapshot_clone().is_some());
+
+        // A cancelled detach leaves the clone dependent on its snapshot. The
+        // detach is polled once, so that it is registered and waits for the
+        // copy of its first cluster.
+        let uuid = clone.uuid();
+        let mut detach = Box::pin(clone.detach_clone());
+        assert!(futures::poll!(detach.as_mut()).is_pending());
+        let progress = clone_detach_progress(&uuid).unwrap();
+        assert_eq!(progress.clusters_copied, 0);
+        assert!(progress.clusters_total > 1);
+        assert!(cancel_clone_detach(&uuid));
+        assert!(matches!(
+            detach.await,
+            Err(LvsError::CloneDetachCancelled { .. })
+        ));
+        assert!(clone_detach_progress(&uuid).is_none());
+        assert!(!cancel_clone_detach(&uuid));
+        assert!(clone.is_snapshot_clone().is_some());
+
+        clone
+            .detach_clone()
+            .await
+            .expect("Failed to detach the clone");
+        assert!(clone_detach_progress(&uuid).is_none());
+        assert!(clone.is_snapshot_clone().is_none());
+        assert!(!clone.is_clone());
+        assert!(snapshot_lvol.list_clones_by_snapshot_uuid().is_empty());
+
+        // A detached clone cannot be detached again.
+        assert!(clone.detach_clone().await.is_err());
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_detach_clone_destroys_discarded_snapshot() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool45",
+            "malloc:///disk45?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol45",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol45_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol45_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot_lvol = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+        let snapshot_uuid = snapshot_lvol.uuid();
+
+        let clone_param = CloneParams::new(
+            Some("lvol45_snap1_clone_1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot_uuid.clone()),
+            Some(Utc::now().to_string()),
+        );
+        let clone = snapshot_lvol
+            .create_clone(clone_param)
+            .await
+            .expect("Failed to create a clone");
+
+        // The snapshot is retained for its clone.
+        snapshot_lvol
+            .destroy_snapshot()
+            .await
+            .expect("Failed to discard the snapshot");
+        let snapshot_lvol = Lvol::try_from(
+            UntypedBdev::lookup_by_uuid_str(&snapshot_uuid).unwrap(),
+        )
+        .unwrap();
+        assert!(snapshot_lvol.is_discarded_snapshot());
+
+        // Once the clone no longer refers to it, it is destroyed at once.
+        clone
+            .detach_clone()
+            .await
+            .expect("Failed to detach the clone");
+        assert!(UntypedBdev::lookup_by_uuid_str(&snapshot_uuid).is_none());
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+        lvol.destroy().await.expect("Failed to destroy the lvol");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_plan_destroy() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool46",
+            "malloc:///disk46?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol46",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol46_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol46_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot_lvol = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+        let snapshot_uuid = snapshot_lvol.uuid();
+
+        let clone_param = CloneParams::new(
+            Some("lvol46_snap1_clone_1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot_uuid.clone()),
+            Some(Utc::now().to_string()),
+        );
+        let clone = snapshot_lvol
+            .create_clone(clone_param)
+            .await
+            .expect("Failed to create a clone");
+
+        // The snapshot has a clone, it would only be discarded.
+        let plan = snapshot_lvol.plan_destroy().expect("Failed to plan");
+        assert!(plan.destroyed.is_empty());
+        assert_eq!(plan.discarded.len(), 1);
+        assert_eq!(plan.discarded[0].uuid, snapshot_uuid);
+        assert_eq!(plan.reclaimed_bytes, 0);
+
+        // The snapshot is not discarded yet, only the clone would go.
+        let plan = clone.plan_destroy().expect("Failed to plan");
+        assert_eq!(plan.destroyed.len(), 1);
+        assert_eq!(plan.destroyed[0].uuid, clone.uuid());
+        assert_eq!(plan.reclaimed_bytes, clone.usage().allocated_bytes);
+
+        snapshot_lvol
+            .destroy_snapshot()
+            .await
+            .expect("Failed to discard the snapshot");
+
+        // The discarded snapshot would go along with its last clone.
+        let plan = clone.plan_destroy().expect("Failed to plan");
+        assert_eq!(
+            plan.destroyed
+                .iter()
+                .map(|n| n.uuid.clone())
+                .collect::<Vec<_>>(),
+            vec![clone.uuid(), snapshot_uuid.clone()]
+        );
+        assert!(UntypedBdev::lookup_by_uuid_str(&snapshot_uuid).is_some());
+
+        clone
+            .destroy_replica()
+            .await
+            .expect("Failed to destroy the clone");
+        assert!(UntypedBdev::lookup_by_uuid_str(&snapshot_uuid).is_none());
+        lvol.destroy().await.expect("Failed to destroy the lvol");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_plan_destroy_without_clones() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool57",
+            "malloc:///disk57?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let cluster_size = pool.blob_cluster_size();
+        let lvol = pool
+            .create_lvol(
+                "lvol57",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        // A replica without snapshots frees all of its clusters.
+        let plan = lvol.plan_destroy().expect("Failed to plan");
+        assert_eq!(plan.destroyed.len(), 1);
+        assert_eq!(plan.destroyed[0].uuid, lvol.uuid());
+        assert!(plan.discarded.is_empty());
+        assert_eq!(plan.reclaimed_bytes, lvol.usage().allocated_bytes);
+
+        let snapshot = |name: &str| {
+            SnapshotParams::new(
+                Some("lvol57_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+        let snapshot1 = lvol
+            .create_snapshot(snapshot("lvol57_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        assert!(snapshot1.usage().allocated_bytes > cluster_size);
+
+        // The clusters of the snapshot are merged into the replica, apart
+        // from the one the replica has overwritten.
+        bdev_io::write_some("lvol57", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to write data to volume");
+        let plan = snapshot1.plan_destroy().expect("Failed to plan");
+        assert_eq!(plan.destroyed.len(), 1);
+        assert_eq!(plan.destroyed[0].uuid, snapshot1.uuid());
+        assert!(plan.discarded.is_empty());
+        assert_eq!(plan.reclaimed_bytes, cluster_size);
+
+        // A clone of a newer snapshot still depends on the data of the
+        // first snapshot, which would only be discarded.
+        let snapshot2 = lvol
+            .create_snapshot(snapshot("lvol57_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot2
+            .create_clone(CloneParams::new(
+                Some("lvol57_snap2_clone_1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot2.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let plan = snapshot1.plan_destroy().expect("Failed to plan");
+        assert!(plan.destroyed.is_empty());
+        assert_eq!(plan.discarded.len(), 1);
+        assert_eq!(plan.discarded[0].uuid, snapshot1.uuid());
+        assert_eq!(plan.reclaimed_bytes, 0);
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+        lvol.destroy().await.expect("Failed to destroy the lvol");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_required_restore_size() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool23",
+            "malloc:///disk23?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let mut lvol = pool
+            .create_lvol(
+                "lvol23",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol23_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol23_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot_lvol = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+
+        let required = snapshot_lvol.required_restore_size();
+        assert_eq!(required, lvol.size());
+        assert!(snapshot_lvol.validate_restore_target_size(required).is_ok());
+        assert!(snapshot_lvol
+            .validate_restore_target_size(required + 1)
+            .is_ok());
+        assert!(matches!(
+            snapshot_lvol.validate_restore_target_size(required - 1),
+            Err(LvsError::RestoreTargetTooSmall { .. })
+        ));
+
+        // A replica shrunk since the snapshot was taken cannot be restored.
+        lvol.resize_replica(LVOL_SIZE / 2)
+            .await
+            .expect("Failed to shrink the lvol");
+        assert!(matches!(
+            lvol.restore_from_snapshot(&snapshot_lvol).await,
+            Err(LvsError::RestoreTargetTooSmall { .. })
+        ));
+
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_retention_class() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool24",
+            "malloc:///disk24?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol24",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        for (idx, retention_class) in [Some("daily"), None].iter().enumerate() {
+            let mut snapshot_params = SnapshotParams::new(
+                Some("lvol24_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(format!("lvol24_snap{idx}")),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_rfc3339()),
+                false,
+            );
+            if let Some(retention_class) = retention_class {
+                snapshot_params
+                    .set_retention_class(retention_class.to_string());
+            }
+            lvol.create_snapshot(snapshot_params)
+                .await
+                .expect("Failed to create a snapshot");
+        }
+
+        let cutoff = Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(lvol.list_retention_candidates(cutoff, None).len(), 2);
+        let daily = lvol.list_retention_candidates(cutoff, Some("daily"));
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].retention_class(), "daily");
+        assert_eq!(
+            lvol.list_retention_candidates(cutoff, Some("uncategorized"))
+                .len(),
+            1
+        );
+
+        let report = lvol
+            .prune_snapshots_older_than(cutoff, Some("daily"))
+            .await
+            .expect("Failed to prune snapshots");
+        assert_eq!(report.destroyed, 1);
+
+        let remaining = lvol.list_snapshot_by_source_uuid();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].retention_class(), "uncategorized");
+
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_pool_topology() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool25",
+            "malloc:///disk25?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol25",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+        let lvol_uuid = lvol.uuid();
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol25_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol25_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol25_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+
+        let topology = Lvol::pool_topology(&pool);
+        assert_eq!(topology.pool_name, "pool25");
+        assert_eq!(topology.nodes.len(), 3);
+        assert_eq!(topology.edges.len(), 2);
+        assert!(topology.nodes.iter().all(|n| !n.orphaned));
+        assert!(topology.edges.iter().any(|e| e.from == snapshot.uuid()
+            && e.to == lvol_uuid
+            && e.kind == TopologyEdgeKind::Parent));
+        assert!(topology.edges.iter().any(|e| e.from == clone.uuid()
+            && e.to == snapshot.uuid()
+            && e.kind == TopologyEdgeKind::Source));
+
+        // Once the replica is gone, the snapshot refers to a missing lvol.
+        lvol.destroy().await.expect("Failed to destroy replica");
+        let topology = Lvol::pool_topology(&pool);
+        assert_eq!(topology.nodes.len(), 2);
+        let node = topology
+            .nodes
+            .iter()
+            .find(|n| n.uuid == snapshot.uuid())
+            .unwrap();
+        assert_eq!(node.kind, TopologyNodeKind::Snapshot);
+        assert!(node.orphaned);
+        assert!(topology
+            .edges
+            .iter()
+            .any(|e| e.to == lvol_uuid && e.dangling));
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_rooted_orphan_snapshots() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool54",
+            "malloc:///disk54?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+
+        let mut replicas = Vec::new();
+        let mut snapshots = Vec::new();
+        for i in 1 ..= 2 {
+            let lvol = pool
+                .create_lvol(
+                    &format!("lvol54_{i}"),
+                    LVOL_SIZE,
+                    Some(&Uuid::new_v4().to_string()),
+                    false,
+                    None,
+                )
+                .await
+                .expect("Failed to create test lvol");
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some(format!("lvol54_{i}_e1")),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(format!("lvol54_{i}_snap1")),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+            snapshots.push(snapshot.uuid());
+            replicas.push(lvol);
+        }
+        let orphans = || {
+            Lvol::list_rooted_orphan_snapshots()
+                .into_iter()
+                .map(|s| s.snapshot_lvol().uuid())
+                .filter(|uuid| snapshots.contains(uuid))
+                .collect::<Vec<_>>()
+        };
+        assert!(orphans().is_empty());
+
+        // Once its replica is gone, the snapshot is rooted at a missing
+        // parent, while the snapshot of the other replica is not.
+        let lvol = replicas.remove(0);
+        lvol.destroy().await.expect("Failed to destroy replica");
+        assert_eq!(orphans(), snapshots[.. 1]);
+
+        // The orphan still has valid metadata: it is not an invalid snapshot.
+        assert!(!Lvol::list_invalid_snapshots()
+            .iter()
+            .any(|s| s.snapshot_lvol().uuid() == snapshots[0]));
+
+        for lvol in replicas {
+            lvol.destroy().await.expect("Failed to destroy replica");
+        }
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_orphaned_snapshots() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool41",
+            "malloc:///disk41?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol41",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |name: &str| {
+            SnapshotParams::new(
+                Some("lvol41_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+        let snapshot1 = lvol
+            .create_snapshot(params("lvol41_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        let snapshot2 = lvol
+            .create_snapshot(params("lvol41_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot2
+            .create_clone(CloneParams::new(
+                Some("lvol41_snap2_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot2.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let is_orphan = |uuid: String| {
+            Lvol::list_orphaned_snapshots()
+                .iter()
+                .any(|s| s.snapshot_lvol().uuid() == uuid)
+        };
+        assert!(!is_orphan(snapshot1.uuid()));
+
+        // The snapshot with a clone is discarded, and retained for the clone.
+        snapshot2
+            .clone()
+            .destroy_snapshot()
+            .await
+            .expect("Failed to discard the snapshot");
+        assert!(snapshot2.is_discarded_snapshot());
+
+        lvol.destroy().await.expect("Failed to destroy replica");
+        assert!(is_orphan(snapshot1.uuid()));
+        assert!(!is_orphan(snapshot2.uuid()));
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        snapshot1
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy snapshot");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_clone_prefetch() {
+    let ms = get_ms();
+
+    let (clone_uuid, cold_uuid) = ms
+        .spawn(async move {
+            let pool = create_test_pool(
+                "pool42",
+                "malloc:///disk42?size_mb=64".to_string(),
+                None,
+            )
+            .await;
+            let lvol = pool
+                .create_lvol(
+                    "lvol42",
+                    LVOL_SIZE,
+                    Some(&Uuid::new_v4().to_string()),
+                    false,
+                    None,
+                )
+                .await
+                .expect("Failed to create test lvol");
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some("lvol42_e1".to_string()),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some("lvol42_snap1".to_string()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+
+            let clone_params = |name: &str| {
+                CloneParams::new(
+                    Some(name.to_string()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(snapshot.uuid()),
+                    Some(Utc::now().to_string()),
+                )
+            };
+            let mut params = clone_params("lvol42_snap1_clone1");
+            params.set_prefetch(true);
+            let clone = snapshot
+                .create_clone(params)
+                .await
+                .expect("Failed to create a clone");
+            let cold = snapshot
+                .create_clone(clone_params("lvol42_snap1_clone2"))
+                .await
+                .expect("Failed to create a clone");
+            (clone.uuid(), cold.uuid())
+        })
+        .await;
+
+    assert!(clone_prefetch_progress(&cold_uuid).is_none());
+    let mut progress = None;
+    for _ in 0 .. 50 {
+        progress = clone_prefetch_progress(&clone_uuid);
+        if progress.map(|p| p.state) != Some(ClonePrefetchState::Running) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    let progress = progress.expect("Clone is not warmed up");
+    assert_eq!(progress.state, ClonePrefetchState::Completed);
+    assert!(progress.clusters_total > 0);
+    assert_eq!(progress.clusters_read, progress.clusters_total);
+
+    ms.spawn(async move {
+        for uuid in [clone_uuid.clone(), cold_uuid] {
+            let clone = UntypedBdev::lookup_by_uuid_str(&uuid)
+                .and_then(|b| Lvol::try_from(b).ok())
+                .expect("Clone not found");
+            clone.destroy().await.expect("Failed to destroy clone");
+        }
+        // The warm-up is forgotten with its clone.
+        assert!(clone_prefetch_progress(&clone_uuid).is_none());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_read_amplification_factor() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool55",
+            "malloc:///disk55?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol55",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        // Every read of a fully allocated replica is served by its own blob.
+        assert_eq!(lvol.read_amplification_factor(), 1.0);
+
+        let mut snapshots = Vec::new();
+        for i in 1 ..= 2 {
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some("lvol55_e1".to_string()),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(format!("lvol55_snap{i}")),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+            snapshots.push(snapshot);
+        }
+        let clone = snapshots[1]
+            .create_clone(CloneParams::new(
+                Some("lvol55_snap2_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshots[1].uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+
+        // The first snapshot took all the clusters of the replica, while
+        // the second one and the clone allocated none: their reads fall
+        // through every layer down to the first snapshot.
+        assert_eq!(snapshots[0].read_amplification_factor(), 1.0);
+        assert_eq!(snapshots[1].read_amplification_factor(), 2.0);
+        assert_eq!(lvol.read_amplification_factor(), 3.0);
+        assert_eq!(clone.read_amplification_factor(), 3.0);
+
+        // The descriptor surfaces the factor of its snapshot.
+        let descriptor = lvol
+            .list_snapshot_by_source_uuid()
+            .into_iter()
+            .find(|s| s.snapshot_lvol().uuid() == snapshots[1].uuid())
+            .expect("Snapshot not listed");
+        assert_eq!(descriptor.read_amplification(), 2.0);
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        lvol.destroy().await.expect("Failed to destroy replica");
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_snapshots_for_clone() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool56",
+            "malloc:///disk56?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol56",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let mut snapshots = Vec::new();
+        for i in 1 ..= 3 {
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some("lvol56_e1".to_string()),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(format!("lvol56_snap{i}")),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+            snapshots.push(snapshot);
+        }
+        let clone = snapshots[2]
+            .create_clone(CloneParams::new(
+                Some("lvol56_snap3_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshots[2].uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        // A snapshot of the clone does not hold the clone.
+        clone
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol56_e1".to_string()),
+                Some(clone.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol56_clone1_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot of the clone");
+
+        assert!(lvol.list_snapshots_for_clone().is_empty());
+
+        // The clone depends on its source snapshot and on all its
+        // ancestors, listed from the root of the chain.
+        let lineage = clone
+            .list_snapshots_for_clone()
+            .into_iter()
+            .map(|s| s.snapshot_lvol().uuid())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lineage,
+            snapshots.iter().map(|s| s.uuid()).collect::<Vec<_>>()
+        );
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        lvol.destroy().await.expect("Failed to destroy replica");
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_verify_clone_parent() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool26",
+            "malloc:///disk26?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol26",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol26_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol26_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol26_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+
+        assert_eq!(snapshot.num_clones(), 1);
+        assert_eq!(lvol.num_clones(), 0);
+        lvol.verify_clone_parent()
+            .expect("A replica has no source snapshot to verify");
+        clone
+            .verify_clone_parent()
+            .expect("The source snapshot of the clone exists");
+
+        // Simulate the source snapshot being destroyed out of order.
+        clone
+            .set_blob_attr(
+                CloneXattrs::SourceUuid.name(),
+                Uuid::new_v4().to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set clone attribute");
+        assert!(matches!(
+            clone.verify_clone_parent(),
+            Err(LvsError::CloneSourceMissing { .. })
+        ));
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        assert_eq!(snapshot.num_clones(), 0);
+        clean_snapshots(Lvol::list_all_snapshots(None)).await;
+    })
+    .await;
+}
+
+/// Unregisters a snapshot creation hook once dropped, so that a failed test
+/// leaves no hook behind for the other tests.
+struct SnapshotHookGuard(SnapshotHookId);
+
+impl Drop for SnapshotHookGuard {
+    fn drop(&mut self) {
+        Lvol::unregister_create_hook(self.0);
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_create_hooks() {
+    static POST_CREATED: AtomicUsize = AtomicUsize::new(0);
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        // Hooks apply to all snapshots, so only act on this test's ones.
+        let pre_hook: SnapshotPreCreateHook = Arc::new(|_lvol, params| {
+            async move {
+                if params.name().as_deref() == Some("lvol27_snap_abort") {
+                    Err("aborted by test".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            .boxed_local()
+        });
+        let post_hook: SnapshotPostCreateHook = Arc::new(|snapshot| {
+            async move {
+                if snapshot.name().starts_with("lvol27_") {
+                    POST_CREATED.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            .boxed_local()
+        });
+        let pre = SnapshotHookGuard(Lvol::register_pre_create_hook(pre_hook));
+        let post =
+            SnapshotHookGuard(Lvol::register_post_create_hook(post_hook));
+
+        let pool = create_test_pool(
+            "pool27",
+            "malloc:///disk27?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol27",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        for name in ["lvol27_snap1", "lvol27_snap_abort"] {
+            let snapshot_params = SnapshotParams::new(
+                Some("lvol27_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            );
+            let result = lvol.create_snapshot(snapshot_params).await;
+            if name == "lvol27_snap_abort" {
+                assert!(matches!(
+                    result,
+                    Err(LvsError::SnapshotHookFailed { .. })
+                ));
+            } else {
+                result.expect("Failed to create a snapshot");
+            }
+        }
+
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 1);
+        assert_eq!(POST_CREATED.load(Ordering::SeqCst), 1);
+
+        // Once unregistered, the hooks are no longer called.
+        assert!(Lvol::unregister_create_hook(pre.0));
+        assert!(Lvol::unregister_create_hook(post.0));
+        assert!(!Lvol::unregister_create_hook(pre.0));
+        lvol.create_snapshot(SnapshotParams::new(
+            Some("lvol27_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol27_snap_abort".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        ))
+        .await
+        .expect("Failed to create a snapshot");
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 2);
+        assert_eq!(POST_CREATED.load(Ordering::SeqCst), 1);
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_create_snapshots_batch() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool28",
+            "malloc:///disk28?size_mb=128".to_string(),
+            None,
+        )
+        .await;
+        let mut volumes = Vec::new();
+        for name in ["lvol28_a", "lvol28_b"] {
+            volumes.push(
+                pool.create_lvol(
+                    name,
+                    LVOL_SIZE,
+                    Some(&Uuid::new_v4().to_string()),
+                    false,
+                    None,
+                )
+                .await
+                .expect("Failed to create test lvol"),
+            );
+        }
+        let params = |names: [&str; 2]| {
+            volumes
+                .iter()
+                .zip(names)
+                .map(|(v, name)| {
+                    SnapshotParams::new(
+                        Some("lvol28_e1".to_string()),
+                        Some(v.uuid()),
+                        None,
+                        Some(name.to_string()),
+                        Some(Uuid::new_v4().to_string()),
+                        Some(Utc::now().to_string()),
+                        false,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let outcome = Lvol::create_snapshots_batch(
+            &volumes,
+            params(["lvol28_a_snap1", "lvol28_b_snap1"]),
+        )
+        .await;
+        assert!(outcome.is_complete());
+        for snapshot in outcome.results.iter().map(|r| r.as_ref().unwrap()) {
+            assert_eq!(
+                Lvol::get_blob_xattr(
+                    snapshot.blob_checked(),
+                    SnapshotXattrs::TxId.name()
+                ),
+                Some(outcome.txn_id.clone())
+            );
+        }
+        assert!(Lvol::is_snapshot_group_complete(&outcome.txn_id));
+
+        // The name of the 2nd snapshot is already taken.
+        let outcome = Lvol::create_snapshots_batch(
+            &volumes,
+            params(["lvol28_a_snap2", "lvol28_b_snap1"]),
+        )
+        .await;
+        assert!(outcome.is_partial_failure());
+        assert!(outcome.results[0].is_ok());
+        assert!(outcome.results[1].is_err());
+
+        for volume in &volumes {
+            clean_snapshots(volume.list_snapshot_by_source_uuid()).await;
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_rename_snapshot() {
+    const DISK_NAME: &str = "/tmp/disk29.img";
+    const DEVICE_NAME: &str = "aio:///tmp/disk29.img";
+    common::delete_file(&[DISK_NAME.into()]);
+    common::truncate_file(DISK_NAME, 64 * 1024);
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let mut pool =
+            create_test_pool("pool29", DEVICE_NAME.into(), None).await;
+        let lvol = pool
+            .create_lvol(
+                "lvol29",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol29_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol29_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+
+        assert!(snapshot.rename_snapshot("").await.is_err());
+        assert!(matches!(
+            snapshot.rename_snapshot("lvol29").await,
+            Err(LvsError::SnapshotRename { .. })
+        ));
+        assert!(lvol.rename_snapshot("lvol29_renamed").await.is_err());
+
+        let snapshot_uuid = snapshot.uuid();
+        let capture = EventCapture::default();
+        snapshot
+            .rename_snapshot("lvol29_snap1_renamed")
+            .with_subscriber(
+                tracing_subscriber::registry().with(capture.clone()),
+            )
+            .await
+            .expect("Failed to rename snapshot");
+        let events = capture.events();
+        let published = events
+            .iter()
+            .filter(|e| e.contains(&snapshot_uuid))
+            .collect::<Vec<_>>();
+        assert_eq!(published.len(), 1, "events: {events:?}");
+        assert!(published[0].contains("lvol29_snap1_renamed"));
+
+        let snapshots = lvol.list_snapshot_by_source_uuid();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            snapshots[0].snapshot_params().name().as_deref(),
+            Some("lvol29_snap1_renamed")
+        );
+
+        // The new name is persisted in the blobstore metadata.
+        pool.export().await.expect("Failed to export test pool");
+        device_create(DEVICE_NAME).await.unwrap();
+        pool = Lvs::import("pool29", DEVICE_NAME)
+            .await
+            .expect("Failed to import pool");
+        let snapshot = UntypedBdev::lookup_by_uuid_str(&snapshot_uuid)
+            .and_then(|b| Lvol::try_from(b).ok())
+            .expect("Snapshot not imported");
+        assert_eq!(snapshot.name(), "lvol29_snap1_renamed");
+        let descriptor = snapshot
+            .snapshot_descriptor(None)
+            .expect("Imported snapshot has no descriptor");
+        assert_eq!(
+            descriptor.snapshot_params().name().as_deref(),
+            Some("lvol29_snap1_renamed")
+        );
+
+        pool.destroy().await.expect("Failed to destroy test pool");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_try_snapshot_descriptor() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool30",
+            "malloc:///disk30?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol30",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+        let other = pool
+            .create_lvol(
+                "lvol30_other",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot_params = SnapshotParams::new(
+            Some("lvol30_e1".to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol30_snap1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(Utc::now().to_string()),
+            false,
+        );
+        let snapshot = lvol
+            .create_snapshot(snapshot_params)
+            .await
+            .expect("Failed to create a snapshot");
+
+        let descriptor = snapshot
+            .try_snapshot_descriptor(None)
+            .expect("Snapshot should be valid");
+        assert!(descriptor.valid_snapshot());
+        assert!(matches!(
+            snapshot.try_snapshot_descriptor(Some(&other)),
+            Err(SnapshotDescriptorError::ParentMismatch { .. })
+        ));
+        assert!(snapshot.snapshot_descriptor(Some(&other)).is_none());
+
+        // Corrupt the discarded flag of the snapshot.
+        snapshot
+            .set_blob_attr(
+                SnapshotXattrs::DiscardedSnapshot.name().to_string(),
+                "maybe".to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set snapshot attribute");
+
+        match snapshot.try_snapshot_descriptor(None) {
+            Err(SnapshotDescriptorError::InvalidXattr {
+                attr,
+                value,
+                ..
+            }) => {
+                assert_eq!(attr, SnapshotXattrs::DiscardedSnapshot);
+                assert_eq!(value, "maybe");
+            }
+            r => panic!("Unexpected result: {r:?}"),
+        }
+        let descriptor = snapshot
+            .snapshot_descriptor(None)
+            .expect("Snapshot should still be listed");
+        assert!(!descriptor.valid_snapshot());
+
+        snapshot
+            .set_blob_attr(
+                SnapshotXattrs::DiscardedSnapshot.name().to_string(),
+                "false".to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set snapshot attribute");
+        assert!(snapshot.try_snapshot_descriptor(None).is_ok());
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_destroy_replica_tree() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool31",
+            "malloc:///disk31?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol31",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol31_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol31_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol31_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let clone_snapshot = clone
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol31_e1".to_string()),
+                Some(clone.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol31_clone1_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot of the clone");
+
+        // The lvols are gone once the tree is destroyed.
+        let (lvol_uuid, snapshot_uuid, clone_uuid, clone_snapshot_uuid) = (
+            lvol.uuid(),
+            snapshot.uuid(),
+            clone.uuid(),
+            clone_snapshot.uuid(),
+        );
+
+        let report = Lvol::destroy_replica_tree(&lvol)
+            .await
+            .expect("Failed to destroy the replica tree");
+        assert_eq!(report.destroyed.len(), 4);
+        assert_eq!(report.count(TopologyNodeKind::Replica), 1);
+        assert_eq!(report.count(TopologyNodeKind::Snapshot), 2);
+        assert_eq!(report.count(TopologyNodeKind::Clone), 1);
+
+        // Every blob is destroyed after the blobs backed by it: the clone is
+        // backed by the snapshot of the clone, itself backed by the snapshot
+        // of the replica, which backs the replica too.
+        let position = |uuid: &String| {
+            report
+                .destroyed
+                .iter()
+                .position(|n| &n.uuid == uuid)
+                .expect("lvol not destroyed")
+        };
+        assert!(position(&clone_uuid) < position(&clone_snapshot_uuid));
+        assert!(position(&clone_snapshot_uuid) < position(&snapshot_uuid));
+        assert!(position(&lvol_uuid) < position(&snapshot_uuid));
+        assert_eq!(position(&snapshot_uuid), 3);
+
+        for uuid in [lvol_uuid, snapshot_uuid, clone_uuid, clone_snapshot_uuid]
+        {
+            assert!(UntypedBdev::lookup_by_uuid_str(&uuid).is_none());
+        }
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_destroy_replica_tree_held() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool52",
+            "malloc:///disk52?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol52",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol52_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol52_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol52_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+
+        // A claimed clone aborts the destruction before any lvol is
+        // destroyed.
+        let desc = clone.as_bdev().open(true).unwrap();
+        assert!(desc.claim());
+        match Lvol::destroy_replica_tree(&lvol).await {
+            Err(LvsError::TreeNodeHeld {
+                name, ..
+            }) => assert_eq!(name, clone.name()),
+            other => panic!("Unexpected result: {other:?}"),
+        }
+        for uuid in [lvol.uuid(), snapshot.uuid(), clone.uuid()] {
+            assert!(UntypedBdev::lookup_by_uuid_str(&uuid).is_some());
+        }
+        desc.unclaim();
+        drop(desc);
+
+        let report = Lvol::destroy_replica_tree(&lvol)
+            .await
+            .expect("Failed to destroy the replica tree");
+        assert_eq!(report.destroyed.len(), 3);
+        pool.destroy().await.expect("Failed to destroy test pool");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_snapshots_page() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool32",
+            "malloc:///disk32?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol32",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let entity_id = Uuid::new_v4().to_string();
+        for i in 0 .. 3 {
+            lvol.create_snapshot(SnapshotParams::new(
+                Some(entity_id.clone()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(format!("lvol32_snap{i}")),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+        }
+
+        let page = Lvol::list_snapshots_page(Some(&entity_id), 0, 10);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.snapshots.len(), 3);
+        assert!(page.snapshots.iter().all(|s| {
+            s.snapshot_params().entity_id().as_deref() == Some(&entity_id)
+        }));
+
+        let page = Lvol::list_snapshots_page(Some(&entity_id), 1, 1);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.snapshots.len(), 1);
+
+        let page = Lvol::list_snapshots_page(Some(&entity_id), 3, 10);
+        assert_eq!(page.total, 3);
+        assert!(page.snapshots.is_empty());
+
+        let page = Lvol::list_snapshots_page(Some("no-such-entity"), 0, 10);
+        assert_eq!(page.total, 0);
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_list_snapshots_by_entity_id() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool39",
+            "malloc:///disk39?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol39",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        // Snapshots created out of order of their creation time.
+        let entity_id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        for (i, age) in [(0, 1), (1, 3), (2, 2)] {
+            lvol.create_snapshot(SnapshotParams::new(
+                Some(entity_id.clone()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(format!("lvol39_snap{i}")),
+                Some(Uuid::new_v4().to_string()),
+                Some((now - chrono::Duration::hours(age)).to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+        }
+        lvol.create_snapshot(SnapshotParams::new(
+            Some(Uuid::new_v4().to_string()),
+            Some(lvol.uuid()),
+            Some(Uuid::new_v4().to_string()),
+            Some("lvol39_snap3".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(now.to_string()),
+            false,
+        ))
+        .await
+        .expect("Failed to create a snapshot");
+
+        let snapshots = Lvol::list_snapshots_by_entity_id(&entity_id);
+        let names = snapshots
+            .iter()
+            .map(|s| s.snapshot_params().name().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["lvol39_snap1", "lvol39_snap2", "lvol39_snap0"]);
+
+        assert!(Lvol::list_snapshots_by_entity_id("no-such-entity").is_empty());
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_create_txn_idempotent() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool40",
+            "malloc:///disk40?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol40",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let txn_id = Uuid::new_v4().to_string();
+        let snap_uuid = Uuid::new_v4().to_string();
+        let params = |name: &str, uuid: &str| {
+            SnapshotParams::new(
+                Some("lvol40_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(txn_id.clone()),
+                Some(name.to_string()),
+                Some(uuid.to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+
+        let snapshot = lvol
+            .create_snapshot(params("lvol40_snap1", &snap_uuid))
+            .await
+            .expect("Failed to create a snapshot");
+
+        // A retry of the transaction returns the snapshot already created.
+        let retried = lvol
+            .create_snapshot(params("lvol40_snap1", &snap_uuid))
+            .await
+            .expect("Failed to retry the snapshot creation");
+        assert_eq!(retried.uuid(), snapshot.uuid());
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 1);
+
+        // The same transaction with another name or uuid conflicts with the
+        // snapshot already created.
+        for conflicting in [
+            params("lvol40_snap1_retry", &snap_uuid),
+            params("lvol40_snap1", &Uuid::new_v4().to_string()),
+        ] {
+            let error = lvol
+                .create_snapshot(conflicting)
+                .await
+                .expect_err("A conflicting retry must fail");
+            assert!(matches!(error, LvsError::SnapshotTxnConflict { .. }));
+        }
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 1);
+
+        // Another transaction creates a new snapshot.
+        let mut other = params("lvol40_snap2", &Uuid::new_v4().to_string());
+        other.set_txn_id(Uuid::new_v4().to_string());
+        let snapshot2 = lvol
+            .create_snapshot(other)
+            .await
+            .expect("Failed to create a snapshot");
+        assert_ne!(snapshot2.uuid(), snapshot.uuid());
+        assert_eq!(lvol.list_snapshot_by_source_uuid().len(), 2);
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_usage_breakdown() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool33",
+            "malloc:///disk33?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol33",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol33_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol33_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+
+        // The replica has not been written to since the snapshot, so it
+        // still reads all of the snapshot clusters.
+        let cluster_size = pool.blob_cluster_size();
+        let allocated = snapshot.usage().allocated_bytes;
+        assert!(allocated > cluster_size);
+        let breakdown = snapshot.snapshot_usage_breakdown();
+        assert_eq!(breakdown.exclusive_bytes, 0);
+        assert_eq!(breakdown.shared_bytes, allocated);
+        assert_eq!(breakdown.referenced_bytes, allocated);
+
+        // Once overwritten by the replica, the first cluster of the snapshot
+        // is only held by the snapshot.
+        bdev_io::write_some("lvol33", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to write data to volume");
+        let breakdown = snapshot.snapshot_usage_breakdown();
+        assert_eq!(breakdown.exclusive_bytes, cluster_size);
+        assert_eq!(breakdown.shared_bytes, allocated - cluster_size);
+        assert_eq!(breakdown.referenced_bytes, allocated);
+
+        // A clone reads the first cluster through to the snapshot again,
+        // until the clone overwrites it in turn.
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol33_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let breakdown = snapshot.snapshot_usage_breakdown();
+        assert_eq!(breakdown.exclusive_bytes, 0);
+        assert_eq!(breakdown.shared_bytes, allocated);
+
+        bdev_io::write_some("lvol33_snap1_clone1", 0, 16, 0xbbu8)
+            .await
+            .expect("Failed to write data to clone");
+        let breakdown = snapshot.snapshot_usage_breakdown();
+        assert_eq!(breakdown.exclusive_bytes, cluster_size);
+        assert_eq!(breakdown.shared_bytes, allocated - cluster_size);
+        assert_eq!(breakdown.referenced_bytes, allocated);
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_checksum() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool34",
+            "malloc:///disk34?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol34",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol34_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol34_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+
+        assert!(Lvol::get_blob_xattr(
+            snapshot.blob_checked(),
+            SnapshotXattrs::Checksum.name()
+        )
+        .is_some());
+        snapshot
+            .verify_snapshot_integrity()
+            .expect("Snapshot attributes should match their checksum");
+
+        // Discarding a snapshot does not invalidate its checksum.
+        snapshot
+            .set_blob_attr(
+                SnapshotXattrs::DiscardedSnapshot.name(),
+                true.to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set snapshot attribute");
+        assert!(snapshot.verify_snapshot_integrity().is_ok());
+
+        snapshot
+            .set_blob_attr(
+                SnapshotXattrs::EntityId.name(),
+                "lvol34_e2".to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set snapshot attribute");
+        assert!(matches!(
+            snapshot.verify_snapshot_integrity(),
+            Err(LvsError::SnapshotChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            snapshot.try_snapshot_descriptor(None),
+            Err(SnapshotDescriptorError::ChecksumMismatch { .. })
+        ));
+        assert!(!snapshot.snapshot_descriptor(None).unwrap().valid_snapshot());
+
+        snapshot
+            .set_blob_attr(
+                SnapshotXattrs::EntityId.name(),
+                "lvol34_e1".to_string(),
+                true,
+            )
+            .await
+            .expect("Failed to set snapshot attribute");
+        assert!(snapshot.verify_snapshot_integrity().is_ok());
+
+        lvol.destroy().await.expect("Failed to destroy lvol");
+        snapshot
+            .destroy()
+            .await
+            .expect("Failed to destroy snapshot");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_diff() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool35",
+            "malloc:///disk35?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol35",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let mut snapshots = Vec::new();
+        for i in 1 ..= 2 {
+            let snapshot = lvol
+                .create_snapshot(SnapshotParams::new(
+                    Some("lvol35_e1".to_string()),
+                    Some(lvol.uuid()),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(format!("lvol35_snap{}", i)),
+                    Some(Uuid::new_v4().to_string()),
+                    Some(Utc::now().to_string()),
+                    false,
+                ))
+                .await
+                .expect("Failed to create a snapshot");
+            snapshots.push(snapshot);
+        }
+
+        // All the clusters of the thick replica were moved to the first
+        // snapshot, nothing has been written since.
+        assert_eq!(lvol.snapshot_diff(&snapshots[0]).unwrap(), vec![]);
+        assert_eq!(snapshots[1].snapshot_diff(&snapshots[0]).unwrap(), vec![]);
+
+        // The base must be a strict ancestor.
+        assert!(matches!(
+            snapshots[0].snapshot_diff(&snapshots[1]),
+            Err(LvsError::NotAnAncestor { .. })
+        ));
+        assert!(matches!(
+            snapshots[0].snapshot_diff(&snapshots[0]),
+            Err(LvsError::NotAnAncestor { .. })
+        ));
+
+        // Overwritten clusters are reported, the last one up to the size of
+        // the replica.
+        let cluster_size = pool.blob_cluster_size();
+        let size = lvol.size();
+        let last = (size - 1) / cluster_size * cluster_size;
+        bdev_io::write_some("lvol35", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to write data to volume");
+        bdev_io::write_some("lvol35", last, 16, 0xbbu8)
+            .await
+            .expect("Failed to write data to volume");
+        assert_eq!(
+            lvol.snapshot_diff(&snapshots[1]).unwrap(),
+            vec![
+                ClusterRange {
+                    offset: 0,
+                    length: cluster_size,
+                },
+                ClusterRange {
+                    offset: last,
+                    length: size - last,
+                },
+            ]
+        );
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_restore_from_snapshot() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool43",
+            "malloc:///disk43?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol43",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+        let other = pool
+            .create_lvol(
+                "lvol43_other",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |lvol: &Lvol, name: &str| {
+            SnapshotParams::new(
+                Some("lvol43_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+
+        bdev_io::write_some("lvol43", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to write data to volume");
+        let snapshot1 = lvol
+            .create_snapshot(params(&lvol, "lvol43_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        bdev_io::write_some("lvol43", 0, 16, 0xbbu8)
+            .await
+            .expect("Failed to write data to volume");
+        let snapshot2 = lvol
+            .create_snapshot(params(&lvol, "lvol43_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+        bdev_io::write_some("lvol43", 0, 16, 0xccu8)
+            .await
+            .expect("Failed to write data to volume");
+
+        // A snapshot of another replica cannot be restored.
+        let other_snapshot = other
+            .create_snapshot(params(&other, "lvol43_other_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        assert!(matches!(
+            lvol.restore_from_snapshot(&other_snapshot).await,
+            Err(LvsError::SnapshotRestoreRefused { .. })
+        ));
+
+        lvol.restore_from_snapshot(&snapshot1)
+            .await
+            .expect("Failed to restore the snapshot");
+        bdev_io::read_some("lvol43", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to read data from volume");
+
+        // The snapshot taken after the restored one is preserved.
+        let snapshots = lvol
+            .list_snapshot_by_source_uuid()
+            .iter()
+            .map(|s| s.snapshot_lvol().uuid())
+            .collect::<Vec<_>>();
+        assert_eq!(snapshots, vec![snapshot1.uuid(), snapshot2.uuid()]);
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+        clean_snapshots(other.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_restore_from_snapshot_refused() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool51",
+            "malloc:///disk51?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol51",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |name: &str| {
+            SnapshotParams::new(
+                Some("lvol51_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+
+        bdev_io::write_some("lvol51", 0, 16, 0xaau8)
+            .await
+            .expect("Failed to write data to volume");
+        let snapshot1 = lvol
+            .create_snapshot(params("lvol51_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        bdev_io::write_some("lvol51", 0, 16, 0xbbu8)
+            .await
+            .expect("Failed to write data to volume");
+
+        // A snapshot with a clone is only discarded when destroyed.
+        let clone = snapshot1
+            .create_clone(CloneParams::new(
+                Some("lvol51_snap1_clone".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot1.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        snapshot1
+            .destroy_snapshot()
+            .await
+            .expect("Failed to discard the snapshot");
+        let snapshot1 = Lvol::try_from(
+            UntypedBdev::lookup_by_uuid_str(&snapshot1.uuid()).unwrap(),
+        )
+        .unwrap();
+        assert!(snapshot1.is_discarded_snapshot());
+        assert!(matches!(
+            lvol.restore_from_snapshot(&snapshot1).await,
+            Err(LvsError::SnapshotRestoreRefused { reason, .. })
+                if reason.contains("discarded")
+        ));
+
+        // A replica in use cannot be restored.
+        let snapshot2 = lvol
+            .create_snapshot(params("lvol51_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+        bdev_io::write_some("lvol51", 0, 16, 0xccu8)
+            .await
+            .expect("Failed to write data to volume");
+        let desc = lvol.as_bdev().open(true).unwrap();
+        assert!(desc.claim());
+        assert!(matches!(
+            lvol.restore_from_snapshot(&snapshot2).await,
+            Err(LvsError::SnapshotRestoreRefused { reason, .. })
+                if reason.contains("in use")
+        ));
+        desc.unclaim();
+        drop(desc);
+        bdev_io::read_some("lvol51", 0, 16, 0xccu8)
+            .await
+            .expect("Failed to read data from volume");
+
+        lvol.restore_from_snapshot(&snapshot2)
+            .await
+            .expect("Failed to restore the snapshot");
+        bdev_io::read_some("lvol51", 0, 16, 0xbbu8)
+            .await
+            .expect("Failed to read data from volume");
+        assert_eq!(lvol.restore_in_progress(), None);
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_thick_clone() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool36",
+            "malloc:///disk36?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol36",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol36_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol36_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+
+        let mut clone_param = CloneParams::new(
+            Some("lvol36_snap1_clone1".to_string()),
+            Some(Uuid::new_v4().to_string()),
+            Some(snapshot.uuid()),
+            Some(Utc::now().to_string()),
+        );
+        clone_param.set_thick(true);
+        let clone = snapshot
+            .create_clone(clone_param)
+            .await
+            .expect("Failed to create a thick clone");
+
+        // The clone holds all of its data and no longer depends on the
+        // snapshot.
+        assert!(clone.is_snapshot_clone().is_none());
+        assert!(snapshot.list_clones_by_snapshot_uuid().is_empty());
+        assert_eq!(
+            clone.usage().allocated_bytes,
+            snapshot.usage().allocated_bytes
+        );
+
+        lvol.destroy().await.expect("Failed to destroy lvol");
+        snapshot
+            .destroy()
+            .await
+            .expect("Failed to destroy snapshot");
+        clone.destroy().await.expect("Failed to destroy clone");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_thick_clone_failure() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool50",
+            "malloc:///disk50?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol50",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol50_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol50_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+
+        let clone_uuid = Uuid::new_v4().to_string();
+        let mut clone_param = CloneParams::new(
+            Some("lvol50_snap1_clone1".to_string()),
+            Some(clone_uuid.clone()),
+            Some(snapshot.uuid()),
+            Some(Utc::now().to_string()),
+        );
+        clone_param.set_thick(true);
+
+        // Cancel the copy as soon as it has started, which fails the thick
+        // clone. The creation only progresses when it is polled, so that the
+        // copy is cancelled while its first cluster is being copied.
+        let mut create = Box::pin(snapshot.create_clone(clone_param));
+        loop {
+            assert!(futures::poll!(create.as_mut()).is_pending());
+            if cancel_clone_detach(&clone_uuid) {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(1)).await.unwrap();
+        }
+        assert!(matches!(
+            create.await,
+            Err(LvsError::CloneDetachCancelled { .. })
+        ));
+
+        // The partial thin clone is not left behind.
+        assert!(UntypedBdev::lookup_by_uuid_str(&clone_uuid).is_none());
+        assert!(snapshot.list_clones_by_snapshot_uuid().is_empty());
+
+        lvol.destroy().await.expect("Failed to destroy lvol");
+        snapshot
+            .destroy()
+            .await
+            .expect("Failed to destroy snapshot");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_create_time() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool37",
+            "malloc:///disk37?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol37",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |name: &str, create_time: String| {
+            SnapshotParams::new(
+                Some("lvol37_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(create_time),
+                false,
+            )
+        };
+
+        // A create time which cannot be parsed is rejected.
+        assert!(matches!(
+            lvol.create_snapshot(params("lvol37_snap0", "yesterday".into()))
+                .await,
+            Err(LvsError::SnapshotConfigFailed { .. })
+        ));
+
+        // The create time is stored in RFC3339 format.
+        let now = Utc::now();
+        let snapshot = lvol
+            .create_snapshot(params("lvol37_snap1", now.to_string()))
+            .await
+            .expect("Failed to create a snapshot");
+        let descr = snapshot.snapshot_descriptor(None).unwrap();
+        let stored = descr.snapshot_params().create_time_str().unwrap();
+        assert_eq!(stored, now.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        assert_eq!(descr.snapshot_params().create_time().unwrap(), Some(now));
+        assert!(descr.valid_snapshot());
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_destroy_snapshot_with_dependents() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool38",
+            "malloc:///disk38?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol38",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol38_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol38_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot
+            .create_clone(CloneParams::new(
+                Some("lvol38_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let clone_snapshot = clone
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol38_e2".to_string()),
+                Some(clone.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol38_clone1_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a clone snapshot");
+        let snapshot_uuid = snapshot.uuid();
+
+        // The snapshot of the clone still depends on the snapshot once the
+        // clone is gone: the snapshot is discarded in place of destroyed.
+        clone.destroy().await.expect("Failed to destroy clone");
+        assert!(snapshot.list_clones_by_snapshot_uuid().is_empty());
+        snapshot
+            .clone()
+            .destroy_snapshot()
+            .await
+            .expect("Failed to discard snapshot");
+        let snapshot = UntypedBdev::lookup_by_uuid_str(&snapshot_uuid)
+            .and_then(|b| Lvol::try_from(b).ok())
+            .expect("Discarded snapshot must remain");
+        assert!(snapshot.is_discarded_snapshot());
+
+        // Without dependents left, the snapshot is destroyed.
+        clone_snapshot
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy clone snapshot");
+        snapshot
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy snapshot");
+        assert!(UntypedBdev::lookup_by_uuid_str(&snapshot_uuid).is_none());
+        lvol.destroy().await.expect("Failed to destroy lvol");
+    })
+    .await;
+}
+
+#[test]
+fn test_derive_snapshot_uuid() {
+    let source_uuid = Uuid::new_v4().to_string();
+
+    let snap_uuid = derive_snapshot_uuid(&source_uuid, "txn1");
+    assert!(Uuid::parse_str(&snap_uuid).is_ok());
+    assert_eq!(snap_uuid, derive_snapshot_uuid(&source_uuid, "txn1"));
+    assert_ne!(snap_uuid, derive_snapshot_uuid(&source_uuid, "txn2"));
+    assert_ne!(
+        snap_uuid,
+        derive_snapshot_uuid(&Uuid::new_v4().to_string(), "txn1")
+    );
+}
+
+#[tokio::test]
+async fn test_lvol_ancestry() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool44",
+            "malloc:///disk44?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol44",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |parent: &Lvol, name: &str| {
+            SnapshotParams::new(
+                Some("lvol44_e1".to_string()),
+                Some(parent.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+        let snapshot1 = lvol
+            .create_snapshot(params(&lvol, "lvol44_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone = snapshot1
+            .create_clone(CloneParams::new(
+                Some("lvol44_snap1_clone1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot1.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let snapshot2 = clone
+            .create_snapshot(params(&clone, "lvol44_clone1_snap1"))
+            .await
+            .expect("Failed to create a snapshot of the clone");
+
+        let ancestry = snapshot2.ancestry().expect("Failed to get ancestry");
+        let lineage = ancestry
+            .iter()
+            .map(|n| (n.uuid.clone(), n.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lineage,
+            vec![
+                (snapshot2.uuid(), TopologyNodeKind::Snapshot),
+                (clone.uuid(), TopologyNodeKind::Clone),
+                (snapshot1.uuid(), TopologyNodeKind::Snapshot),
+                (lvol.uuid(), TopologyNodeKind::Replica),
+            ]
+        );
+        assert_eq!(ancestry[1].name, clone.name());
+
+        // A replica is its own root.
+        assert_eq!(lvol.ancestry().unwrap().len(), 1);
+
+        snapshot2
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy snapshot");
+        clone.destroy().await.expect("Failed to destroy clone");
+        snapshot1
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy snapshot");
+        lvol.destroy().await.expect("Failed to destroy replica");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_snapshot_label() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool47",
+            "malloc:///disk47?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol47",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let params = |name: &str| {
+            SnapshotParams::new(
+                Some("lvol47_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_rfc3339()),
+                false,
+            )
+        };
+        let labelled = lvol
+            .create_snapshot_labeled(params("lvol47_snap1"))
+            .await
+            .expect("Failed to create a labelled snapshot");
+        let plain = lvol
+            .create_snapshot(params("lvol47_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+
+        let label = labelled
+            .read_snapshot_label()
+            .expect("Labelled snapshot has no label");
+        assert!(label.is_valid());
+        assert_eq!(label.source_uuid, lvol.uuid());
+        assert_eq!(
+            Some(label.create_time),
+            Lvol::get_blob_xattr(
+                labelled.blob_checked(),
+                SnapshotXattrs::SnapshotCreateTime.name(),
+            )
+        );
+
+        assert_eq!(plain.read_snapshot_label(), None);
+
+        clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+        lvol.destroy().await.expect("Failed to destroy replica");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_clone_cluster_size_override() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool48",
+            "malloc:///disk48?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol48",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+
+        let snapshot = lvol
+            .create_snapshot(SnapshotParams::new(
+                Some("lvol48_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some("lvol48_snap1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            ))
+            .await
+            .expect("Failed to create a snapshot");
+
+        let cluster_size = snapshot.usage().cluster_size as u32;
+        let params = |name: &str, cluster_size: u32| {
+            let mut clone_param = CloneParams::new(
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            );
+            clone_param.set_cluster_size_override(cluster_size);
+            clone_param
+        };
+
+        // Neither a size that is not a multiple of the pool cluster size nor
+        // a larger one the blobstore cannot honour is accepted.
+        for size in [cluster_size / 2, cluster_size + 512, cluster_size * 2] {
+            let error = snapshot
+                .create_clone(params("lvol48_bad_clone", size))
+                .await
+                .expect_err("Clone with a bad cluster size was created");
+            assert!(
+                matches!(error, LvsError::CloneConfigFailed { .. }),
+                "unexpected error: {error:?}"
+            );
+        }
+        assert!(snapshot.list_clones_by_snapshot_uuid().is_empty());
+
+        // The pool cluster size itself is accepted.
+        let clone = snapshot
+            .create_clone(params("lvol48_clone1", cluster_size))
+            .await
+            .expect("Failed to create a clone");
+        assert_eq!(clone.usage().cluster_size, cluster_size as u64);
+
+        clone.destroy().await.expect("Failed to destroy clone");
+        lvol.destroy().await.expect("Failed to destroy lvol");
+        snapshot
+            .destroy()
+            .await
+            .expect("Failed to destroy snapshot");
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn test_detach_clone_of_snapshot_chain() {
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool49",
+            "malloc:///disk49?size_mb=64".to_string(),
+            None,
+        )
+        .await;
+        let lvol = pool
+            .create_lvol(
+                "lvol49",
+                LVOL_SIZE,
+                Some(&Uuid::new_v4().to_string()),
+                false,
+                None,
+            )
+            .await
+            .expect("Failed to create test lvol");
+        let cluster_size = lvol.usage().cluster_size;
+
+        let params = |name: &str| {
+            SnapshotParams::new(
+                Some("lvol49_e1".to_string()),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+
+        // The first cluster is only held by the oldest snapshot, which is
+        // not the source snapshot of the clone.
+        bdev_io::write_some("lvol49", 0, 16, 0xaa)
+            .await
+            .expect("Failed to write to the lvol");
+        let snap1 = lvol
+            .create_snapshot(params("lvol49_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        bdev_io::write_some("lvol49", cluster_size, 16, 0xbb)
+            .await
+            .expect("Failed to write to the lvol");
+        let snap2 = lvol
+            .create_snapshot(params("lvol49_snap2"))
+            .await
+            .expect("Failed to create a snapshot");
+
+        let clone = snap2
+            .create_clone(CloneParams::new(
+                Some("lvol49_snap2_clone_1".to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snap2.uuid()),
+                Some(Utc::now().to_string()),
+            ))
+            .await
+            .expect("Failed to create a clone");
+        let clone_name = clone.name();
+
+        clone
+            .detach_clone()
+            .await
+            .expect("Failed to detach the clone");
+        assert!(clone.is_snapshot_clone().is_none());
+
+        // The clone depends on none of the snapshots of the chain anymore:
+        // its data survives all of them.
+        lvol.destroy().await.expect("Failed to destroy lvol");
+        snap2.destroy().await.expect("Failed to destroy snapshot");
+        snap1.destroy().await.expect("Failed to destroy snapshot");
+        bdev_io::read_some(&clone_name, 0, 2, 0xaa)
+            .await
+            .expect("Failed to read the first cluster of the clone");
+        bdev_io::read_some(&clone_name, cluster_size, 2, 0xbb)
+            .await
+            .expect("Failed to read the second cluster of the clone");
+
+        clone.destroy().await.expect("Failed to destroy the clone");
+    })
+    .await;
+}
+
+#[cfg(feature = "io-engine-testing")]
+#[tokio::test]
+async fn test_wildcard_snapshot_tree() {
+    use std::collections::HashSet;
+
+    // The walk the single scan replaced, searching all the bdevs for the
+    // snapshots and the clones linked to each lvol met along the way.
+    fn scan_walk() -> HashSet<String> {
+        let snapshots = |parent: Option<&Lvol>| {
+            Lvol::list_all_snapshots(parent)
+                .iter()
+                .map(|s| s.snapshot_lvol().clone())
+                .collect::<Vec<_>>()
+        };
+        let mut visited = HashSet::new();
+        let mut successor_clones: Vec<Lvol> = vec![];
+        let mut successor_snapshots = snapshots(None);
+        while !successor_snapshots.is_empty() || !successor_clones.is_empty() {
+            if let Some(snapshot) = successor_snapshots.pop() {
+                visited.insert(snapshot.uuid());
+                successor_clones
+                    .extend(snapshot.list_clones_by_snapshot_uuid());
+            }
+            if let Some(clone) = successor_clones.pop() {
+                visited.insert(clone.uuid());
+                successor_snapshots.extend(snapshots(Some(&clone)));
+            }
+        }
+        visited
+    }
+
+    let ms = get_ms();
+
+    ms.spawn(async move {
+        let pool = create_test_pool(
+            "pool58",
+            "malloc:///disk58?size_mb=128".to_string(),
+            None,
+        )
+        .await;
+        let snapshot_params = |lvol: &Lvol, name: &str| {
+            SnapshotParams::new(
+                Some(format!("{name}_e1")),
+                Some(lvol.uuid()),
+                Some(Uuid::new_v4().to_string()),
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(Utc::now().to_string()),
+                false,
+            )
+        };
+        let clone_params = |snapshot: &Lvol, name: &str| {
+            CloneParams::new(
+                Some(name.to_string()),
+                Some(Uuid::new_v4().to_string()),
+                Some(snapshot.uuid()),
+                Some(Utc::now().to_string()),
+            )
+        };
+
+        // A replica with a snapshot, a clone of it with a snapshot and a
+        // clone of its own, and a second replica with a snapshot.
+        let mut lvols = Vec::new();
+        for name in ["lvol58_1", "lvol58_2"] {
+            let lvol = pool
+                .create_lvol(
+                    name,
+                    LVOL_SIZE,
+                    Some(&Uuid::new_v4().to_string()),
+                    true,
+                    None,
+                )
+                .await
+                .expect("Failed to create test lvol");
+            lvols.push(lvol);
+        }
+        let snap1 = lvols[0]
+            .create_snapshot(snapshot_params(&lvols[0], "lvol58_1_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone1 = snap1
+            .create_clone(clone_params(&snap1, "lvol58_1_snap1_clone1"))
+            .await
+            .expect("Failed to create a clone");
+        let snap2 = clone1
+            .create_snapshot(snapshot_params(&clone1, "lvol58_clone1_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+        let clone2 = snap2
+            .create_clone(clone_params(&snap2, "lvol58_clone1_snap1_clone1"))
+            .await
+            .expect("Failed to create a clone");
+        let snap3 = lvols[1]
+            .create_snapshot(snapshot_params(&lvols[1], "lvol58_2_snap1"))
+            .await
+            .expect("Failed to create a snapshot");
+
+        // Both walks reset the same lvols, the single scan each of them
+        // once.
+        let tree = Lvol::wildcard_snapshot_tree_uuids();
+        let unique = tree.iter().cloned().collect::<HashSet<_>>();
+        assert_eq!(unique.len(), tree.len());
+        assert_eq!(unique, scan_walk());
+        for lvol in [&snap1, &clone1, &snap2, &clone2, &snap3] {
+            assert!(unique.contains(&lvol.uuid()));
+        }
+        for lvol in &lvols {
+            assert!(!unique.contains(&lvol.uuid()));
+        }
+
+        clone2.destroy().await.expect("Failed to destroy the clone");
+        snap2
+            .destroy_snapshot()
+            .await
+            .expect("Failed to destroy the snapshot");
+        clone1.destroy().await.expect("Failed to destroy the clone");
+        for lvol in lvols {
+            clean_snapshots(lvol.list_snapshot_by_source_uuid()).await;
+            lvol.destroy().await.expect("Failed to destroy the lvol");
+        }
+    })
+    .await;
+}
@@ -4,7 +4,7 @@ use futures::channel::oneshot;
 use io_engine::{bdev::nexus::NexusSnapshotStatus, core::SnapshotDescriptor};
 use once_cell::sync::OnceCell;
 
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use common::{
     compose::{
         rpc::v1::{
@@ -244,9 +244,7 @@ fn check_replica_snapshot(params: &SnapshotParams, snapshot: &SnapshotInfo) {
 
     assert_eq!(
         snapshot.timestamp,
-        params
-            .create_time()
-            .map(|s| s.parse::<DateTime<Utc>>().unwrap_or_default().into()),
+        params.create_time().unwrap().map(Into::into),
         "Snapshot CreateTime doesn't match",
     );
 }
@@ -0,0 +1,53 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use io_engine::core::{
+    runtime::{spawn_bounded, SpawnLimiter},
+    MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn spawn_limiter_bounds_in_flight() {
+    let _ms = MayastorTest::new(MayastorCliArgs::default());
+
+    let limiter = SpawnLimiter::subsystem("test", 2);
+    // the limiter is shared by the callers of the subsystem
+    let shared = SpawnLimiter::subsystem("test", 10);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    for i in 0 .. 6 {
+        let running = running.clone();
+        let max_running = max_running.clone();
+        let done = done.clone();
+        let limiter = if i % 2 == 0 { &limiter } else { &shared };
+        spawn_bounded(limiter, async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            done.fetch_add(1, Ordering::SeqCst);
+        })
+        .await;
+    }
+
+    for _ in 0 .. 50 {
+        if done.load(Ordering::SeqCst) == 6 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(done.load(Ordering::SeqCst), 6);
+    assert!(max_running.load(Ordering::SeqCst) <= 2);
+    assert_eq!(limiter.available(), 2);
+    assert_eq!(shared.available(), 2);
+}
@@ -1,4 +1,11 @@
-use std::{pin::Pin, time::Duration};
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
 use io_engine::{
     bdev_api::bdev_create,
@@ -63,4 +70,35 @@ async fn thread_tokio() {
     let th = io_engine::core::runtime::spawn_blocking(running_on_thread);
     tokio::time::sleep(Duration::from_secs(1)).await;
     th.await.unwrap();
+
+    // the output of a future awaited on the runtime is handed back
+    let value = ms
+        .spawn(async {
+            io_engine::core::runtime::spawn_await_output(async {
+                assert_eq!(Mthread::current(), None);
+                42
+            })
+            .await
+            .unwrap()
+        })
+        .await;
+    assert_eq!(value, 42);
+
+    // a unit future is awaited as before
+    let ran = Arc::new(AtomicBool::new(false));
+    let flag = ran.clone();
+    ms.spawn(io_engine::core::runtime::spawn_await(async move {
+        flag.store(true, Ordering::SeqCst);
+    }))
+    .await;
+    assert!(ran.load(Ordering::SeqCst));
+
+    // tasks spawned with a handle can be awaited or aborted
+    let handle = io_engine::core::runtime::spawn_handle(async { 7 });
+    assert_eq!(handle.await.unwrap(), 7);
+    let handle = io_engine::core::runtime::spawn_handle(async {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+    });
+    handle.abort();
+    assert!(handle.await.unwrap_err().is_cancelled());
 }